@@ -0,0 +1,808 @@
+//! Import files for bookkeeping tools, built from the same data as the structured export.
+//!
+//! Every invoice becomes a balanced transaction: accounts receivable is debited with the amount
+//! payable, revenue credited per line and tax payable credited per tax rate. Credit notes post
+//! the other way round and payments move money from receivable to the bank account.
+
+use std::collections::HashMap;
+
+use chrono::{Duration, NaiveDate};
+
+use crate::export::{csv_table, ExportData, ExportedInvoice};
+use crate::settings::AccountMap;
+use crate::structs::{Customer, LineKind, Payment};
+use crate::totals::{round_cents, tax_category, Totals};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AccountingFormat {
+    // QuickBooks Desktop interchange format
+    QuickBooksIif,
+    // QuickBooks Online invoice import
+    QuickBooksCsv,
+    // Xero sales invoice import template
+    XeroCsv,
+    // one row per posting
+    JournalCsv,
+    // plain text journal read by Ledger and hledger
+    Ledger,
+}
+
+impl AccountingFormat {
+    pub const ALL: [AccountingFormat; 5] = [
+        AccountingFormat::QuickBooksIif,
+        AccountingFormat::QuickBooksCsv,
+        AccountingFormat::XeroCsv,
+        AccountingFormat::JournalCsv,
+        AccountingFormat::Ledger,
+    ];
+
+    pub fn key(&self) -> &'static str {
+        match self {
+            AccountingFormat::QuickBooksIif => "quickbooks-iif",
+            AccountingFormat::QuickBooksCsv => "quickbooks-csv",
+            AccountingFormat::XeroCsv => "xero",
+            AccountingFormat::JournalCsv => "journal",
+            AccountingFormat::Ledger => "ledger",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            AccountingFormat::QuickBooksIif => "QuickBooks IIF",
+            AccountingFormat::QuickBooksCsv => "QuickBooks Online CSV",
+            AccountingFormat::XeroCsv => "Xero sales invoices CSV",
+            AccountingFormat::JournalCsv => "Journal CSV",
+            AccountingFormat::Ledger => "Ledger / hledger journal",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AccountingFormat::QuickBooksIif => "iif",
+            AccountingFormat::Ledger => "journal",
+            _ => "csv",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Self> {
+        let key = key.to_ascii_lowercase();
+        Self::ALL.iter().copied().find(|format| format.key() == key)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TransactionKind {
+    Invoice,
+    CreditNote,
+    Payment,
+}
+
+// amounts are in cents so every transaction balances exactly, debits are positive
+#[derive(Clone, Debug, PartialEq)]
+pub struct Posting {
+    pub account: String,
+    pub amount: i64,
+    pub memo: String,
+    pub line: Option<LineDetail>,
+}
+
+// the invoice line behind a revenue posting, for formats that list items
+#[derive(Clone, Debug, PartialEq)]
+pub struct LineDetail {
+    pub sku: Option<String>,
+    pub quantity: f64,
+    pub price: f64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transaction {
+    pub kind: TransactionKind,
+    pub date: String,
+    // the invoice number, payments carry the number of the invoice they settle
+    pub number: String,
+    // the customer's company
+    pub name: String,
+    pub postings: Vec<Posting>,
+}
+
+impl Transaction {
+    pub fn balance(&self) -> i64 {
+        self.postings.iter().map(|posting| posting.amount).sum()
+    }
+
+    // postings merged per account in order of appearance, for the journal formats
+    pub fn summary(&self) -> Vec<(String, i64)> {
+        let mut accounts: Vec<(String, i64)> = Vec::new();
+        for posting in &self.postings {
+            match accounts
+                .iter_mut()
+                .find(|(account, _)| *account == posting.account)
+            {
+                Some((_, amount)) => *amount += posting.amount,
+                None => accounts.push((posting.account.clone(), posting.amount)),
+            }
+        }
+        accounts
+    }
+}
+
+fn cents(value: f64) -> i64 {
+    (value * 100.0).round() as i64
+}
+
+fn money(cents: i64) -> String {
+    let sign = if cents < 0 { "-" } else { "" };
+    format!("{}{}.{:02}", sign, cents.abs() / 100, cents.abs() % 100)
+}
+
+fn tax_label(code: &str, percent: f64) -> String {
+    format!("Tax {} {}%", code, percent)
+}
+
+fn invoice_transaction(exported: &ExportedInvoice, accounts: &AccountMap) -> Transaction {
+    let invoice = &exported.invoice;
+    let totals = Totals::new(&exported.items);
+    // credit notes are stated with positive amounts and posted in reverse
+    let sign = if totals.credit_note { -1 } else { 1 };
+    let mut postings = vec![Posting {
+        account: accounts.receivable.clone(),
+        amount: 0,
+        memo: format!("Invoice {}", invoice.invoice_id),
+        line: None,
+    }];
+    for line in &totals.lines {
+        postings.push(Posting {
+            account: accounts.revenue.clone(),
+            amount: -sign * cents(line.amount),
+            memo: line.item.description.clone(),
+            line: Some(LineDetail {
+                sku: line.item.sku.clone(),
+                quantity: line.item.quantity,
+                price: line.item.price,
+            }),
+        });
+    }
+    for entry in totals.breakdown.iter().filter(|entry| entry.tax != 0.0) {
+        postings.push(Posting {
+            account: accounts.tax_payable.clone(),
+            amount: -sign * cents(entry.tax),
+            memo: tax_label(&entry.category.code, entry.category.percent),
+            line: None,
+        });
+    }
+    postings[0].amount = -postings.iter().map(|posting| posting.amount).sum::<i64>();
+    Transaction {
+        kind: if totals.credit_note {
+            TransactionKind::CreditNote
+        } else {
+            TransactionKind::Invoice
+        },
+        date: invoice.issue_date.clone(),
+        number: invoice.invoice_id.clone(),
+        name: invoice.customer.clone(),
+        postings,
+    }
+}
+
+fn payment_transaction(payment: &Payment, name: &str, accounts: &AccountMap) -> Transaction {
+    let amount = cents(payment.amount);
+    let memo = if payment.reference.is_empty() {
+        format!("Payment {}", payment.method)
+    } else {
+        format!("Payment {} {}", payment.method, payment.reference)
+    };
+    Transaction {
+        kind: TransactionKind::Payment,
+        date: payment.paid_on.clone(),
+        number: payment.invoice_id.clone(),
+        name: name.to_string(),
+        postings: vec![
+            Posting {
+                account: accounts.bank.clone(),
+                amount,
+                memo: memo.trim().to_string(),
+                line: None,
+            },
+            Posting {
+                account: accounts.receivable.clone(),
+                amount: -amount,
+                memo: format!("Invoice {}", payment.invoice_id),
+                line: None,
+            },
+        ],
+    }
+}
+
+// Invoices and payments in date order. A payment whose invoice isn't part of the export is
+// named after the customer id in its invoice number.
+pub fn transactions(data: &ExportData, accounts: &AccountMap) -> Vec<Transaction> {
+    let customer_of: HashMap<&str, &str> = data
+        .invoices
+        .iter()
+        .map(|exported| {
+            (
+                exported.invoice.invoice_id.as_str(),
+                exported.invoice.customer.as_str(),
+            )
+        })
+        .collect();
+    let mut transactions: Vec<Transaction> = data
+        .invoices
+        .iter()
+        .map(|exported| invoice_transaction(exported, accounts))
+        .collect();
+    for payment in &data.payments {
+        let name = customer_of
+            .get(payment.invoice_id.as_str())
+            .copied()
+            .unwrap_or_else(|| {
+                payment
+                    .invoice_id
+                    .rsplit_once('-')
+                    .map_or(payment.invoice_id.as_str(), |(cust_id, _)| cust_id)
+            });
+        transactions.push(payment_transaction(payment, name, accounts));
+    }
+    transactions.sort_by(|a, b| a.date.cmp(&b.date));
+    transactions
+}
+
+pub fn render(
+    format: AccountingFormat,
+    data: &ExportData,
+    accounts: &AccountMap,
+    currency: &str,
+) -> String {
+    match format {
+        AccountingFormat::QuickBooksIif => quickbooks_iif(&transactions(data, accounts)),
+        AccountingFormat::QuickBooksCsv => quickbooks_csv(data, accounts, currency),
+        AccountingFormat::XeroCsv => xero_csv(data, accounts, currency),
+        AccountingFormat::JournalCsv => journal_csv(&transactions(data, accounts)),
+        AccountingFormat::Ledger => ledger(&transactions(data, accounts), currency),
+    }
+}
+
+// dates are stored as YYYY-MM-DD, anything else is passed through untouched
+fn reformat_date(date: &str, format: &str) -> String {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_or_else(|_| date.to_string(), |date| date.format(format).to_string())
+}
+
+pub(crate) fn due_date(issue_date: &str, accounts: &AccountMap, format: &str) -> String {
+    NaiveDate::parse_from_str(issue_date, "%Y-%m-%d").map_or_else(
+        |_| issue_date.to_string(),
+        |date| {
+            (date + Duration::days(accounts.due_days.into()))
+                .format(format)
+                .to_string()
+        },
+    )
+}
+
+fn journal_csv(transactions: &[Transaction]) -> String {
+    let mut rows = Vec::new();
+    for transaction in transactions {
+        let kind = match transaction.kind {
+            TransactionKind::Invoice => "invoice",
+            TransactionKind::CreditNote => "credit note",
+            TransactionKind::Payment => "payment",
+        };
+        for (account, amount) in transaction.summary() {
+            let (debit, credit) = if amount >= 0 {
+                (money(amount), String::new())
+            } else {
+                (String::new(), money(-amount))
+            };
+            rows.push(vec![
+                transaction.date.clone(),
+                transaction.number.clone(),
+                kind.to_string(),
+                transaction.name.clone(),
+                account,
+                debit,
+                credit,
+            ]);
+        }
+    }
+    csv_table(
+        &[
+            "date", "number", "type", "name", "account", "debit", "credit",
+        ],
+        rows,
+    )
+}
+
+// Ledger ends an account name at two spaces or a tab and reads ";" as a comment
+fn ledger_text(value: &str) -> String {
+    value
+        .replace(';', ",")
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+fn ledger(transactions: &[Transaction], currency: &str) -> String {
+    let mut out = format!("; Invoicy journal, amounts in {}\n", currency);
+    for transaction in transactions {
+        let description = match transaction.kind {
+            TransactionKind::Invoice => format!("Invoice {}", transaction.number),
+            TransactionKind::CreditNote => format!("Credit note {}", transaction.number),
+            TransactionKind::Payment => format!("Payment for {}", transaction.number),
+        };
+        out.push_str(&format!(
+            "\n{} * ({}) {} | {}\n",
+            transaction.date,
+            ledger_text(&transaction.number),
+            ledger_text(&transaction.name),
+            description
+        ));
+        let summary: Vec<(String, i64)> = transaction
+            .summary()
+            .into_iter()
+            .map(|(account, amount)| (ledger_text(&account), amount))
+            .collect();
+        let width = summary
+            .iter()
+            .map(|(account, _)| account.chars().count())
+            .max()
+            .unwrap_or(0);
+        for (account, amount) in summary {
+            out.push_str(&format!(
+                "    {:<width$}  {:>10} {}\n",
+                account,
+                money(amount),
+                currency,
+                width = width
+            ));
+        }
+    }
+    out
+}
+
+// IIF fields are tab separated and can't contain tabs or line breaks
+fn iif_field(value: &str) -> String {
+    value.replace(['\t', '\r', '\n'], " ")
+}
+
+fn quickbooks_iif(transactions: &[Transaction]) -> String {
+    let mut out = String::new();
+    out.push_str("!TRNS\tTRNSTYPE\tDATE\tACCNT\tNAME\tAMOUNT\tDOCNUM\tMEMO\r\n");
+    out.push_str(
+        "!SPL\tTRNSTYPE\tDATE\tACCNT\tNAME\tAMOUNT\tDOCNUM\tMEMO\tQNTY\tPRICE\tINVITEM\r\n",
+    );
+    out.push_str("!ENDTRNS\r\n");
+    for transaction in transactions {
+        let kind = match transaction.kind {
+            TransactionKind::Invoice => "INVOICE",
+            TransactionKind::CreditNote => "CREDIT MEMO",
+            TransactionKind::Payment => "PAYMENT",
+        };
+        let date = reformat_date(&transaction.date, "%m/%d/%Y");
+        for (i, posting) in transaction.postings.iter().enumerate() {
+            let mut fields = vec![
+                if i == 0 { "TRNS" } else { "SPL" }.to_string(),
+                kind.to_string(),
+                date.clone(),
+                iif_field(&posting.account),
+                iif_field(&transaction.name),
+                money(posting.amount),
+                iif_field(&transaction.number),
+                iif_field(&posting.memo),
+            ];
+            if i > 0 {
+                // split quantities carry the sign of the amount
+                match &posting.line {
+                    Some(line) => {
+                        let quantity = if posting.amount.signum() == 1 {
+                            line.quantity.abs()
+                        } else {
+                            -line.quantity.abs()
+                        };
+                        fields.push(quantity.to_string());
+                        fields.push(line.price.abs().to_string());
+                        fields.push(iif_field(line.sku.as_deref().unwrap_or("")));
+                    }
+                    None => fields.extend([String::new(), String::new(), String::new()]),
+                }
+            }
+            out.push_str(&fields.join("\t"));
+            out.push_str("\r\n");
+        }
+        out.push_str("ENDTRNS\r\n");
+    }
+    out
+}
+
+// an item line of an import that lists invoice lines, with the tax charged on it
+struct ItemRow<'a> {
+    exported: &'a ExportedInvoice,
+    description: &'a str,
+    sku: &'a str,
+    quantity: f64,
+    price: f64,
+    amount: f64,
+    tax_code: &'a str,
+    tax: f64,
+}
+
+fn item_rows(data: &ExportData) -> Vec<ItemRow<'_>> {
+    let mut rows = Vec::new();
+    for exported in &data.invoices {
+        for item in exported
+            .items
+            .iter()
+            .filter(|item| item.kind == LineKind::Item)
+        {
+            let amount = round_cents(item.quantity * item.price);
+            let percent = tax_category(&item.tax_code).map_or(0.0, |category| category.percent);
+            rows.push(ItemRow {
+                exported,
+                description: &item.description,
+                sku: item.sku.as_deref().unwrap_or(""),
+                quantity: item.quantity,
+                price: item.price,
+                amount,
+                tax_code: &item.tax_code,
+                tax: round_cents(amount * percent / 100.0),
+            });
+        }
+    }
+    rows
+}
+
+fn quickbooks_csv(data: &ExportData, accounts: &AccountMap, currency: &str) -> String {
+    let rows = item_rows(data)
+        .into_iter()
+        .map(|row| {
+            let invoice = &row.exported.invoice;
+            vec![
+                invoice.invoice_id.clone(),
+                invoice.customer.clone(),
+                reformat_date(&invoice.issue_date, "%m/%d/%Y"),
+                due_date(&invoice.issue_date, accounts, "%m/%d/%Y"),
+                invoice.text.terms.clone(),
+                invoice.text.notes.clone(),
+                row.sku.to_string(),
+                row.description.to_string(),
+                row.quantity.to_string(),
+                row.price.to_string(),
+                format!("{:.2}", row.amount),
+                accounts.tax_type(row.tax_code),
+                format!("{:.2}", row.tax),
+                currency.to_string(),
+            ]
+        })
+        .collect();
+    csv_table(
+        &[
+            "InvoiceNo",
+            "Customer",
+            "InvoiceDate",
+            "DueDate",
+            "Terms",
+            "Memo",
+            "Item(Product/Service)",
+            "ItemDescription",
+            "ItemQuantity",
+            "ItemRate",
+            "ItemAmount",
+            "ItemTaxCode",
+            "ItemTaxAmount",
+            "Currency",
+        ],
+        rows,
+    )
+}
+
+// Xero reads dates in the organisation's regional format, day first is the common case
+fn xero_csv(data: &ExportData, accounts: &AccountMap, currency: &str) -> String {
+    let customers: HashMap<&str, &Customer> = data
+        .customers
+        .iter()
+        .map(|customer| (customer.company.as_str(), customer))
+        .collect();
+    let empty = Customer::default();
+    let rows = item_rows(data)
+        .into_iter()
+        .map(|row| {
+            let invoice = &row.exported.invoice;
+            let customer = customers
+                .get(invoice.customer.as_str())
+                .copied()
+                .unwrap_or(&empty);
+            let reference = if invoice.text.po_number.is_empty() {
+                invoice.text.reference.clone()
+            } else {
+                invoice.text.po_number.clone()
+            };
+            vec![
+                invoice.customer.clone(),
+                String::new(),
+                customer.address.clone(),
+                String::new(),
+                String::new(),
+                String::new(),
+                customer.city.clone(),
+                String::new(),
+                customer.postal_code.clone(),
+                customer.country.clone(),
+                invoice.invoice_id.clone(),
+                reference,
+                reformat_date(&invoice.issue_date, "%d/%m/%Y"),
+                due_date(&invoice.issue_date, accounts, "%d/%m/%Y"),
+                String::new(),
+                row.sku.to_string(),
+                row.description.to_string(),
+                row.quantity.to_string(),
+                row.price.to_string(),
+                String::new(),
+                accounts.revenue.clone(),
+                accounts.tax_type(row.tax_code),
+                format!("{:.2}", row.tax),
+                String::new(),
+                String::new(),
+                String::new(),
+                String::new(),
+                currency.to_string(),
+                String::new(),
+            ]
+        })
+        .collect();
+    csv_table(
+        &[
+            "*ContactName",
+            "EmailAddress",
+            "POAddressLine1",
+            "POAddressLine2",
+            "POAddressLine3",
+            "POAddressLine4",
+            "POCity",
+            "PORegion",
+            "POPostalCode",
+            "POCountry",
+            "*InvoiceNumber",
+            "Reference",
+            "*InvoiceDate",
+            "*DueDate",
+            "Total",
+            "InventoryItemCode",
+            "*Description",
+            "*Quantity",
+            "*UnitAmount",
+            "Discount",
+            "*AccountCode",
+            "*TaxType",
+            "TaxAmount",
+            "TrackingName1",
+            "TrackingOption1",
+            "TrackingName2",
+            "TrackingOption2",
+            "Currency",
+            "BrandingTheme",
+        ],
+        rows,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{NewInvoice, Store};
+    use crate::export::{collect, ExportFilter};
+    use crate::structs::{DatabaseData, Invoice, InvoiceStatus, InvoiceText};
+    use crate::test_support::issue;
+    use std::fs;
+
+    fn item(description: &str, quantity: f64, price: f64, tax_code: &str) -> DatabaseData {
+        DatabaseData {
+            description: description.to_string(),
+            quantity,
+            price,
+            total: quantity * price,
+            tax_code: tax_code.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn exported(number: usize, issue_date: &str, items: Vec<DatabaseData>) -> ExportedInvoice {
+        ExportedInvoice {
+            invoice: Invoice {
+                invoice_id: format!("ACME-{}", number),
+                cust_id: "ACME".to_string(),
+                estimate_number: number,
+                customer: "Acme; Sons".to_string(),
+                contact: "Fake Co.".to_string(),
+                issue_date: issue_date.to_string(),
+                grand_total: crate::totals::gross_total(&items),
+                file_path: None,
+                file_hash: None,
+                text: InvoiceText {
+                    po_number: "PO-7".to_string(),
+                    ..Default::default()
+                },
+                status: InvoiceStatus::Issued,
+            },
+            items,
+        }
+    }
+
+    fn data() -> ExportData {
+        ExportData {
+            invoices: vec![
+                exported(
+                    1,
+                    "2024-03-01",
+                    vec![
+                        DatabaseData {
+                            kind: LineKind::Heading,
+                            description: "Website".to_string(),
+                            ..Default::default()
+                        },
+                        item("Design", 2.0, 33.335, "S:21"),
+                        item("Hosting", 1.0, 10.0, "S:9"),
+                        item("Domain", 1.0, 5.0, "Z"),
+                    ],
+                ),
+                exported(2, "2024-03-10", vec![item("Refund", -1.0, 10.0, "S:21")]),
+            ],
+            customers: vec![Customer {
+                company: "Acme; Sons".to_string(),
+                city: "Town".to_string(),
+                ..Default::default()
+            }],
+            contacts: Vec::new(),
+            payments: vec![Payment {
+                payment_id: 1,
+                invoice_id: "ACME-1".to_string(),
+                paid_on: "2024-03-05".to_string(),
+                amount: 100.0,
+                method: "transfer".to_string(),
+                ..Default::default()
+            }],
+        }
+    }
+
+    #[test]
+    fn test_transactions_balance() {
+        let accounts = AccountMap::default();
+        let transactions = transactions(&data(), &accounts);
+        let kinds: Vec<TransactionKind> = transactions.iter().map(|t| t.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                TransactionKind::Invoice,
+                TransactionKind::Payment,
+                TransactionKind::CreditNote
+            ]
+        );
+        assert!(transactions.iter().all(|t| t.balance() == 0));
+        // 66.67 + 10 + 5 net, 14.00 + 0.90 tax
+        assert_eq!(
+            transactions[0].summary(),
+            vec![
+                ("Accounts Receivable".to_string(), 9657),
+                ("Sales".to_string(), -8167),
+                ("Sales Tax Payable".to_string(), -1490),
+            ]
+        );
+        assert_eq!(transactions[1].name, "Acme; Sons");
+        assert_eq!(
+            transactions[2].summary(),
+            vec![
+                ("Accounts Receivable".to_string(), -1210),
+                ("Sales".to_string(), 1000),
+                ("Sales Tax Payable".to_string(), 210),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_full_payment_settles_receivable() {
+        let mut store = Store::open_in_memory().unwrap();
+        let items = vec![
+            DatabaseData {
+                row_number: 0,
+                ..item("Design", 2.0, 33.335, "S:21")
+            },
+            DatabaseData {
+                row_number: 1,
+                ..item("Hosting", 1.0, 10.0, "S:9")
+            },
+        ];
+        let invoice = NewInvoice {
+            cust_id: "ACME".to_string(),
+            customer: Customer {
+                company: "Acme".to_string(),
+                ..Default::default()
+            },
+            contact_company: "Fake Co.".to_string(),
+            items,
+            text: InvoiceText::default(),
+        };
+        let (issued, path) = issue(&mut store, &invoice, "accounting", b"%PDF-1.4");
+        // the stored grand total is what the customer owes, VAT included
+        let invoice = store.invoice(&issued.invoice_id).unwrap().unwrap();
+        assert_eq!(invoice.grand_total, 91.57);
+        store
+            .save_payment(&Payment {
+                invoice_id: invoice.invoice_id.clone(),
+                paid_on: "2024-03-05".to_string(),
+                amount: invoice.grand_total,
+                method: "transfer".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+
+        let accounts = AccountMap::default();
+        let data = collect(&store, &ExportFilter::default()).unwrap();
+        let receivable: i64 = transactions(&data, &accounts)
+            .iter()
+            .flat_map(|transaction| transaction.postings.iter())
+            .filter(|posting| posting.account == accounts.receivable)
+            .map(|posting| posting.amount)
+            .sum();
+        assert_eq!(receivable, 0);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_formats() {
+        let accounts = AccountMap {
+            revenue: "200".to_string(),
+            tax_types: vec![("S:21".to_string(), "21% (VAT on Income)".to_string())],
+            ..Default::default()
+        };
+        let data = data();
+
+        let ledger = render(AccountingFormat::Ledger, &data, &accounts, "EUR");
+        assert!(ledger.contains("\n2024-03-01 * (ACME-1) Acme, Sons | Invoice ACME-1\n"));
+        assert!(ledger.contains("    Accounts Receivable       96.57 EUR\n"));
+        assert!(ledger.contains("\n2024-03-10 * (ACME-2) Acme, Sons | Credit note ACME-2\n"));
+
+        let journal = render(AccountingFormat::JournalCsv, &data, &accounts, "EUR");
+        let lines: Vec<&str> = journal.lines().collect();
+        assert_eq!(lines[0], "date,number,type,name,account,debit,credit");
+        assert_eq!(
+            lines[1],
+            "2024-03-01,ACME-1,invoice,Acme; Sons,Accounts Receivable,96.57,"
+        );
+        assert_eq!(lines.len(), 1 + 3 + 2 + 3);
+
+        let iif = render(AccountingFormat::QuickBooksIif, &data, &accounts, "EUR");
+        assert!(iif.contains(
+            "TRNS\tINVOICE\t03/01/2024\tAccounts Receivable\tAcme; Sons\t96.57\tACME-1\tInvoice ACME-1\r\n"
+        ));
+        assert!(iif.contains(
+            "SPL\tINVOICE\t03/01/2024\t200\tAcme; Sons\t-66.67\tACME-1\tDesign\t-2\t33.335\t\r\n"
+        ));
+        assert!(
+            iif.contains("TRNS\tCREDIT MEMO\t03/10/2024\tAccounts Receivable\tAcme; Sons\t-12.10")
+        );
+        assert!(iif.contains("TRNS\tPAYMENT\t03/05/2024\tUndeposited Funds\tAcme; Sons\t100.00"));
+        assert_eq!(iif.matches("ENDTRNS\r\n").count(), 4);
+
+        let xero = render(AccountingFormat::XeroCsv, &data, &accounts, "EUR");
+        let lines: Vec<&str> = xero.lines().collect();
+        assert!(lines[0].starts_with("*ContactName,EmailAddress,"));
+        assert_eq!(lines.len(), 1 + 4);
+        assert!(lines[1].starts_with("Acme; Sons,,,,,,Town,,,,ACME-1,PO-7,01/03/2024,31/03/2024,,,Design,2,33.335,,200,21% (VAT on Income),14.00,"));
+        assert!(lines[3].contains(",Domain,1,5,,200,Z,0.00,"));
+
+        let quickbooks = render(AccountingFormat::QuickBooksCsv, &data, &accounts, "EUR");
+        let lines: Vec<&str> = quickbooks.lines().collect();
+        assert!(lines[0].starts_with("InvoiceNo,Customer,InvoiceDate,DueDate,"));
+        assert!(lines[4].starts_with("ACME-2,Acme; Sons,03/10/2024,04/09/2024,"));
+        assert!(lines[4].contains(",Refund,-1,10,-10.00,21% (VAT on Income),-2.10,EUR"));
+    }
+
+    #[test]
+    fn test_money() {
+        assert_eq!(money(9657), "96.57");
+        assert_eq!(money(-5), "-0.05");
+        assert_eq!(money(0), "0.00");
+        assert_eq!(
+            AccountingFormat::from_key("Xero"),
+            Some(AccountingFormat::XeroCsv)
+        );
+    }
+}
@@ -0,0 +1,735 @@
+//! Backup archives: a gzip compressed tar holding a consistent snapshot of the database taken
+//! with SQLite's online backup, the logo and the templates folder, described by a manifest with
+//! a checksum per file. Restoring validates all of that before the live database is replaced.
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chrono::{Duration, NaiveDateTime};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rusqlite::{Connection, ErrorCode, OpenFlags};
+
+use crate::db::{self, Store};
+use crate::paths;
+use crate::settings::BackupSettings;
+
+pub const FORMAT_NAME: &str = "invoicy-backup";
+pub const FORMAT_VERSION: u32 = 1;
+
+const MANIFEST: &str = "manifest.txt";
+const DATABASE: &str = "invoicy.db";
+const LOGO: &str = "logo.jpg";
+const TEMPLATES: &str = "templates";
+const FILE_PREFIX: &str = "invoicy-backup-";
+const FILE_SUFFIX: &str = ".tar.gz";
+const STAMP_FORMAT: &str = "%Y%m%d-%H%M%S";
+const CREATED_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+#[derive(Debug)]
+pub enum BackupError {
+    Database(rusqlite::Error),
+    File(io::Error),
+    Invalid(String),
+    // the backup is encrypted and the passphrase given does not open it
+    Passphrase,
+}
+
+impl fmt::Display for BackupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackupError::Database(e) => write!(f, "Unable to copy the database: {}", e),
+            BackupError::File(e) => write!(f, "Unable to access the backup: {}", e),
+            BackupError::Invalid(reason) => write!(f, "Not a usable backup: {}", reason),
+            BackupError::Passphrase => {
+                write!(
+                    f,
+                    "The backup is encrypted, enter the passphrase it was made with"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for BackupError {}
+
+impl From<rusqlite::Error> for BackupError {
+    fn from(e: rusqlite::Error) -> Self {
+        BackupError::Database(e)
+    }
+}
+
+impl From<io::Error> for BackupError {
+    fn from(e: io::Error) -> Self {
+        BackupError::File(e)
+    }
+}
+
+// files kept next to the database that belong in a backup
+#[derive(Clone, Debug)]
+pub struct Assets {
+    pub logo: PathBuf,
+    pub templates: PathBuf,
+}
+
+impl Assets {
+    pub fn in_data_dir() -> Self {
+        Self {
+            logo: paths::logo_path(),
+            templates: paths::templates_dir(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Manifest {
+    pub created_at: String,
+    pub schema_version: i32,
+    pub app_version: String,
+    // the database inside is encrypted with the passphrase of the database it was made from
+    pub encrypted: bool,
+    // archive path and sha256 of every file besides the manifest
+    pub files: Vec<(String, String)>,
+}
+
+impl Manifest {
+    fn serialize(&self) -> String {
+        let mut out = format!(
+            "format={}\nversion={}\ncreated_at={}\nschema_version={}\napp_version={}\nencrypted={}\n",
+            FORMAT_NAME,
+            FORMAT_VERSION,
+            self.created_at,
+            self.schema_version,
+            self.app_version,
+            self.encrypted
+        );
+        for (name, hash) in &self.files {
+            out.push_str(&format!("sha256.{}={}\n", name, hash));
+        }
+        out
+    }
+
+    fn parse(text: &str) -> Result<Self, BackupError> {
+        let mut format = None;
+        let mut version = None;
+        let mut manifest = Manifest::default();
+        for line in text.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            match key {
+                "format" => format = Some(value),
+                "version" => version = value.parse::<u32>().ok(),
+                "created_at" => manifest.created_at = value.to_string(),
+                "schema_version" => {
+                    manifest.schema_version = value.parse().map_err(|_| {
+                        BackupError::Invalid(format!("schema version {} is not a number", value))
+                    })?
+                }
+                "app_version" => manifest.app_version = value.to_string(),
+                "encrypted" => manifest.encrypted = value == "true",
+                _ => {
+                    if let Some(name) = key.strip_prefix("sha256.") {
+                        manifest.files.push((name.to_string(), value.to_string()));
+                    }
+                }
+            }
+        }
+        if format != Some(FORMAT_NAME) {
+            return Err(BackupError::Invalid(
+                "the manifest is not an Invoicy backup manifest".to_string(),
+            ));
+        }
+        match version {
+            Some(version) if version <= FORMAT_VERSION => Ok(manifest),
+            Some(version) => Err(BackupError::Invalid(format!(
+                "archive version {} needs a newer Invoicy",
+                version
+            ))),
+            None => Err(BackupError::Invalid(
+                "the manifest has no archive version".to_string(),
+            )),
+        }
+    }
+}
+
+pub fn archive_name(created: NaiveDateTime) -> String {
+    format!(
+        "{}{}{}",
+        FILE_PREFIX,
+        created.format(STAMP_FORMAT),
+        FILE_SUFFIX
+    )
+}
+
+// the time a backup was taken, read from its file name so copies keep their place in the list
+pub fn archive_time(path: &Path) -> Option<NaiveDateTime> {
+    let name = path.file_name()?.to_str()?;
+    let stamp = name.strip_prefix(FILE_PREFIX)?.strip_suffix(FILE_SUFFIX)?;
+    NaiveDateTime::parse_from_str(stamp, STAMP_FORMAT).ok()
+}
+
+// writes a new archive into `dir` and returns its path. The archive is written under a temporary
+// name first so an interrupted backup never looks like a complete one.
+pub fn create_backup(
+    store: &Store,
+    dir: &Path,
+    assets: &Assets,
+    now: NaiveDateTime,
+) -> Result<PathBuf, BackupError> {
+    fs::create_dir_all(dir)?;
+    let staging = staging_dir()?;
+    let result = write_archive(store, dir, assets, now, &staging);
+    if let Err(e) = fs::remove_dir_all(&staging) {
+        println!("ERROR: Unable to remove {:?}: {}", staging, e);
+    }
+    result
+}
+
+fn write_archive(
+    store: &Store,
+    dir: &Path,
+    assets: &Assets,
+    now: NaiveDateTime,
+    staging: &Path,
+) -> Result<PathBuf, BackupError> {
+    let snapshot = staging.join(DATABASE);
+    store.backup_to(&snapshot)?;
+    let mut files = vec![(DATABASE.to_string(), snapshot)];
+    if assets.logo.is_file() {
+        files.push((LOGO.to_string(), assets.logo.clone()));
+    }
+    if assets.templates.is_dir() {
+        collect_files(&assets.templates, TEMPLATES, &mut files)?;
+    }
+    let manifest = Manifest {
+        created_at: now.format(CREATED_FORMAT).to_string(),
+        schema_version: store.schema_version()?,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        encrypted: store.is_encrypted(),
+        files: files
+            .iter()
+            .map(|(name, path)| Ok((name.clone(), db::hash_file(path)?)))
+            .collect::<io::Result<_>>()?,
+    };
+
+    let path = dir.join(archive_name(now));
+    let partial = dir.join(format!("{}.partial", archive_name(now)));
+    let encoder = GzEncoder::new(File::create(&partial)?, Compression::default());
+    let mut builder = tar::Builder::new(encoder);
+    let text = manifest.serialize();
+    let mut header = tar::Header::new_gnu();
+    header.set_size(text.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(now.and_utc().timestamp().max(0) as u64);
+    header.set_cksum();
+    builder.append_data(&mut header, MANIFEST, text.as_bytes())?;
+    for (name, source) in &files {
+        builder.append_path_with_name(source, name)?;
+    }
+    builder.into_inner()?.finish()?.sync_all()?;
+    fs::rename(&partial, &path)?;
+    Ok(path)
+}
+
+// every file below `dir`, named as `prefix/relative/path` inside the archive
+fn collect_files(dir: &Path, prefix: &str, files: &mut Vec<(String, PathBuf)>) -> io::Result<()> {
+    let mut entries = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let Some(file_name) = entry.file_name().to_str().map(str::to_string) else {
+            continue;
+        };
+        let name = format!("{}/{}", prefix, file_name);
+        let path = entry.path();
+        if path.is_dir() {
+            collect_files(&path, &name, files)?;
+        } else if path.is_file() {
+            files.push((name, path));
+        }
+    }
+    Ok(())
+}
+
+// an archive unpacked into a temporary folder and checked, removed again when dropped
+pub struct ValidatedBackup {
+    pub manifest: Manifest,
+    staging: PathBuf,
+    // the passphrase that opened an encrypted backup
+    passphrase: Option<String>,
+}
+
+impl fmt::Debug for ValidatedBackup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ValidatedBackup")
+            .field("manifest", &self.manifest)
+            .field("staging", &self.staging)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for ValidatedBackup {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_dir_all(&self.staging) {
+            println!("ERROR: Unable to remove {:?}: {}", self.staging, e);
+        }
+    }
+}
+
+impl ValidatedBackup {
+    pub fn has_logo(&self) -> bool {
+        self.manifest.files.iter().any(|(name, _)| name == LOGO)
+    }
+
+    pub fn template_count(&self) -> usize {
+        self.manifest
+            .files
+            .iter()
+            .filter(|(name, _)| name.starts_with(&format!("{}/", TEMPLATES)))
+            .count()
+    }
+
+    // replaces the live database, then the logo and templates when the archive has them
+    pub fn restore(&self, store: &mut Store, assets: &Assets) -> Result<(), BackupError> {
+        store.restore_from(&self.staging.join(DATABASE), self.passphrase.as_deref())?;
+        if self.has_logo() {
+            if let Some(parent) = assets.logo.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::copy(self.staging.join(LOGO), &assets.logo)?;
+        }
+        if self.template_count() > 0 {
+            if assets.templates.exists() {
+                fs::remove_dir_all(&assets.templates)?;
+            }
+            let mut files = Vec::new();
+            collect_files(&self.staging.join(TEMPLATES), "", &mut files)?;
+            for (name, source) in files {
+                let target = assets.templates.join(name.trim_start_matches('/'));
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::copy(source, target)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// unpacks and checks an archive without touching the live data: the manifest, every checksum,
+// the database's integrity and that its schema is one this version can open. `passphrase` is
+// only used when the backup is encrypted.
+pub fn open_backup(
+    archive: &Path,
+    passphrase: Option<&str>,
+) -> Result<ValidatedBackup, BackupError> {
+    let staging = staging_dir()?;
+    // built first so the folder is removed on every early return below
+    let mut backup = ValidatedBackup {
+        manifest: Manifest::default(),
+        staging,
+        passphrase: None,
+    };
+    unpack(archive, &backup.staging)?;
+    let text = fs::read_to_string(backup.staging.join(MANIFEST))
+        .map_err(|_| BackupError::Invalid(format!("{} has no manifest", archive.display())))?;
+    backup.manifest = Manifest::parse(&text)?;
+    for (name, expected) in &backup.manifest.files {
+        let path = backup.staging.join(name);
+        let actual = db::hash_file(&path)
+            .map_err(|_| BackupError::Invalid(format!("{} is missing", name)))?;
+        if &actual != expected {
+            return Err(BackupError::Invalid(format!(
+                "{} does not match its checksum",
+                name
+            )));
+        }
+    }
+    if !backup
+        .manifest
+        .files
+        .iter()
+        .any(|(name, _)| name == DATABASE)
+    {
+        return Err(BackupError::Invalid(
+            "the archive has no database".to_string(),
+        ));
+    }
+    if backup.manifest.encrypted {
+        backup.passphrase = Some(passphrase.ok_or(BackupError::Passphrase)?.to_string());
+    }
+    check_database(
+        &backup.staging.join(DATABASE),
+        backup.manifest.schema_version,
+        backup.passphrase.as_deref(),
+    )?;
+    Ok(backup)
+}
+
+fn unpack(archive: &Path, staging: &Path) -> Result<(), BackupError> {
+    let mut tar = tar::Archive::new(GzDecoder::new(File::open(archive)?));
+    let entries = tar
+        .entries()
+        .map_err(|_| BackupError::Invalid(format!("{} is not a backup", archive.display())))?;
+    for entry in entries {
+        let mut entry =
+            entry.map_err(|_| BackupError::Invalid(format!("{} is damaged", archive.display())))?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = entry.path()?.into_owned();
+        // names stay inside the staging folder, no absolute paths or `..`
+        if !name
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            return Err(BackupError::Invalid(format!(
+                "{} points outside the archive",
+                name.display()
+            )));
+        }
+        let target = staging.join(&name);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut bytes = Vec::new();
+        entry
+            .read_to_end(&mut bytes)
+            .map_err(|_| BackupError::Invalid(format!("{} is damaged", archive.display())))?;
+        fs::write(target, bytes)?;
+    }
+    Ok(())
+}
+
+fn check_database(
+    path: &Path,
+    expected_version: i32,
+    passphrase: Option<&str>,
+) -> Result<(), BackupError> {
+    let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    if let Some(passphrase) = passphrase {
+        connection.pragma_update(None, "key", passphrase)?;
+    }
+    let integrity: String = connection
+        .pragma_query_value(None, "integrity_check", |row| row.get(0))
+        .map_err(|e| match e.sqlite_error_code() {
+            Some(ErrorCode::NotADatabase) if passphrase.is_some() => BackupError::Passphrase,
+            _ => BackupError::Database(e),
+        })?;
+    if integrity != "ok" {
+        return Err(BackupError::Invalid(format!(
+            "the database is damaged: {}",
+            integrity
+        )));
+    }
+    let version: i32 = connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    if version > db::SCHEMA_VERSION {
+        return Err(BackupError::Invalid(format!(
+            "the database uses schema version {}, this version of Invoicy reads up to {}",
+            version,
+            db::SCHEMA_VERSION
+        )));
+    }
+    if version != expected_version {
+        return Err(BackupError::Invalid(format!(
+            "the database has schema version {} but the manifest says {}",
+            version, expected_version
+        )));
+    }
+    for table in db::REQUIRED_TABLES {
+        let found: i64 = connection.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+            [table],
+            |row| row.get(0),
+        )?;
+        if found == 0 {
+            return Err(BackupError::Invalid(format!(
+                "the database has no {} table",
+                table
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn staging_dir() -> io::Result<PathBuf> {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos())
+        .unwrap_or(0);
+    let dir = std::env::temp_dir().join(format!("invoicy-backup-{}-{}", std::process::id(), nanos));
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+// archives in `dir`, newest first. Other files in the folder are left alone.
+pub fn backups(dir: &Path) -> Vec<(PathBuf, NaiveDateTime)> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut found: Vec<_> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter_map(|path| archive_time(&path).map(|time| (path, time)))
+        .collect();
+    found.sort_by_key(|(_, created)| std::cmp::Reverse(*created));
+    found
+}
+
+// the archives, newest first, that fall outside the retention rules. The newest archive is
+// always kept, whatever the rules say.
+pub fn expired<T: Clone>(
+    archives: &[(T, NaiveDateTime)],
+    settings: &BackupSettings,
+    now: NaiveDateTime,
+) -> Vec<T> {
+    let cutoff = (settings.keep_days > 0).then(|| now - Duration::days(settings.keep_days.into()));
+    archives
+        .iter()
+        .enumerate()
+        .skip(1)
+        .filter(|(idx, (_, created))| {
+            *idx >= settings.keep.max(1) || cutoff.is_some_and(|cutoff| *created < cutoff)
+        })
+        .map(|(_, (archive, _))| archive.clone())
+        .collect()
+}
+
+// removes archives beyond the retention rules and returns them
+pub fn prune(
+    dir: &Path,
+    settings: &BackupSettings,
+    now: NaiveDateTime,
+) -> io::Result<Vec<PathBuf>> {
+    let removed = expired(&backups(dir), settings, now);
+    for path in &removed {
+        fs::remove_file(path)?;
+    }
+    Ok(removed)
+}
+
+pub fn backup_due(dir: &Path, settings: &BackupSettings, now: NaiveDateTime) -> bool {
+    if settings.interval_hours == 0 {
+        return false;
+    }
+    backups(dir)
+        .first()
+        .is_none_or(|(_, latest)| now - *latest >= Duration::hours(settings.interval_hours.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structs::Customer;
+    use crate::test_support::at;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "invoicy-backup-test-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn customer(company: &str) -> Customer {
+        Customer {
+            company: company.to_string(),
+            ..Default::default()
+        }
+    }
+
+    // writes a tar.gz with the given entries, for archives the app would never produce
+    fn write_tar(path: &Path, entries: &[(&str, &[u8])]) {
+        let encoder = GzEncoder::new(File::create(path).unwrap(), Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        for (name, bytes) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(bytes.len() as u64);
+            header.set_mode(0o644);
+            // set_path refuses `..`, so the name is written into the raw header
+            header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+            header.set_cksum();
+            builder.append(&header, *bytes).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+    }
+
+    #[test]
+    fn test_backup_round_trip() {
+        let dir = temp_dir("round-trip");
+        let assets = Assets {
+            logo: dir.join("data/logo.jpg"),
+            templates: dir.join("data/templates"),
+        };
+        fs::create_dir_all(assets.templates.join("mail")).unwrap();
+        fs::write(&assets.logo, b"logo").unwrap();
+        fs::write(assets.templates.join("mail/reminder.txt"), b"Dear {name}").unwrap();
+
+        let mut store = Store::open_in_memory().unwrap();
+        store.save_customer(&customer("Acme")).unwrap();
+        let archive = create_backup(&store, &dir.join("backups"), &assets, at(9)).unwrap();
+        assert_eq!(
+            archive.file_name().unwrap(),
+            "invoicy-backup-20240301-090000.tar.gz"
+        );
+        assert_eq!(archive_time(&archive), Some(at(9)));
+
+        store.save_customer(&customer("Globex")).unwrap();
+        fs::write(&assets.logo, b"new logo").unwrap();
+        fs::remove_dir_all(&assets.templates).unwrap();
+
+        let backup = open_backup(&archive, None).unwrap();
+        assert_eq!(backup.manifest.schema_version, db::SCHEMA_VERSION);
+        assert_eq!(backup.manifest.created_at, "2024-03-01 09:00:00");
+        assert!(backup.has_logo());
+        assert_eq!(backup.template_count(), 1);
+        backup.restore(&mut store, &assets).unwrap();
+
+        let companies: Vec<_> = store
+            .customers()
+            .unwrap()
+            .into_iter()
+            .map(|c| c.company)
+            .collect();
+        assert_eq!(companies, vec!["Acme".to_string()]);
+        assert_eq!(fs::read(&assets.logo).unwrap(), b"logo");
+        assert_eq!(
+            fs::read(assets.templates.join("mail/reminder.txt")).unwrap(),
+            b"Dear {name}"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_encrypted_backup() {
+        let dir = temp_dir("encrypted");
+        let assets = Assets {
+            logo: dir.join("data/logo.jpg"),
+            templates: dir.join("data/templates"),
+        };
+        let database = dir.join("invoicy.db");
+        let mut store = crate::encryption::open(&database, None).unwrap();
+        crate::encryption::set_passphrase(&mut store, &database, Some("correct horse")).unwrap();
+        store.save_customer(&customer("Acme")).unwrap();
+        let archive = create_backup(&store, &dir.join("backups"), &assets, at(9)).unwrap();
+
+        assert!(matches!(
+            open_backup(&archive, None),
+            Err(BackupError::Passphrase)
+        ));
+        assert!(matches!(
+            open_backup(&archive, Some("battery staple")),
+            Err(BackupError::Passphrase)
+        ));
+        let backup = open_backup(&archive, Some("correct horse")).unwrap();
+        assert!(backup.manifest.encrypted);
+
+        // restored into a plain database the data is stored in plain text again
+        let mut plain = Store::open_in_memory().unwrap();
+        backup.restore(&mut plain, &assets).unwrap();
+        assert!(!plain.is_encrypted());
+        assert_eq!(plain.customers().unwrap()[0].company, "Acme");
+
+        // and into a database with another passphrase it takes that passphrase
+        store.save_customer(&customer("Globex")).unwrap();
+        crate::encryption::set_passphrase(&mut store, &database, Some("battery staple")).unwrap();
+        backup.restore(&mut store, &assets).unwrap();
+        drop(store);
+        let reopened = crate::encryption::open(&database, Some("battery staple")).unwrap();
+        assert_eq!(reopened.customers().unwrap().len(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_rejects_invalid_archives() {
+        let dir = temp_dir("invalid");
+        let store = Store::open_in_memory().unwrap();
+        let snapshot = dir.join("snapshot.db");
+        store.backup_to(&snapshot).unwrap();
+        let database = fs::read(&snapshot).unwrap();
+        let hash = db::hash_file(&snapshot).unwrap();
+        let manifest = |schema: i32, hash: &str| {
+            format!(
+                "format=invoicy-backup\nversion=1\ncreated_at=2024-03-01 09:00:00\n\
+                 schema_version={}\napp_version=1.0.0\nsha256.invoicy.db={}\n",
+                schema, hash
+            )
+        };
+        let archive = dir.join("archive.tar.gz");
+        let reason = |entries: &[(&str, &[u8])]| {
+            write_tar(&archive, entries);
+            match open_backup(&archive, None) {
+                Err(BackupError::Invalid(reason)) => reason,
+                other => panic!("expected an invalid backup, got {:?}", other),
+            }
+        };
+
+        let good = manifest(db::SCHEMA_VERSION, &hash);
+        write_tar(
+            &archive,
+            &[(MANIFEST, good.as_bytes()), (DATABASE, &database)],
+        );
+        assert!(open_backup(&archive, None).is_ok());
+
+        let tampered = manifest(db::SCHEMA_VERSION, &"0".repeat(64));
+        assert!(
+            reason(&[(MANIFEST, tampered.as_bytes()), (DATABASE, &database)]).contains("checksum")
+        );
+        let newer = manifest(db::SCHEMA_VERSION + 1, &hash);
+        assert!(
+            reason(&[(MANIFEST, newer.as_bytes()), (DATABASE, &database)])
+                .contains("schema version")
+        );
+        assert!(reason(&[(DATABASE, &database)]).contains("no manifest"));
+        assert!(reason(&[
+            (MANIFEST, good.as_bytes()),
+            (DATABASE, &database),
+            ("../escape.txt", b"x"),
+        ])
+        .contains("outside the archive"));
+
+        fs::write(&archive, b"not a tar").unwrap();
+        assert!(open_backup(&archive, None).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_retention() {
+        let dir = temp_dir("retention");
+        for hour in [1, 2, 3, 4] {
+            fs::write(dir.join(archive_name(at(hour))), b"").unwrap();
+        }
+        fs::write(dir.join("notes.txt"), b"").unwrap();
+        let mut settings = BackupSettings {
+            dir: Some(dir.clone()),
+            interval_hours: 6,
+            keep: 3,
+            keep_days: 0,
+        };
+
+        assert!(!backup_due(&dir, &settings, at(9)));
+        assert!(backup_due(&dir, &settings, at(10)));
+
+        let removed = prune(&dir, &settings, at(10)).unwrap();
+        assert_eq!(removed, vec![dir.join(archive_name(at(1)))]);
+
+        // everything is past the age limit, only the newest one survives
+        settings.keep_days = 1;
+        let removed = prune(&dir, &settings, at(10) + Duration::days(2)).unwrap();
+        assert_eq!(removed.len(), 2);
+        let left: Vec<_> = backups(&dir).into_iter().map(|(_, time)| time).collect();
+        assert_eq!(left, vec![at(4)]);
+        assert!(dir.join("notes.txt").exists());
+
+        settings.interval_hours = 0;
+        assert!(!backup_due(&dir, &settings, at(10) + Duration::days(30)));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,409 @@
+//! UN/CEFACT Cross Industry Invoice (CII D16B), the XML half of a Factur-X / ZUGFeRD invoice
+//! in the EN 16931 profile. The figures come from the same model as the UBL export, only the
+//! Peppol specific rules are left out because Factur-X invoices don't travel over Peppol.
+
+use crate::structs::Contact;
+use crate::totals::{round_cents, TaxCategory, Totals};
+use crate::ubl::{
+    self, amount, decimal, exemption_reason, unit_code, EInvoice, Party, UblError, Violation, Xml,
+};
+
+const GUIDELINE_ID: &str = "urn:cen.eu:en16931:2017";
+const RSM: &str = "urn:un:unece:uncefact:data:standard:CrossIndustryInvoice:100";
+const RAM: &str =
+    "urn:un:unece:uncefact:data:standard:ReusableAggregateBusinessInformationEntity:100";
+const UDT: &str = "urn:un:unece:uncefact:data:standard:UnqualifiedDataType:100";
+
+// the name Factur-X requires for the embedded XML
+pub const FILE_NAME: &str = "factur-x.xml";
+
+// the EN 16931 rules, `ubl::validate` without the Peppol ones
+pub fn validate(e_invoice: &EInvoice) -> Vec<Violation> {
+    ubl::validate(e_invoice)
+        .into_iter()
+        .filter(|violation| !violation.rule.starts_with("PEPPOL-"))
+        .collect()
+}
+
+impl Xml {
+    fn trade_tax(&mut self, category: &TaxCategory, amounts: Option<(f64, f64)>) {
+        self.open("ram:ApplicableTradeTax");
+        if let Some((_, tax)) = amounts {
+            self.leaf("ram:CalculatedAmount", &amount(tax));
+        }
+        self.leaf("ram:TypeCode", "VAT");
+        if let Some((taxable, _)) = amounts {
+            if let Some(reason) = exemption_reason(&category.code) {
+                self.leaf("ram:ExemptionReason", reason);
+            }
+            self.leaf("ram:BasisAmount", &amount(taxable));
+        }
+        self.leaf("ram:CategoryCode", &category.code);
+        if category.code != "O" {
+            self.leaf("ram:RateApplicablePercent", &decimal(category.percent));
+        }
+        self.close("ram:ApplicableTradeTax");
+    }
+
+    fn trade_party(&mut self, tag: &str, party: Party, contact: Option<&Contact>) {
+        self.open(tag);
+        self.leaf("ram:Name", party.name);
+        if let Some(contact) = contact {
+            self.open("ram:DefinedTradeContact");
+            if !contact.name.trim().is_empty() {
+                self.leaf("ram:PersonName", contact.name.trim());
+            }
+            if !contact.telephone.trim().is_empty() {
+                self.open("ram:TelephoneUniversalCommunication");
+                self.leaf("ram:CompleteNumber", contact.telephone.trim());
+                self.close("ram:TelephoneUniversalCommunication");
+            }
+            if !contact.email.trim().is_empty() {
+                self.open("ram:EmailURIUniversalCommunication");
+                self.leaf("ram:URIID", contact.email.trim());
+                self.close("ram:EmailURIUniversalCommunication");
+            }
+            self.close("ram:DefinedTradeContact");
+        }
+        self.open("ram:PostalTradeAddress");
+        if !party.postal_code.trim().is_empty() {
+            self.leaf("ram:PostcodeCode", party.postal_code.trim());
+        }
+        if !party.street.trim().is_empty() {
+            self.leaf("ram:LineOne", party.street.trim());
+        }
+        self.leaf("ram:CityName", party.city.trim());
+        self.leaf("ram:CountryID", party.country.trim());
+        self.close("ram:PostalTradeAddress");
+        // the electronic address is optional outside Peppol
+        if let Some((scheme, id)) = party.endpoint_id.split_once(':') {
+            self.open("ram:URIUniversalCommunication");
+            self.leaf_with("ram:URIID", &[("schemeID", scheme.trim())], id.trim());
+            self.close("ram:URIUniversalCommunication");
+        }
+        if !party.vat_id.trim().is_empty() {
+            self.open("ram:SpecifiedTaxRegistration");
+            self.leaf_with("ram:ID", &[("schemeID", "VA")], party.vat_id.trim());
+            self.close("ram:SpecifiedTaxRegistration");
+        }
+        self.close(tag);
+    }
+}
+
+// the CII document, call `validate` first, the XML is only meaningful for a valid invoice
+pub fn to_xml(e_invoice: &EInvoice) -> String {
+    let EInvoice {
+        invoice,
+        seller,
+        buyer,
+        currency,
+        ..
+    } = e_invoice;
+    let totals = Totals::new(e_invoice.items);
+    let text = &invoice.text;
+
+    let mut xml = Xml::new();
+    xml.open(&format!(
+        "rsm:CrossIndustryInvoice xmlns:rsm=\"{}\" xmlns:ram=\"{}\" xmlns:udt=\"{}\"",
+        RSM, RAM, UDT
+    ));
+    xml.open("rsm:ExchangedDocumentContext");
+    xml.open("ram:GuidelineSpecifiedDocumentContextParameter");
+    xml.leaf("ram:ID", GUIDELINE_ID);
+    xml.close("ram:GuidelineSpecifiedDocumentContextParameter");
+    xml.close("rsm:ExchangedDocumentContext");
+
+    xml.open("rsm:ExchangedDocument");
+    xml.leaf("ram:ID", &invoice.invoice_id);
+    xml.leaf(
+        "ram:TypeCode",
+        if totals.credit_note { "381" } else { "380" },
+    );
+    xml.open("ram:IssueDateTime");
+    xml.leaf_with(
+        "udt:DateTimeString",
+        &[("format", "102")],
+        &invoice.issue_date.replace('-', ""),
+    );
+    xml.close("ram:IssueDateTime");
+    if !text.notes.trim().is_empty() {
+        xml.open("ram:IncludedNote");
+        xml.leaf("ram:Content", text.notes.trim());
+        xml.close("ram:IncludedNote");
+    }
+    xml.close("rsm:ExchangedDocument");
+
+    xml.open("rsm:SupplyChainTradeTransaction");
+    for (idx, line) in totals.lines.iter().enumerate() {
+        xml.open("ram:IncludedSupplyChainTradeLineItem");
+        xml.open("ram:AssociatedDocumentLineDocument");
+        xml.leaf("ram:LineID", &(idx + 1).to_string());
+        xml.close("ram:AssociatedDocumentLineDocument");
+        xml.open("ram:SpecifiedTradeProduct");
+        if let Some(sku) = &line.item.sku {
+            xml.leaf("ram:SellerAssignedID", sku);
+        }
+        let description = line.item.description.trim();
+        let name = description.lines().next().unwrap_or("").trim();
+        xml.leaf("ram:Name", name);
+        if description != name {
+            xml.leaf("ram:Description", description);
+        }
+        xml.close("ram:SpecifiedTradeProduct");
+        xml.open("ram:SpecifiedLineTradeAgreement");
+        xml.open("ram:NetPriceProductTradePrice");
+        xml.leaf("ram:ChargeAmount", &decimal(line.item.price));
+        xml.close("ram:NetPriceProductTradePrice");
+        xml.close("ram:SpecifiedLineTradeAgreement");
+        xml.open("ram:SpecifiedLineTradeDelivery");
+        xml.leaf_with(
+            "ram:BilledQuantity",
+            &[("unitCode", unit_code(&line.item.unit))],
+            &decimal(line.quantity),
+        );
+        xml.close("ram:SpecifiedLineTradeDelivery");
+        xml.open("ram:SpecifiedLineTradeSettlement");
+        if let Some(category) = &line.category {
+            xml.trade_tax(category, None);
+        }
+        xml.open("ram:SpecifiedTradeSettlementLineMonetarySummation");
+        xml.leaf("ram:LineTotalAmount", &amount(line.amount));
+        xml.close("ram:SpecifiedTradeSettlementLineMonetarySummation");
+        xml.close("ram:SpecifiedLineTradeSettlement");
+        xml.close("ram:IncludedSupplyChainTradeLineItem");
+    }
+
+    xml.open("ram:ApplicableHeaderTradeAgreement");
+    if !text.reference.trim().is_empty() {
+        xml.leaf("ram:BuyerReference", text.reference.trim());
+    }
+    xml.trade_party("ram:SellerTradeParty", Party::from(*seller), Some(seller));
+    xml.trade_party("ram:BuyerTradeParty", Party::from(*buyer), None);
+    if !text.po_number.trim().is_empty() {
+        xml.open("ram:BuyerOrderReferencedDocument");
+        xml.leaf("ram:IssuerAssignedID", text.po_number.trim());
+        xml.close("ram:BuyerOrderReferencedDocument");
+    }
+    xml.close("ram:ApplicableHeaderTradeAgreement");
+    // required even when there is nothing to say about the delivery
+    xml.open("ram:ApplicableHeaderTradeDelivery");
+    xml.close("ram:ApplicableHeaderTradeDelivery");
+
+    xml.open("ram:ApplicableHeaderTradeSettlement");
+    xml.leaf("ram:InvoiceCurrencyCode", currency);
+    for entry in &totals.breakdown {
+        xml.trade_tax(&entry.category, Some((entry.taxable, entry.tax)));
+    }
+    if !text.terms.trim().is_empty() {
+        xml.open("ram:SpecifiedTradePaymentTerms");
+        xml.leaf("ram:Description", text.terms.trim());
+        xml.close("ram:SpecifiedTradePaymentTerms");
+    }
+    xml.open("ram:SpecifiedTradeSettlementHeaderMonetarySummation");
+    xml.leaf("ram:LineTotalAmount", &amount(totals.line_total));
+    xml.leaf("ram:TaxBasisTotalAmount", &amount(totals.line_total));
+    xml.leaf_with(
+        "ram:TaxTotalAmount",
+        &[("currencyID", currency)],
+        &amount(totals.tax_total),
+    );
+    xml.leaf("ram:GrandTotalAmount", &amount(totals.payable()));
+    xml.leaf("ram:DuePayableAmount", &amount(totals.payable()));
+    xml.close("ram:SpecifiedTradeSettlementHeaderMonetarySummation");
+    xml.close("ram:ApplicableHeaderTradeSettlement");
+    xml.close("rsm:SupplyChainTradeTransaction");
+    xml.close("rsm:CrossIndustryInvoice");
+    xml.output
+}
+
+// Reads a written document back: namespace, profile, required elements and the totals.
+pub fn check_document(xml: &str) -> Vec<Violation> {
+    let document = match roxmltree::Document::parse(xml) {
+        Ok(document) => document,
+        Err(e) => return vec![Violation::new("CII-XML", format!("Not well-formed: {}", e))],
+    };
+    let mut violations = Vec::new();
+    let root = document.root_element();
+    if root.tag_name().name() != "CrossIndustryInvoice" || root.tag_name().namespace() != Some(RSM)
+    {
+        return vec![Violation::new(
+            "CII-XML",
+            "Not a Cross Industry Invoice document",
+        )];
+    }
+    let find = |name: &str| {
+        root.descendants()
+            .find(|node| node.tag_name().name() == name && node.tag_name().namespace() == Some(RAM))
+    };
+    if node_text(
+        find("GuidelineSpecifiedDocumentContextParameter").and_then(|node| {
+            node.children()
+                .find(|child| child.tag_name().name() == "ID")
+        }),
+    ) != GUIDELINE_ID
+    {
+        violations.push(Violation::new(
+            "BR-01",
+            "The document does not name the EN 16931 specification",
+        ));
+    }
+    for required in [
+        "TypeCode",
+        "IssueDateTime",
+        "SellerTradeParty",
+        "BuyerTradeParty",
+        "InvoiceCurrencyCode",
+        "SpecifiedTradeSettlementHeaderMonetarySummation",
+        "IncludedSupplyChainTradeLineItem",
+    ] {
+        if find(required).is_none() {
+            violations.push(Violation::new(
+                "CII-XML",
+                format!("Required element {} is missing", required),
+            ));
+        }
+    }
+
+    let number = |node: Option<roxmltree::Node>| node_text(node).parse::<f64>().unwrap_or(0.0);
+    let summation = find("SpecifiedTradeSettlementHeaderMonetarySummation");
+    let total = |name: &str| {
+        number(summation.and_then(|node| {
+            node.children()
+                .find(|child| child.tag_name().name() == name)
+        }))
+    };
+    let line_sum: f64 = root
+        .descendants()
+        .filter(|node| node.tag_name().name() == "SpecifiedTradeSettlementLineMonetarySummation")
+        .map(|node| {
+            number(
+                node.children()
+                    .find(|child| child.tag_name().name() == "LineTotalAmount"),
+            )
+        })
+        .sum();
+    if (round_cents(line_sum) - total("LineTotalAmount")).abs() > 0.001 {
+        violations.push(Violation::new(
+            "BR-CO-10",
+            "The line total does not match the sum of the lines",
+        ));
+    }
+    let expected = total("TaxBasisTotalAmount") + total("TaxTotalAmount");
+    if (round_cents(expected) - total("GrandTotalAmount")).abs() > 0.001 {
+        violations.push(Violation::new(
+            "BR-CO-15",
+            "The total with VAT does not equal the total without VAT plus VAT",
+        ));
+    }
+    violations
+}
+
+fn node_text<'a>(node: Option<roxmltree::Node<'a, '_>>) -> &'a str {
+    node.and_then(|node| node.text()).unwrap_or("")
+}
+
+// validates, builds and checks the document
+pub fn invoice_xml(e_invoice: &EInvoice) -> Result<String, UblError> {
+    let violations = validate(e_invoice);
+    if !violations.is_empty() {
+        return Err(UblError::Invalid(violations));
+    }
+    let xml = to_xml(e_invoice);
+    let violations = check_document(&xml);
+    if !violations.is_empty() {
+        return Err(UblError::Invalid(violations));
+    }
+    Ok(xml)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structs::{Customer, DatabaseData, Invoice, InvoiceStatus, InvoiceText, LineKind};
+
+    fn seller() -> Contact {
+        Contact {
+            company: "Invoicy GmbH".to_string(),
+            name: "Ada".to_string(),
+            email: "billing@invoicy.example".to_string(),
+            address: "Hauptstr. 1".to_string(),
+            city: "Berlin".to_string(),
+            postal_code: "10115".to_string(),
+            country: "DE".to_string(),
+            vat_id: "DE123456789".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn buyer() -> Customer {
+        Customer {
+            company: "Client SARL".to_string(),
+            city: "Paris".to_string(),
+            country: "FR".to_string(),
+            vat_id: "FR12345678901".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn item(description: &str, quantity: f64, price: f64, tax_code: &str) -> DatabaseData {
+        DatabaseData {
+            description: description.to_string(),
+            quantity,
+            price,
+            unit: "hours".to_string(),
+            tax_code: tax_code.to_string(),
+            kind: LineKind::Item,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_factur_x_document() {
+        let invoice = Invoice {
+            invoice_id: "CLI-7".to_string(),
+            cust_id: "CLI".to_string(),
+            estimate_number: 7,
+            customer: "Client SARL".to_string(),
+            contact: "Invoicy GmbH".to_string(),
+            issue_date: "2024-03-01".to_string(),
+            grand_total: 1249.5,
+            file_path: None,
+            file_hash: None,
+            text: InvoiceText {
+                terms: "30 days net".to_string(),
+                ..Default::default()
+            },
+            status: InvoiceStatus::Issued,
+        };
+        let (seller, buyer) = (seller(), buyer());
+        let items = vec![
+            item("Consulting", 10.0, 100.0, "S:19"),
+            item("Travel", 1.0, 50.0, "S:19"),
+        ];
+        let e_invoice = EInvoice {
+            invoice: &invoice,
+            seller: &seller,
+            buyer: &buyer,
+            items: &items,
+            currency: "EUR",
+        };
+        // no electronic addresses and no buyer reference, both are Peppol requirements
+        assert!(ubl::validate(&e_invoice)
+            .iter()
+            .any(|violation| violation.rule.starts_with("PEPPOL-")));
+        let xml = invoice_xml(&e_invoice).unwrap();
+        assert!(xml.contains("<udt:DateTimeString format=\"102\">20240301</udt:DateTimeString>"));
+        assert!(xml.contains("<ram:GrandTotalAmount>1249.50</ram:GrandTotalAmount>"));
+        assert!(xml.contains("<ram:ID schemeID=\"VA\">FR12345678901</ram:ID>"));
+
+        let tampered = xml.replace(
+            "<ram:GrandTotalAmount>1249.50",
+            "<ram:GrandTotalAmount>1250.00",
+        );
+        let rules: Vec<&str> = check_document(&tampered)
+            .iter()
+            .map(|violation| violation.rule)
+            .collect();
+        assert_eq!(rules, vec!["BR-CO-15"]);
+    }
+}
@@ -0,0 +1,280 @@
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use eframe::egui::{self, Color32, ScrollArea, Ui, Window};
+use rfd::FileDialog;
+
+use crate::Invoicy;
+use invoicy::backup::{self, Assets, BackupError};
+use invoicy::paths;
+
+// how often the schedule is looked at, backups themselves run every few hours at most
+const BACKUP_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+const LISTED_BACKUPS: usize = 10;
+
+// functions related to the backup UI
+impl Invoicy {
+    pub fn show_backup(&mut self, ui: &mut Ui) {
+        if !self.backup_form {
+            return;
+        }
+        let mut open = true;
+        Window::new("Backup and Restore")
+            .open(&mut open)
+            .show(ui.ctx(), |ui| {
+                ui.label("Archives hold the database, the logo and the templates folder.");
+                ui.horizontal(|ui| {
+                    ui.label("Folder: ");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.backup_dir_input).hint_text(
+                            paths::backup_dir(&Default::default()).display().to_string(),
+                        ),
+                    );
+                    if ui.button("Browse").clicked() {
+                        if let Some(dir) = FileDialog::new().pick_folder() {
+                            self.backup_dir_input = dir.display().to_string();
+                        }
+                    }
+                });
+                egui::Grid::new("backup_schedule").show(ui, |ui| {
+                    ui.label("Every (hours, 0 = off): ");
+                    ui.add(egui::DragValue::new(
+                        &mut self.settings.backup.interval_hours,
+                    ));
+                    ui.end_row();
+                    ui.label("Keep newest: ");
+                    ui.add(egui::DragValue::new(&mut self.settings.backup.keep).range(1..=1000));
+                    ui.end_row();
+                    ui.label("Remove after (days, 0 = never): ");
+                    ui.add(egui::DragValue::new(&mut self.settings.backup.keep_days));
+                    ui.end_row();
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("Save Schedule").clicked() {
+                        self.save_backup_settings();
+                    }
+                    if ui.button("Back Up Now").clicked() {
+                        self.run_backup();
+                    }
+                    if ui.button("Restore…").clicked() {
+                        if let Some(path) = FileDialog::new()
+                            .add_filter("Invoicy backup", &["gz"])
+                            .set_directory(paths::backup_dir(&self.settings))
+                            .pick_file()
+                        {
+                            self.open_restore(path);
+                        }
+                    }
+                });
+                if let Some(message) = &self.backup_message {
+                    ui.label(message);
+                }
+                self.restore_prompt(ui);
+                self.remote_section(ui);
+                ui.separator();
+                let dir = paths::backup_dir(&self.settings);
+                let archives = backup::backups(&dir);
+                if archives.is_empty() {
+                    ui.label(format!("No backups in {} yet.", dir.display()));
+                }
+                ScrollArea::vertical().max_height(160.0).show(ui, |ui| {
+                    for (path, created) in archives.into_iter().take(LISTED_BACKUPS) {
+                        ui.horizontal(|ui| {
+                            ui.label(created.format("%Y-%m-%d %H:%M").to_string());
+                            if ui.small_button("Restore").clicked() {
+                                self.open_restore(path);
+                            }
+                        });
+                    }
+                });
+            });
+        if !open {
+            self.backup_form = false;
+            self.backup_restore = None;
+            self.backup_locked = None;
+            self.backup_passphrase_input.clear();
+        }
+    }
+
+    // the checked archive waits here until the restore is confirmed
+    fn restore_prompt(&mut self, ui: &mut Ui) {
+        if self.backup_locked.is_some() {
+            ui.horizontal(|ui| {
+                ui.label("Backup passphrase: ");
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.backup_passphrase_input).password(true),
+                );
+                if ui.button("Open").clicked() {
+                    self.open_locked_restore();
+                }
+            });
+        }
+        let Some((path, pending)) = &self.backup_restore else {
+            return;
+        };
+        ui.separator();
+        ui.colored_label(
+            Color32::RED,
+            "Restoring replaces every invoice, customer, contact and product.",
+        );
+        ui.label(format!(
+            "{} taken {} by Invoicy {}, schema version {}{}{}",
+            path.file_name().unwrap_or_default().to_string_lossy(),
+            pending.manifest.created_at,
+            pending.manifest.app_version,
+            pending.manifest.schema_version,
+            if pending.has_logo() {
+                ", with logo"
+            } else {
+                ""
+            },
+            match pending.template_count() {
+                0 => String::new(),
+                count => format!(", {} template file(s)", count),
+            }
+        ));
+        ui.label("The current data is backed up first.");
+        ui.horizontal(|ui| {
+            if ui.button("Restore").clicked() {
+                self.restore_backup();
+            }
+            if ui.button("Cancel").clicked() {
+                self.backup_restore = None;
+            }
+        });
+    }
+}
+
+// functions related to backup actions
+impl Invoicy {
+    fn save_backup_settings(&mut self) {
+        let dir = self.backup_dir_input.trim();
+        self.settings.backup.dir = (!dir.is_empty()).then(|| PathBuf::from(dir));
+        match self.settings.save() {
+            Ok(_) => {
+                println!("LOG: Backup schedule saved");
+                self.backup_message = Some("Schedule saved".to_string());
+            }
+            Err(e) => {
+                println!("ERROR: Backup schedule unable to be Saved {}", e);
+                self.error_message = Some(format!("Unable to save settings: {}", e));
+            }
+        }
+    }
+
+    // writes a new archive and applies the retention rules, returning the archive on success
+    fn run_backup(&mut self) -> Option<PathBuf> {
+        let dir = paths::backup_dir(&self.settings);
+        let now = chrono::Local::now().naive_local();
+        match backup::create_backup(&self.store, &dir, &Assets::in_data_dir(), now) {
+            Ok(path) => {
+                println!("LOG: Backup written to {:?}", path);
+                self.backup_message = Some(format!("Backed up to {}", path.display()));
+                match backup::prune(&dir, &self.settings.backup, now) {
+                    Ok(removed) if !removed.is_empty() => {
+                        println!("LOG: Removed {} old backup(s)", removed.len())
+                    }
+                    Ok(_) => {}
+                    Err(e) => println!("ERROR: Unable to remove old backups {}", e),
+                }
+                self.push_remote_backup(path.clone());
+                Some(path)
+            }
+            Err(e) => {
+                println!("ERROR: Backup failed {}", e);
+                self.backup_message = Some(format!("Backup failed: {}", e));
+                None
+            }
+        }
+    }
+
+    // archives made from an encrypted database are tried with its passphrase first
+    pub fn open_restore(&mut self, path: PathBuf) {
+        let passphrase = self.store.passphrase().map(str::to_string);
+        self.check_backup(path, passphrase.as_deref());
+    }
+
+    fn open_locked_restore(&mut self) {
+        let Some(path) = self.backup_locked.take() else {
+            return;
+        };
+        let passphrase = std::mem::take(&mut self.backup_passphrase_input);
+        self.check_backup(path, Some(&passphrase));
+    }
+
+    fn check_backup(&mut self, path: PathBuf, passphrase: Option<&str>) {
+        self.backup_locked = None;
+        match backup::open_backup(&path, passphrase) {
+            Ok(pending) => {
+                println!("LOG: Backup {:?} checked", path);
+                self.backup_message = None;
+                self.backup_restore = Some((path, pending));
+            }
+            Err(BackupError::Passphrase) => {
+                println!("LOG: Backup {:?} needs a passphrase", path);
+                self.backup_restore = None;
+                self.backup_message = Some(BackupError::Passphrase.to_string());
+                self.backup_locked = Some(path);
+            }
+            Err(e) => {
+                println!("ERROR: Backup {:?} rejected {}", path, e);
+                self.backup_restore = None;
+                self.error_message = Some(e.to_string());
+            }
+        }
+    }
+
+    fn restore_backup(&mut self) {
+        let Some((path, pending)) = self.backup_restore.take() else {
+            return;
+        };
+        // the open draft is kept with the data it was written against
+        self.autosave_draft(true);
+        if self.run_backup().is_none() {
+            self.error_message =
+                Some("Nothing was restored, the current data could not be backed up".to_string());
+            return;
+        }
+        // a fresh editor, the drafts are replaced along with everything else
+        self.new_draft();
+        self.drafts.clear();
+        match pending.restore(&mut self.store, &Assets::in_data_dir()) {
+            Ok(_) => {
+                println!("LOG: Restored {:?}", path);
+                self.reset_loaded_data();
+                self.backup_message = Some(format!(
+                    "Restored the backup from {}",
+                    pending.manifest.created_at
+                ));
+            }
+            Err(e) => {
+                println!("ERROR: Restore failed {}", e);
+                self.error_message = Some(format!("Restore failed: {}", e));
+            }
+        }
+    }
+
+    // runs a backup when one is due, checked about once a minute while the app is open
+    pub fn schedule_backup(&mut self, ctx: &egui::Context) {
+        self.poll_remote_upload(ctx);
+        if self.settings.backup.interval_hours == 0 {
+            return;
+        }
+        ctx.request_repaint_after(BACKUP_CHECK_INTERVAL);
+        if self
+            .last_backup_check
+            .is_some_and(|checked| checked.elapsed() < BACKUP_CHECK_INTERVAL)
+        {
+            return;
+        }
+        self.last_backup_check = Some(Instant::now());
+        let now = chrono::Local::now().naive_local();
+        if backup::backup_due(
+            &paths::backup_dir(&self.settings),
+            &self.settings.backup,
+            now,
+        ) {
+            self.run_backup();
+        }
+    }
+}
@@ -1,13 +1,16 @@
 use std::convert::TryInto;
+use std::path::Path;
 
-use eframe::egui::{Align, Layout, ProgressBar, Ui};
+use eframe::egui::{Align, Align2, Color32, Context, Layout, Ui, Window};
 use rfd::FileDialog;
-use rusqlite::params;
 
-use crate::document::generate_invoice;
 use crate::functions::sanitize_string;
-use crate::structs::DatabaseData;
 use crate::Invoicy;
+use invoicy::cii;
+use invoicy::db::NewInvoice;
+use invoicy::document::{generate_invoice, DocumentError, DocumentOptions};
+use invoicy::structs::{Invoice, InvoiceStatus};
+use invoicy::ubl::{self, EInvoice};
 
 // Functions related to Bottom Bar UI
 impl Invoicy {
@@ -20,31 +23,42 @@ impl Invoicy {
     }
 
     pub fn generate_invoice(&mut self, ui: &mut Ui) {
-        if ui.button("Generate Invoice").clicked() {
-            if let Some(path) = FileDialog::new()
-                .set_file_name(format!("{}.pdf", self.file_name.clone()))
-                .save_file()
-            {
-                // Handle the file path here
-                let result = generate_invoice(
-                    &path,
-                    self.contact.clone(),
-                    self.customer.clone(),
-                    self.table_data.clone(),
-                    self.current_row_value
-                        .estimate_number
-                        .clone()
-                        .try_into()
-                        .unwrap(),
-                    self.grand_total,
-                );
-                println!("{:?}", result);
-                println!("File saved to: {:?}", &path);
-                self.progress = 100.0;
-                ui.add(ProgressBar::new(self.progress).show_percentage());
-                self.add_data();
-                self.add_customer();
+        ui.horizontal(|ui| {
+            if ui.button("Generate Invoice").clicked() {
+                if let Some(path) = FileDialog::new()
+                    .set_file_name(format!("{}.pdf", self.file_name.clone()))
+                    .save_file()
+                {
+                    self.issue_invoice(&path);
+                }
             }
+            ui.checkbox(&mut self.summary_only, "Summary only")
+                .on_hover_text(
+                    "List section totals only, with the full breakdown on an extra page",
+                );
+            ui.checkbox(&mut self.e_invoice, "E-invoice")
+                .on_hover_text("Also save a Peppol BIS 3.0 (UBL) XML next to the PDF");
+            ui.checkbox(&mut self.factur_x, "Factur-X")
+                .on_hover_text("Save a PDF/A-3 with the invoice embedded as CII XML (ZUGFeRD)");
+        });
+        if let Some(message) = &self.status_message {
+            ui.label(message);
+        }
+    }
+
+    pub fn show_error(&mut self, ctx: &Context) {
+        if let Some(message) = self.error_message.clone() {
+            Window::new("Something went wrong")
+                .collapsible(false)
+                .resizable(false)
+                .anchor(Align2::CENTER_CENTER, [0.0, 0.0])
+                .show(ctx, |ui| {
+                    ui.colored_label(Color32::RED, message);
+                    ui.label("Your line items have been kept, fix the problem and try again.");
+                    if ui.button("OK").clicked() {
+                        self.error_message = None;
+                    }
+                });
         }
     }
 
@@ -66,80 +80,162 @@ impl Invoicy {
         self.file_name = format!(
             "{}-{:?}",
             sanitize_string(&self.customers[self.customer_selected].company.clone()),
-            self.current_row_value.estimate_number
+            self.estimate_number
         );
     }
 
-    pub fn add_data(&mut self) {
-        for i in 0..self.row_count {
-            let mut data: DatabaseData = DatabaseData {
-                entry_id: "".to_string(),
-                cust_id: self.generate_customer_id(self.customer_selected),
-                estimate_number: self.current_row_value.estimate_number,
-                row_number: 0,
-                description: "".to_string(),
-                quantity: 1.0,
-                price: 1.0,
-                total: 1.0,
+    pub fn issue_invoice(&mut self, path: &Path) {
+        if let Some(error) = self.line_items.first_error() {
+            self.error_message = Some(error);
+            return;
+        }
+        let cust_id = self.generate_customer_id(self.customer_selected);
+        if self.e_invoice || self.factur_x {
+            // nothing is issued when the e-invoice could not be sent
+            if let Err(e) = self.check_e_invoice(&cust_id) {
+                self.error_message = Some(e);
+                return;
+            }
+        }
+        let invoice = NewInvoice {
+            items: self
+                .line_items
+                .to_database_data(&cust_id, self.estimate_number),
+            cust_id,
+            customer: self.customers[self.customer_selected].clone(),
+            contact_company: self.contacts[self.contact_selected].company.clone(),
+            text: self.invoice_text.clone(),
+        };
+        let contact = &self.contacts[self.contact_selected];
+        let line_items = &self.line_items;
+        let record = self.e_invoice_record(&invoice.cust_id);
+        let factur_x = self.factur_x;
+        let currency = &self.settings.currency;
+        let (font_registry, fonts, text_layout, summary_only, archival) = (
+            &self.font_registry,
+            &self.settings.fonts,
+            &self.settings.text_layout,
+            self.summary_only,
+            self.settings.archival_pdf,
+        );
+        let result = self.store.issue_invoice(&invoice, path, |estimate_number| {
+            // the XML can only be built once the invoice has its number
+            let xml = if factur_x {
+                let record = Invoice {
+                    invoice_id: format!("{}-{}", invoice.cust_id, estimate_number),
+                    estimate_number,
+                    ..record
+                };
+                let e_invoice = EInvoice {
+                    invoice: &record,
+                    seller: contact,
+                    buyer: &invoice.customer,
+                    items: &invoice.items,
+                    currency,
+                };
+                Some(cii::invoice_xml(&e_invoice).map_err(DocumentError::EInvoice)?)
+            } else {
+                None
             };
-            for _ in 0..5 {
-                for item in &self.table_data {
-                    if item.1 == (i, 0) {
-                        data.row_number = i;
-                    } else if item.1 == (i, 1) {
-                        data.description = item.0.clone()
-                    } else if item.1 == (i, 2) {
-                        if item.0.parse::<f64>().is_ok() {
-                            data.quantity = item.0.parse().unwrap();
-                        } else {
-                            data.quantity = 0.0
-                        };
-                    } else if item.1 == (i, 3) {
-                        if item.0.parse::<f64>().is_ok() {
-                            data.price = item.0.parse().unwrap();
-                        } else {
-                            data.price = 0.0
-                        };
-                    } else if item.1 == (i, 4) {
-                        if item.0.parse::<f64>().is_ok() {
-                            data.total = item.0.parse().unwrap();
-                        } else {
-                            data.total = 0.0
-                        };
+            generate_invoice(
+                &path.to_path_buf(),
+                contact.clone(),
+                invoice.customer.clone(),
+                line_items,
+                &invoice.text,
+                estimate_number.try_into().unwrap_or(i32::MAX),
+                DocumentOptions {
+                    font_registry,
+                    fonts,
+                    text_layout,
+                    summary_only,
+                    archival,
+                    factur_x: xml.as_deref(),
+                },
+            )
+        });
+        match result {
+            Ok(issued) => {
+                println!(
+                    "LOG: Invoice {} saved to {:?} ({})",
+                    issued.invoice_id, issued.file_path, issued.file_hash
+                );
+                self.progress = 100.0;
+                self.database_data_vec.extend(issued.items);
+                self.finish_draft();
+                // ready to be emailed from the email window
+                self.select_mail_invoice(&issued.invoice_id);
+                self.status_message = Some(format!("Invoice saved to {}", path.display()));
+                if self.e_invoice {
+                    let xml_path = path.with_extension("xml");
+                    match ubl::export_invoice(
+                        &self.store,
+                        &issued.invoice_id,
+                        &self.settings.currency,
+                        &xml_path,
+                    ) {
+                        Ok(_) => {
+                            println!("LOG: E-invoice saved to {:?}", xml_path);
+                            self.status_message = Some(format!(
+                                "Invoice saved to {} and {}",
+                                path.display(),
+                                xml_path.display()
+                            ));
+                        }
+                        Err(e) => {
+                            println!("ERROR: E-invoice not written {}", e);
+                            self.error_message = Some(e.to_string());
+                        }
                     }
                 }
             }
+            Err(e) => {
+                println!("ERROR: Invoice not generated {}", e);
+                self.error_message = Some(e.to_string());
+            }
+        }
+    }
 
-            data.entry_id = format!(
-                "{}-{:?}-{:?}",
-                self.generate_customer_id(self.customer_selected),
-                data.estimate_number,
-                data.row_number
-            );
-            self.new_database_data_vec.push(data);
+    // the e-invoice rules checked against the editor before anything is issued, a Peppol invoice
+    // is written and validated against the UBL schema as well, Factur-X alone leaves out the
+    // Peppol rules
+    fn check_e_invoice(&self, cust_id: &str) -> Result<(), String> {
+        let invoice = self.e_invoice_record(cust_id);
+        let items = self
+            .line_items
+            .to_database_data(cust_id, self.estimate_number);
+        let e_invoice = EInvoice {
+            invoice: &invoice,
+            seller: &self.contacts[self.contact_selected],
+            buyer: &self.customers[self.customer_selected],
+            items: &items,
+            currency: &self.settings.currency,
+        };
+        let violations = if self.e_invoice {
+            ubl::check(&e_invoice)
+        } else {
+            cii::validate(&e_invoice)
+        };
+        if violations.is_empty() {
+            return Ok(());
         }
-        for item in &self.new_database_data_vec {
-            let updated = &self.connection.execute(
-                        "INSERT OR REPLACE INTO data (entry_id, estimate_number, cust_id, row_number, description, quantity, price, total) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
-                        params![
-                            item.entry_id,
-                            item.estimate_number,
-                            item.cust_id,
-                            item.row_number,
-                            item.description,
-                            item.quantity,
-                            item.price,
-                            item.total,
+        Err(ubl::UblError::Invalid(violations).to_string())
+    }
 
-                        ],
-                    );
-            match updated {
-                Ok(value) => println!("LOG: Data Added Successfully: {}", value),
-                Err(e) => println!("LOG: Error in Data Addition: {}", e),
-            }
+    // the invoice as it will be stored, numbered with the editor's estimate number
+    fn e_invoice_record(&self, cust_id: &str) -> Invoice {
+        Invoice {
+            invoice_id: format!("{}-{}", cust_id, self.estimate_number),
+            cust_id: cust_id.to_string(),
+            estimate_number: self.estimate_number,
+            customer: self.customers[self.customer_selected].company.clone(),
+            contact: self.contacts[self.contact_selected].company.clone(),
+            issue_date: chrono::Local::now().format("%Y-%m-%d").to_string(),
+            grand_total: self.line_items.gross_total(),
+            file_path: None,
+            file_hash: None,
+            text: self.invoice_text.clone(),
+            status: InvoiceStatus::Issued,
         }
-        self.database_data_vec
-            .extend_from_slice(&self.new_database_data_vec);
-        self.new_database_data_vec.clear();
     }
 }
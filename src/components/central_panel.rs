@@ -1,8 +1,13 @@
-use eframe::egui::{self, FontId, RichText, TextEdit, Ui};
+use eframe::egui::{
+    self, Color32, FontId, Id, Key, KeyboardShortcut, Modifiers, RichText, TextEdit, Ui,
+};
 use egui_extras::{Column, TableBuilder};
+use invoicy::history::{EditCommand, EditorState};
+use invoicy::line_items::{LineItem, DESCRIPTION, PRICE, QUANTITY, UNITS};
+use invoicy::pricing::{quote, PriceSource};
+use invoicy::structs::{InvoiceText, LineKind};
 
-use crate::functions::*;
-use crate::structs::{DatabaseData, Total};
+use crate::components::products::product_suggestions;
 use crate::Invoicy;
 
 impl Invoicy {
@@ -16,9 +21,12 @@ impl Invoicy {
                         &self.customers[self.customer_selected],
                         &self.customers[i].company,
                     );
-                    if value.clicked() {
+                    if value.clicked() && i != self.customer_selected {
+                        self.history.record(EditCommand::SelectCustomer {
+                            before: self.customer_selected,
+                            after: i,
+                        });
                         self.customer_selected = i;
-                        self.current_row_value.cust_id = self.generate_customer_id(i);
                     }
                 }
             });
@@ -34,7 +42,11 @@ impl Invoicy {
                         &self.contacts[self.contact_selected],
                         &self.contacts[i].company,
                     );
-                    if value.clicked() {
+                    if value.clicked() && i != self.contact_selected {
+                        self.history.record(EditCommand::SelectContact {
+                            before: self.contact_selected,
+                            after: i,
+                        });
                         self.contact_selected = i;
                     }
                 }
@@ -42,13 +54,28 @@ impl Invoicy {
     }
 
     pub fn table(&mut self, ui: &mut Ui) {
+        self.line_item_keys(ui);
+        let mut action = None;
+        let products = &self.products;
+        let customer = &self.customers[self.customer_selected].company;
+        let customer_prices = &self.customer_prices;
+        let subtotals: Vec<f64> = (0..self.line_items.len())
+            .map(|idx| self.line_items.subtotal_at(idx))
+            .collect();
+        let sections = self.line_items.sections();
+        let line_items = &mut self.line_items;
+        let history = &mut self.history;
         TableBuilder::new(ui)
+            .column(Column::auto())
+            .column(Column::auto())
+            .column(Column::auto().at_least(200.0))
             .column(Column::auto())
             .column(Column::auto())
             .column(Column::auto())
             .column(Column::auto())
             .column(Column::remainder())
             .header(20.0, |mut header| {
+                header.col(|_| {});
                 header.col(|ui| {
                     ui.heading("Row #");
                 });
@@ -58,219 +85,515 @@ impl Invoicy {
                 header.col(|ui| {
                     ui.heading("Quantity");
                 });
+                header.col(|ui| {
+                    ui.heading("Unit");
+                });
                 header.col(|ui| {
                     ui.heading("Unit Price");
                 });
                 header.col(|ui| {
                     ui.heading("Total");
                 });
+                header.col(|_| {});
             })
             .body(|mut body| {
-                if self.last_updated_row < self.row_count {
-                    // sets up default rows and adds it to storage table
-                    for idx in self.last_updated_row..self.row_count {
-                        body.row(30.0, |mut row| {
-                            // currently hardcoded until there is a plan for table customizability
-                            for column_count in 0..5 {
-                                let mut text = format!("{:?}", (idx, column_count));
-                                if column_count == 0 {
-                                    let output = row.col(|ui| {
-                                        ui.label(idx.to_string());
-                                    });
-                                    self.table_data.push((text, (idx, column_count), output));
-                                } else if column_count == 4 {
-                                    let output = row.col(|ui| {
-                                        ui.label(text.to_string());
-                                    });
-                                    self.table_data.push((text, (idx, column_count), output));
-                                } else {
-                                    let output = row.col(|ui| {
-                                        ui.add(TextEdit::singleline(&mut text));
-                                        ui.end_row();
-                                    });
-                                    // pushing all the table data into the requested amount of rows
-                                    self.table_data.push((text, (idx, column_count), output));
+                for (idx, item) in line_items.items_mut().iter_mut().enumerate() {
+                    let before = item.clone();
+                    // multi-line descriptions grow the row
+                    let lines = item.description.lines().count().max(1);
+                    body.row(10.0 + 20.0 * lines as f32, |mut row| {
+                        row.col(|ui| {
+                            // the handle carries the row index, dropping it on another row moves it
+                            ui.dnd_drag_source(Id::new(("line_item", item.id)), idx, |ui| {
+                                ui.label("☰");
+                            })
+                            .response
+                            .on_hover_text("Drag to reorder");
+                        });
+                        row.col(|ui| {
+                            ui.label((idx + 1).to_string());
+                        });
+                        row.col(|ui| {
+                            let id = cell_id(item.id, DESCRIPTION);
+                            match item.kind {
+                                LineKind::Item => {
+                                    let response = ui.add(
+                                        TextEdit::multiline(&mut item.description)
+                                            .id(id)
+                                            .desired_rows(1)
+                                            .lock_focus(true)
+                                            .hint_text("Shift+Enter for a new line, - for bullets"),
+                                    );
+                                    // a cleared description detaches the row from its product
+                                    if response.changed() && item.description.trim().is_empty() {
+                                        item.sku = None;
+                                    }
+                                    if let Some(product) =
+                                        product_suggestions(ui, products, item, &response)
+                                    {
+                                        let quote = quote(&product, customer, customer_prices);
+                                        item.apply_product(&product, quote);
+                                    }
+                                }
+                                LineKind::Heading => {
+                                    ui.add(
+                                        TextEdit::singleline(&mut item.description)
+                                            .id(id)
+                                            .lock_focus(true)
+                                            .font(egui::TextStyle::Heading)
+                                            .hint_text("Section heading"),
+                                    );
+                                }
+                                LineKind::Subtotal => {
+                                    ui.add(
+                                        TextEdit::singleline(&mut item.description)
+                                            .id(id)
+                                            .lock_focus(true)
+                                            .font(egui::TextStyle::Button),
+                                    );
                                 }
                             }
                         });
-                    }
-                    self.last_updated_row = self.row_count;
-                }
-                // continuously updates tables based on table data
-                for idx in 0..self.row_count {
-                    body.row(30.0, |mut row| {
-                        // currently hardcoded until there is a plan for table customizability
-                        for column_count in 0..5 {
-                            for cell in &mut self.table_data {
-                                if (cell.1) == (idx, column_count) {
-                                    if column_count == 0 {
-                                        row.col(|ui| {
-                                            ui.label(idx.to_string());
-                                        });
-                                    } else if column_count == 2 {
-                                        self.current_row_value.quantity =
-                                            if cell.0.parse::<f64>().is_ok() {
-                                                cell.0.parse().unwrap()
-                                            } else {
-                                                0.0
-                                            };
-                                        row.col(|ui| {
-                                            ui.add(TextEdit::singleline(&mut cell.0));
-                                            ui.end_row();
-                                        });
-                                    } else if column_count == 3 {
-                                        self.current_row_value.price =
-                                            if cell.0.parse::<f64>().is_ok() {
-                                                cell.0.parse().unwrap()
-                                            } else {
-                                                0.0
-                                            };
-                                        row.col(|ui| {
-                                            ui.add(TextEdit::singleline(&mut cell.0));
-                                            ui.end_row();
-                                        });
-                                    } else if column_count == 4 {
-                                        let total_val = self.current_row_value.quantity.clone()
-                                            * self.current_row_value.price.clone();
-                                        cell.0 = total_val.to_string();
-                                        row.col(|ui| {
-                                            ui.label(format!("{:?}", total_val));
-                                        });
-
-                                        let incoming_total = Total {
-                                            value: total_val,
-                                            position: (idx, column_count),
-                                        };
-                                        if !contains_field(&self.totals, &incoming_total.position) {
-                                            self.totals.push(incoming_total.clone())
-                                        }
-                                        for idx in 0..self.totals.len() {
-                                            if self.totals[idx].position == incoming_total.position
-                                            {
-                                                self.totals[idx].value =
-                                                    incoming_total.value.clone();
-                                            }
-                                        }
-                                    } else {
-                                        row.col(|ui| {
-                                            ui.add(TextEdit::singleline(&mut cell.0));
-                                            ui.end_row();
-                                        });
-                                    }
+                        if item.is_item() {
+                            row.col(|ui| {
+                                let id = cell_id(item.id, QUANTITY);
+                                if number_input(
+                                    ui,
+                                    id,
+                                    &mut item.quantity_input,
+                                    &item.quantity_error,
+                                ) {
+                                    item.parse();
+                                }
+                            });
+                            row.col(|ui| {
+                                unit_select(ui, Id::new(("unit", item.id)), &mut item.unit);
+                            });
+                            row.col(|ui| {
+                                let id = cell_id(item.id, PRICE);
+                                if number_input(ui, id, &mut item.price_input, &item.price_error) {
+                                    item.price_source = None;
+                                    item.parse();
+                                }
+                                // negotiated prices are marked, the catalogue price is the norm
+                                if let Some(source) = item
+                                    .price_source
+                                    .filter(|source| *source != PriceSource::Catalogue)
+                                {
+                                    ui.label(RichText::new("🏷").color(Color32::DARK_GREEN))
+                                        .on_hover_text(source.label());
+                                }
+                            });
+                        } else {
+                            for _ in 0..3 {
+                                row.col(|_| {});
+                            }
+                        }
+                        row.col(|ui| match item.kind {
+                            LineKind::Item => {
+                                ui.label(format!("{:.2}", item.total()));
+                            }
+                            LineKind::Subtotal => {
+                                ui.strong(format!("{:.2}", subtotals[idx]));
+                            }
+                            LineKind::Heading => {
+                                // the whole section, shown greyed out next to its heading
+                                if let Some(section) =
+                                    sections.iter().find(|section| section.rows.start == idx)
+                                {
+                                    ui.weak(format!("{:.2}", section.total))
+                                        .on_hover_text("Section total");
                                 }
                             }
+                        });
+                        row.col(|ui| {
+                            if ui
+                                .small_button("+")
+                                .on_hover_text("Insert row below")
+                                .clicked()
+                            {
+                                action = Some(RowAction::Insert(idx + 1));
+                            }
+                            if ui.small_button("🗑").on_hover_text("Delete row").clicked() {
+                                action = Some(RowAction::Delete(idx));
+                            }
+                        });
+                        if let Some(from) = row.response().dnd_release_payload::<usize>() {
+                            action = Some(RowAction::Move(*from, idx));
                         }
                     });
+                    if *item != before {
+                        history.record(EditCommand::EditRow {
+                            index: idx,
+                            before: Box::new(before),
+                            after: Box::new(item.clone()),
+                        });
+                    }
                 }
             });
-        self.calculate_grand_total();
+        // leaving a cell ends its undo step
+        let focused = ui.memory(|memory| memory.focused());
+        if focused != self.edit_focus {
+            self.history.seal();
+            self.edit_focus = focused;
+        }
+        match action {
+            Some(RowAction::Insert(idx)) => self.insert_row(idx),
+            Some(RowAction::Delete(idx)) => {
+                if let Some(item) = self.line_items.remove(idx) {
+                    self.history
+                        .record(EditCommand::RemoveRow { index: idx, item });
+                }
+            }
+            Some(RowAction::Move(from, to)) if from != to && from < self.line_items.len() => {
+                self.line_items.move_item(from, to);
+                self.history.record(EditCommand::MoveRow { from, to });
+            }
+            _ => {}
+        }
         ui.label(
-            RichText::new(format!("Grand Total: {}", self.grand_total.to_string()))
+            RichText::new(format!("Grand Total: {:.2}", self.line_items.gross_total()))
                 .font(FontId::proportional(16.0)),
         );
         ui.horizontal(|ui| {
             if ui.button("+ Add Row").clicked() {
-                self.row_count += 1;
+                self.insert_row(self.line_items.len());
+            }
+            if ui.button("+ Heading").clicked() {
+                self.insert_kind(self.line_items.len(), LineKind::Heading);
             }
-            if ui.button("- Delete Row").clicked() {
-                self.row_count -= 1;
-                // can add loop to delete row based on latest index. For now the data persists in table_data
+            if ui
+                .button("+ Subtotal")
+                .on_hover_text("Sums the rows above it, back to the previous heading")
+                .clicked()
+            {
+                self.insert_kind(self.line_items.len(), LineKind::Subtotal);
+            }
+            if ui
+                .add_enabled(self.history.can_undo(), egui::Button::new("Undo"))
+                .on_hover_text("Ctrl+Z")
+                .clicked()
+            {
+                self.undo();
+            }
+            if ui
+                .add_enabled(self.history.can_redo(), egui::Button::new("Redo"))
+                .on_hover_text("Ctrl+Shift+Z")
+                .clicked()
+            {
+                self.redo();
             }
         });
     }
 }
 
+// functions related to editor history
 impl Invoicy {
-    pub fn calculate_grand_total(&mut self) {
-        self.grand_total = self.totals.iter().map(|item| item.value).sum();
+    pub fn insert_kind(&mut self, idx: usize, kind: LineKind) {
+        let index = self.line_items.insert_kind(idx, kind);
+        let item = self.line_items.items()[index].clone();
+        self.history.record(EditCommand::InsertRow { index, item });
     }
 
-    pub fn generate_customer_id(&mut self, idx: usize) -> String {
-        let cust_id = format!(
-            "{}",
-            &self.customers[idx]
-                .company
-                .clone()
-                .to_uppercase()
-                .get(0..4)
-                .unwrap(),
-        );
-        return cust_id;
+    pub fn insert_row(&mut self, idx: usize) {
+        let index = self.line_items.insert(idx);
+        let item = self.line_items.items()[index].clone();
+        self.history.record(EditCommand::InsertRow { index, item });
+    }
+
+    pub fn undo(&mut self) {
+        let mut state = EditorState {
+            line_items: &mut self.line_items,
+            customer_selected: &mut self.customer_selected,
+            contact_selected: &mut self.contact_selected,
+        };
+        self.history.undo(&mut state);
+        self.prices_for = Some((self.customer_selected, self.catalogue_version));
+    }
+
+    pub fn redo(&mut self) {
+        let mut state = EditorState {
+            line_items: &mut self.line_items,
+            customer_selected: &mut self.customer_selected,
+            contact_selected: &mut self.contact_selected,
+        };
+        self.history.redo(&mut state);
+        self.prices_for = Some((self.customer_selected, self.catalogue_version));
+    }
+
+    // Ctrl+Z undoes, Ctrl+Shift+Z (or Ctrl+Y) redoes. Checked before the widgets run so
+    // the item table's text fields don't swallow the shortcut.
+    pub fn history_shortcuts(&mut self, ctx: &egui::Context) {
+        let redo = KeyboardShortcut::new(Modifiers::COMMAND | Modifiers::SHIFT, Key::Z);
+        let redo_alt = KeyboardShortcut::new(Modifiers::COMMAND, Key::Y);
+        let undo = KeyboardShortcut::new(Modifiers::COMMAND, Key::Z);
+        // the shift variant has to be consumed first, it would also match plain Ctrl+Z
+        let (redo_pressed, undo_pressed) = ctx.input_mut(|input| {
+            let redo_pressed = input.consume_shortcut(&redo) || input.consume_shortcut(&redo_alt);
+            (redo_pressed, input.consume_shortcut(&undo))
+        });
+        if redo_pressed {
+            self.redo();
+        } else if undo_pressed {
+            self.undo();
+        }
+    }
+}
+
+// text cells reachable with Tab, in order. The unit picker is left out, it isn't a text field.
+const TAB_ORDER: [usize; 3] = [DESCRIPTION, QUANTITY, PRICE];
+
+fn cells_of(item: &LineItem) -> &'static [usize] {
+    if item.is_item() {
+        &TAB_ORDER
+    } else {
+        &[DESCRIPTION]
     }
 }
 
+fn cell_id(item_id: u64, column: usize) -> Id {
+    Id::new(("line_item_cell", item_id, column))
+}
+
+// functions related to keyboard entry in the item table
 impl Invoicy {
-    pub fn get_data(&mut self) -> Result<String, rusqlite::Error> {
-        let mut stmt = self.connection.prepare("SELECT * FROM data")?;
-        let rows = stmt.query_map([], |row| {
-            Ok(DatabaseData {
-                entry_id: row.get(0)?,
-                cust_id: row.get(1)?,
-                estimate_number: row.get(2)?,
-                row_number: row.get(3)?,
-                description: row.get(4)?,
-                quantity: row.get(5)?,
-                price: row.get(6)?,
-                total: row.get(7)?,
+    // row and column of the item cell that has keyboard focus
+    fn focused_cell(&self, ui: &Ui) -> Option<(usize, usize)> {
+        let focused = ui.memory(|memory| memory.focused())?;
+        self.line_items
+            .items()
+            .iter()
+            .enumerate()
+            .find_map(|(row, item)| {
+                TAB_ORDER
+                    .iter()
+                    .find(|column| cell_id(item.id, **column) == focused)
+                    .map(|column| (row, *column))
             })
-        })?;
-        // this will end up being slow as data builds up will need to figure out a better method
-        for data_row in rows {
-            self.database_data_vec.push(data_row.unwrap())
+    }
+
+    // falls back to the description for headings and subtotals, which have no other cells
+    fn focus_cell(&self, ui: &Ui, row: usize, column: usize) {
+        if let Some(item) = self.line_items.items().get(row) {
+            let column = if cells_of(item).contains(&column) {
+                column
+            } else {
+                DESCRIPTION
+            };
+            ui.memory_mut(|memory| memory.request_focus(cell_id(item.id, column)));
         }
-        Ok("Data Initialized from DB.".to_string())
     }
 
-    pub fn setup_tables(&mut self) {
-        let customer_result = self.connection.execute(
-            "CREATE TABLE IF NOT EXISTS customers (
-                    company TEXT PRIMARY KEY,
-                    address TEXT NOT NULL,
-                    city TEXT NOT NULL,
-                    postal_code TEXT NOT NULL,
-                    country TEXT NOT NULL,
-                    estimate_number INTEGER
-                )",
-            [],
-        );
-        match customer_result {
-            Ok(value) => println!("LOG: Customer Table Setup was Successful: {}", value),
-            Err(e) => println!("ERROR: Customer Table not setup Correctly {}", e),
+    // Spreadsheet style keys for the focused cell. Runs before the table is drawn so the
+    // text fields never see the keys handled here.
+    fn line_item_keys(&mut self, ui: &mut Ui) {
+        let Some((row, column)) = self.focused_cell(ui) else {
+            return;
+        };
+        let cells = cells_of(&self.line_items.items()[row]);
+        let cell_index = cells.iter().position(|c| *c == column).unwrap_or(0);
+        let duplicate = KeyboardShortcut::new(Modifiers::COMMAND, Key::D);
+        let row_is_empty = self.line_items.items()[row].is_empty();
+        let (shift_tab, tab, enter, duplicate, delete, paste) = ui.input_mut(|input| {
+            // Only blocks are handled here, a single value pastes into the cell. Lines without
+            // tabs pasted into a description stay there as multi-line text.
+            let paste = input.events.iter().find_map(|event| match event {
+                egui::Event::Paste(text)
+                    if text.contains('\t') || (text.contains('\n') && column != DESCRIPTION) =>
+                {
+                    Some(text.clone())
+                }
+                _ => None,
+            });
+            if paste.is_some() {
+                input
+                    .events
+                    .retain(|event| !matches!(event, egui::Event::Paste(_)));
+            }
+            (
+                input.consume_key(Modifiers::SHIFT, Key::Tab),
+                input.consume_key(Modifiers::NONE, Key::Tab),
+                // Shift+Enter starts a new line in the description
+                !input.modifiers.shift && input.consume_key(Modifiers::NONE, Key::Enter),
+                input.consume_shortcut(&duplicate),
+                row_is_empty && input.consume_key(Modifiers::NONE, Key::Delete),
+                paste,
+            )
+        });
+        let last_row = self.line_items.len() - 1;
+
+        if let Some(text) = paste {
+            self.paste_rows(row, column, &text);
+        } else if tab {
+            if cell_index + 1 < cells.len() {
+                self.focus_cell(ui, row, cells[cell_index + 1]);
+            } else {
+                if row == last_row {
+                    self.insert_row(row + 1);
+                }
+                self.focus_cell(ui, row + 1, TAB_ORDER[0]);
+            }
+        } else if shift_tab {
+            if cell_index > 0 {
+                self.focus_cell(ui, row, cells[cell_index - 1]);
+            } else if row > 0 {
+                let previous = cells_of(&self.line_items.items()[row - 1]);
+                self.focus_cell(ui, row - 1, previous[previous.len() - 1]);
+            }
+        } else if enter {
+            if row == last_row {
+                self.insert_row(row + 1);
+            }
+            self.focus_cell(ui, row + 1, column);
+        } else if duplicate {
+            if let Some(index) = self.line_items.duplicate(row) {
+                let item = self.line_items.items()[index].clone();
+                self.history.record(EditCommand::InsertRow { index, item });
+                self.focus_cell(ui, index, column);
+            }
+        } else if delete && last_row > 0 {
+            if let Some(item) = self.line_items.remove(row) {
+                self.history
+                    .record(EditCommand::RemoveRow { index: row, item });
+                self.focus_cell(ui, row.min(last_row - 1), column);
+            }
         }
-        let contact_result = self.connection.execute(
-            "CREATE TABLE IF NOT EXISTS contacts (
-                    company TEXT PRIMARY KEY,
-                    address Text NOT NULL,
-                    city TEXT NOT NULL,
-                    postal_code TEXT NOT NULL,
-                    country TEXT NOT NULL,
-                    name TEXT NOT NULL,
-                    telephone TEXT NOT NULL,
-                    email TEXT NOT NULL,
-                    website TEXT NOT NULL
-                )",
-            [],
+    }
+
+    // pastes a tab-separated block as one undo step
+    fn paste_rows(&mut self, row: usize, column: usize, text: &str) {
+        let before = self.line_items.clone();
+        let (edited, appended) = self.line_items.paste_block(row, column, text);
+        let mut commands: Vec<EditCommand> = edited
+            .into_iter()
+            .map(|index| EditCommand::EditRow {
+                index,
+                before: Box::new(before.items()[index].clone()),
+                after: Box::new(self.line_items.items()[index].clone()),
+            })
+            .collect();
+        for index in before.len()..before.len() + appended {
+            commands.push(EditCommand::InsertRow {
+                index,
+                item: self.line_items.items()[index].clone(),
+            });
+        }
+        self.history.seal();
+        self.history.record(EditCommand::Batch(commands));
+        self.history.seal();
+        println!(
+            "LOG: Pasted {} rows into the item table",
+            text.lines().count()
         );
-        match contact_result {
-            Ok(value) => println!("LOG: Contact Table Setup was Successful {}", value),
-            Err(e) => println!("ERROR: Contact Table not setup Correctly {}", e),
+    }
+}
+
+enum RowAction {
+    Insert(usize),
+    Delete(usize),
+    Move(usize, usize),
+}
+
+// text field that turns red and explains itself when the input doesn't parse
+fn number_input(ui: &mut Ui, id: Id, input: &mut String, error: &Option<String>) -> bool {
+    let mut text_edit = TextEdit::singleline(input)
+        .id(id)
+        .lock_focus(true)
+        .desired_width(80.0);
+    if error.is_some() {
+        text_edit = text_edit.text_color(Color32::RED);
+    }
+    let response = ui.add(text_edit);
+    let changed = response.changed();
+    if let Some(error) = error {
+        response.on_hover_text(error);
+    }
+    changed
+}
+
+// functions related to the invoice notes and terms
+impl Invoicy {
+    pub fn invoice_text_form(&mut self, ui: &mut Ui) {
+        egui::CollapsingHeader::new("Notes & Terms").show(ui, |ui| {
+            egui::Grid::new("invoice_text")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("PO Number: ");
+                    ui.text_edit_singleline(&mut self.invoice_text.po_number);
+                    ui.end_row();
+                    ui.label("Project / Reference: ");
+                    ui.text_edit_singleline(&mut self.invoice_text.reference);
+                    ui.end_row();
+                    ui.label("Notes: ");
+                    ui.text_edit_multiline(&mut self.invoice_text.notes);
+                    ui.end_row();
+                    ui.label("Terms: ");
+                    ui.text_edit_multiline(&mut self.invoice_text.terms);
+                    ui.end_row();
+                });
+            if ui
+                .button("Use Defaults")
+                .on_hover_text("Notes and terms from the customer, or else the contact")
+                .clicked()
+            {
+                self.text_defaults_for = None;
+            }
+        });
+    }
+
+    // refills notes and terms whenever a different customer or contact is selected
+    pub fn update_text_defaults(&mut self) {
+        let selected = (self.customer_selected, self.contact_selected);
+        if self.text_defaults_for == Some(selected)
+            || self.customers.is_empty()
+            || self.contacts.is_empty()
+        {
+            return;
         }
-        let data_result = self.connection.execute(
-            "CREATE TABLE IF NOT EXISTS data (
-                    entry_id TEXT PRIMARY KEY,
-                    cust_id TEXT NOT NULL,
-                    estimate_number INTEGER NOT NULL,
-                    row_number INTEGER NOT NULL,
-                    description TEXT NOT NULL,
-                    quantity REAL,
-                    price REAL,
-                    total REAL
-                )",
-            [],
+        let defaults = InvoiceText::defaults_for(
+            &self.contacts[self.contact_selected],
+            &self.customers[self.customer_selected],
         );
-        match data_result {
-            Ok(value) => println!("LOG: Data Table Setup was Successful {}", value),
-            Err(e) => println!("ERROR: Data Table not setup Correctly {}", e),
+        self.invoice_text.notes = defaults.notes;
+        self.invoice_text.terms = defaults.terms;
+        self.text_defaults_for = Some(selected);
+    }
+}
+
+// preset units plus a free text field for anything else
+pub(crate) fn unit_select(ui: &mut Ui, id: Id, unit: &mut String) {
+    egui::ComboBox::from_id_source(id)
+        .width(70.0)
+        .selected_text(unit.as_str())
+        .show_ui(ui, |ui| {
+            for preset in UNITS {
+                ui.selectable_value(unit, preset.to_string(), preset);
+            }
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label("Custom: ");
+                ui.add(TextEdit::singleline(unit).desired_width(60.0));
+            });
+        });
+}
+
+impl Invoicy {
+    pub fn generate_customer_id(&mut self, idx: usize) -> String {
+        self.customers[idx].customer_id()
+    }
+}
+
+impl Invoicy {
+    pub fn get_data(&mut self) -> Result<String, rusqlite::Error> {
+        // this will end up being slow as data builds up will need to figure out a better method
+        self.database_data_vec.extend(self.store.line_items()?);
+        Ok("Data Initialized from DB.".to_string())
+    }
+
+    pub fn setup_tables(&mut self) {
+        match self.store.create_tables() {
+            Ok(()) => println!("LOG: Table Setup was Successful"),
+            Err(e) => println!("ERROR: Tables not setup Correctly {}", e),
         }
     }
 }
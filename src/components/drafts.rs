@@ -0,0 +1,236 @@
+use std::time::{Duration, Instant};
+
+use eframe::egui::{self, Ui, Window};
+use invoicy::line_items::LineItems;
+use invoicy::structs::Draft;
+
+use crate::Invoicy;
+
+// how long edits may sit in memory before they are written out
+const AUTOSAVE_DELAY: Duration = Duration::from_secs(2);
+
+// functions related to the drafts UI
+impl Invoicy {
+    pub fn show_drafts(&mut self, ui: &mut Ui) {
+        if self.drafts_form {
+            Window::new("Drafts").show(ui.ctx(), |ui| {
+                if self.drafts.is_empty() {
+                    ui.label("No saved drafts.");
+                }
+                for draft in self.drafts.clone() {
+                    ui.horizontal(|ui| {
+                        let current = if draft.draft_id == self.draft_id {
+                            " (editing)"
+                        } else {
+                            ""
+                        };
+                        ui.label(format!(
+                            "#{} {} - {} ({}){}",
+                            draft.draft_id,
+                            draft.customer,
+                            draft.contact,
+                            draft.updated_at,
+                            current
+                        ));
+                        if ui.button("Resume").clicked() {
+                            self.resume_draft(draft.draft_id);
+                        }
+                        if ui.button("🗑").on_hover_text("Delete draft").clicked() {
+                            self.delete_draft(draft.draft_id);
+                        }
+                    });
+                }
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button("+ new draft").clicked() {
+                        self.new_draft();
+                    }
+                    if ui.button("Close").clicked() {
+                        self.drafts_form = false;
+                    }
+                });
+            });
+        }
+    }
+
+    // offered once on startup when drafts were left behind by the last session
+    pub fn show_recovery_prompt(&mut self, ctx: &egui::Context) {
+        let Some(draft) = self.recovery_prompt.clone() else {
+            return;
+        };
+        Window::new("Recover Draft")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "An unfinished invoice for {} from {} was found.",
+                    draft.customer, draft.updated_at
+                ));
+                ui.horizontal(|ui| {
+                    if ui.button("Recover").clicked() {
+                        self.resume_draft(draft.draft_id);
+                        self.recovery_prompt = None;
+                    }
+                    if ui.button("All Drafts").clicked() {
+                        self.open_drafts();
+                        self.recovery_prompt = None;
+                    }
+                    if ui.button("Start New").clicked() {
+                        self.recovery_prompt = None;
+                    }
+                });
+            });
+    }
+
+    pub fn drafts_button(&mut self, ui: &mut Ui) {
+        if ui.button("drafts").clicked() {
+            self.open_drafts();
+        }
+    }
+}
+
+// functions related to draft actions
+impl Invoicy {
+    pub fn get_drafts(&mut self) -> Result<String, rusqlite::Error> {
+        self.drafts = self.store.drafts()?;
+        if let Some(draft) = self.drafts.first() {
+            self.recovery_prompt = Some(draft.clone());
+        }
+        Ok("Drafts Initialized from DB.".to_string())
+    }
+
+    fn open_drafts(&mut self) {
+        self.autosave_draft(true);
+        match self.store.drafts() {
+            Ok(drafts) => self.drafts = drafts,
+            Err(e) => println!("ERROR: Drafts unable to be loaded {}", e),
+        }
+        self.drafts_form = true;
+    }
+
+    // the editor as it would be stored, compared against the last save to spot changes
+    fn current_draft(&self) -> Draft {
+        Draft {
+            draft_id: self.draft_id,
+            customer: self.customers[self.customer_selected].company.clone(),
+            contact: self.contacts[self.contact_selected].company.clone(),
+            updated_at: String::new(),
+            text: self.invoice_text.clone(),
+            items: self.line_items.to_draft_items(),
+        }
+    }
+
+    // Writes the editor to the current draft once it has been left alone for a moment.
+    // `force` skips the delay, used before switching drafts and on exit.
+    pub fn autosave_draft(&mut self, force: bool) {
+        if self.customers.is_empty() || self.contacts.is_empty() {
+            return;
+        }
+        let draft = self.current_draft();
+        if self.saved_draft.as_ref() == Some(&draft) {
+            self.draft_changed_at = None;
+            return;
+        }
+        // nothing worth keeping in a fresh editor
+        if draft.draft_id == 0 && self.line_items.items().iter().all(|item| item.is_empty()) {
+            return;
+        }
+        let changed_at = *self.draft_changed_at.get_or_insert_with(Instant::now);
+        if !force && changed_at.elapsed() < AUTOSAVE_DELAY {
+            return;
+        }
+        match self.store.save_draft(&draft) {
+            Ok(draft_id) => {
+                println!("LOG: Draft {} Saved", draft_id);
+                self.draft_id = draft_id;
+                self.saved_draft = Some(Draft { draft_id, ..draft });
+                self.draft_changed_at = None;
+            }
+            Err(e) => println!("ERROR: Draft unable to be Saved {}", e),
+        }
+    }
+
+    // keeps the app ticking while an autosave is pending, egui only repaints on input
+    pub fn schedule_autosave(&mut self, ctx: &egui::Context) {
+        self.autosave_draft(false);
+        if self.draft_changed_at.is_some() {
+            ctx.request_repaint_after(AUTOSAVE_DELAY);
+        }
+    }
+
+    pub fn resume_draft(&mut self, draft_id: i64) {
+        self.autosave_draft(true);
+        let draft = match self.store.draft(draft_id) {
+            Ok(Some(draft)) => draft,
+            Ok(None) => {
+                self.error_message = Some(format!("Draft #{} no longer exists", draft_id));
+                return;
+            }
+            Err(e) => {
+                self.error_message = Some(format!("Unable to load draft: {}", e));
+                return;
+            }
+        };
+        if let Some(idx) = self
+            .customers
+            .iter()
+            .position(|customer| customer.company == draft.customer)
+        {
+            self.customer_selected = idx;
+        }
+        if let Some(idx) = self
+            .contacts
+            .iter()
+            .position(|contact| contact.company == draft.contact)
+        {
+            self.contact_selected = idx;
+        }
+        self.line_items = LineItems::from_draft_items(&draft.items);
+        self.invoice_text = draft.text;
+        // keep the draft's own notes instead of the selection defaults
+        self.text_defaults_for = Some((self.customer_selected, self.contact_selected));
+        self.draft_id = draft.draft_id;
+        self.saved_draft = Some(self.current_draft());
+        self.draft_changed_at = None;
+        self.history.clear();
+        self.prices_for = None;
+        self.drafts_form = false;
+        println!("LOG: Draft {} Resumed", draft_id);
+    }
+
+    pub fn new_draft(&mut self) {
+        self.autosave_draft(true);
+        self.line_items = LineItems::default();
+        self.text_defaults_for = None;
+        self.prices_for = None;
+        self.draft_id = 0;
+        self.saved_draft = None;
+        self.draft_changed_at = None;
+        self.history.clear();
+        self.drafts_form = false;
+    }
+
+    pub fn delete_draft(&mut self, draft_id: i64) {
+        match self.store.delete_draft(draft_id) {
+            Ok(_) => println!("LOG: Draft {} Deleted", draft_id),
+            Err(e) => println!("ERROR: Draft unable to be Deleted {}", e),
+        }
+        if draft_id == self.draft_id {
+            // the editor keeps its rows, they become a new draft on the next edit
+            self.draft_id = 0;
+            self.saved_draft = Some(self.current_draft());
+        }
+        self.drafts.retain(|draft| draft.draft_id != draft_id);
+    }
+
+    // an issued invoice no longer needs its draft
+    pub fn finish_draft(&mut self) {
+        if self.draft_id != 0 {
+            self.delete_draft(self.draft_id);
+        }
+        self.draft_id = 0;
+        self.saved_draft = Some(self.current_draft());
+        self.draft_changed_at = None;
+    }
+}
@@ -0,0 +1,176 @@
+use eframe::egui::{self, Color32, Grid, Ui, Window};
+
+use crate::Invoicy;
+use invoicy::db;
+use invoicy::encryption::{self, EncryptionError};
+
+// functions related to the encryption UI
+impl Invoicy {
+    // shown instead of the app until an encrypted database is unlocked
+    pub fn show_unlock(&mut self, ctx: &egui::Context) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.add_space(40.0);
+            ui.vertical_centered(|ui| {
+                ui.heading("The database is encrypted");
+                ui.label(self.database_path.display().to_string());
+                ui.add_space(8.0);
+                let response = ui.add(
+                    egui::TextEdit::singleline(&mut self.unlock_input)
+                        .password(true)
+                        .hint_text("Passphrase"),
+                );
+                let entered =
+                    response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                if ui.button("Unlock").clicked() || entered {
+                    self.unlock_database();
+                }
+                if let Some(message) = &self.unlock_message {
+                    ui.colored_label(Color32::RED, message);
+                }
+            });
+        });
+    }
+
+    pub fn show_encryption(&mut self, ui: &mut Ui) {
+        if !self.encryption_form {
+            return;
+        }
+        let mut open = true;
+        let encrypted = self.store.is_encrypted();
+        Window::new("Encryption")
+            .open(&mut open)
+            .show(ui.ctx(), |ui| {
+                if encrypted {
+                    ui.label(
+                        "The database is encrypted, it is unlocked with its passphrase on start.",
+                    );
+                } else {
+                    ui.label("The database is stored in plain text.");
+                }
+                Grid::new("passphrase_fields").show(ui, |ui| {
+                    let field = |ui: &mut Ui, label: &str, value: &mut String| {
+                        ui.label(label);
+                        ui.add(egui::TextEdit::singleline(value).password(true));
+                        ui.end_row();
+                    };
+                    if encrypted {
+                        field(ui, "Current passphrase: ", &mut self.passphrase_current);
+                    }
+                    field(ui, "New passphrase: ", &mut self.passphrase_new);
+                    field(ui, "Confirm: ", &mut self.passphrase_confirm);
+                });
+                ui.colored_label(
+                    Color32::RED,
+                    "A forgotten passphrase cannot be recovered, the data is lost with it.",
+                );
+                ui.horizontal(|ui| {
+                    let label = if encrypted {
+                        "Change Passphrase"
+                    } else {
+                        "Encrypt Database"
+                    };
+                    if ui.button(label).clicked() {
+                        self.change_passphrase();
+                    }
+                    if encrypted && ui.button("Remove Encryption").clicked() {
+                        self.remove_passphrase();
+                    }
+                });
+                if let Some(message) = &self.encryption_message {
+                    ui.label(message);
+                }
+            });
+        if !open {
+            self.encryption_form = false;
+            self.clear_passphrase_fields();
+        }
+    }
+}
+
+// functions related to encryption actions
+impl Invoicy {
+    fn unlock_database(&mut self) {
+        match encryption::open(&self.database_path, Some(&self.unlock_input)) {
+            Ok(store) => {
+                println!("LOG: Unlocked database {:?}", self.database_path);
+                self.store = store;
+                self.locked = false;
+                self.unlock_input.clear();
+                self.unlock_message = None;
+                self.reset_loaded_data();
+            }
+            Err(e) => {
+                println!("ERROR: Unable to unlock {:?}: {}", self.database_path, e);
+                self.unlock_message = Some(e.to_string());
+            }
+        }
+    }
+
+    // an encrypted database is only opened once its passphrase is entered
+    pub fn lock_database(&mut self) {
+        println!("LOG: Database {:?} is encrypted", self.database_path);
+        self.store = db::Store::open_in_memory().unwrap();
+        self.locked = true;
+        self.unlock_message = None;
+        self.reset_loaded_data();
+    }
+
+    fn current_passphrase_matches(&mut self) -> bool {
+        if !self.store.is_encrypted() || self.store.passphrase() == Some(&self.passphrase_current) {
+            return true;
+        }
+        self.encryption_message = Some(EncryptionError::WrongPassphrase.to_string());
+        false
+    }
+
+    fn change_passphrase(&mut self) {
+        if !self.current_passphrase_matches() {
+            return;
+        }
+        if let Err(e) =
+            encryption::validate_passphrase(&self.passphrase_new, &self.passphrase_confirm)
+        {
+            self.encryption_message = Some(e.to_string());
+            return;
+        }
+        let done = if self.store.is_encrypted() {
+            "Passphrase changed, backups made earlier still need the old one"
+        } else {
+            "Database encrypted, backups made earlier still hold it in plain text"
+        };
+        let passphrase = self.passphrase_new.clone();
+        self.set_passphrase(Some(&passphrase), done);
+    }
+
+    fn remove_passphrase(&mut self) {
+        if !self.current_passphrase_matches() {
+            return;
+        }
+        self.set_passphrase(
+            None,
+            "Encryption removed, the database is stored in plain text",
+        );
+    }
+
+    fn set_passphrase(&mut self, passphrase: Option<&str>, done: &str) {
+        // the open draft is written first so it ends up in the converted file
+        self.autosave_draft(true);
+        match encryption::set_passphrase(&mut self.store, &self.database_path, passphrase) {
+            Ok(_) => {
+                println!("LOG: Encryption of {:?} changed", self.database_path);
+                self.clear_passphrase_fields();
+                self.encryption_message = Some(done.to_string());
+            }
+            Err(e) => {
+                println!("ERROR: Unable to change encryption {}", e);
+                self.encryption_message = Some(e.to_string());
+            }
+        }
+    }
+
+    fn clear_passphrase_fields(&mut self) {
+        self.passphrase_current.clear();
+        self.passphrase_new.clear();
+        self.passphrase_confirm.clear();
+    }
+}
@@ -0,0 +1,153 @@
+use chrono::NaiveDate;
+use eframe::egui::{self, Ui, Window};
+use rfd::FileDialog;
+
+use crate::Invoicy;
+use invoicy::export::{self, ExportFilter, ExportFormat};
+
+// functions related to the data export UI
+impl Invoicy {
+    pub fn show_export(&mut self, ui: &mut Ui) {
+        if !self.export_form {
+            return;
+        }
+        let mut open = true;
+        Window::new("Export Data")
+            .open(&mut open)
+            .show(ui.ctx(), |ui| {
+                ui.label("Invoices, line items, customers, contacts and payments.");
+                ui.horizontal(|ui| {
+                    ui.label("From: ");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.export_from)
+                            .hint_text("YYYY-MM-DD")
+                            .desired_width(90.0),
+                    );
+                    ui.label("To: ");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.export_to)
+                            .hint_text("YYYY-MM-DD")
+                            .desired_width(90.0),
+                    );
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Customer: ");
+                    egui::ComboBox::from_id_source("export_customer")
+                        .selected_text(self.export_customer.as_deref().unwrap_or("All customers"))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut self.export_customer, None, "All customers");
+                            for customer in &self.customers {
+                                ui.selectable_value(
+                                    &mut self.export_customer,
+                                    Some(customer.company.clone()),
+                                    &customer.company,
+                                );
+                            }
+                        });
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Format: ");
+                    egui::ComboBox::from_id_source("export_format")
+                        .selected_text(self.export_format.label())
+                        .show_ui(ui, |ui| {
+                            for format in ExportFormat::all() {
+                                ui.selectable_value(
+                                    &mut self.export_format,
+                                    format,
+                                    format.label(),
+                                );
+                            }
+                        });
+                });
+                if let ExportFormat::Accounting(_) = self.export_format {
+                    ui.label(format!(
+                        "Posts to {}, {}, {} and {}, change these under settings.",
+                        self.settings.accounts.receivable,
+                        self.settings.accounts.revenue,
+                        self.settings.accounts.tax_payable,
+                        self.settings.accounts.bank
+                    ));
+                }
+                ui.label(format!(
+                    "JSON follows the {} schema version {}.",
+                    export::FORMAT_NAME,
+                    export::SCHEMA_VERSION
+                ));
+                if let Some(message) = &self.export_message {
+                    ui.label(message);
+                }
+                if ui.button("Export").clicked() {
+                    self.export_data();
+                }
+            });
+        if !open {
+            self.export_form = false;
+        }
+    }
+}
+
+// functions related to data export actions
+impl Invoicy {
+    fn export_filter(&self) -> Result<ExportFilter, String> {
+        let date = |input: &str| -> Result<Option<NaiveDate>, String> {
+            let input = input.trim();
+            if input.is_empty() {
+                return Ok(None);
+            }
+            NaiveDate::parse_from_str(input, "%Y-%m-%d")
+                .map(Some)
+                .map_err(|_| format!("{} is not a date, use YYYY-MM-DD", input))
+        };
+        let filter = ExportFilter {
+            from: date(&self.export_from)?,
+            to: date(&self.export_to)?,
+            customer: self.export_customer.clone(),
+        };
+        if let (Some(from), Some(to)) = (filter.from, filter.to) {
+            if from > to {
+                return Err("The start date is after the end date".to_string());
+            }
+        }
+        Ok(filter)
+    }
+
+    fn export_data(&mut self) {
+        let filter = match self.export_filter() {
+            Ok(filter) => filter,
+            Err(e) => {
+                self.error_message = Some(e);
+                return;
+            }
+        };
+        let path = match self.export_format {
+            ExportFormat::Json => FileDialog::new()
+                .add_filter("json", &["json"])
+                .set_file_name("invoicy-export.json")
+                .save_file(),
+            ExportFormat::Csv => FileDialog::new().pick_folder(),
+            ExportFormat::Accounting(format) => FileDialog::new()
+                .add_filter(format.extension(), &[format.extension()])
+                .set_file_name(format!("invoicy-{}.{}", format.key(), format.extension()))
+                .save_file(),
+        };
+        let Some(path) = path else {
+            return;
+        };
+        match export::write_export(
+            &self.store,
+            &filter,
+            self.export_format,
+            &self.settings,
+            &path,
+        ) {
+            Ok(files) => {
+                println!("LOG: Exported {} file(s) to {:?}", files.len(), path);
+                self.export_message = Some(format!("Exported to {}", path.display()));
+            }
+            Err(e) => {
+                println!("ERROR: Export failed {}", e);
+                self.error_message = Some(e.to_string());
+            }
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use eframe::egui::{self, Color32, Grid, ScrollArea, Ui, Window};
+use rfd::FileDialog;
+
+use crate::Invoicy;
+use invoicy::import::{self, DuplicatePolicy, Field, ImportPlan, ImportSource, ImportTarget};
+
+const PREVIEW_ROWS: usize = 5;
+
+// functions related to the customer and contact import UI
+impl Invoicy {
+    pub fn show_import(&mut self, ui: &mut Ui) {
+        if !self.import_form {
+            return;
+        }
+        let mut open = true;
+        Window::new("Import Customers and Contacts")
+            .open(&mut open)
+            .show(ui.ctx(), |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Import into: ");
+                    for target in [ImportTarget::Customers, ImportTarget::Contacts] {
+                        if ui
+                            .radio_value(&mut self.import_target, target, target.label())
+                            .changed()
+                        {
+                            self.guess_import_mapping();
+                        }
+                    }
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("Choose File").clicked() {
+                        self.choose_import_file();
+                    }
+                    ui.label("CSV or vCard (.vcf)");
+                });
+                if self.import_source.is_none() {
+                    return;
+                }
+                self.import_mapping_grid(ui);
+                ui.horizontal(|ui| {
+                    ui.label("Existing companies: ");
+                    ui.radio_value(&mut self.import_policy, DuplicatePolicy::Skip, "Skip");
+                    ui.radio_value(
+                        &mut self.import_policy,
+                        DuplicatePolicy::Update,
+                        "Update with the file's values",
+                    );
+                });
+                ui.separator();
+                // the dry run, nothing is written until Import is clicked
+                let plan = self.import_plan();
+                ui.label(format!(
+                    "{} to add, {} to update, {} skipped",
+                    plan.additions(),
+                    plan.updates(),
+                    plan.skipped()
+                ));
+                ScrollArea::vertical()
+                    .id_source("import_report")
+                    .max_height(160.0)
+                    .show(ui, |ui| {
+                        for line in plan.to_string().lines().skip(1) {
+                            ui.label(line);
+                        }
+                    });
+                if let Some(message) = &self.import_message {
+                    ui.label(message);
+                }
+                let changes = plan.additions() + plan.updates();
+                if ui
+                    .add_enabled(
+                        changes > 0,
+                        egui::Button::new(format!("Import {}", changes)),
+                    )
+                    .clicked()
+                {
+                    self.apply_import(&plan);
+                }
+            });
+        if !open {
+            self.import_form = false;
+        }
+    }
+
+    // a combo box per CSV column with the first rows underneath as a preview
+    fn import_mapping_grid(&mut self, ui: &mut Ui) {
+        let Some(ImportSource::Csv(table)) = &self.import_source else {
+            return;
+        };
+        let fields = Field::for_target(self.import_target);
+        let mapping = &mut self.import_mapping;
+        if !mapping.contains(&Some(Field::Company)) {
+            ui.colored_label(
+                Color32::RED,
+                "Pick the column holding the company, rows without one are skipped.",
+            );
+        }
+        ScrollArea::horizontal().show(ui, |ui| {
+            Grid::new("import_mapping").striped(true).show(ui, |ui| {
+                for (idx, header) in table.headers.iter().enumerate() {
+                    ui.vertical(|ui| {
+                        ui.strong(header);
+                        let selected = mapping[idx].map_or("Ignore", |field| field.label());
+                        egui::ComboBox::from_id_source(("import_column", idx))
+                            .selected_text(selected)
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut mapping[idx], None, "Ignore");
+                                for field in &fields {
+                                    ui.selectable_value(
+                                        &mut mapping[idx],
+                                        Some(*field),
+                                        field.label(),
+                                    );
+                                }
+                            });
+                    });
+                }
+                ui.end_row();
+                for row in table.rows.iter().take(PREVIEW_ROWS) {
+                    for value in row {
+                        ui.label(value.replace('\n', " "));
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+        if table.rows.len() > PREVIEW_ROWS {
+            ui.label(format!("and {} more rows", table.rows.len() - PREVIEW_ROWS));
+        }
+    }
+}
+
+// functions related to import actions
+impl Invoicy {
+    fn choose_import_file(&mut self) {
+        let Some(path) = FileDialog::new()
+            .add_filter("CSV or vCard", &["csv", "txt", "vcf", "vcard"])
+            .pick_file()
+        else {
+            return;
+        };
+        match std::fs::read(&path) {
+            Ok(bytes) => {
+                // exports from older spreadsheets may not be UTF-8, keep what can be read
+                let text = String::from_utf8_lossy(&bytes);
+                self.import_source = Some(ImportSource::read(&text));
+                self.import_message = None;
+                self.guess_import_mapping();
+                println!("LOG: Import file {:?} loaded", path);
+            }
+            Err(e) => {
+                println!("ERROR: Unable to read {:?}: {}", path, e);
+                self.error_message = Some(format!("Unable to read {}: {}", path.display(), e));
+            }
+        }
+    }
+
+    fn guess_import_mapping(&mut self) {
+        self.import_mapping = match &self.import_source {
+            Some(ImportSource::Csv(table)) => {
+                import::guess_mapping(&table.headers, self.import_target)
+            }
+            _ => Vec::new(),
+        };
+    }
+
+    fn import_plan(&self) -> ImportPlan {
+        let records = self
+            .import_source
+            .as_ref()
+            .map(|source| source.records(&self.import_mapping))
+            .unwrap_or_default();
+        let existing =
+            import::existing_records(self.import_target, &self.customers, &self.contacts);
+        import::plan(self.import_target, records, &existing, self.import_policy)
+    }
+
+    fn apply_import(&mut self, plan: &ImportPlan) {
+        match plan.apply(&mut self.store) {
+            Ok(written) => {
+                println!("LOG: Imported {} {}", written, plan.target.label());
+                self.import_message = Some(format!(
+                    "Imported {} {}",
+                    written,
+                    plan.target.label().to_lowercase()
+                ));
+                self.reload_parties();
+            }
+            Err(e) => {
+                println!("ERROR: Import failed {}", e);
+                self.error_message = Some(format!("Nothing was imported: {}", e));
+            }
+        }
+    }
+
+    // reloads customers and contacts, keeping the editor on the same companies
+    fn reload_parties(&mut self) {
+        let customer = self
+            .customers
+            .get(self.customer_selected)
+            .map(|c| c.company.clone());
+        let contact = self
+            .contacts
+            .get(self.contact_selected)
+            .map(|c| c.company.clone());
+        match (self.store.customers(), self.store.contacts()) {
+            (Ok(customers), Ok(contacts)) => {
+                self.customers = customers;
+                self.contacts = contacts;
+            }
+            (Err(e), _) | (_, Err(e)) => {
+                println!("ERROR: Unable to reload customers and contacts {}", e);
+                return;
+            }
+        }
+        self.customer_selected = customer
+            .and_then(|company| self.customers.iter().position(|c| c.company == company))
+            .unwrap_or(0);
+        self.contact_selected = contact
+            .and_then(|company| self.contacts.iter().position(|c| c.company == company))
+            .unwrap_or(0);
+    }
+}
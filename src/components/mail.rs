@@ -0,0 +1,392 @@
+use std::path::Path;
+use std::sync::mpsc::{self, TryRecvError};
+use std::time::Duration;
+
+use eframe::egui::{self, CollapsingHeader, Color32, Grid, ScrollArea, Ui, Window};
+
+use crate::Invoicy;
+use invoicy::mail::{self, Email};
+use invoicy::settings::MailSecurity;
+use invoicy::structs::{Contact, Invoice};
+
+// how often a message being sent is looked at
+const SEND_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+// functions related to the email UI
+impl Invoicy {
+    pub fn show_mail(&mut self, ui: &mut Ui) {
+        if !self.mail_form {
+            return;
+        }
+        let mut open = true;
+        Window::new("Email Invoice")
+            .open(&mut open)
+            .show(ui.ctx(), |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Invoice: ");
+                    let mut selected = self.mail_invoice.clone();
+                    egui::ComboBox::from_id_source("mail_invoice")
+                        .selected_text(selected.as_deref().unwrap_or("Select an invoice"))
+                        .show_ui(ui, |ui| {
+                            for invoice in self.invoices.iter().rev() {
+                                ui.selectable_value(
+                                    &mut selected,
+                                    Some(invoice.invoice_id.clone()),
+                                    format!(
+                                        "{} {} ({:.2}) {}",
+                                        invoice.invoice_id,
+                                        invoice.customer,
+                                        invoice.grand_total,
+                                        invoice.status.label()
+                                    ),
+                                );
+                            }
+                        });
+                    if selected != self.mail_invoice {
+                        if let Some(invoice_id) = selected {
+                            self.select_mail_invoice(&invoice_id);
+                        }
+                    }
+                });
+                if let Some(invoice) = self.mail_invoice_record.clone() {
+                    self.mail_message_form(ui, &invoice);
+                }
+                if let Some(message) = &self.mail_message {
+                    ui.label(message);
+                }
+                ui.separator();
+                self.mail_server_section(ui);
+                self.mail_sender_section(ui);
+            });
+        if !open {
+            self.mail_form = false;
+        }
+    }
+
+    fn mail_message_form(&mut self, ui: &mut Ui, invoice: &Invoice) {
+        let email = &mut self.mail_email;
+        Grid::new("mail_fields").show(ui, |ui| {
+            ui.label("To: ");
+            ui.add(
+                egui::TextEdit::singleline(&mut email.to)
+                    .hint_text("Set the customer's email in the customer form"),
+            );
+            ui.end_row();
+            ui.label("Subject: ");
+            ui.text_edit_singleline(&mut email.subject);
+            ui.end_row();
+        });
+        ui.text_edit_multiline(&mut email.body);
+        let attachment = invoice
+            .file_path
+            .as_deref()
+            .and_then(|path| Path::new(path).file_name())
+            .map_or("none".to_string(), |name| {
+                name.to_string_lossy().to_string()
+            });
+        ui.label(format!("Attached: {}", attachment));
+        ui.horizontal(|ui| {
+            let sending = self.mail_sending.is_some();
+            if ui
+                .add_enabled(!sending, egui::Button::new("Send"))
+                .clicked()
+            {
+                self.send_mail(invoice);
+            }
+            if ui
+                .button("Rewrite from Templates")
+                .on_hover_text("Replaces your edits with the sender's templates")
+                .clicked()
+            {
+                self.select_mail_invoice(&invoice.invoice_id);
+            }
+            if sending {
+                ui.label("Sending…");
+            }
+        });
+        if self.mail_log.is_empty() {
+            ui.label("Not emailed yet.");
+        }
+        ScrollArea::vertical()
+            .id_source("sent_mail")
+            .max_height(80.0)
+            .show(ui, |ui| {
+                for sent in &self.mail_log {
+                    ui.label(format!(
+                        "{} to {}: {}",
+                        sent.sent_at, sent.recipients, sent.response
+                    ));
+                }
+            });
+    }
+
+    fn mail_server_section(&mut self, ui: &mut Ui) {
+        CollapsingHeader::new("Mail Server").show(ui, |ui| {
+            let server = &mut self.settings.mail;
+            let password = &mut self.remote_credentials.smtp_password;
+            Grid::new("mail_server_fields").show(ui, |ui| {
+                ui.label("Host: ");
+                ui.text_edit_singleline(&mut server.host);
+                ui.end_row();
+                ui.label("Security: ");
+                let previous = server.security;
+                egui::ComboBox::from_id_source("mail_security")
+                    .selected_text(server.security.label())
+                    .show_ui(ui, |ui| {
+                        for security in MailSecurity::ALL {
+                            ui.selectable_value(&mut server.security, security, security.label());
+                        }
+                    });
+                // the usual port follows the security unless a custom one was set
+                if server.security != previous && server.port == previous.default_port() {
+                    server.port = server.security.default_port();
+                }
+                ui.end_row();
+                ui.label("Port: ");
+                ui.add(egui::DragValue::new(&mut server.port));
+                ui.end_row();
+                ui.label("User: ");
+                ui.text_edit_singleline(&mut server.user);
+                ui.end_row();
+                ui.label("Password: ");
+                ui.add(egui::TextEdit::singleline(password).password(true));
+                ui.end_row();
+            });
+            ui.label("The password is kept in its own file, not in the database.");
+            if ui.button("Save Server").clicked() {
+                self.save_mail_server();
+            }
+        });
+    }
+
+    fn mail_sender_section(&mut self, ui: &mut Ui) {
+        let title = format!("Sender for {}", self.mail_sender.contact);
+        CollapsingHeader::new(title)
+            .id_source("mail_sender")
+            .show(ui, |ui| {
+                let sender = &mut self.mail_sender;
+                Grid::new("mail_sender_fields").show(ui, |ui| {
+                    for (label, value) in [
+                        ("From Name: ", &mut sender.from_name),
+                        ("From Address: ", &mut sender.from_address),
+                        ("Reply-To: ", &mut sender.reply_to),
+                        ("Bcc: ", &mut sender.bcc),
+                        ("Subject: ", &mut sender.subject),
+                    ] {
+                        ui.label(label);
+                        ui.text_edit_singleline(value);
+                        ui.end_row();
+                    }
+                });
+                ui.label("Message: ");
+                ui.text_edit_multiline(&mut sender.body);
+                CollapsingHeader::new("Placeholders").show(ui, |ui| {
+                    for (name, description) in mail::PLACEHOLDERS {
+                        ui.label(format!("{{{}}} {}", name, description));
+                    }
+                });
+                if let Some(error) = &self.mail_sender_error {
+                    ui.colored_label(Color32::RED, error);
+                }
+                if ui.button("Save Sender").clicked() {
+                    self.save_mail_sender();
+                }
+            });
+    }
+}
+
+// functions related to email actions
+impl Invoicy {
+    // picks up invoices issued and emailed since the last time
+    pub fn open_mail(&mut self) {
+        match self.store.invoices() {
+            Ok(invoices) => self.invoices = invoices,
+            Err(e) => println!("ERROR: Unable to load invoices {}", e),
+        }
+        if self.mail_invoice.is_none() {
+            let contact = self.contact.clone();
+            self.load_mail_sender(&contact);
+        }
+        self.mail_form = true;
+    }
+
+    // writes the message for an invoice from the templates of the contact that issued it
+    pub fn select_mail_invoice(&mut self, invoice_id: &str) {
+        let invoice = match self.store.invoice(invoice_id) {
+            Ok(Some(invoice)) => invoice,
+            Ok(None) => return,
+            Err(e) => {
+                println!("ERROR: Unable to load invoice {}: {}", invoice_id, e);
+                self.mail_message = Some(e.to_string());
+                return;
+            }
+        };
+        let contact = self
+            .contacts
+            .iter()
+            .find(|contact| contact.company == invoice.contact)
+            .cloned()
+            .unwrap_or_else(|| Contact {
+                company: invoice.contact.clone(),
+                ..Default::default()
+            });
+        let customer = self
+            .customers
+            .iter()
+            .find(|customer| customer.company == invoice.customer)
+            .cloned()
+            .unwrap_or_default();
+        self.load_mail_sender(&contact);
+        self.mail_message = None;
+        match mail::compose(
+            &invoice,
+            &customer,
+            &contact,
+            &self.mail_sender,
+            &self.settings,
+        ) {
+            Ok(email) => self.mail_email = email,
+            Err(e) => {
+                self.mail_email = Email {
+                    to: customer.email.clone(),
+                    ..Default::default()
+                };
+                self.mail_message = Some(e.to_string());
+            }
+        }
+        self.mail_log = self.store.sent_mail(invoice_id).unwrap_or_else(|e| {
+            println!("ERROR: Unable to load the sent log {}", e);
+            Vec::new()
+        });
+        self.mail_invoice = Some(invoice.invoice_id.clone());
+        self.mail_invoice_record = Some(invoice);
+    }
+
+    fn load_mail_sender(&mut self, contact: &Contact) {
+        self.mail_sender_error = None;
+        self.mail_sender = match self.store.mail_sender(&contact.company) {
+            Ok(Some(sender)) => sender,
+            Ok(None) => mail::default_sender(contact),
+            Err(e) => {
+                println!("ERROR: Unable to load the sender {}", e);
+                mail::default_sender(contact)
+            }
+        };
+    }
+
+    fn save_mail_sender(&mut self) {
+        // a typo in a placeholder is caught here rather than when sending
+        let values: Vec<(&str, String)> = mail::PLACEHOLDERS
+            .iter()
+            .map(|(name, _)| (*name, String::new()))
+            .collect();
+        for template in [&self.mail_sender.subject, &self.mail_sender.body] {
+            if let Err(e) = mail::render(template, &values) {
+                self.mail_sender_error = Some(e.to_string());
+                return;
+            }
+        }
+        match self.store.save_mail_sender(&self.mail_sender) {
+            Ok(_) => {
+                println!("LOG: Sender for {} saved", self.mail_sender.contact);
+                self.mail_sender_error = None;
+                self.mail_message = Some("Sender saved".to_string());
+            }
+            Err(e) => {
+                println!("ERROR: Sender unable to be Saved {}", e);
+                self.mail_sender_error = Some(e.to_string());
+            }
+        }
+    }
+
+    fn save_mail_server(&mut self) {
+        if let Err(e) = self.settings.save() {
+            println!("ERROR: Settings unable to be Saved {}", e);
+            self.error_message = Some(format!("Unable to save settings: {}", e));
+            return;
+        }
+        match self.remote_credentials.save() {
+            Ok(_) => {
+                println!("LOG: Mail server saved");
+                self.mail_message = Some("Mail server saved".to_string());
+            }
+            Err(e) => {
+                println!("ERROR: Credentials unable to be Saved {}", e);
+                self.error_message = Some(format!("Unable to save the credentials: {}", e));
+            }
+        }
+    }
+
+    // the message is written here and sent in the background so a slow server does not hold up
+    // the app
+    fn send_mail(&mut self, invoice: &Invoice) {
+        let now = chrono::Local::now().naive_local();
+        let outgoing = match mail::prepare(invoice, &self.mail_sender, &self.mail_email, now) {
+            Ok(outgoing) => outgoing,
+            Err(e) => {
+                self.mail_message = Some(e.to_string());
+                return;
+            }
+        };
+        let server = self.settings.mail.clone();
+        let password = self.remote_credentials.smtp_password.clone();
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let result = mail::send(&server, &password, outgoing);
+            if let Err(e) = &result {
+                println!("ERROR: Mail through {} failed {}", server.host, e);
+            }
+            let _ = sender.send(result.map_err(|e| e.to_string()));
+        });
+        self.mail_message = None;
+        self.mail_sending = Some(receiver);
+    }
+
+    pub fn poll_mail_sending(&mut self, ctx: &egui::Context) {
+        let Some(receiver) = &self.mail_sending else {
+            return;
+        };
+        let result = match receiver.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => {
+                ctx.request_repaint_after(SEND_POLL_INTERVAL);
+                return;
+            }
+            Err(TryRecvError::Disconnected) => {
+                self.mail_sending = None;
+                return;
+            }
+        };
+        self.mail_sending = None;
+        let sent = match result {
+            Ok(sent) => sent,
+            Err(e) => {
+                self.mail_message = Some(e);
+                return;
+            }
+        };
+        println!(
+            "LOG: Invoice {} emailed to {} ({})",
+            sent.invoice_id, sent.recipients, sent.response
+        );
+        // the message is out, failing to log it must not read as a failed send
+        self.mail_message = Some(match self.store.record_sent_mail(&sent) {
+            Ok(_) => format!("Sent to {}", sent.recipients),
+            Err(e) => {
+                println!("ERROR: Sent mail unable to be Logged {}", e);
+                format!("Sent to {}, but not logged: {}", sent.recipients, e)
+            }
+        });
+        if let Ok(invoices) = self.store.invoices() {
+            self.invoices = invoices;
+        }
+        if self.mail_invoice.as_deref() == Some(sent.invoice_id.as_str()) {
+            if let Ok(Some(invoice)) = self.store.invoice(&sent.invoice_id) {
+                self.mail_invoice_record = Some(invoice);
+            }
+            if let Ok(log) = self.store.sent_mail(&sent.invoice_id) {
+                self.mail_log = log;
+            }
+        }
+    }
+}
@@ -1,3 +1,12 @@
+pub(crate) mod backup;
 pub(crate) mod bottom_bar;
 pub(crate) mod central_panel;
+pub(crate) mod drafts;
+pub(crate) mod encryption;
+pub(crate) mod export;
+pub(crate) mod import;
+pub(crate) mod mail;
+pub(crate) mod payments;
+pub(crate) mod products;
+pub(crate) mod remote;
 pub(crate) mod top_bar;
@@ -0,0 +1,159 @@
+use chrono::NaiveDate;
+use eframe::egui::{self, Color32, ScrollArea, Ui, Window};
+
+use crate::Invoicy;
+use invoicy::structs::Payment;
+
+// functions related to the payments UI
+impl Invoicy {
+    pub fn show_payments(&mut self, ui: &mut Ui) {
+        if !self.payments_form {
+            return;
+        }
+        let mut open = true;
+        Window::new("Payments")
+            .open(&mut open)
+            .show(ui.ctx(), |ui| {
+                ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+                    if self.payments.is_empty() {
+                        ui.label("No payments recorded yet.");
+                    }
+                    for payment in self.payments.clone() {
+                        ui.horizontal(|ui| {
+                            ui.label(format!(
+                                "{} {} {:.2} {} {}",
+                                payment.paid_on,
+                                payment.invoice_id,
+                                payment.amount,
+                                payment.method,
+                                payment.reference
+                            ));
+                            if ui
+                                .small_button("🗑")
+                                .on_hover_text("Remove payment")
+                                .clicked()
+                            {
+                                self.delete_payment(payment.payment_id);
+                            }
+                        });
+                    }
+                });
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.label("Invoice: ");
+                    let selected = if self.payment.invoice_id.is_empty() {
+                        "Select an invoice".to_string()
+                    } else {
+                        self.payment.invoice_id.clone()
+                    };
+                    egui::ComboBox::from_id_source("payment_invoice")
+                        .selected_text(selected)
+                        .show_ui(ui, |ui| {
+                            for invoice in self.invoices.iter().rev() {
+                                ui.selectable_value(
+                                    &mut self.payment.invoice_id,
+                                    invoice.invoice_id.clone(),
+                                    format!(
+                                        "{} {} ({:.2})",
+                                        invoice.invoice_id, invoice.customer, invoice.grand_total
+                                    ),
+                                );
+                            }
+                        });
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Paid On: ");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.payment.paid_on)
+                            .hint_text("YYYY-MM-DD"),
+                    );
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Amount: ");
+                    ui.text_edit_singleline(&mut self.payment_amount_input);
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Method: ");
+                    ui.text_edit_singleline(&mut self.payment.method);
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Reference: ");
+                    ui.text_edit_singleline(&mut self.payment.reference);
+                });
+                if let Some(error) = &self.payment_error {
+                    ui.colored_label(Color32::RED, error);
+                }
+                if ui.button("Record Payment").clicked() {
+                    self.save_payment();
+                }
+            });
+        if !open {
+            self.payments_form = false;
+        }
+    }
+}
+
+// functions related to payment actions
+impl Invoicy {
+    pub fn get_payments(&mut self) -> Result<String, rusqlite::Error> {
+        self.invoices = self.store.invoices()?;
+        self.payments = self.store.payments()?;
+        Ok("Payments Initialized from DB.".to_string())
+    }
+
+    fn save_payment(&mut self) {
+        if self.payment.invoice_id.is_empty() {
+            self.payment_error = Some("Select the invoice that was paid".to_string());
+            return;
+        }
+        let paid_on = self.payment.paid_on.trim();
+        if NaiveDate::parse_from_str(paid_on, "%Y-%m-%d").is_err() {
+            self.payment_error = Some(format!("{} is not a date, use YYYY-MM-DD", paid_on));
+            return;
+        }
+        let amount = match self.payment_amount_input.trim().parse::<f64>() {
+            Ok(amount) if amount.is_finite() && amount != 0.0 => amount,
+            _ => {
+                self.payment_error = Some("Amount is not a non-zero number".to_string());
+                return;
+            }
+        };
+        let payment = Payment {
+            paid_on: paid_on.to_string(),
+            amount,
+            ..self.payment.clone()
+        };
+        match self.store.save_payment(&payment) {
+            Ok(payment_id) => {
+                println!(
+                    "LOG: Payment {} saved for {}",
+                    payment_id, payment.invoice_id
+                );
+                self.payment_error = None;
+                self.payment_amount_input.clear();
+                self.payment.reference.clear();
+                if let Err(e) = self.get_payments() {
+                    println!("ERROR: Unable to reload payments {}", e);
+                }
+            }
+            Err(e) => {
+                println!("ERROR: Payment not saved {}", e);
+                self.payment_error = Some(e.to_string());
+            }
+        }
+    }
+
+    fn delete_payment(&mut self, payment_id: i64) {
+        match self.store.delete_payment(payment_id) {
+            Ok(_) => {
+                println!("LOG: Payment {} deleted", payment_id);
+                self.payments
+                    .retain(|payment| payment.payment_id != payment_id);
+            }
+            Err(e) => {
+                println!("ERROR: Payment not deleted {}", e);
+                self.payment_error = Some(e.to_string());
+            }
+        }
+    }
+}
@@ -0,0 +1,335 @@
+use eframe::egui::{self, Color32, Id, Response, ScrollArea, Ui, Window};
+use invoicy::history::EditCommand;
+use invoicy::line_items::LineItem;
+use invoicy::pricing::quote;
+use invoicy::structs::{CustomerPrice, PriceRule, Product};
+
+use crate::components::central_panel::unit_select;
+use crate::functions::validate_text_input;
+use crate::Invoicy;
+
+const SUGGESTION_LIMIT: usize = 8;
+
+// functions related to the product catalogue UI
+impl Invoicy {
+    pub fn show_products(&mut self, ui: &mut Ui) {
+        if !self.product_form {
+            return;
+        }
+        let mut open = true;
+        Window::new("Products")
+            .open(&mut open)
+            .show(ui.ctx(), |ui| {
+                ui.horizontal(|ui| {
+                    ui.vertical(|ui| {
+                        ui.label("Catalogue");
+                        ScrollArea::vertical().max_height(240.0).show(ui, |ui| {
+                            for product in self.products.clone() {
+                                let label = if product.active {
+                                    format!("{} - {}", product.sku, product.name)
+                                } else {
+                                    format!("{} - {} (inactive)", product.sku, product.name)
+                                };
+                                if ui
+                                    .selectable_label(self.product.sku == product.sku, label)
+                                    .clicked()
+                                {
+                                    self.product_price_input = format!("{:.2}", product.price);
+                                    self.product = product;
+                                }
+                            }
+                        });
+                        if ui.button("+ new product").clicked() {
+                            self.product = Product {
+                                active: true,
+                                ..Default::default()
+                            };
+                            self.product_price_input.clear();
+                        }
+                    });
+                    ui.separator();
+                    ui.vertical(|ui| {
+                        ui.horizontal(|ui| {
+                            ui.label("SKU: ");
+                            ui.text_edit_singleline(&mut self.product.sku);
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Name: ");
+                            ui.text_edit_singleline(&mut self.product.name);
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Description: ");
+                            ui.text_edit_singleline(&mut self.product.description);
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Unit: ");
+                            unit_select(ui, Id::new("product_unit"), &mut self.product.unit);
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Default Price: ");
+                            ui.text_edit_singleline(&mut self.product_price_input);
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Default Tax Code: ");
+                            ui.text_edit_singleline(&mut self.product.tax_code);
+                        });
+                        ui.checkbox(&mut self.product.active, "Active");
+                        if let Some(error) = &self.product_error {
+                            ui.colored_label(Color32::RED, error);
+                        }
+                        if ui.button("Save Product").clicked() {
+                            self.save_product();
+                        }
+                    });
+                });
+                ui.separator();
+                self.price_list(ui);
+                egui::CollapsingHeader::new("Revenue by Product").show(ui, |ui| {
+                    match self.store.revenue_by_product() {
+                        Ok(rows) => {
+                            for (label, revenue) in rows {
+                                ui.label(format!("{}: {:.2}", label, revenue));
+                            }
+                        }
+                        Err(e) => {
+                            ui.colored_label(Color32::RED, format!("Unable to load report: {}", e));
+                        }
+                    }
+                });
+            });
+        if !open {
+            self.product_form = false;
+        }
+    }
+}
+
+// functions related to the customer price list UI
+impl Invoicy {
+    // negotiated prices of the customer selected in the editor
+    fn price_list(&mut self, ui: &mut Ui) {
+        let Some(customer) = self.customers.get(self.customer_selected) else {
+            return;
+        };
+        let customer = customer.company.clone();
+        egui::CollapsingHeader::new(format!("Prices for {}", customer))
+            .id_source("customer_prices")
+            .show(ui, |ui| {
+                let prices: Vec<CustomerPrice> = self
+                    .customer_prices
+                    .iter()
+                    .filter(|price| price.customer == customer)
+                    .cloned()
+                    .collect();
+                if prices.is_empty() {
+                    ui.label("No negotiated prices, the catalogue price applies.");
+                }
+                for price in prices {
+                    ui.horizontal(|ui| {
+                        let product = price.sku.as_deref().unwrap_or("All products");
+                        let rule = match price.rule {
+                            PriceRule::Fixed(value) => format!("{:.2}", value),
+                            PriceRule::PercentOff(percent) => format!("{}% off", percent),
+                        };
+                        ui.label(format!("{}: {}", product, rule));
+                        if ui.small_button("🗑").on_hover_text("Remove price").clicked() {
+                            self.delete_customer_price(&price);
+                        }
+                    });
+                }
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_id_source("price_rule_product")
+                        .selected_text(self.price_rule_sku.as_deref().unwrap_or("All products"))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut self.price_rule_sku, None, "All products");
+                            for product in &self.products {
+                                ui.selectable_value(
+                                    &mut self.price_rule_sku,
+                                    Some(product.sku.clone()),
+                                    format!("{} - {}", product.sku, product.name),
+                                );
+                            }
+                        });
+                    ui.radio_value(&mut self.price_rule_percent, false, "Fixed price");
+                    ui.radio_value(&mut self.price_rule_percent, true, "% off");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.price_rule_input).desired_width(60.0),
+                    );
+                    if ui.button("Save Price").clicked() {
+                        self.save_customer_price(customer);
+                    }
+                });
+            });
+    }
+}
+
+// suggestion list under a description cell, returns the product the user picked
+pub fn product_suggestions(
+    ui: &mut Ui,
+    products: &[Product],
+    item: &LineItem,
+    response: &Response,
+) -> Option<Product> {
+    let popup_id = Id::new(("product_suggestions", item.id));
+    let query = item.description.trim().to_lowercase();
+    let matches: Vec<&Product> = if query.is_empty() {
+        Vec::new()
+    } else {
+        products
+            .iter()
+            .filter(|product| product.active)
+            .filter(|product| {
+                product.sku.to_lowercase().contains(&query)
+                    || product.name.to_lowercase().contains(&query)
+                    || product.description.to_lowercase().contains(&query)
+            })
+            .take(SUGGESTION_LIMIT)
+            .collect()
+    };
+    if response.has_focus() && !matches.is_empty() && item.sku.is_none() {
+        ui.memory_mut(|memory| memory.open_popup(popup_id));
+    }
+    let mut selected = None;
+    egui::popup_below_widget(
+        ui,
+        popup_id,
+        response,
+        egui::PopupCloseBehavior::CloseOnClickOutside,
+        |ui| {
+            ui.set_min_width(200.0);
+            for product in matches {
+                let label = format!("{} - {} ({:.2})", product.sku, product.name, product.price);
+                if ui.selectable_label(false, label).clicked() {
+                    selected = Some(product.clone());
+                }
+            }
+        },
+    );
+    if selected.is_some() {
+        ui.memory_mut(|memory| memory.close_popup());
+    }
+    selected
+}
+
+// functions related to product catalogue actions
+impl Invoicy {
+    pub fn get_products(&mut self) -> Result<String, rusqlite::Error> {
+        self.products = self.store.products()?;
+        self.catalogue_version += 1;
+        Ok("Products Initialized from DB.".to_string())
+    }
+
+    pub fn get_customer_prices(&mut self) -> Result<String, rusqlite::Error> {
+        self.customer_prices = self.store.customer_prices()?;
+        self.catalogue_version += 1;
+        Ok("Customer Prices Initialized from DB.".to_string())
+    }
+
+    fn save_customer_price(&mut self, customer: String) {
+        let value = match self.price_rule_input.trim().parse::<f64>() {
+            Ok(value) if value >= 0.0 => value,
+            _ => {
+                self.product_error = Some("Price is not a positive number".to_string());
+                return;
+            }
+        };
+        let rule = if self.price_rule_percent {
+            if value > 100.0 {
+                self.product_error = Some("Discount cannot be more than 100%".to_string());
+                return;
+            }
+            PriceRule::PercentOff(value)
+        } else if self.price_rule_sku.is_none() {
+            self.product_error = Some("A fixed price needs a product".to_string());
+            return;
+        } else {
+            PriceRule::Fixed(value)
+        };
+        let price = CustomerPrice {
+            customer,
+            sku: self.price_rule_sku.clone(),
+            rule,
+        };
+        self.product_error = None;
+        match self.store.save_customer_price(&price) {
+            Ok(value) => println!("LOG: Customer Price Saved Successfully: {}", value),
+            Err(e) => self.product_error = Some(format!("Unable to save price: {}", e)),
+        }
+        self.price_rule_input.clear();
+        if let Err(e) = self.get_customer_prices() {
+            println!("ERROR: Customer prices unable to be loaded {}", e);
+        }
+    }
+
+    fn delete_customer_price(&mut self, price: &CustomerPrice) {
+        match self
+            .store
+            .delete_customer_price(&price.customer, price.sku.as_deref())
+        {
+            Ok(value) => println!("LOG: Customer Price Deleted: {}", value),
+            Err(e) => println!("ERROR: Customer price unable to be Deleted {}", e),
+        }
+        self.customer_prices.retain(|rule| rule != price);
+        self.catalogue_version += 1;
+    }
+
+    // Re-prices catalogue rows when the customer or the catalogue changed, as one undo step.
+    // A freshly loaded editor is priced without one, undo and redo restore prices themselves.
+    pub fn update_prices(&mut self) {
+        let priced_for = (self.customer_selected, self.catalogue_version);
+        if self.prices_for == Some(priced_for) {
+            return;
+        }
+        let Some(customer) = self.customers.get(self.customer_selected) else {
+            return;
+        };
+        let record = self.prices_for.is_some();
+        self.prices_for = Some(priced_for);
+        let before = self.line_items.clone();
+        let products = &self.products;
+        let prices = &self.customer_prices;
+        let changed = self.line_items.reprice(|sku| {
+            products
+                .iter()
+                .find(|product| product.sku == sku)
+                .map(|product| quote(product, &customer.company, prices))
+        });
+        if !record || changed.is_empty() {
+            return;
+        }
+        let commands = changed
+            .into_iter()
+            .map(|index| EditCommand::EditRow {
+                index,
+                before: Box::new(before.items()[index].clone()),
+                after: Box::new(self.line_items.items()[index].clone()),
+            })
+            .collect();
+        self.history.seal();
+        self.history.record(EditCommand::Batch(commands));
+        self.history.seal();
+        println!("LOG: Items re-priced for {}", customer.company);
+    }
+
+    pub fn save_product(&mut self) {
+        self.product_error =
+            validate_text_input(self.product.sku.trim()).map(|_| "SKU cannot be empty".to_string());
+        if self.product_error.is_some() {
+            return;
+        }
+        match self.product_price_input.trim().parse::<f64>() {
+            Ok(price) => self.product.price = price,
+            Err(_) if self.product_price_input.trim().is_empty() => self.product.price = 0.0,
+            Err(_) => {
+                self.product_error = Some("Default price is not a number".to_string());
+                return;
+            }
+        }
+        match self.store.save_product(&self.product) {
+            Ok(value) => println!("LOG: Product Saved Successfully: {}", value),
+            Err(e) => self.product_error = Some(format!("Unable to save product: {}", e)),
+        }
+        if let Err(e) = self.get_products() {
+            println!("ERROR: Products unable to be loaded {}", e);
+        }
+    }
+}
@@ -0,0 +1,227 @@
+use std::path::PathBuf;
+use std::sync::mpsc::{self, TryRecvError};
+use std::time::Duration;
+
+use eframe::egui::{self, CollapsingHeader, Grid, ScrollArea, Ui};
+
+use crate::Invoicy;
+use invoicy::paths;
+use invoicy::remote::{self, StorageTarget};
+use invoicy::settings::RemoteKind;
+
+// how often a running upload is looked at
+const UPLOAD_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+// functions related to the off-site backup UI, shown inside the backup window
+impl Invoicy {
+    pub fn remote_section(&mut self, ui: &mut Ui) {
+        CollapsingHeader::new("Off-site Copy").show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Push each backup to: ");
+                egui::ComboBox::from_id_source("remote_target")
+                    .selected_text(
+                        self.settings
+                            .remote
+                            .target
+                            .map_or("Nowhere", |kind| kind.label()),
+                    )
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.settings.remote.target, None, "Nowhere");
+                        for kind in RemoteKind::ALL {
+                            ui.selectable_value(
+                                &mut self.settings.remote.target,
+                                Some(kind),
+                                kind.label(),
+                            );
+                        }
+                    });
+            });
+            let Some(kind) = self.settings.remote.target else {
+                return;
+            };
+            let remote = &mut self.settings.remote;
+            let credentials = &mut self.remote_credentials;
+            Grid::new("remote_fields").show(ui, |ui| {
+                let field = |ui: &mut Ui, label: &str, value: &mut String, secret: bool| {
+                    ui.label(label);
+                    ui.add(egui::TextEdit::singleline(value).password(secret));
+                    ui.end_row();
+                };
+                match kind {
+                    RemoteKind::WebDav => {
+                        field(ui, "Folder URL: ", &mut remote.url, false);
+                        field(ui, "User: ", &mut remote.user, false);
+                        field(ui, "Password: ", &mut credentials.webdav_password, true);
+                    }
+                    RemoteKind::S3 => {
+                        field(ui, "Endpoint: ", &mut remote.url, false);
+                        field(ui, "Bucket: ", &mut remote.bucket, false);
+                        field(ui, "Region: ", &mut remote.region, false);
+                        field(ui, "Key Prefix: ", &mut remote.folder, false);
+                        field(ui, "Access Key: ", &mut remote.user, false);
+                        field(ui, "Secret Key: ", &mut credentials.s3_secret_key, true);
+                    }
+                    RemoteKind::GoogleDrive => {
+                        field(ui, "Folder ID: ", &mut remote.folder, false);
+                        field(ui, "Client ID: ", &mut remote.user, false);
+                        field(
+                            ui,
+                            "Client Secret: ",
+                            &mut credentials.google_client_secret,
+                            true,
+                        );
+                        field(
+                            ui,
+                            "Refresh Token: ",
+                            &mut credentials.google_refresh_token,
+                            true,
+                        );
+                    }
+                }
+            });
+            ui.label("Passwords and tokens are kept in their own file, not in the database.");
+            ui.horizontal(|ui| {
+                if ui.button("Save Target").clicked() {
+                    self.save_remote_settings();
+                }
+                if ui.button("List Remote Backups").clicked() {
+                    self.list_remote_backups();
+                }
+            });
+            if self.remote_upload.is_some() {
+                ui.label("Uploading…");
+            }
+            if let Some(message) = &self.remote_message {
+                ui.label(message);
+            }
+            ScrollArea::vertical()
+                .id_source("remote_backups")
+                .max_height(120.0)
+                .show(ui, |ui| {
+                    for (name, created) in self.remote_backups.clone() {
+                        ui.horizontal(|ui| {
+                            ui.label(created.format("%Y-%m-%d %H:%M").to_string());
+                            if ui.small_button("Download and Restore").clicked() {
+                                self.fetch_remote_backup(&name);
+                            }
+                        });
+                    }
+                });
+        });
+    }
+}
+
+// functions related to off-site backup actions
+impl Invoicy {
+    fn save_remote_settings(&mut self) {
+        if let Err(e) = self.settings.save() {
+            println!("ERROR: Settings unable to be Saved {}", e);
+            self.error_message = Some(format!("Unable to save settings: {}", e));
+            return;
+        }
+        match self.remote_credentials.save() {
+            Ok(_) => {
+                println!("LOG: Remote target saved");
+                self.remote_message = Some("Target saved".to_string());
+            }
+            Err(e) => {
+                println!("ERROR: Credentials unable to be Saved {}", e);
+                self.error_message = Some(format!("Unable to save the credentials: {}", e));
+            }
+        }
+    }
+
+    fn remote_target(&mut self) -> Option<Box<dyn StorageTarget + Send>> {
+        match remote::configured_target(&self.settings.remote, &self.remote_credentials) {
+            Ok(target) => Some(target),
+            Err(e) => {
+                self.remote_message = Some(e.to_string());
+                None
+            }
+        }
+    }
+
+    fn list_remote_backups(&mut self) {
+        let Some(target) = self.remote_target() else {
+            return;
+        };
+        match remote::remote_backups(target.as_ref()) {
+            Ok(found) => {
+                self.remote_message = Some(format!(
+                    "{} backup(s) on {}",
+                    found.len(),
+                    target.describe()
+                ));
+                self.remote_backups = found;
+            }
+            Err(e) => {
+                println!("ERROR: Unable to list {}: {}", target.describe(), e);
+                self.remote_message = Some(e.to_string());
+            }
+        }
+    }
+
+    // downloads into the local backup folder and opens the usual restore prompt
+    fn fetch_remote_backup(&mut self, name: &str) {
+        let Some(target) = self.remote_target() else {
+            return;
+        };
+        let dir = paths::backup_dir(&self.settings);
+        match remote::fetch_backup(target.as_ref(), name, &dir) {
+            Ok(path) => {
+                println!("LOG: Downloaded {} from {}", name, target.describe());
+                self.open_restore(path);
+            }
+            Err(e) => {
+                println!("ERROR: Unable to download {}: {}", name, e);
+                self.remote_message = Some(e.to_string());
+            }
+        }
+    }
+
+    // uploads in the background so a slow connection does not hold up the app
+    pub fn push_remote_backup(&mut self, archive: PathBuf) {
+        if self.settings.remote.target.is_none() || self.remote_upload.is_some() {
+            return;
+        }
+        let Some(target) = self.remote_target() else {
+            return;
+        };
+        let retention = self.settings.backup.clone();
+        let now = chrono::Local::now().naive_local();
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let message = match remote::push_backup(target.as_ref(), &archive, &retention, now) {
+                Ok(removed) => {
+                    println!(
+                        "LOG: Uploaded {:?} to {}, removed {} old backup(s)",
+                        archive,
+                        target.describe(),
+                        removed.len()
+                    );
+                    format!("Copied to {}", target.describe())
+                }
+                Err(e) => {
+                    println!("ERROR: Upload to {} failed {}", target.describe(), e);
+                    format!("Upload failed: {}", e)
+                }
+            };
+            let _ = sender.send(message);
+        });
+        self.remote_upload = Some(receiver);
+    }
+
+    pub fn poll_remote_upload(&mut self, ctx: &egui::Context) {
+        let Some(receiver) = &self.remote_upload else {
+            return;
+        };
+        match receiver.try_recv() {
+            Ok(message) => {
+                self.remote_message = Some(message);
+                self.remote_upload = None;
+            }
+            Err(TryRecvError::Empty) => ctx.request_repaint_after(UPLOAD_POLL_INTERVAL),
+            Err(TryRecvError::Disconnected) => self.remote_upload = None,
+        }
+    }
+}
@@ -1,11 +1,16 @@
 use std::{fs, path::PathBuf};
 
 use crate::functions::validate_text_input;
-use crate::structs::{Contact, Customer};
 use crate::Invoicy;
 use eframe::egui::{self, Window};
 use egui::Ui;
-use rusqlite::params;
+use invoicy::db::{self, Store};
+use invoicy::paths;
+use invoicy::settings::TextPosition;
+
+// shown on the e-invoice fields of the contact and customer forms
+const ENDPOINT_HINT: &str = "Peppol address as scheme:id, e.g. 0088:5790000435975. \
+    E-invoices also need the country as a two-letter code such as DE.";
 
 // functions related to Top Bar UI
 impl Invoicy {
@@ -56,6 +61,24 @@ impl Invoicy {
                         ui.label("Website: ");
                         ui.text_edit_singleline(&mut self.contact.website);
                     });
+                    ui.horizontal(|ui| {
+                        ui.label("VAT ID: ");
+                        ui.text_edit_singleline(&mut self.contact.vat_id);
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Electronic Address: ");
+                        ui.text_edit_singleline(&mut self.contact.endpoint_id)
+                            .on_hover_text(ENDPOINT_HINT);
+                    });
+                    ui.label("Default notes and terms for new invoices:");
+                    ui.horizontal(|ui| {
+                        ui.label("Notes: ");
+                        ui.text_edit_multiline(&mut self.contact.default_notes);
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Terms: ");
+                        ui.text_edit_multiline(&mut self.contact.default_terms);
+                    });
                     if ui.button("Save Contact").clicked() {
                         // can add checks for same contact later on
                         self.contacts.push(self.contact.clone());
@@ -100,6 +123,31 @@ impl Invoicy {
                         ui.label("Country: ");
                         ui.text_edit_singleline(&mut self.customer.country);
                     });
+                    ui.horizontal(|ui| {
+                        ui.label("Email: ");
+                        ui.text_edit_singleline(&mut self.customer.email)
+                            .on_hover_text(
+                                "Where invoices are emailed to, separate several with commas",
+                            );
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("VAT ID: ");
+                        ui.text_edit_singleline(&mut self.customer.vat_id);
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Electronic Address: ");
+                        ui.text_edit_singleline(&mut self.customer.endpoint_id)
+                            .on_hover_text(ENDPOINT_HINT);
+                    });
+                    ui.label("Leave empty to use the contact's notes and terms:");
+                    ui.horizontal(|ui| {
+                        ui.label("Notes: ");
+                        ui.text_edit_multiline(&mut self.customer.notes);
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Terms: ");
+                        ui.text_edit_multiline(&mut self.customer.terms);
+                    });
                     if ui.button("Save Customer").clicked() {
                         // can add checks for same contact later on
 
@@ -116,40 +164,13 @@ impl Invoicy {
         }
     }
     pub fn add_contact(&mut self) {
-        let updated = &self.connection.execute(
-            "INSERT OR REPLACE INTO contacts (company, address, city, postal_code, country, name, telephone, email, website )
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
-            ",
-            params![
-                self.contact.company.clone(),
-                self.contact.address.clone(),
-                self.contact.city.clone(),
-                self.contact.postal_code.clone(),
-                self.contact.country.clone(),
-                self.contact.name.clone(),
-                self.contact.telephone.clone(),
-                self.contact.email.clone(),
-                self.contact.website.clone()
-            ],
-        );
-        match updated {
+        match self.store.save_contact(&self.contact) {
             Ok(value) => println!("LOG: Contact Added Succesfully {}", value),
             Err(e) => println!("ERROR: Contact unable to be Added {}", e),
         }
     }
     pub fn add_customer(&mut self) {
-        let updated = &self.connection.execute(
-            "INSERT OR REPLACE INTO customers (company, address, city, postal_code, country, estimate_number) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-            params![
-                self.customer.company.clone(),
-                self.customer.address.clone(),
-                self.customer.city.clone(),
-                self.customer.postal_code.clone(),
-                self.customer.country.clone(),
-                self.current_row_value.estimate_number.clone()
-            ],
-        );
-        match updated {
+        match self.store.save_customer(&self.customer) {
             Ok(value) => println!("LOG: Customer Added Successfully: {}", value),
             Err(e) => println!("ERROR: Customer unable to be Added {}", e),
         }
@@ -171,7 +192,7 @@ impl Invoicy {
                 // currently only supports one logo option.
                 // This would change with the template feature.
                 ui.label(format!("Selected file: {:?}", path));
-                let destination = PathBuf::from("support/images/logo.jpg");
+                let destination = paths::logo_path();
                 let result = fs::copy(path, destination);
                 match result {
                     Ok(value) => println!("LOG: Logo Upload Successful {}", value),
@@ -188,46 +209,236 @@ impl Invoicy {
             if ui.button("+ customer").clicked() {
                 self.customer_form = true
             }
+            if ui.button("import").clicked() {
+                self.import_form = true
+            }
+            if ui.button("products").clicked() {
+                self.product_form = true
+            }
+            if ui.button("payments").clicked() {
+                // picks up invoices issued since the last time
+                if let Err(e) = self.get_payments() {
+                    println!("ERROR: Unable to load payments {}", e);
+                }
+                self.payments_form = true
+            }
+            if ui.button("export").clicked() {
+                self.export_form = true
+            }
+            if ui.button("backup").clicked() {
+                self.backup_dir_input = self
+                    .settings
+                    .backup
+                    .dir
+                    .as_ref()
+                    .map(|dir| dir.display().to_string())
+                    .unwrap_or_default();
+                self.backup_form = true
+            }
+            if ui.button("email").clicked() {
+                self.open_mail();
+            }
+            if ui.button("encryption").clicked() {
+                self.encryption_form = true
+            }
+            if ui.button("settings").clicked() {
+                self.settings_form = true
+            }
         });
     }
+    pub fn show_settings(&mut self, ui: &mut Ui) {
+        if self.settings_form {
+            Window::new("Settings").show(ui.ctx(), |ui| {
+                ui.label(format!("Data directory: {}", paths::data_dir().display()));
+                ui.horizontal(|ui| {
+                    ui.label("Database File: ");
+                    ui.text_edit_singleline(&mut self.database_path_input);
+                    if ui.button("Browse").clicked() {
+                        if let Some(path) = rfd::FileDialog::new()
+                            .add_filter("db", &["db"])
+                            .set_file_name("invoicy.db")
+                            .save_file()
+                        {
+                            self.database_path_input = path.display().to_string();
+                        }
+                    }
+                });
+                ui.label(format!(
+                    "--database <file> or {} take priority over this setting.",
+                    paths::DATABASE_ENV_VAR
+                ));
+                ui.separator();
+                ui.label("Invoice Fonts");
+                let font_names = self.font_registry.names();
+                let sections = [
+                    ("Body", &mut self.settings.fonts.body),
+                    ("Headings", &mut self.settings.fonts.heading),
+                    ("Item Table", &mut self.settings.fonts.table),
+                ];
+                for (label, selected) in sections {
+                    egui::ComboBox::from_label(label)
+                        .selected_text(selected.as_str())
+                        .show_ui(ui, |ui| {
+                            for name in &font_names {
+                                ui.selectable_value(selected, name.clone(), name);
+                            }
+                        });
+                }
+                ui.separator();
+                ui.label("Text Placement");
+                let positions = [
+                    ("Notes", &mut self.settings.text_layout.notes),
+                    ("Terms", &mut self.settings.text_layout.terms),
+                ];
+                for (label, selected) in positions {
+                    egui::ComboBox::from_label(label)
+                        .selected_text(selected.label())
+                        .show_ui(ui, |ui| {
+                            for position in TextPosition::ALL {
+                                ui.selectable_value(selected, position, position.label());
+                            }
+                        });
+                }
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.label("Currency: ");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.settings.currency).desired_width(50.0),
+                    )
+                    .on_hover_text("Three-letter code stated on e-invoices, e.g. EUR");
+                });
+                ui.checkbox(&mut self.settings.archival_pdf, "Archive invoices as PDF/A-2b")
+                    .on_hover_text(
+                        "Embeds fonts and metadata for long-term retention, Factur-X invoices are PDF/A-3",
+                    );
+                ui.separator();
+                ui.label("Accounting Exports");
+                let accounts = &mut self.settings.accounts;
+                egui::Grid::new("account_map").show(ui, |ui| {
+                    for (label, account) in [
+                        ("Accounts Receivable: ", &mut accounts.receivable),
+                        ("Revenue: ", &mut accounts.revenue),
+                        ("Tax Payable: ", &mut accounts.tax_payable),
+                        ("Payments Deposited To: ", &mut accounts.bank),
+                    ] {
+                        ui.label(label);
+                        ui.text_edit_singleline(account);
+                        ui.end_row();
+                    }
+                    ui.label("Due After (days): ");
+                    ui.add(egui::DragValue::new(&mut accounts.due_days).range(0..=365));
+                    ui.end_row();
+                });
+                egui::CollapsingHeader::new("Tax Rate Names").show(ui, |ui| {
+                    ui.label("Tax codes of your lines as named by the accounting tool.");
+                    let mut removed = None;
+                    for (idx, (code, name)) in accounts.tax_types.iter_mut().enumerate() {
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                egui::TextEdit::singleline(code)
+                                    .hint_text("S:21")
+                                    .desired_width(60.0),
+                            );
+                            ui.add(
+                                egui::TextEdit::singleline(name).hint_text("21% (VAT on Income)"),
+                            );
+                            if ui.small_button("🗑").clicked() {
+                                removed = Some(idx);
+                            }
+                        });
+                    }
+                    if let Some(idx) = removed {
+                        accounts.tax_types.remove(idx);
+                    }
+                    if ui.button("+ tax rate").clicked() {
+                        accounts.tax_types.push((String::new(), String::new()));
+                    }
+                });
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button("Save Settings").clicked() {
+                        self.save_settings();
+                        self.settings_form = false;
+                    }
+                    if ui.button("Use Default").clicked() {
+                        self.database_path_input =
+                            paths::default_database_path().display().to_string();
+                    }
+                });
+                ui.separator();
+                if ui.button("Close").clicked() {
+                    self.settings_form = false;
+                }
+            });
+        }
+    }
 }
 
 // functions related to Top Bar actions
 impl Invoicy {
-    pub fn get_contacts(&mut self) -> Result<String, rusqlite::Error> {
-        let mut stmt = self.connection.prepare("SELECT * FROM contacts")?;
-        let rows = stmt.query_map([], |row| {
-            Ok(Contact {
-                company: row.get(0)?,
-                address: row.get(1)?,
-                city: row.get(2)?,
-                postal_code: row.get(3)?,
-                country: row.get(4)?,
-                name: row.get(5)?,
-                telephone: row.get(6)?,
-                email: row.get(7)?,
-                website: row.get(8)?,
-            })
-        })?;
-        for contact_row in rows {
-            self.contacts.push(contact_row.unwrap())
+    pub fn save_settings(&mut self) {
+        let path = PathBuf::from(self.database_path_input.trim());
+        self.settings.database_path = if path == paths::default_database_path() {
+            None
+        } else {
+            Some(path.clone())
+        };
+        match self.settings.save() {
+            Ok(_) => println!("LOG: Settings Saved to {:?}", paths::settings_path()),
+            Err(e) => println!("ERROR: Settings unable to be Saved {}", e),
+        }
+        if path != self.database_path {
+            self.switch_database(path);
+        }
+    }
+    pub fn switch_database(&mut self, path: PathBuf) {
+        paths::ensure_parent_dir(&path);
+        if db::is_encrypted_file(&path) {
+            self.autosave_draft(true);
+            self.new_draft();
+            self.drafts.clear();
+            self.database_path = path;
+            self.lock_database();
+            return;
+        }
+        match Store::open(&path) {
+            Ok(store) => {
+                println!("LOG: Switched database to {:?}", path);
+                // the open draft belongs to the old database
+                self.autosave_draft(true);
+                self.new_draft();
+                self.drafts.clear();
+                self.store = store;
+                self.database_path = path;
+                self.reset_loaded_data();
+            }
+            Err(e) => println!("ERROR: Unable to open database {:?}: {}", path, e),
         }
+    }
+    // drops everything read from the store so it is loaded again on the next frame
+    pub fn reset_loaded_data(&mut self) {
+        self.contacts.clear();
+        self.customers.clear();
+        self.products.clear();
+        self.customer_prices.clear();
+        self.database_data_vec.clear();
+        self.customer_selected = 0;
+        self.contact_selected = 0;
+        self.history.clear();
+        self.prices_for = None;
+        // the selected invoice and its sender belong to the old database
+        self.mail_invoice = None;
+        self.mail_invoice_record = None;
+        self.mail_log.clear();
+        // initialize_application re-creates the tables and reloads everything
+        self.initialized = false;
+    }
+    pub fn get_contacts(&mut self) -> Result<String, rusqlite::Error> {
+        self.contacts.extend(self.store.contacts()?);
         Ok("Contacts Initialized from DB.".to_string())
     }
     pub fn get_customers(&mut self) -> Result<String, rusqlite::Error> {
-        let mut stmt = self.connection.prepare("SELECT * FROM customers")?;
-        let rows = stmt.query_map([], |row| {
-            Ok(Customer {
-                company: row.get(0)?,
-                address: row.get(1)?,
-                city: row.get(2)?,
-                postal_code: row.get(3)?,
-                country: row.get(4)?,
-            })
-        })?;
-        for customer_row in rows {
-            self.customers.push(customer_row.unwrap())
-        }
+        self.customers.extend(self.store.customers()?);
         Ok("Customers Initialized from DB.".to_string())
     }
 }
@@ -0,0 +1,1659 @@
+//! Data access for Invoicy. `Store` owns the SQLite connection and exposes typed CRUD for
+//! customers, contacts, products, drafts, invoices and their line items so it can be used without the GUI.
+
+use std::fmt;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::Local;
+use rusqlite::backup::Backup;
+use rusqlite::{params, Connection, DatabaseName, OptionalExtension, Row, Transaction};
+use sha2::{Digest, Sha256};
+
+use crate::document::DocumentError;
+use crate::structs::{
+    Contact, Customer, CustomerPrice, DatabaseData, Draft, DraftItem, Invoice, InvoiceStatus,
+    InvoiceText, LineKind, MailSender, Payment, PriceRule, Product, SentMail,
+};
+use crate::totals;
+
+// stored in `PRAGMA user_version`, raised whenever `create_tables` changes the tables so a
+// backup made by a newer version is not restored into an older one
+pub const SCHEMA_VERSION: i32 = 3;
+// tables every Invoicy database has, used to tell a database apart from any other SQLite file
+pub const REQUIRED_TABLES: [&str; 4] = ["customers", "contacts", "data", "invoices"];
+
+const CUSTOMER_COLUMNS: &str =
+    "company, address, city, postal_code, country, notes, terms, vat_id, endpoint_id, email";
+const CONTACT_COLUMNS: &str = "company, address, city, postal_code, country, name, telephone, email, website, default_notes, default_terms, vat_id, endpoint_id";
+const LINE_ITEM_COLUMNS: &str = "entry_id, cust_id, estimate_number, row_number, description, quantity, price, total, sku, unit, tax_code, kind";
+const PRODUCT_COLUMNS: &str = "sku, name, description, unit, price, tax_code, active";
+const DRAFT_ITEM_COLUMNS: &str =
+    "description, quantity_input, price_input, sku, unit, tax_code, kind";
+const INVOICE_COLUMNS: &str = "invoice_id, cust_id, estimate_number, customer, contact, issue_date, grand_total, file_path, file_hash, po_number, reference, notes, terms, status";
+const PAYMENT_COLUMNS: &str = "payment_id, invoice_id, paid_on, amount, method, reference";
+const MAIL_SENDER_COLUMNS: &str = "contact, from_name, from_address, reply_to, bcc, subject, body";
+const SENT_MAIL_COLUMNS: &str =
+    "mail_id, invoice_id, sent_at, sender, recipients, subject, message_id, response";
+const DRAFT_COLUMNS: &str =
+    "draft_id, customer, contact, updated_at, po_number, reference, notes, terms";
+
+#[derive(Debug)]
+pub struct Store {
+    connection: Connection,
+    // the SQLCipher passphrase, None for a database stored in plain text
+    passphrase: Option<Passphrase>,
+}
+
+// kept out of debug output, the app and the store are both printed when logging
+#[derive(Clone, PartialEq)]
+struct Passphrase(String);
+
+impl fmt::Debug for Passphrase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Passphrase(..)")
+    }
+}
+
+// everything needed to issue an invoice, the estimate number is reserved while issuing
+#[derive(Clone, Debug, PartialEq)]
+pub struct NewInvoice {
+    pub cust_id: String,
+    pub customer: Customer,
+    pub contact_company: String,
+    pub items: Vec<DatabaseData>,
+    pub text: InvoiceText,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct IssuedInvoice {
+    pub invoice_id: String,
+    pub estimate_number: usize,
+    pub items: Vec<DatabaseData>,
+    pub file_path: PathBuf,
+    pub file_hash: String,
+}
+
+#[derive(Debug)]
+pub enum IssueError {
+    Database(rusqlite::Error),
+    Document(DocumentError),
+    File(std::io::Error),
+}
+
+impl fmt::Display for IssueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IssueError::Database(e) => write!(f, "Unable to save the invoice: {}", e),
+            IssueError::Document(e) => write!(f, "{}", e),
+            IssueError::File(e) => write!(f, "Unable to read the generated invoice: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for IssueError {}
+
+impl From<rusqlite::Error> for IssueError {
+    fn from(e: rusqlite::Error) -> Self {
+        IssueError::Database(e)
+    }
+}
+
+impl From<DocumentError> for IssueError {
+    fn from(e: DocumentError) -> Self {
+        IssueError::Document(e)
+    }
+}
+
+impl Store {
+    pub fn open(path: impl AsRef<Path>) -> rusqlite::Result<Self> {
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    // opens a database encrypted with SQLCipher, a new file is created encrypted. A wrong
+    // passphrase fails with `ErrorCode::NotADatabase`.
+    pub fn open_encrypted(path: impl AsRef<Path>, passphrase: &str) -> rusqlite::Result<Self> {
+        let store = Self {
+            connection: open_with_key(path.as_ref(), Some(passphrase))?,
+            passphrase: Some(Passphrase(passphrase.to_string())),
+        };
+        store.create_tables()?;
+        Ok(store)
+    }
+
+    pub fn open_in_memory() -> rusqlite::Result<Self> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    pub fn from_connection(connection: Connection) -> rusqlite::Result<Self> {
+        let store = Self {
+            connection,
+            passphrase: None,
+        };
+        store.create_tables()?;
+        Ok(store)
+    }
+
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+
+    pub fn create_tables(&self) -> rusqlite::Result<()> {
+        self.connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS customers (
+                    company TEXT PRIMARY KEY,
+                    address TEXT NOT NULL,
+                    city TEXT NOT NULL,
+                    postal_code TEXT NOT NULL,
+                    country TEXT NOT NULL,
+                    estimate_number INTEGER
+                );
+            CREATE TABLE IF NOT EXISTS contacts (
+                    company TEXT PRIMARY KEY,
+                    address Text NOT NULL,
+                    city TEXT NOT NULL,
+                    postal_code TEXT NOT NULL,
+                    country TEXT NOT NULL,
+                    name TEXT NOT NULL,
+                    telephone TEXT NOT NULL,
+                    email TEXT NOT NULL,
+                    website TEXT NOT NULL
+                );
+            CREATE TABLE IF NOT EXISTS data (
+                    entry_id TEXT PRIMARY KEY,
+                    cust_id TEXT NOT NULL,
+                    estimate_number INTEGER NOT NULL,
+                    row_number INTEGER NOT NULL,
+                    description TEXT NOT NULL,
+                    quantity REAL,
+                    price REAL,
+                    total REAL
+                );
+            CREATE TABLE IF NOT EXISTS invoices (
+                    invoice_id TEXT PRIMARY KEY,
+                    cust_id TEXT NOT NULL,
+                    estimate_number INTEGER NOT NULL,
+                    customer TEXT NOT NULL,
+                    contact TEXT NOT NULL,
+                    issue_date TEXT NOT NULL,
+                    grand_total REAL NOT NULL,
+                    file_path TEXT,
+                    file_hash TEXT,
+                    UNIQUE (cust_id, estimate_number)
+                );
+            CREATE TABLE IF NOT EXISTS products (
+                    sku TEXT PRIMARY KEY,
+                    name TEXT NOT NULL,
+                    description TEXT NOT NULL,
+                    unit TEXT NOT NULL,
+                    price REAL NOT NULL,
+                    tax_code TEXT NOT NULL,
+                    active INTEGER NOT NULL DEFAULT 1
+                );
+            CREATE TABLE IF NOT EXISTS drafts (
+                    draft_id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    customer TEXT NOT NULL,
+                    contact TEXT NOT NULL,
+                    updated_at TEXT NOT NULL
+                );
+            CREATE TABLE IF NOT EXISTS draft_items (
+                    draft_id INTEGER NOT NULL REFERENCES drafts (draft_id) ON DELETE CASCADE,
+                    row_number INTEGER NOT NULL,
+                    description TEXT NOT NULL,
+                    quantity_input TEXT NOT NULL,
+                    price_input TEXT NOT NULL,
+                    sku TEXT,
+                    unit TEXT NOT NULL,
+                    tax_code TEXT NOT NULL,
+                    PRIMARY KEY (draft_id, row_number)
+                );
+            CREATE TABLE IF NOT EXISTS customer_prices (
+                    customer TEXT NOT NULL,
+                    sku TEXT NOT NULL,
+                    price REAL,
+                    percent_off REAL,
+                    PRIMARY KEY (customer, sku)
+                );
+            CREATE TABLE IF NOT EXISTS payments (
+                    payment_id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    invoice_id TEXT NOT NULL,
+                    paid_on TEXT NOT NULL,
+                    amount REAL NOT NULL,
+                    method TEXT NOT NULL,
+                    reference TEXT NOT NULL
+                );
+            CREATE TABLE IF NOT EXISTS mail_senders (
+                    contact TEXT PRIMARY KEY,
+                    from_name TEXT NOT NULL,
+                    from_address TEXT NOT NULL,
+                    reply_to TEXT NOT NULL,
+                    bcc TEXT NOT NULL,
+                    subject TEXT NOT NULL,
+                    body TEXT NOT NULL
+                );
+            CREATE TABLE IF NOT EXISTS sent_mail (
+                    mail_id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    invoice_id TEXT NOT NULL,
+                    sent_at TEXT NOT NULL,
+                    sender TEXT NOT NULL,
+                    recipients TEXT NOT NULL,
+                    subject TEXT NOT NULL,
+                    message_id TEXT NOT NULL,
+                    response TEXT NOT NULL
+                );",
+        )?;
+        // columns added after the first release, older databases get them here
+        self.add_column_if_missing("data", "sku", "TEXT")?;
+        self.add_column_if_missing("data", "unit", "TEXT NOT NULL DEFAULT ''")?;
+        self.add_column_if_missing("data", "tax_code", "TEXT NOT NULL DEFAULT ''")?;
+        for (table, column) in [
+            ("customers", "notes"),
+            ("customers", "terms"),
+            ("contacts", "default_notes"),
+            ("contacts", "default_terms"),
+            ("customers", "vat_id"),
+            ("customers", "endpoint_id"),
+            ("contacts", "vat_id"),
+            ("contacts", "endpoint_id"),
+            ("invoices", "po_number"),
+            ("invoices", "reference"),
+            ("invoices", "notes"),
+            ("invoices", "terms"),
+            ("drafts", "po_number"),
+            ("drafts", "reference"),
+            ("drafts", "notes"),
+            ("drafts", "terms"),
+            ("customers", "email"),
+        ] {
+            self.add_column_if_missing(table, column, "TEXT NOT NULL DEFAULT ''")?;
+        }
+        for table in ["data", "draft_items"] {
+            self.add_column_if_missing(table, "kind", "TEXT NOT NULL DEFAULT 'item'")?;
+        }
+        self.add_column_if_missing("invoices", "status", "TEXT NOT NULL DEFAULT 'issued'")?;
+        // invoices before version 3 stored their net total, the grand total now includes VAT
+        if self.schema_version()? < 3 {
+            self.recompute_grand_totals()?;
+        }
+        self.connection
+            .pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        Ok(())
+    }
+
+    fn recompute_grand_totals(&self) -> rusqlite::Result<()> {
+        for invoice in self.invoices()? {
+            let items = self.invoice_line_items(&invoice.cust_id, invoice.estimate_number)?;
+            if items.is_empty() {
+                continue;
+            }
+            self.connection.execute(
+                "UPDATE invoices SET grand_total = ?1 WHERE invoice_id = ?2",
+                params![totals::gross_total(&items), invoice.invoice_id],
+            )?;
+        }
+        Ok(())
+    }
+
+    fn add_column_if_missing(
+        &self,
+        table: &str,
+        column: &str,
+        declaration: &str,
+    ) -> rusqlite::Result<()> {
+        let mut stmt = self
+            .connection
+            .prepare(&format!("PRAGMA table_info({})", table))?;
+        let exists = stmt
+            .query_map([], |row| row.get::<_, String>(1))?
+            .collect::<rusqlite::Result<Vec<String>>>()?
+            .iter()
+            .any(|name| name == column);
+        if !exists {
+            self.connection.execute(
+                &format!(
+                    "ALTER TABLE {} ADD COLUMN {} {}",
+                    table, column, declaration
+                ),
+                [],
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// backups, both directions use SQLite's online backup so the copy is consistent while the app
+// keeps the database open. Copies of an encrypted database are encrypted with its passphrase.
+impl Store {
+    pub fn schema_version(&self) -> rusqlite::Result<i32> {
+        self.connection
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+    }
+
+    pub fn backup_to(&self, path: &Path) -> rusqlite::Result<()> {
+        let mut copy = open_with_key(path, self.passphrase())?;
+        copy_database(&self.connection, &mut copy)
+    }
+
+    // replaces every table with the copy at `path`, encrypted with `passphrase` or stored in
+    // plain text, and brings it up to the current schema
+    pub fn restore_from(&mut self, path: &Path, passphrase: Option<&str>) -> rusqlite::Result<()> {
+        let source = open_with_key(path, passphrase)?;
+        if passphrase == self.passphrase() {
+            copy_database(&source, &mut self.connection)?;
+        } else {
+            // the online backup only copies between databases with the same key, so the copy
+            // is first written out again with this database's passphrase
+            let rekeyed = path.with_extension("rekeyed");
+            let result = export_database(&source, &rekeyed, self.passphrase()).and_then(|_| {
+                let source = open_with_key(&rekeyed, self.passphrase())?;
+                copy_database(&source, &mut self.connection)
+            });
+            let _ = fs::remove_file(&rekeyed);
+            result?;
+        }
+        self.create_tables()
+    }
+}
+
+// encryption, `encryption` switches a database file between plain text and encrypted
+impl Store {
+    pub fn is_encrypted(&self) -> bool {
+        self.passphrase.is_some()
+    }
+
+    pub fn passphrase(&self) -> Option<&str> {
+        self.passphrase
+            .as_ref()
+            .map(|passphrase| passphrase.0.as_str())
+    }
+
+    // changes the passphrase of an encrypted database in place
+    pub fn rekey(&mut self, passphrase: &str) -> rusqlite::Result<()> {
+        if !self.is_encrypted() {
+            return Err(rusqlite::Error::InvalidQuery);
+        }
+        self.connection.pragma_update(None, "rekey", passphrase)?;
+        self.passphrase = Some(Passphrase(passphrase.to_string()));
+        Ok(())
+    }
+
+    // writes a complete copy to a new file, encrypted with `passphrase` or in plain text
+    pub fn export_to(&self, path: &Path, passphrase: Option<&str>) -> rusqlite::Result<()> {
+        export_database(&self.connection, path, passphrase)
+    }
+}
+
+// SQLite files start with this header, SQLCipher files with a random salt instead
+const PLAIN_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+// false for a missing or empty file, those are created in whatever form they are opened with
+pub fn is_encrypted_file(path: &Path) -> bool {
+    let mut header = [0; 16];
+    match fs::File::open(path).and_then(|mut file| file.read_exact(&mut header)) {
+        Ok(_) => &header != PLAIN_HEADER,
+        Err(_) => false,
+    }
+}
+
+fn open_with_key(path: &Path, passphrase: Option<&str>) -> rusqlite::Result<Connection> {
+    let connection = Connection::open(path)?;
+    if let Some(passphrase) = passphrase {
+        connection.pragma_update(None, "key", passphrase)?;
+    }
+    Ok(connection)
+}
+
+fn copy_database(source: &Connection, target: &mut Connection) -> rusqlite::Result<()> {
+    Backup::new(source, target)?.run_to_completion(100, Duration::from_millis(10), None)
+}
+
+// SQLCipher's export copies every table into an attached database with its own key, an empty
+// key attaches it in plain text
+fn export_database(
+    source: &Connection,
+    path: &Path,
+    passphrase: Option<&str>,
+) -> rusqlite::Result<()> {
+    let _ = fs::remove_file(path);
+    let version: i32 = source.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    source.execute(
+        "ATTACH DATABASE ?1 AS export KEY ?2",
+        params![path.to_string_lossy(), passphrase.unwrap_or("")],
+    )?;
+    let result = source
+        .query_row("SELECT sqlcipher_export('export')", [], |_| Ok(()))
+        .and_then(|_| {
+            source.pragma_update(
+                Some(DatabaseName::Attached("export")),
+                "user_version",
+                version,
+            )
+        });
+    source.execute("DETACH DATABASE export", [])?;
+    result
+}
+
+// customers
+impl Store {
+    pub fn customers(&self) -> rusqlite::Result<Vec<Customer>> {
+        let mut stmt = self.connection.prepare(&format!(
+            "SELECT {} FROM customers ORDER BY rowid",
+            CUSTOMER_COLUMNS
+        ))?;
+        let rows = stmt.query_map([], customer_from_row)?;
+        rows.collect()
+    }
+
+    pub fn customer(&self, company: &str) -> rusqlite::Result<Option<Customer>> {
+        self.connection
+            .query_row(
+                &format!(
+                    "SELECT {} FROM customers WHERE company = ?1",
+                    CUSTOMER_COLUMNS
+                ),
+                [company],
+                customer_from_row,
+            )
+            .optional()
+    }
+
+    // the stored estimate number is owned by `issue_invoice` and left untouched here
+    pub fn save_customer(&self, customer: &Customer) -> rusqlite::Result<usize> {
+        save_customer(&self.connection, customer)
+    }
+
+    pub fn delete_customer(&self, company: &str) -> rusqlite::Result<usize> {
+        self.connection
+            .execute("DELETE FROM customer_prices WHERE customer = ?1", [company])?;
+        self.connection
+            .execute("DELETE FROM customers WHERE company = ?1", [company])
+    }
+}
+
+// customer price lists, an empty sku column stands for every product
+impl Store {
+    pub fn customer_prices(&self) -> rusqlite::Result<Vec<CustomerPrice>> {
+        let mut stmt = self.connection.prepare(
+            "SELECT customer, sku, price, percent_off FROM customer_prices
+            ORDER BY customer, sku",
+        )?;
+        let rows = stmt.query_map([], customer_price_from_row)?;
+        rows.collect()
+    }
+
+    // one rule per customer and product, saving again replaces it
+    pub fn save_customer_price(&self, price: &CustomerPrice) -> rusqlite::Result<usize> {
+        let (fixed, percent_off) = match price.rule {
+            PriceRule::Fixed(value) => (Some(value), None),
+            PriceRule::PercentOff(value) => (None, Some(value)),
+        };
+        self.connection.execute(
+            "INSERT OR REPLACE INTO customer_prices (customer, sku, price, percent_off)
+            VALUES (?1, ?2, ?3, ?4)",
+            params![
+                price.customer,
+                price.sku.as_deref().unwrap_or(""),
+                fixed,
+                percent_off
+            ],
+        )
+    }
+
+    pub fn delete_customer_price(
+        &self,
+        customer: &str,
+        sku: Option<&str>,
+    ) -> rusqlite::Result<usize> {
+        self.connection.execute(
+            "DELETE FROM customer_prices WHERE customer = ?1 AND sku = ?2",
+            params![customer, sku.unwrap_or("")],
+        )
+    }
+}
+
+// contacts
+impl Store {
+    pub fn contacts(&self) -> rusqlite::Result<Vec<Contact>> {
+        let mut stmt = self.connection.prepare(&format!(
+            "SELECT {} FROM contacts ORDER BY rowid",
+            CONTACT_COLUMNS
+        ))?;
+        let rows = stmt.query_map([], contact_from_row)?;
+        rows.collect()
+    }
+
+    pub fn contact(&self, company: &str) -> rusqlite::Result<Option<Contact>> {
+        self.connection
+            .query_row(
+                &format!(
+                    "SELECT {} FROM contacts WHERE company = ?1",
+                    CONTACT_COLUMNS
+                ),
+                [company],
+                contact_from_row,
+            )
+            .optional()
+    }
+
+    pub fn save_contact(&self, contact: &Contact) -> rusqlite::Result<usize> {
+        save_contact(&self.connection, contact)
+    }
+
+    pub fn delete_contact(&self, company: &str) -> rusqlite::Result<usize> {
+        self.connection
+            .execute("DELETE FROM contacts WHERE company = ?1", [company])
+    }
+}
+
+// bulk imports, a failing row leaves the tables as they were
+impl Store {
+    pub fn import_parties(
+        &mut self,
+        customers: &[Customer],
+        contacts: &[Contact],
+    ) -> rusqlite::Result<()> {
+        let transaction = self.connection.transaction()?;
+        for customer in customers {
+            save_customer(&transaction, customer)?;
+        }
+        for contact in contacts {
+            save_contact(&transaction, contact)?;
+        }
+        transaction.commit()
+    }
+}
+
+// product catalogue
+impl Store {
+    pub fn products(&self) -> rusqlite::Result<Vec<Product>> {
+        let mut stmt = self.connection.prepare(&format!(
+            "SELECT {} FROM products ORDER BY name",
+            PRODUCT_COLUMNS
+        ))?;
+        let rows = stmt.query_map([], product_from_row)?;
+        rows.collect()
+    }
+
+    pub fn product(&self, sku: &str) -> rusqlite::Result<Option<Product>> {
+        self.connection
+            .query_row(
+                &format!("SELECT {} FROM products WHERE sku = ?1", PRODUCT_COLUMNS),
+                [sku],
+                product_from_row,
+            )
+            .optional()
+    }
+
+    // active products whose sku, name or description contain `query`, for autocomplete
+    pub fn search_products(&self, query: &str, limit: usize) -> rusqlite::Result<Vec<Product>> {
+        let pattern = format!("%{}%", query.trim());
+        let mut stmt = self.connection.prepare(&format!(
+            "SELECT {} FROM products WHERE active = 1
+            AND (sku LIKE ?1 OR name LIKE ?1 OR description LIKE ?1)
+            ORDER BY name LIMIT ?2",
+            PRODUCT_COLUMNS
+        ))?;
+        let rows = stmt.query_map(params![pattern, limit], product_from_row)?;
+        rows.collect()
+    }
+
+    pub fn save_product(&self, product: &Product) -> rusqlite::Result<usize> {
+        self.connection.execute(
+            &format!(
+                "INSERT OR REPLACE INTO products ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                PRODUCT_COLUMNS
+            ),
+            params![
+                product.sku,
+                product.name,
+                product.description,
+                product.unit,
+                product.price,
+                product.tax_code,
+                product.active,
+            ],
+        )
+    }
+
+    // invoiced lines keep their sku, so products are deactivated rather than deleted once used
+    pub fn delete_product(&self, sku: &str) -> rusqlite::Result<usize> {
+        let invoiced: bool = self.connection.query_row(
+            "SELECT EXISTS(SELECT 1 FROM data WHERE sku = ?1)",
+            [sku],
+            |row| row.get(0),
+        )?;
+        if invoiced {
+            self.connection
+                .execute("UPDATE products SET active = 0 WHERE sku = ?1", [sku])
+        } else {
+            self.connection
+                .execute("DELETE FROM products WHERE sku = ?1", [sku])
+        }
+    }
+
+    // revenue per catalogue product, lines without a product are grouped by description
+    pub fn revenue_by_product(&self) -> rusqlite::Result<Vec<(String, f64)>> {
+        let mut stmt = self.connection.prepare(
+            "SELECT COALESCE(products.name, data.description) AS label, SUM(data.total)
+            FROM data LEFT JOIN products ON products.sku = data.sku
+            WHERE data.kind = 'item'
+            GROUP BY COALESCE(data.sku, data.description)
+            ORDER BY SUM(data.total) DESC",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get(0)?, row.get::<_, Option<f64>>(1)?.unwrap_or(0.0)))
+        })?;
+        rows.collect()
+    }
+}
+
+// drafts
+impl Store {
+    // most recently edited first, without their rows
+    pub fn drafts(&self) -> rusqlite::Result<Vec<Draft>> {
+        let mut stmt = self.connection.prepare(&format!(
+            "SELECT {} FROM drafts ORDER BY updated_at DESC, draft_id DESC",
+            DRAFT_COLUMNS
+        ))?;
+        let rows = stmt.query_map([], draft_from_row)?;
+        rows.collect()
+    }
+
+    pub fn draft(&self, draft_id: i64) -> rusqlite::Result<Option<Draft>> {
+        let draft = self
+            .connection
+            .query_row(
+                &format!("SELECT {} FROM drafts WHERE draft_id = ?1", DRAFT_COLUMNS),
+                [draft_id],
+                draft_from_row,
+            )
+            .optional()?;
+        let Some(mut draft) = draft else {
+            return Ok(None);
+        };
+        let mut stmt = self.connection.prepare(&format!(
+            "SELECT {} FROM draft_items WHERE draft_id = ?1 ORDER BY row_number",
+            DRAFT_ITEM_COLUMNS
+        ))?;
+        draft.items = stmt
+            .query_map([draft_id], draft_item_from_row)?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(Some(draft))
+    }
+
+    // Inserts the draft when `draft_id` is 0, otherwise replaces the stored copy. Returns the
+    // id so callers can keep saving to the same draft.
+    pub fn save_draft(&mut self, draft: &Draft) -> rusqlite::Result<i64> {
+        let transaction = self.connection.transaction()?;
+        let updated_at = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        // NULL makes SQLite pick a fresh id for a new draft
+        let draft_id = (draft.draft_id != 0).then_some(draft.draft_id);
+        transaction.execute(
+            &format!(
+                "INSERT OR REPLACE INTO drafts ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                DRAFT_COLUMNS
+            ),
+            params![
+                draft_id,
+                draft.customer,
+                draft.contact,
+                updated_at,
+                draft.text.po_number,
+                draft.text.reference,
+                draft.text.notes,
+                draft.text.terms,
+            ],
+        )?;
+        let draft_id = draft_id.unwrap_or_else(|| transaction.last_insert_rowid());
+        transaction.execute("DELETE FROM draft_items WHERE draft_id = ?1", [draft_id])?;
+        for (row_number, item) in draft.items.iter().enumerate() {
+            transaction.execute(
+                &format!(
+                    "INSERT INTO draft_items (draft_id, row_number, {}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                    DRAFT_ITEM_COLUMNS
+                ),
+                params![
+                    draft_id,
+                    row_number,
+                    item.description,
+                    item.quantity_input,
+                    item.price_input,
+                    item.sku,
+                    item.unit,
+                    item.tax_code,
+                    item.kind.key(),
+                ],
+            )?;
+        }
+        transaction.commit()?;
+        Ok(draft_id)
+    }
+
+    pub fn delete_draft(&mut self, draft_id: i64) -> rusqlite::Result<usize> {
+        let transaction = self.connection.transaction()?;
+        transaction.execute("DELETE FROM draft_items WHERE draft_id = ?1", [draft_id])?;
+        let deleted = transaction.execute("DELETE FROM drafts WHERE draft_id = ?1", [draft_id])?;
+        transaction.commit()?;
+        Ok(deleted)
+    }
+}
+
+// invoices and line items
+impl Store {
+    pub fn invoices(&self) -> rusqlite::Result<Vec<Invoice>> {
+        let mut stmt = self.connection.prepare(&format!(
+            "SELECT {} FROM invoices ORDER BY issue_date, invoice_id",
+            INVOICE_COLUMNS
+        ))?;
+        let rows = stmt.query_map([], invoice_from_row)?;
+        rows.collect()
+    }
+
+    pub fn invoice(&self, invoice_id: &str) -> rusqlite::Result<Option<Invoice>> {
+        self.connection
+            .query_row(
+                &format!(
+                    "SELECT {} FROM invoices WHERE invoice_id = ?1",
+                    INVOICE_COLUMNS
+                ),
+                [invoice_id],
+                invoice_from_row,
+            )
+            .optional()
+    }
+
+    pub fn line_items(&self) -> rusqlite::Result<Vec<DatabaseData>> {
+        let mut stmt = self.connection.prepare(&format!(
+            "SELECT {} FROM data ORDER BY cust_id, estimate_number, row_number",
+            LINE_ITEM_COLUMNS
+        ))?;
+        let rows = stmt.query_map([], line_item_from_row)?;
+        rows.collect()
+    }
+
+    pub fn invoice_line_items(
+        &self,
+        cust_id: &str,
+        estimate_number: usize,
+    ) -> rusqlite::Result<Vec<DatabaseData>> {
+        let mut stmt = self.connection.prepare(&format!(
+            "SELECT {} FROM data WHERE cust_id = ?1 AND estimate_number = ?2 ORDER BY row_number",
+            LINE_ITEM_COLUMNS
+        ))?;
+        let rows = stmt.query_map(params![cust_id, estimate_number], line_item_from_row)?;
+        rows.collect()
+    }
+
+    pub fn save_line_item(&self, item: &DatabaseData) -> rusqlite::Result<usize> {
+        insert_line_item(&self.connection, item, "INSERT OR REPLACE")
+    }
+
+    pub fn delete_line_item(&self, entry_id: &str) -> rusqlite::Result<usize> {
+        self.connection
+            .execute("DELETE FROM data WHERE entry_id = ?1", [entry_id])
+    }
+
+    pub fn payments(&self) -> rusqlite::Result<Vec<Payment>> {
+        let mut stmt = self.connection.prepare(&format!(
+            "SELECT {} FROM payments ORDER BY paid_on, payment_id",
+            PAYMENT_COLUMNS
+        ))?;
+        let rows = stmt.query_map([], payment_from_row)?;
+        rows.collect()
+    }
+
+    // records a new payment and returns its id, the id of `payment` is ignored
+    pub fn save_payment(&self, payment: &Payment) -> rusqlite::Result<i64> {
+        self.connection.execute(
+            "INSERT INTO payments (invoice_id, paid_on, amount, method, reference)
+            VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                payment.invoice_id,
+                payment.paid_on,
+                payment.amount,
+                payment.method,
+                payment.reference
+            ],
+        )?;
+        Ok(self.connection.last_insert_rowid())
+    }
+
+    pub fn delete_payment(&self, payment_id: i64) -> rusqlite::Result<usize> {
+        self.connection
+            .execute("DELETE FROM payments WHERE payment_id = ?1", [payment_id])
+    }
+
+    // Issues an invoice in a single transaction: reserves the next estimate number, inserts the
+    // header and line items, updates the customer and records the hash of the file written by
+    // `render`. Any failure rolls the whole operation back and removes the generated file.
+    pub fn issue_invoice<F>(
+        &mut self,
+        invoice: &NewInvoice,
+        file_path: &Path,
+        render: F,
+    ) -> Result<IssuedInvoice, IssueError>
+    where
+        F: FnOnce(usize) -> Result<(), DocumentError>,
+    {
+        let transaction = self.connection.transaction()?;
+        let estimate_number = reserve_estimate_number(&transaction, &invoice.cust_id)?;
+        let invoice_id = format!("{}-{}", invoice.cust_id, estimate_number);
+
+        transaction.execute(
+            "INSERT INTO invoices (invoice_id, cust_id, estimate_number, customer, contact, issue_date, grand_total, po_number, reference, notes, terms) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                invoice_id,
+                invoice.cust_id,
+                estimate_number,
+                invoice.customer.company,
+                invoice.contact_company,
+                Local::now().format("%Y-%m-%d").to_string(),
+                totals::gross_total(&invoice.items),
+                invoice.text.po_number,
+                invoice.text.reference,
+                invoice.text.notes,
+                invoice.text.terms,
+            ],
+        )?;
+
+        let mut items = Vec::with_capacity(invoice.items.len());
+        for item in &invoice.items {
+            let item = DatabaseData {
+                entry_id: format!(
+                    "{}-{}-{}",
+                    invoice.cust_id, estimate_number, item.row_number
+                ),
+                cust_id: invoice.cust_id.clone(),
+                estimate_number,
+                ..item.clone()
+            };
+            insert_line_item(&transaction, &item, "INSERT")?;
+            items.push(item);
+        }
+
+        save_customer(&transaction, &invoice.customer)?;
+        transaction.execute(
+            "UPDATE customers SET estimate_number = ?1 WHERE company = ?2",
+            params![estimate_number, invoice.customer.company],
+        )?;
+
+        let result = render(estimate_number)
+            .map_err(IssueError::from)
+            .and_then(|()| finish_issue(transaction, &invoice_id, file_path));
+        match result {
+            Ok(file_hash) => Ok(IssuedInvoice {
+                invoice_id,
+                estimate_number,
+                items,
+                file_path: file_path.to_path_buf(),
+                file_hash,
+            }),
+            Err(e) => {
+                // the transaction has been rolled back, don't leave an unrecorded pdf behind, also
+                // when rendering failed after the pdf was written
+                let _ = fs::remove_file(file_path);
+                Err(e)
+            }
+        }
+    }
+}
+
+// email senders and the sent log
+impl Store {
+    pub fn mail_sender(&self, contact: &str) -> rusqlite::Result<Option<MailSender>> {
+        self.connection
+            .query_row(
+                &format!(
+                    "SELECT {} FROM mail_senders WHERE contact = ?1",
+                    MAIL_SENDER_COLUMNS
+                ),
+                [contact],
+                mail_sender_from_row,
+            )
+            .optional()
+    }
+
+    pub fn save_mail_sender(&self, sender: &MailSender) -> rusqlite::Result<usize> {
+        self.connection.execute(
+            &format!(
+                "INSERT OR REPLACE INTO mail_senders ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                MAIL_SENDER_COLUMNS
+            ),
+            params![
+                sender.contact,
+                sender.from_name,
+                sender.from_address,
+                sender.reply_to,
+                sender.bcc,
+                sender.subject,
+                sender.body,
+            ],
+        )
+    }
+
+    // messages sent for one invoice, newest first
+    pub fn sent_mail(&self, invoice_id: &str) -> rusqlite::Result<Vec<SentMail>> {
+        let mut stmt = self.connection.prepare(&format!(
+            "SELECT {} FROM sent_mail WHERE invoice_id = ?1 ORDER BY sent_at DESC, mail_id DESC",
+            SENT_MAIL_COLUMNS
+        ))?;
+        let rows = stmt.query_map([invoice_id], sent_mail_from_row)?;
+        rows.collect()
+    }
+
+    // logs a sent message and marks its invoice as sent, the id of `mail` is ignored
+    pub fn record_sent_mail(&mut self, mail: &SentMail) -> rusqlite::Result<i64> {
+        let transaction = self.connection.transaction()?;
+        transaction.execute(
+            "INSERT INTO sent_mail (invoice_id, sent_at, sender, recipients, subject, message_id, response)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                mail.invoice_id,
+                mail.sent_at,
+                mail.sender,
+                mail.recipients,
+                mail.subject,
+                mail.message_id,
+                mail.response,
+            ],
+        )?;
+        let mail_id = transaction.last_insert_rowid();
+        transaction.execute(
+            "UPDATE invoices SET status = ?2 WHERE invoice_id = ?1",
+            params![mail.invoice_id, InvoiceStatus::Sent.key()],
+        )?;
+        transaction.commit()?;
+        Ok(mail_id)
+    }
+}
+
+fn save_customer(connection: &Connection, customer: &Customer) -> rusqlite::Result<usize> {
+    connection.execute(
+        &format!(
+            "INSERT INTO customers ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+            ON CONFLICT (company) DO UPDATE SET address = excluded.address, city = excluded.city,
+            postal_code = excluded.postal_code, country = excluded.country,
+            notes = excluded.notes, terms = excluded.terms, vat_id = excluded.vat_id,
+            endpoint_id = excluded.endpoint_id, email = excluded.email",
+            CUSTOMER_COLUMNS
+        ),
+        params![
+            customer.company,
+            customer.address,
+            customer.city,
+            customer.postal_code,
+            customer.country,
+            customer.notes,
+            customer.terms,
+            customer.vat_id,
+            customer.endpoint_id,
+            customer.email,
+        ],
+    )
+}
+
+fn save_contact(connection: &Connection, contact: &Contact) -> rusqlite::Result<usize> {
+    connection.execute(
+        &format!(
+            "INSERT OR REPLACE INTO contacts ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+            CONTACT_COLUMNS
+        ),
+        params![
+            contact.company,
+            contact.address,
+            contact.city,
+            contact.postal_code,
+            contact.country,
+            contact.name,
+            contact.telephone,
+            contact.email,
+            contact.website,
+            contact.default_notes,
+            contact.default_terms,
+            contact.vat_id,
+            contact.endpoint_id,
+        ],
+    )
+}
+
+fn insert_line_item(
+    connection: &Connection,
+    item: &DatabaseData,
+    verb: &str,
+) -> rusqlite::Result<usize> {
+    connection.execute(
+        &format!(
+            "{} INTO data ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            verb, LINE_ITEM_COLUMNS
+        ),
+        params![
+            item.entry_id,
+            item.cust_id,
+            item.estimate_number,
+            item.row_number,
+            item.description,
+            item.quantity,
+            item.price,
+            item.total,
+            item.sku,
+            item.unit,
+            item.tax_code,
+            item.kind.key(),
+        ],
+    )
+}
+
+fn finish_issue(
+    transaction: Transaction,
+    invoice_id: &str,
+    file_path: &Path,
+) -> Result<String, IssueError> {
+    let file_hash = hash_file(file_path).map_err(IssueError::File)?;
+    transaction.execute(
+        "UPDATE invoices SET file_path = ?1, file_hash = ?2 WHERE invoice_id = ?3",
+        params![file_path.display().to_string(), file_hash, invoice_id],
+    )?;
+    transaction.commit()?;
+    Ok(file_hash)
+}
+
+fn reserve_estimate_number(transaction: &Transaction, cust_id: &str) -> rusqlite::Result<usize> {
+    let from_invoices: Option<usize> = transaction.query_row(
+        "SELECT MAX(estimate_number) FROM invoices WHERE cust_id = ?1",
+        [cust_id],
+        |row| row.get(0),
+    )?;
+    let from_data: Option<usize> = transaction.query_row(
+        "SELECT MAX(estimate_number) FROM data WHERE cust_id = ?1",
+        [cust_id],
+        |row| row.get(0),
+    )?;
+    Ok(from_invoices.max(from_data).unwrap_or(0) + 1)
+}
+
+pub fn hash_file(path: &Path) -> std::io::Result<String> {
+    let bytes = fs::read(path)?;
+    Ok(hex(&Sha256::digest(bytes)))
+}
+
+// lower case hex, as file hashes are stored
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn customer_from_row(row: &Row) -> rusqlite::Result<Customer> {
+    Ok(Customer {
+        company: row.get(0)?,
+        address: row.get(1)?,
+        city: row.get(2)?,
+        postal_code: row.get(3)?,
+        country: row.get(4)?,
+        notes: row.get(5)?,
+        terms: row.get(6)?,
+        vat_id: row.get(7)?,
+        endpoint_id: row.get(8)?,
+        email: row.get(9)?,
+    })
+}
+
+fn contact_from_row(row: &Row) -> rusqlite::Result<Contact> {
+    Ok(Contact {
+        company: row.get(0)?,
+        address: row.get(1)?,
+        city: row.get(2)?,
+        postal_code: row.get(3)?,
+        country: row.get(4)?,
+        name: row.get(5)?,
+        telephone: row.get(6)?,
+        email: row.get(7)?,
+        website: row.get(8)?,
+        default_notes: row.get(9)?,
+        default_terms: row.get(10)?,
+        vat_id: row.get(11)?,
+        endpoint_id: row.get(12)?,
+    })
+}
+
+fn line_item_from_row(row: &Row) -> rusqlite::Result<DatabaseData> {
+    Ok(DatabaseData {
+        entry_id: row.get(0)?,
+        cust_id: row.get(1)?,
+        estimate_number: row.get(2)?,
+        row_number: row.get(3)?,
+        description: row.get(4)?,
+        quantity: row.get::<_, Option<f64>>(5)?.unwrap_or(0.0),
+        price: row.get::<_, Option<f64>>(6)?.unwrap_or(0.0),
+        total: row.get::<_, Option<f64>>(7)?.unwrap_or(0.0),
+        sku: row.get(8)?,
+        unit: row.get(9)?,
+        tax_code: row.get(10)?,
+        kind: LineKind::from_key(&row.get::<_, String>(11)?),
+    })
+}
+
+fn product_from_row(row: &Row) -> rusqlite::Result<Product> {
+    Ok(Product {
+        sku: row.get(0)?,
+        name: row.get(1)?,
+        description: row.get(2)?,
+        unit: row.get(3)?,
+        price: row.get(4)?,
+        tax_code: row.get(5)?,
+        active: row.get(6)?,
+    })
+}
+
+fn customer_price_from_row(row: &Row) -> rusqlite::Result<CustomerPrice> {
+    let sku: String = row.get(1)?;
+    let rule = match row.get::<_, Option<f64>>(2)? {
+        Some(price) => PriceRule::Fixed(price),
+        None => PriceRule::PercentOff(row.get::<_, Option<f64>>(3)?.unwrap_or(0.0)),
+    };
+    Ok(CustomerPrice {
+        customer: row.get(0)?,
+        sku: if sku.is_empty() { None } else { Some(sku) },
+        rule,
+    })
+}
+
+fn draft_from_row(row: &Row) -> rusqlite::Result<Draft> {
+    Ok(Draft {
+        draft_id: row.get(0)?,
+        customer: row.get(1)?,
+        contact: row.get(2)?,
+        updated_at: row.get(3)?,
+        text: text_from_row(row, 4)?,
+        items: Vec::new(),
+    })
+}
+
+fn draft_item_from_row(row: &Row) -> rusqlite::Result<DraftItem> {
+    Ok(DraftItem {
+        description: row.get(0)?,
+        quantity_input: row.get(1)?,
+        price_input: row.get(2)?,
+        sku: row.get(3)?,
+        unit: row.get(4)?,
+        tax_code: row.get(5)?,
+        kind: LineKind::from_key(&row.get::<_, String>(6)?),
+    })
+}
+
+fn invoice_from_row(row: &Row) -> rusqlite::Result<Invoice> {
+    Ok(Invoice {
+        invoice_id: row.get(0)?,
+        cust_id: row.get(1)?,
+        estimate_number: row.get(2)?,
+        customer: row.get(3)?,
+        contact: row.get(4)?,
+        issue_date: row.get(5)?,
+        grand_total: row.get(6)?,
+        file_path: row.get(7)?,
+        file_hash: row.get(8)?,
+        text: text_from_row(row, 9)?,
+        status: InvoiceStatus::from_key(&row.get::<_, String>(13)?),
+    })
+}
+
+fn mail_sender_from_row(row: &Row) -> rusqlite::Result<MailSender> {
+    Ok(MailSender {
+        contact: row.get(0)?,
+        from_name: row.get(1)?,
+        from_address: row.get(2)?,
+        reply_to: row.get(3)?,
+        bcc: row.get(4)?,
+        subject: row.get(5)?,
+        body: row.get(6)?,
+    })
+}
+
+fn sent_mail_from_row(row: &Row) -> rusqlite::Result<SentMail> {
+    Ok(SentMail {
+        mail_id: row.get(0)?,
+        invoice_id: row.get(1)?,
+        sent_at: row.get(2)?,
+        sender: row.get(3)?,
+        recipients: row.get(4)?,
+        subject: row.get(5)?,
+        message_id: row.get(6)?,
+        response: row.get(7)?,
+    })
+}
+
+fn payment_from_row(row: &Row) -> rusqlite::Result<Payment> {
+    Ok(Payment {
+        payment_id: row.get(0)?,
+        invoice_id: row.get(1)?,
+        paid_on: row.get(2)?,
+        amount: row.get(3)?,
+        method: row.get(4)?,
+        reference: row.get(5)?,
+    })
+}
+
+// po number, reference, notes and terms stored in consecutive columns from `start`
+fn text_from_row(row: &Row, start: usize) -> rusqlite::Result<InvoiceText> {
+    Ok(InvoiceText {
+        po_number: row.get(start)?,
+        reference: row.get(start + 1)?,
+        notes: row.get(start + 2)?,
+        terms: row.get(start + 3)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{issue, temp_pdf};
+
+    fn customer() -> Customer {
+        Customer {
+            company: "Acme".to_string(),
+            address: "1 Road".to_string(),
+            city: "Town".to_string(),
+            postal_code: "A1A 1A1".to_string(),
+            country: "Canada".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn contact() -> Contact {
+        Contact {
+            company: "Fake Co.".to_string(),
+            address: "1111 Fake Ave.".to_string(),
+            city: "Fakeston".to_string(),
+            postal_code: "F4K 3E5".to_string(),
+            country: "United Fakes".to_string(),
+            name: "Fake Fake Smith".to_string(),
+            telephone: "111-111-1111".to_string(),
+            email: "fake@fake.com".to_string(),
+            website: "fake.fake".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn new_invoice() -> NewInvoice {
+        let item = |row_number: usize, description: &str, quantity: f64, price: f64| DatabaseData {
+            row_number,
+            description: description.to_string(),
+            quantity,
+            price,
+            total: quantity * price,
+            ..Default::default()
+        };
+        NewInvoice {
+            cust_id: "ACME".to_string(),
+            customer: customer(),
+            contact_company: "Fake Co.".to_string(),
+            items: vec![item(0, "Design", 2.0, 50.0), item(1, "Build", 1.0, 100.0)],
+            text: InvoiceText {
+                po_number: "PO-77".to_string(),
+                notes: "Thank you for your business".to_string(),
+                ..Default::default()
+            },
+        }
+    }
+
+    fn count(store: &Store, table: &str) -> usize {
+        store
+            .connection()
+            .query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
+                row.get(0)
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn test_customer_crud() {
+        let store = Store::open_in_memory().unwrap();
+        let mut acme = customer();
+        store.save_customer(&acme).unwrap();
+        acme.city = "City".to_string();
+        store.save_customer(&acme).unwrap();
+
+        assert_eq!(store.customers().unwrap(), vec![acme.clone()]);
+        assert_eq!(store.customer("Acme").unwrap(), Some(acme));
+        assert_eq!(store.delete_customer("Acme").unwrap(), 1);
+        assert_eq!(store.customer("Acme").unwrap(), None);
+    }
+
+    #[test]
+    fn test_customer_terms_override_contact_defaults() {
+        let store = Store::open_in_memory().unwrap();
+        let mut fake = contact();
+        fake.default_notes = "Thank you for your business".to_string();
+        fake.default_terms = "Net 30".to_string();
+        let mut acme = customer();
+        acme.terms = "Net 15".to_string();
+        store.save_contact(&fake).unwrap();
+        store.save_customer(&acme).unwrap();
+
+        let text = InvoiceText::defaults_for(
+            &store.contact("Fake Co.").unwrap().unwrap(),
+            &store.customer("Acme").unwrap().unwrap(),
+        );
+        assert_eq!(text.notes, "Thank you for your business");
+        assert_eq!(text.terms, "Net 15");
+    }
+
+    #[test]
+    fn test_contact_crud() {
+        let store = Store::open_in_memory().unwrap();
+        store.save_contact(&contact()).unwrap();
+
+        assert_eq!(store.contacts().unwrap(), vec![contact()]);
+        assert_eq!(store.contact("Fake Co.").unwrap(), Some(contact()));
+        assert_eq!(store.delete_contact("Fake Co.").unwrap(), 1);
+        assert!(store.contacts().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_line_item_crud() {
+        let store = Store::open_in_memory().unwrap();
+        let mut item = new_invoice().items[0].clone();
+        item.entry_id = "ACME-1-0".to_string();
+        item.cust_id = "ACME".to_string();
+        item.estimate_number = 1;
+        store.save_line_item(&item).unwrap();
+
+        assert_eq!(store.invoice_line_items("ACME", 1).unwrap(), vec![item]);
+        assert_eq!(store.delete_line_item("ACME-1-0").unwrap(), 1);
+        assert!(store.line_items().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_products_search_and_revenue() {
+        let mut store = Store::open_in_memory().unwrap();
+        let consulting = Product {
+            sku: "CONS".to_string(),
+            name: "Consulting".to_string(),
+            unit: "h".to_string(),
+            price: 100.0,
+            active: true,
+            ..Default::default()
+        };
+        let retired = Product {
+            sku: "OLD".to_string(),
+            name: "Old consulting package".to_string(),
+            active: false,
+            ..Default::default()
+        };
+        store.save_product(&consulting).unwrap();
+        store.save_product(&retired).unwrap();
+
+        assert_eq!(
+            store.search_products("consult", 10).unwrap(),
+            vec![consulting.clone()]
+        );
+        assert_eq!(store.products().unwrap().len(), 2);
+
+        let mut invoice = new_invoice();
+        // free-text description differs from the product name, grouping still uses the sku
+        invoice.items[0].sku = Some("CONS".to_string());
+        invoice.items[1].sku = Some("CONS".to_string());
+        let (_, path) = issue(&mut store, &invoice, "revenue", b"pdf");
+        assert_eq!(
+            store.revenue_by_product().unwrap(),
+            vec![("Consulting".to_string(), 200.0)]
+        );
+        fs::remove_file(&path).unwrap();
+
+        // the invoiced product is only deactivated, the unused one goes
+        assert_eq!(store.delete_product("CONS").unwrap(), 1);
+        assert_eq!(store.delete_product("OLD").unwrap(), 1);
+        assert_eq!(
+            store.products().unwrap(),
+            vec![Product {
+                active: false,
+                ..consulting
+            }]
+        );
+        assert!(store.search_products("consult", 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_customer_prices() {
+        let store = Store::open_in_memory().unwrap();
+        store.save_customer(&customer()).unwrap();
+        let discount = CustomerPrice {
+            customer: customer().company,
+            sku: None,
+            rule: PriceRule::PercentOff(10.0),
+        };
+        let mut fixed = CustomerPrice {
+            sku: Some("CONS".to_string()),
+            rule: PriceRule::PercentOff(5.0),
+            ..discount.clone()
+        };
+        store.save_customer_price(&discount).unwrap();
+        store.save_customer_price(&fixed).unwrap();
+        // the product's rule is replaced, not added
+        fixed.rule = PriceRule::Fixed(80.0);
+        store.save_customer_price(&fixed).unwrap();
+        assert_eq!(
+            store.customer_prices().unwrap(),
+            vec![discount.clone(), fixed.clone()]
+        );
+
+        store
+            .delete_customer_price(&fixed.customer, Some("CONS"))
+            .unwrap();
+        assert_eq!(store.customer_prices().unwrap(), vec![discount]);
+        store.delete_customer(&fixed.customer).unwrap();
+        assert!(store.customer_prices().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_payments() {
+        let store = Store::open_in_memory().unwrap();
+        let payment = |paid_on: &str, amount: f64| Payment {
+            invoice_id: "ACME-1".to_string(),
+            paid_on: paid_on.to_string(),
+            amount,
+            method: "transfer".to_string(),
+            ..Default::default()
+        };
+        let second = store.save_payment(&payment("2024-02-01", 50.0)).unwrap();
+        let first = store.save_payment(&payment("2024-01-15", 25.0)).unwrap();
+        let payments = store.payments().unwrap();
+        assert_eq!(
+            payments,
+            vec![
+                Payment {
+                    payment_id: first,
+                    ..payment("2024-01-15", 25.0)
+                },
+                Payment {
+                    payment_id: second,
+                    ..payment("2024-02-01", 50.0)
+                }
+            ]
+        );
+        assert_eq!(store.delete_payment(first).unwrap(), 1);
+        assert_eq!(store.payments().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_sent_mail_marks_invoice_sent() {
+        let mut store = Store::open_in_memory().unwrap();
+        let (_, path) = issue(&mut store, &new_invoice(), "sent", b"invoice");
+        assert_eq!(
+            store.invoice("ACME-1").unwrap().unwrap().status,
+            InvoiceStatus::Issued
+        );
+
+        let sender = MailSender {
+            contact: "Fake Co.".to_string(),
+            from_address: "billing@fake.example".to_string(),
+            subject: "Invoice {invoice_number}".to_string(),
+            ..Default::default()
+        };
+        store.save_mail_sender(&sender).unwrap();
+        assert_eq!(store.mail_sender("Fake Co.").unwrap(), Some(sender));
+        assert_eq!(store.mail_sender("Other Co.").unwrap(), None);
+
+        let mail = |sent_at: &str| SentMail {
+            invoice_id: "ACME-1".to_string(),
+            sent_at: sent_at.to_string(),
+            recipients: "ap@acme.example".to_string(),
+            ..Default::default()
+        };
+        let first = store
+            .record_sent_mail(&mail("2024-03-01 09:00:00"))
+            .unwrap();
+        let second = store
+            .record_sent_mail(&mail("2024-03-08 09:00:00"))
+            .unwrap();
+        let log: Vec<i64> = store
+            .sent_mail("ACME-1")
+            .unwrap()
+            .iter()
+            .map(|mail| mail.mail_id)
+            .collect();
+        assert_eq!(log, vec![second, first]);
+        assert_eq!(
+            store.invoice("ACME-1").unwrap().unwrap().status,
+            InvoiceStatus::Sent
+        );
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_drafts_round_trip() {
+        let mut store = Store::open_in_memory().unwrap();
+        let item = |description: &str| DraftItem {
+            description: description.to_string(),
+            quantity_input: "2h30m".to_string(),
+            price_input: "abc".to_string(),
+            ..Default::default()
+        };
+        let mut draft = Draft {
+            customer: "Acme".to_string(),
+            contact: "Fake Co.".to_string(),
+            text: InvoiceText {
+                reference: "Website rebuild".to_string(),
+                terms: "Net 30".to_string(),
+                ..Default::default()
+            },
+            items: vec![item("Design"), item("Build")],
+            ..Default::default()
+        };
+        draft.draft_id = store.save_draft(&draft).unwrap();
+        // a second draft for the same customer lives alongside the first
+        let other = store
+            .save_draft(&Draft {
+                draft_id: 0,
+                ..draft.clone()
+            })
+            .unwrap();
+        assert_ne!(draft.draft_id, other);
+
+        draft.items.pop();
+        assert_eq!(store.save_draft(&draft).unwrap(), draft.draft_id);
+        let stored = store.draft(draft.draft_id).unwrap().unwrap();
+        assert_eq!(stored.items, vec![item("Design")]);
+        assert_eq!(stored.text, draft.text);
+        assert_eq!(store.drafts().unwrap().len(), 2);
+
+        assert_eq!(store.delete_draft(draft.draft_id).unwrap(), 1);
+        assert_eq!(store.draft(draft.draft_id).unwrap(), None);
+        assert_eq!(count(&store, "draft_items"), 2);
+    }
+
+    #[test]
+    fn test_issue_invoice_commits_everything() {
+        let mut store = Store::open_in_memory().unwrap();
+        let path = temp_pdf("commit");
+        let issued = store
+            .issue_invoice(&new_invoice(), &path, |number| {
+                fs::write(&path, format!("invoice {}", number)).unwrap();
+                Ok(())
+            })
+            .unwrap();
+
+        assert_eq!(issued.estimate_number, 1);
+        assert_eq!(issued.invoice_id, "ACME-1");
+        assert_eq!(issued.items[1].entry_id, "ACME-1-1");
+        assert_eq!(store.invoice_line_items("ACME", 1).unwrap(), issued.items);
+        let invoice = store.invoice("ACME-1").unwrap().unwrap();
+        assert_eq!(invoice.file_hash, Some(hash_file(&path).unwrap()));
+        assert_eq!(invoice.text, new_invoice().text);
+        assert_eq!(store.customers().unwrap(), vec![customer()]);
+
+        let second = store
+            .issue_invoice(&new_invoice(), &path, |_| Ok(()))
+            .unwrap();
+        assert_eq!(second.estimate_number, 2);
+        assert_eq!(store.invoices().unwrap().len(), 2);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_upgrade_recomputes_grand_totals() {
+        let mut store = Store::open_in_memory().unwrap();
+        let mut invoice = new_invoice();
+        invoice.items[1].tax_code = "S:21".to_string();
+        let (_, path) = issue(&mut store, &invoice, "upgrade", b"invoice");
+        let grand_total = |store: &Store| store.invoice("ACME-1").unwrap().unwrap().grand_total;
+        assert_eq!(grand_total(&store), 221.0);
+
+        // databases before version 3 hold the net total
+        store
+            .connection()
+            .execute("UPDATE invoices SET grand_total = 200.0", [])
+            .unwrap();
+        store
+            .connection()
+            .pragma_update(None, "user_version", 2)
+            .unwrap();
+        store.create_tables().unwrap();
+        assert_eq!(grand_total(&store), 221.0);
+        assert_eq!(store.schema_version().unwrap(), SCHEMA_VERSION);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_render_failure_rolls_back() {
+        let mut store = Store::open_in_memory().unwrap();
+        let path = temp_pdf("rollback");
+        // the pdf is written, then embedding the e-invoice fails
+        let result = store.issue_invoice(&new_invoice(), &path, |_| {
+            fs::write(&path, "pdf").unwrap();
+            Err(DocumentError::EmptyTable)
+        });
+
+        assert!(matches!(result, Err(IssueError::Document(_))));
+        assert_eq!(count(&store, "invoices"), 0);
+        assert_eq!(count(&store, "data"), 0);
+        assert_eq!(count(&store, "customers"), 0);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_missing_file_rolls_back() {
+        let mut store = Store::open_in_memory().unwrap();
+        let path = temp_pdf("missing");
+        // render reports success but never writes the file, so hashing fails after the inserts
+        let result = store.issue_invoice(&new_invoice(), &path, |_| Ok(()));
+
+        assert!(matches!(result, Err(IssueError::File(_))));
+        assert_eq!(count(&store, "invoices"), 0);
+        assert_eq!(count(&store, "data"), 0);
+    }
+
+    #[test]
+    fn test_duplicate_line_rolls_back() {
+        let mut store = Store::open_in_memory().unwrap();
+        let mut invoice = new_invoice();
+        // two lines with the same row number collide on entry_id half way through the inserts
+        invoice.items[1].row_number = 0;
+        let path = temp_pdf("duplicate");
+        let result = store.issue_invoice(&invoice, &path, |_| Ok(()));
+
+        assert!(matches!(result, Err(IssueError::Database(_))));
+        assert_eq!(count(&store, "invoices"), 0);
+        assert_eq!(count(&store, "data"), 0);
+    }
+}
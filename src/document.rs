@@ -1,73 +1,173 @@
-//! You may have to adapt the `FONT_DIRS` and `DEFAULT_FONT_NAME` constants for your system so that
-//! these files exist:
-//! - `{FONT_DIR}/{DEFAULT_FONT_NAME}-Regular.ttf`
-//! - `{FONT_DIR}/{DEFAULT_FONT_NAME}-Bold.ttf`
-//! - `{FONT_DIR}/{DEFAULT_FONT_NAME}-Italic.ttf`
-//! - `{FONT_DIR}/{DEFAULT_FONT_NAME}-BoldItalic.ttf`
-//!
-//! These fonts must be metrically identical to the built-in PDF sans-serif font (Helvetica/Arial).
+//! Fonts are resolved through the `FontRegistry`, so the bundled Calibri and JetBrains Mono
+//! families work no matter which directory the application is launched from. The logo is read
+//! from the per-user data directory (see `paths::logo_path`).
 
+use std::fmt;
 use std::path::PathBuf;
 
-use crate::Rect;
-use crate::Response;
+use crate::font_registry::FontRegistry;
+use crate::line_items::{LineItem, LineItems};
+use crate::paths;
+use crate::pdfa;
+use crate::settings::{DocumentFonts, TextLayout, TextPosition};
 use chrono::prelude::*;
-use genpdf::elements::TableLayoutRow;
 use genpdf::Alignment;
 use genpdf::Element as _;
-use genpdf::{elements, fonts, style};
+use genpdf::{elements, style};
 
-use crate::Contact;
-use crate::Customer;
+use crate::structs::{Contact, Customer, InvoiceText, LineKind};
+use crate::totals::Totals;
+use crate::ubl::{UblError, Violation};
 
-const IMAGE_PATH_JPG: &'static str = r"support/images/logo.jpg";
-const DIR_NAME: &str = r"fonts/JetbrainsMono/";
+#[derive(Debug)]
+pub enum DocumentError {
+    EmptyTable,
+    Logo {
+        path: PathBuf,
+        source: genpdf::error::Error,
+    },
+    Layout(genpdf::error::Error),
+    Render {
+        path: PathBuf,
+        source: genpdf::error::Error,
+    },
+    EInvoice(UblError),
+    PdfA {
+        path: PathBuf,
+        source: lopdf::Error,
+    },
+    // the converted file failed the PDF/A self-check, usually a font that could not be embedded
+    NotPdfA(Vec<Violation>),
+}
+
+impl fmt::Display for DocumentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DocumentError::EmptyTable => write!(f, "The invoice has no line items"),
+            DocumentError::Logo { path, source } => {
+                write!(f, "Unable to load logo {}: {}", path.display(), source)
+            }
+            DocumentError::Layout(source) => write!(f, "Invalid invoice layout: {}", source),
+            DocumentError::Render { path, source } => {
+                write!(f, "Failed to write {}: {}", path.display(), source)
+            }
+            DocumentError::EInvoice(source) => write!(f, "Invalid e-invoice: {}", source),
+            DocumentError::PdfA { path, source } => {
+                write!(f, "Failed to write PDF/A {}: {}", path.display(), source)
+            }
+            DocumentError::NotPdfA(violations) => {
+                writeln!(f, "The invoice would not be a valid PDF/A:")?;
+                for violation in violations {
+                    writeln!(f, "{}", violation)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for DocumentError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DocumentError::EmptyTable | DocumentError::NotPdfA(_) => None,
+            DocumentError::Logo { source, .. }
+            | DocumentError::Layout(source)
+            | DocumentError::Render { source, .. } => Some(source),
+            DocumentError::EInvoice(source) => Some(source),
+            DocumentError::PdfA { source, .. } => Some(source),
+        }
+    }
+}
+
+// table rows are only rejected when their cell count does not match the column count
+impl From<genpdf::error::Error> for DocumentError {
+    fn from(source: genpdf::error::Error) -> Self {
+        DocumentError::Layout(source)
+    }
+}
+
+// rendering choices that come from the settings rather than from the invoice itself
+pub struct DocumentOptions<'a> {
+    pub font_registry: &'a FontRegistry,
+    pub fonts: &'a DocumentFonts,
+    pub text_layout: &'a TextLayout,
+    // list only section totals and attach every row as an appendix page
+    pub summary_only: bool,
+    // write a PDF/A-2b for long-term archiving
+    pub archival: bool,
+    // the CII XML of the invoice, when set the PDF becomes a Factur-X PDF/A-3 carrying it
+    pub factur_x: Option<&'a str>,
+}
 
 pub fn generate_invoice(
     file_name: &PathBuf,
     contact_info: Contact,
     customer_info: Customer,
-    table: Vec<(String, (usize, i32), (Rect, Response))>,
+    items: &LineItems,
+    text: &InvoiceText,
     estimate_number: i32,
-    grand_total: f64,
-) {
-    // wasn't sure how to get system name in global variables so doing this for now
-    // let account_name: String = whoami::username().to_string();
-    // let dir_name: String = format!("/Users/{account_name}/Library/Fonts/").to_string();
-
-    let font_dirs: &[String] = &[DIR_NAME.to_string()];
-    let collection: Vec<&str> = (DIR_NAME.split(r"/")).collect::<Vec<&str>>();
-
-    // this part is kind of unnecessary, simplify in the future
-    let font_name_index: usize = 1;
-    let font_name: &String = &collection[font_name_index].to_string();
-
+    options: DocumentOptions,
+) -> Result<(), DocumentError> {
+    let DocumentOptions {
+        font_registry,
+        fonts,
+        text_layout,
+        summary_only,
+        archival,
+        factur_x,
+    } = options;
     let output_file = file_name;
+    // PDF/A metadata, taken before the contact and customer are moved into the layout
+    let metadata = pdfa::Metadata {
+        title: format!("Invoice {}", estimate_number),
+        author: contact_info.company.clone(),
+        subject: format!("Invoice {} to {}", estimate_number, customer_info.company),
+        invoice_number: estimate_number.to_string(),
+        created: Local::now().into(),
+    };
+    let logo_path = paths::logo_path();
 
-    let font_dir = font_dirs
-        .iter()
-        .filter(|path| std::path::Path::new(path).exists())
-        .next()
-        .expect("Could not find font directory");
-    let default_font = fonts::from_files(font_dir, font_name, Some(fonts::Builtin::Helvetica))
-        .expect("Failed to load the default font family");
-
-    // fonts loaded
-    let mut doc = genpdf::Document::new(default_font);
-
-    let mut decorator = genpdf::SimplePageDecorator::new();
-    decorator.set_margins(10);
-    decorator.set_header(|page| {
-        let mut layout = elements::LinearLayout::vertical();
-        if page > 1 {
-            layout.push(
-                elements::Paragraph::new(format!("Page {}", page)).aligned(Alignment::Center),
-            );
-            layout.push(elements::Break::new(1));
+    // a PDF/A may only use embedded fonts, the built-in Helvetica is swapped for a bundled family
+    let embed_fonts = archival || factur_x.is_some();
+    let load = |name: &str| {
+        if embed_fonts {
+            font_registry.load_embedded(name)
+        } else {
+            font_registry.load(name)
         }
-        layout.styled(style::Style::new().with_font_size(10))
+    };
+    let mut doc = genpdf::Document::new(load(&fonts.body));
+    // every family added is embedded in full, so sections sharing a font share the family
+    let mut families = vec![(fonts.body.clone(), doc.font_cache().default_font_family())];
+    let mut style_for = |name: &String| {
+        let family = match families.iter().find(|(loaded, _)| loaded == name) {
+            Some((_, family)) => *family,
+            None => {
+                let family = doc.add_font_family(load(name));
+                families.push((name.clone(), family));
+                family
+            }
+        };
+        style::Style::new().with_font_family(family)
+    };
+    let heading_style = style_for(&fonts.heading);
+    let table_style = style_for(&fonts.table);
+
+    let sections = [
+        ("Notes", &text.notes, text_layout.notes),
+        ("Terms & Conditions", &text.terms, text_layout.terms),
+    ];
+    let placed_at = |position: TextPosition| {
+        sections
+            .iter()
+            .filter(move |(_, value, placed)| *placed == position && !value.trim().is_empty())
+    };
+    doc.set_page_decorator(InvoicePages {
+        page: 0,
+        footer: placed_at(TextPosition::Footer)
+            .map(|(label, value, _)| (label.to_string(), value.to_string()))
+            .collect(),
     });
-    doc.set_page_decorator(decorator);
 
     doc.push(elements::Break::new(1.5));
 
@@ -79,48 +179,39 @@ pub fn generate_invoice(
     address_table
         .row()
         .element(elements::Paragraph::new(contact_info.company).aligned(Alignment::Left))
-        .push()
-        .expect("Invalid table row");
+        .push()?;
     address_table
         .row()
         .element(elements::Paragraph::new(contact_info.address).aligned(Alignment::Left))
-        .push()
-        .expect("Invalid table row");
+        .push()?;
     address_table
         .row()
         .element(elements::Paragraph::new(contact_info.city).aligned(Alignment::Left))
-        .push()
-        .expect("Invalid table row");
+        .push()?;
     address_table
         .row()
         .element(elements::Paragraph::new(contact_info.postal_code).aligned(Alignment::Left))
-        .push()
-        .expect("Invalid table row");
+        .push()?;
     address_table
         .row()
         .element(elements::Paragraph::new(contact_info.country).aligned(Alignment::Left))
-        .push()
-        .expect("Invalid table row");
+        .push()?;
     address_table
         .row()
         .element(elements::Paragraph::new(contact_info.name).aligned(Alignment::Left))
-        .push()
-        .expect("Invalid table row");
+        .push()?;
     address_table
         .row()
         .element(elements::Paragraph::new(contact_info.telephone).aligned(Alignment::Left))
-        .push()
-        .expect("Invalid table row");
+        .push()?;
     address_table
         .row()
         .element(elements::Paragraph::new(contact_info.email).aligned(Alignment::Left))
-        .push()
-        .expect("Invalid table row");
+        .push()?;
     address_table
         .row()
         .element(elements::Paragraph::new(contact_info.website).aligned(Alignment::Left))
-        .push()
-        .expect("Invalid table row");
+        .push()?;
 
     let mut top_header_table = elements::TableLayout::new(vec![1, 1]);
     top_header_table.set_cell_decorator(elements::FrameCellDecorator::new(false, false, false));
@@ -128,17 +219,23 @@ pub fn generate_invoice(
         .row()
         .element(address_table)
         .element(
-            elements::Image::from_path(IMAGE_PATH_JPG)
-                .expect("Unable to load image")
+            elements::Image::from_path(&logo_path)
+                .map_err(|source| DocumentError::Logo {
+                    path: logo_path.clone(),
+                    source,
+                })?
                 .with_alignment(Alignment::Right),
         )
-        .push()
-        .expect("Invalid header table");
+        .push()?;
 
     doc.push(top_header_table);
     doc.push(elements::Break::new(1.5));
 
-    doc.push(elements::Paragraph::new("FOR").styled(style::Effect::Bold));
+    doc.push(
+        elements::Paragraph::new("FOR")
+            .styled(heading_style)
+            .styled(style::Effect::Bold),
+    );
 
     let mut customer_info_table = elements::TableLayout::new(vec![1]);
     customer_info_table.set_cell_decorator(elements::FrameCellDecorator::new(false, false, false));
@@ -150,8 +247,7 @@ pub fn generate_invoice(
                 .aligned(Alignment::Left)
                 .styled(style::Effect::Bold),
         )
-        .push()
-        .expect("Invalid table row");
+        .push()?;
     customer_info_table
         .row()
         .element(
@@ -159,8 +255,7 @@ pub fn generate_invoice(
                 .aligned(Alignment::Left)
                 .styled(style::Effect::Bold),
         )
-        .push()
-        .expect("Invalid table row");
+        .push()?;
     customer_info_table
         .row()
         .element(
@@ -171,8 +266,7 @@ pub fn generate_invoice(
             .aligned(Alignment::Left)
             .styled(style::Effect::Bold),
         )
-        .push()
-        .expect("Invalid table row");
+        .push()?;
     customer_info_table
         .row()
         .element(
@@ -180,8 +274,7 @@ pub fn generate_invoice(
                 .aligned(Alignment::Left)
                 .styled(style::Effect::Bold),
         )
-        .push()
-        .expect("Invalid table row");
+        .push()?;
 
     let current_date = Local::now();
     let valid_until_date: DateTime<Local> = current_date + chrono::Days::new(7);
@@ -193,8 +286,7 @@ pub fn generate_invoice(
         .row()
         .element(elements::Paragraph::new("Estimate No.:"))
         .element(elements::Paragraph::new(estimate_number.to_string()).aligned(Alignment::Left))
-        .push()
-        .expect("Invalid header table");
+        .push()?;
     date_table
         .row()
         .element(elements::Paragraph::new("Issue Date:"))
@@ -202,8 +294,7 @@ pub fn generate_invoice(
             elements::Paragraph::new(current_date.format("%B %d, %Y").to_string())
                 .aligned(Alignment::Left),
         )
-        .push()
-        .expect("Invalid header table");
+        .push()?;
     date_table
         .row()
         .element(elements::Paragraph::new("Valid Until:"))
@@ -211,27 +302,146 @@ pub fn generate_invoice(
             elements::Paragraph::new(valid_until_date.format("%B %d, %Y").to_string())
                 .aligned(Alignment::Left),
         )
-        .push()
-        .expect("Invalid header table");
+        .push()?;
+
+    for (label, value) in [
+        ("PO Number:", &text.po_number),
+        ("Reference:", &text.reference),
+    ] {
+        if !value.trim().is_empty() {
+            date_table
+                .row()
+                .element(elements::Paragraph::new(label))
+                .element(elements::Paragraph::new(value.trim()).aligned(Alignment::Left))
+                .push()?;
+        }
+    }
 
     let mut bottom_header_table = elements::TableLayout::new(vec![1, 1]);
     bottom_header_table
         .row()
         .element(customer_info_table)
         .element(date_table)
-        .push()
-        .expect("Invalid header table");
+        .push()?;
 
     doc.push(bottom_header_table);
     doc.push(elements::Break::new(1.5));
 
     // table length will be dependant variable based on the number of columns necessary
 
-    let mut item_table = elements::TableLayout::new(vec![1; 4]);
+    // notes and terms placed by the text layout setting
+    let push_text = |doc: &mut genpdf::Document, position: TextPosition| {
+        for (label, value, _) in placed_at(position) {
+            doc.push(text_block(label, value, position));
+            doc.push(elements::Break::new(1));
+        }
+    };
+    push_text(&mut doc, TextPosition::AboveItems);
+
+    if items.is_empty() {
+        return Err(DocumentError::EmptyTable);
+    }
+    if summary_only {
+        doc.push(summary_table(items)?.styled(table_style));
+    } else {
+        doc.push(item_table(items)?.styled(table_style));
+    }
+
+    let mut totals = total_lines(items);
+    let (label, payable) = totals.pop().expect("total lines end with the grand total");
+    for (label, value) in totals {
+        doc.push(
+            elements::Paragraph::new(format!("{}: ${:.2}", label, value)).styled(heading_style),
+        );
+    }
+    doc.push(
+        elements::Paragraph::new(format!("{}: ${:.2}", label, payable))
+            .styled(heading_style)
+            .styled(style::Effect::Bold),
+    );
+    doc.push(elements::Break::new(1));
+    push_text(&mut doc, TextPosition::BelowTotal);
+
+    // the full breakdown follows the summary on its own page
+    if summary_only {
+        doc.push(elements::PageBreak::new());
+        doc.push(
+            elements::Paragraph::new("Detailed Breakdown")
+                .styled(heading_style)
+                .styled(style::Effect::Bold),
+        );
+        doc.push(elements::Break::new(1));
+        doc.push(item_table(items)?.styled(table_style));
+    }
+
+    if factur_x.is_none() && !archival {
+        return doc
+            .render_to_file(output_file)
+            .map_err(|source| DocumentError::Render {
+                path: output_file.clone(),
+                source,
+            });
+    }
+    // printpdf's PDF/X metadata would only be replaced, so it is left out
+    doc.set_minimal_conformance();
+    doc.set_title(metadata.title.clone());
+    let mut rendered = Vec::new();
+    doc.render(&mut rendered)
+        .map_err(|source| DocumentError::Render {
+            path: output_file.clone(),
+            source,
+        })?;
+    // a Factur-X file is a PDF/A-3 and archival as it is
+    let converted = match factur_x {
+        Some(xml) => pdfa::factur_x(&rendered, &metadata, xml),
+        None => pdfa::archive(&rendered, &metadata),
+    };
+    let pdf = converted.map_err(|source| DocumentError::PdfA {
+        path: output_file.clone(),
+        source,
+    })?;
+    let violations = pdfa::check(&pdf);
+    if !violations.is_empty() {
+        return Err(DocumentError::NotPdfA(violations));
+    }
+    std::fs::write(output_file, pdf).map_err(|source| DocumentError::PdfA {
+        path: output_file.clone(),
+        source: source.into(),
+    })
+}
+
+// The net total, the tax per rate and the grand total, worked out with the same `Totals` as the
+// e-invoice XML so a Factur-X file states one amount in both halves. Lines without a tax code
+// carry no tax, the net total and tax lines are left out when nothing is taxed.
+fn total_lines(items: &LineItems) -> Vec<(String, f64)> {
+    let data = items.to_database_data("", 0);
+    let totals = Totals::new(&data);
+    // credit notes are computed with positive amounts
+    let sign = if totals.credit_note { -1.0 } else { 1.0 };
+    let mut lines = Vec::new();
+    let taxed: Vec<_> = totals
+        .breakdown
+        .iter()
+        .filter(|entry| entry.tax != 0.0)
+        .collect();
+    if !taxed.is_empty() {
+        lines.push(("Net Total".to_string(), sign * totals.line_total));
+        for entry in taxed {
+            lines.push((
+                format!("Tax {} {}%", entry.category.code, entry.category.percent),
+                sign * entry.tax,
+            ));
+        }
+    }
+    lines.push(("Grand Total".to_string(), sign * totals.payable()));
+    lines
+}
+
+// every row of the invoice: items, section headings and subtotal rows
+fn item_table(items: &LineItems) -> Result<elements::TableLayout, DocumentError> {
+    // the description gets the most room, it can run over several lines
+    let mut item_table = elements::TableLayout::new(vec![2, 1, 1, 1]);
     item_table.set_cell_decorator(elements::FrameCellDecorator::new(true, true, false));
-    let max_cell = table.iter().max_by_key(|&&(_, y, _)| y).unwrap();
-    let max_row = max_cell.1 .0 + 1;
-    let max_col = max_cell.1 .1 + 1;
     item_table
         .row()
         .element(
@@ -258,34 +468,381 @@ pub fn generate_invoice(
                 .styled(style::Effect::Bold)
                 .padded(2),
         )
-        .push()
-        .expect("Invalid header table");
-    for i in 0..max_row {
-        let mut table_row: TableLayoutRow = item_table.row();
-        for j in 0..max_col {
-            for item in &table {
-                if item.1 == (i, 0) {
-                    // this is just the row number so we can skip it
-                } else if item.1 == (i, j) {
-                    table_row.push_element(
-                        elements::Paragraph::new(item.0.clone())
-                            .aligned(Alignment::Left)
-                            .padded(2),
-                    );
-                }
+        .push()?;
+    for (idx, item) in items.items().iter().enumerate() {
+        let cell = |text: String| {
+            elements::Paragraph::new(text)
+                .aligned(Alignment::Left)
+                .padded(2)
+        };
+        match item.kind {
+            LineKind::Heading => {
+                item_table
+                    .row()
+                    .element(
+                        cell(item.description.trim().to_string())
+                            .styled(style::Style::new().bold().with_font_size(13)),
+                    )
+                    .element(cell(String::new()))
+                    .element(cell(String::new()))
+                    .element(cell(String::new()))
+                    .push()?;
+                continue;
             }
+            LineKind::Subtotal => {
+                item_table
+                    .row()
+                    .element(cell(item.description.trim().to_string()).styled(style::Effect::Bold))
+                    .element(cell(String::new()))
+                    .element(cell(String::new()))
+                    .element(
+                        cell(format!("{:.2}", items.subtotal_at(idx))).styled(style::Effect::Bold),
+                    )
+                    .push()?;
+                continue;
+            }
+            LineKind::Item => {}
         }
-        table_row.push().expect("Invalid Row");
+        item_table
+            .row()
+            .element(description_cell(item))
+            .element(
+                elements::Paragraph::new(item.quantity_label())
+                    .aligned(Alignment::Left)
+                    .padded(2),
+            )
+            .element(
+                elements::Paragraph::new(format!("{:.2}", item.price))
+                    .aligned(Alignment::Left)
+                    .padded(2),
+            )
+            .element(
+                elements::Paragraph::new(format!("{:.2}", item.total()))
+                    .aligned(Alignment::Left)
+                    .padded(2),
+            )
+            .push()?;
     }
-    doc.push(item_table);
+    Ok(item_table)
+}
 
-    doc.push(
-        elements::Paragraph::new(format!("Grand Total: ${}", grand_total))
+// one row per section with its total, for the summary only mode
+fn summary_table(items: &LineItems) -> Result<elements::TableLayout, DocumentError> {
+    let mut table = elements::TableLayout::new(vec![3, 1]);
+    table.set_cell_decorator(elements::FrameCellDecorator::new(true, true, false));
+    let cell = |text: String| {
+        elements::Paragraph::new(text)
+            .aligned(Alignment::Left)
+            .padded(2)
+    };
+    table
+        .row()
+        .element(cell("Section".to_string()).styled(style::Effect::Bold))
+        .element(cell("Total".to_string()).styled(style::Effect::Bold))
+        .push()?;
+    for section in items.sections() {
+        table
+            .row()
+            .element(cell(section.title))
+            .element(cell(format!("{:.2}", section.total)))
+            .push()?;
+    }
+    Ok(table)
+}
+
+// description lines followed by the row's sub-item bullets
+fn description_cell(item: &LineItem) -> impl genpdf::Element {
+    let (text, bullets) = item.description_lines();
+    let mut layout = elements::LinearLayout::vertical();
+    for line in text {
+        layout.push(elements::Paragraph::new(line).aligned(Alignment::Left));
+    }
+    for bullet in bullets {
+        layout.push(
+            elements::BulletPoint::new(elements::Paragraph::new(bullet))
+                .with_bullet("•")
+                .styled(style::Style::new().with_font_size(10)),
+        );
+    }
+    layout.padded(2)
+}
+
+// Margins, the page number from the second page on, and the footer text at the bottom of every
+// page. genpdf's SimplePageDecorator has no footer, so the body area is shortened by its height.
+struct InvoicePages {
+    page: usize,
+    // label and text of the sections placed in the footer
+    footer: Vec<(String, String)>,
+}
+
+impl genpdf::PageDecorator for InvoicePages {
+    fn decorate_page<'a>(
+        &mut self,
+        context: &genpdf::Context,
+        mut area: genpdf::render::Area<'a>,
+        style: style::Style,
+    ) -> Result<genpdf::render::Area<'a>, genpdf::error::Error> {
+        self.page += 1;
+        area.add_margins(10);
+        if self.page > 1 {
+            let mut header = elements::LinearLayout::vertical();
+            header.push(
+                elements::Paragraph::new(format!("Page {}", self.page)).aligned(Alignment::Center),
+            );
+            header.push(elements::Break::new(1));
+            let mut header = header.styled(style::Style::new().with_font_size(10));
+            let result = header.render(context, area.clone(), style)?;
+            area.add_offset(genpdf::Position::new(0, result.size.height));
+        }
+        if self.footer.is_empty() {
+            return Ok(area);
+        }
+        // the footer is drawn before the body, so its height is taken from the wrapped lines
+        let footer_style = style.with_font_size(8);
+        let width = area.size().width;
+        let lines: usize = self
+            .footer
+            .iter()
+            .map(|(_, value)| {
+                1 + value
+                    .trim()
+                    .lines()
+                    .map(|line| wrapped_lines(&context.font_cache, footer_style, line, width))
+                    .sum::<usize>()
+            })
+            .sum::<usize>()
+            + self.footer.len()
+            - 1;
+        let height = footer_style.line_height(&context.font_cache) * lines as f64;
+        let mut footer = elements::LinearLayout::vertical();
+        for (i, (label, value)) in self.footer.iter().enumerate() {
+            if i > 0 {
+                footer.push(elements::Break::new(1).styled(footer_style));
+            }
+            footer.push(text_block(label, value, TextPosition::Footer));
+        }
+        let mut footer_area = area.clone();
+        footer_area.add_offset(genpdf::Position::new(0, area.size().height - height));
+        footer.render(context, footer_area, style)?;
+        area.set_height(area.size().height - height - genpdf::Mm::from(5));
+        Ok(area)
+    }
+}
+
+// how many lines genpdf wraps a paragraph into, breaking between words
+fn wrapped_lines(
+    font_cache: &genpdf::fonts::FontCache,
+    style: style::Style,
+    line: &str,
+    width: genpdf::Mm,
+) -> usize {
+    let mut lines = 1;
+    let mut x = genpdf::Mm::default();
+    for word in line.split_inclusive(' ') {
+        let word_width = style.str_width(font_cache, word);
+        if x > genpdf::Mm::default() && x + word_width > width {
+            lines += 1;
+            x = word_width;
+        } else {
+            x += word_width;
+        }
+    }
+    lines
+}
+
+// genpdf paragraphs don't break on newlines, so every line gets its own paragraph
+fn text_block(label: &str, value: &str, position: TextPosition) -> impl genpdf::Element {
+    let mut layout = elements::LinearLayout::vertical();
+    let (alignment, size) = match position {
+        TextPosition::Footer => (Alignment::Center, 8),
+        _ => (Alignment::Left, 10),
+    };
+    layout.push(
+        elements::Paragraph::new(label)
+            .aligned(alignment)
             .styled(style::Effect::Bold),
     );
+    for line in value.trim().lines() {
+        layout.push(elements::Paragraph::new(line).aligned(alignment));
+    }
+    layout.styled(style::Style::new().with_font_size(size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font_registry::HELVETICA;
+    use crate::line_items::{DESCRIPTION, PRICE, QUANTITY};
+
+    fn items() -> LineItems {
+        let mut items = LineItems::default();
+        items.items_mut()[0].set_cell(DESCRIPTION, "Consulting");
+        items.items_mut()[0].set_cell(PRICE, "120.50");
+        items.items_mut()[0].tax_code = "S:21".to_string();
+        items
+    }
+
+    fn render(
+        name: &str,
+        fonts: &DocumentFonts,
+        factur_x: Option<&str>,
+    ) -> Result<PathBuf, DocumentError> {
+        let path = std::env::temp_dir().join(format!(
+            "invoicy-document-test-{}-{}.pdf",
+            name,
+            std::process::id()
+        ));
+        generate_invoice(
+            &path,
+            Contact::default(),
+            Customer::default(),
+            &items(),
+            &InvoiceText::default(),
+            7,
+            DocumentOptions {
+                font_registry: &FontRegistry::embedded(),
+                fonts,
+                text_layout: &TextLayout::default(),
+                summary_only: false,
+                archival: factur_x.is_none(),
+                factur_x,
+            },
+        )?;
+        Ok(path)
+    }
+
+    #[test]
+    fn test_archival_with_default_fonts() {
+        let path = render("default", &DocumentFonts::default(), None).unwrap();
+        assert!(pdfa::check(&std::fs::read(&path).unwrap()).is_empty());
+        std::fs::remove_file(&path).unwrap();
+
+        // the built-in Helvetica cannot be embedded and is swapped for a bundled family
+        let helvetica = DocumentFonts {
+            body: HELVETICA.to_string(),
+            heading: HELVETICA.to_string(),
+            table: HELVETICA.to_string(),
+        };
+        let path = render("helvetica", &helvetica, None).unwrap();
+        assert!(pdfa::check(&std::fs::read(&path).unwrap()).is_empty());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_factur_x_with_default_fonts() {
+        let path = render(
+            "factur-x",
+            &DocumentFonts::default(),
+            Some("<rsm:CrossIndustryInvoice/>"),
+        )
+        .unwrap();
+        assert!(pdfa::check(&std::fs::read(&path).unwrap()).is_empty());
+        std::fs::remove_file(&path).unwrap();
+    }
 
-    doc.render_to_file(output_file)
-        .expect("Failed to write output file");
+    #[test]
+    fn test_footer_on_every_page() {
+        let mut items = items();
+        for _ in 0..80 {
+            let idx = items.push();
+            items.items_mut()[idx].set_cell(DESCRIPTION, "Consulting");
+            items.items_mut()[idx].set_cell(QUANTITY, "1");
+            items.items_mut()[idx].set_cell(PRICE, "10");
+        }
+        let text = InvoiceText {
+            terms: "Payable within 30 days".to_string(),
+            ..Default::default()
+        };
+        let helvetica = DocumentFonts {
+            body: HELVETICA.to_string(),
+            heading: HELVETICA.to_string(),
+            table: HELVETICA.to_string(),
+        };
+        let path = std::env::temp_dir().join(format!(
+            "invoicy-document-test-footer-{}.pdf",
+            std::process::id()
+        ));
+        // the built-in font writes every character as its hex code
+        generate_invoice(
+            &path,
+            Contact::default(),
+            Customer::default(),
+            &items,
+            &text,
+            7,
+            DocumentOptions {
+                font_registry: &FontRegistry::embedded(),
+                fonts: &helvetica,
+                text_layout: &TextLayout::default(),
+                summary_only: false,
+                archival: false,
+                factur_x: None,
+            },
+        )
+        .unwrap();
+        let pdf = lopdf::Document::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let pages = pdf.get_pages();
+        assert!(pages.len() > 1);
+        // words are written one at a time
+        let encoded: String = "Payable "
+            .chars()
+            .map(|c| format!("<{:04X}>", c as u32))
+            .collect();
+        for id in pages.values() {
+            let content = pdf.get_page_content(*id).unwrap();
+            assert!(String::from_utf8_lossy(&content).contains(&encoded));
+        }
+    }
+
+    #[test]
+    fn test_wrapped_lines() {
+        let mut font_cache =
+            genpdf::fonts::FontCache::new(FontRegistry::embedded().load(HELVETICA));
+        font_cache
+            .load_pdf_fonts(
+                &genpdf::render::Renderer::new(genpdf::Size::new(210, 297), "").unwrap(),
+            )
+            .unwrap();
+        let style = style::Style::new().with_font_size(8);
+        let width = style.str_width(&font_cache, "Payable within ");
+        assert_eq!(wrapped_lines(&font_cache, style, "Payable", width), 1);
+        assert_eq!(
+            wrapped_lines(&font_cache, style, "Payable within 30 days", width),
+            2
+        );
+    }
+
+    #[test]
+    fn test_total_lines() {
+        let mut items = items();
+        let idx = items.push();
+        items.items_mut()[idx].set_cell(DESCRIPTION, "Books");
+        items.items_mut()[idx].set_cell(PRICE, "10");
+        items.items_mut()[idx].tax_code = "Z".to_string();
+        // the amounts the CII and UBL documents declare for the same lines
+        let data = items.to_database_data("", 0);
+        let totals = Totals::new(&data);
+        assert_eq!(
+            total_lines(&items),
+            vec![
+                ("Net Total".to_string(), 130.5),
+                ("Tax S 21%".to_string(), 25.31),
+                ("Grand Total".to_string(), totals.payable()),
+            ]
+        );
+        assert_eq!(totals.payable(), 155.81);
+
+        // nothing taxed, only the grand total, negative for a credit note
+        let mut refund = LineItems::default();
+        refund.items_mut()[0].set_cell(DESCRIPTION, "Refund");
+        refund.items_mut()[0].set_cell(QUANTITY, "-1");
+        refund.items_mut()[0].set_cell(PRICE, "40");
+        assert_eq!(
+            total_lines(&refund),
+            vec![("Grand Total".to_string(), -40.0)]
+        );
+    }
 }
 
 // Only import the images if the feature is enabled. This helps verify our handling of feature toggles.
@@ -0,0 +1,200 @@
+//! Optional encryption of the database at rest with SQLCipher. An encrypted database is unlocked
+//! with its passphrase when the app starts and backups made from it are encrypted with the same
+//! passphrase. There is no way back in without the passphrase, nothing is stored to recover it.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use rusqlite::ErrorCode;
+
+use crate::db::{self, Store};
+
+// read by `invoicy export` so encrypted databases can be exported from scripts
+pub const PASSPHRASE_ENV_VAR: &str = "INVOICY_PASSPHRASE";
+pub const MIN_PASSPHRASE_LENGTH: usize = 8;
+
+#[derive(Debug)]
+pub enum EncryptionError {
+    Database(rusqlite::Error),
+    File(io::Error),
+    // the database is encrypted and no passphrase was given
+    Locked,
+    WrongPassphrase,
+    TooShort,
+    Mismatch,
+}
+
+impl fmt::Display for EncryptionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncryptionError::Database(e) => write!(f, "Unable to open the database: {}", e),
+            EncryptionError::File(e) => write!(f, "Unable to replace the database file: {}", e),
+            EncryptionError::Locked => write!(f, "The database is encrypted, enter its passphrase"),
+            EncryptionError::WrongPassphrase => write!(f, "The passphrase is not correct"),
+            EncryptionError::TooShort => write!(
+                f,
+                "Use a passphrase of at least {} characters",
+                MIN_PASSPHRASE_LENGTH
+            ),
+            EncryptionError::Mismatch => write!(f, "The passphrases do not match"),
+        }
+    }
+}
+
+impl std::error::Error for EncryptionError {}
+
+impl From<rusqlite::Error> for EncryptionError {
+    fn from(e: rusqlite::Error) -> Self {
+        match e.sqlite_error_code() {
+            // SQLCipher cannot tell a wrong key from a file that is not a database
+            Some(ErrorCode::NotADatabase) => EncryptionError::WrongPassphrase,
+            _ => EncryptionError::Database(e),
+        }
+    }
+}
+
+impl From<io::Error> for EncryptionError {
+    fn from(e: io::Error) -> Self {
+        EncryptionError::File(e)
+    }
+}
+
+// opens the database at `path`, unlocking it with `passphrase` when it is encrypted. A new
+// database is created in plain text, encryption is turned on afterwards.
+pub fn open(path: &Path, passphrase: Option<&str>) -> Result<Store, EncryptionError> {
+    if !db::is_encrypted_file(path) {
+        return Ok(Store::open(path)?);
+    }
+    match passphrase {
+        Some(passphrase) => Ok(Store::open_encrypted(path, passphrase)?),
+        None => Err(EncryptionError::Locked),
+    }
+}
+
+pub fn validate_passphrase(passphrase: &str, confirmation: &str) -> Result<(), EncryptionError> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_LENGTH {
+        return Err(EncryptionError::TooShort);
+    }
+    if passphrase != confirmation {
+        return Err(EncryptionError::Mismatch);
+    }
+    Ok(())
+}
+
+// encrypts, re-keys or decrypts the database at `path` that `store` has open, None stores it in
+// plain text again. Switching between the two writes a converted copy which then replaces the
+// file, so `store` is reopened on the new file.
+pub fn set_passphrase(
+    store: &mut Store,
+    path: &Path,
+    passphrase: Option<&str>,
+) -> Result<(), EncryptionError> {
+    match (store.is_encrypted(), passphrase) {
+        (true, Some(passphrase)) => return Ok(store.rekey(passphrase)?),
+        (false, None) => return Ok(()),
+        _ => {}
+    }
+    let previous = store.passphrase().map(str::to_string);
+    let converted = path.with_extension("converting");
+    if let Err(e) = store.export_to(&converted, passphrase) {
+        let _ = fs::remove_file(&converted);
+        return Err(e.into());
+    }
+    // the file can only be replaced once nothing has it open
+    drop(std::mem::replace(store, Store::open_in_memory()?));
+    if let Err(e) = fs::rename(&converted, path) {
+        let _ = fs::remove_file(&converted);
+        *store = open(path, previous.as_deref())?;
+        return Err(e.into());
+    }
+    *store = open(path, passphrase)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structs::Customer;
+    use std::path::PathBuf;
+
+    fn temp_database(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "invoicy-encryption-test-{}-{}.db",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn companies(store: &Store) -> Vec<String> {
+        store
+            .customers()
+            .unwrap()
+            .into_iter()
+            .map(|customer| customer.company)
+            .collect()
+    }
+
+    #[test]
+    fn test_encrypt_rekey_and_decrypt() {
+        let path = temp_database("cycle");
+        let mut store = open(&path, None).unwrap();
+        store
+            .save_customer(&Customer {
+                company: "Acme".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(!db::is_encrypted_file(&path));
+
+        set_passphrase(&mut store, &path, Some("correct horse")).unwrap();
+        assert!(store.is_encrypted());
+        assert!(db::is_encrypted_file(&path));
+        assert!(!fs::read(&path)
+            .unwrap()
+            .windows(4)
+            .any(|window| window == b"Acme"));
+        assert_eq!(companies(&store), vec!["Acme".to_string()]);
+        drop(store);
+        assert!(matches!(open(&path, None), Err(EncryptionError::Locked)));
+        assert!(matches!(
+            open(&path, Some("battery staple")),
+            Err(EncryptionError::WrongPassphrase)
+        ));
+
+        let mut store = open(&path, Some("correct horse")).unwrap();
+        set_passphrase(&mut store, &path, Some("battery staple")).unwrap();
+        drop(store);
+        assert!(matches!(
+            open(&path, Some("correct horse")),
+            Err(EncryptionError::WrongPassphrase)
+        ));
+
+        let mut store = open(&path, Some("battery staple")).unwrap();
+        set_passphrase(&mut store, &path, None).unwrap();
+        assert!(!store.is_encrypted());
+        drop(store);
+        assert!(!db::is_encrypted_file(&path));
+        assert_eq!(
+            companies(&open(&path, None).unwrap()),
+            vec!["Acme".to_string()]
+        );
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_validate_passphrase() {
+        assert!(matches!(
+            validate_passphrase("short", "short"),
+            Err(EncryptionError::TooShort)
+        ));
+        assert!(matches!(
+            validate_passphrase("long enough", "long enuogh"),
+            Err(EncryptionError::Mismatch)
+        ));
+        assert!(validate_passphrase("long enough", "long enough").is_ok());
+    }
+}
@@ -0,0 +1,872 @@
+//! Structured exports for reporting tools. Invoices with their line items, customers, contacts
+//! and payments are written either as a single JSON document or as one CSV file per table.
+//!
+//! The JSON layout is described by `support/schema/export-v1.json`. `SCHEMA_VERSION` only changes
+//! when a field is renamed or removed, new fields can appear within a version. New fields go at
+//! the end of their record, so CSV columns keep their positions.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::NaiveDate;
+use serde::Serialize;
+
+use crate::accounting::{self, AccountingFormat};
+use crate::db::Store;
+use crate::settings::Settings;
+use crate::structs::{Contact, Customer, DatabaseData, Invoice, Payment};
+
+pub const FORMAT_NAME: &str = "invoicy-export";
+pub const SCHEMA_VERSION: u32 = 1;
+
+pub const USAGE: &str = "usage: invoicy export --output <path> \
+[--format json|csv|quickbooks-iif|quickbooks-csv|xero|journal|ledger] \
+[--from YYYY-MM-DD] [--to YYYY-MM-DD] [--customer <company>] [--database <file>]";
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ExportFormat {
+    #[default]
+    Json,
+    // one file per table in a directory
+    Csv,
+    // an import file for a bookkeeping tool
+    Accounting(AccountingFormat),
+}
+
+impl ExportFormat {
+    pub fn all() -> Vec<ExportFormat> {
+        let mut formats = vec![ExportFormat::Json, ExportFormat::Csv];
+        formats.extend(AccountingFormat::ALL.map(ExportFormat::Accounting));
+        formats
+    }
+
+    pub fn key(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Csv => "csv",
+            ExportFormat::Accounting(format) => format.key(),
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "JSON",
+            ExportFormat::Csv => "CSV (folder)",
+            ExportFormat::Accounting(format) => format.label(),
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Self> {
+        match key.to_ascii_lowercase().as_str() {
+            "json" => Some(ExportFormat::Json),
+            "csv" => Some(ExportFormat::Csv),
+            key => AccountingFormat::from_key(key).map(ExportFormat::Accounting),
+        }
+    }
+}
+
+// which invoices and payments to export, dates are inclusive and `customer` is a company name
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ExportFilter {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub customer: Option<String>,
+}
+
+impl ExportFilter {
+    fn includes_date(&self, date: &str) -> bool {
+        if self.from.is_none() && self.to.is_none() {
+            return true;
+        }
+        // records without a readable date can't be placed in a range
+        match NaiveDate::parse_from_str(date, DATE_FORMAT) {
+            Ok(date) => {
+                self.from.is_none_or(|from| date >= from) && self.to.is_none_or(|to| date <= to)
+            }
+            Err(_) => false,
+        }
+    }
+
+    fn includes_customer(&self, company: &str) -> bool {
+        self.customer
+            .as_deref()
+            .is_none_or(|customer| customer == company)
+    }
+}
+
+#[derive(Debug)]
+pub enum ExportError {
+    Database(rusqlite::Error),
+    File(std::io::Error),
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportError::Database(e) => write!(f, "Unable to load the data to export: {}", e),
+            ExportError::File(e) => write!(f, "Unable to write the export: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ExportError {}
+
+impl From<rusqlite::Error> for ExportError {
+    fn from(e: rusqlite::Error) -> Self {
+        ExportError::Database(e)
+    }
+}
+
+impl From<std::io::Error> for ExportError {
+    fn from(e: std::io::Error) -> Self {
+        ExportError::File(e)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExportedInvoice {
+    pub invoice: Invoice,
+    pub items: Vec<DatabaseData>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ExportData {
+    pub invoices: Vec<ExportedInvoice>,
+    pub customers: Vec<Customer>,
+    pub contacts: Vec<Contact>,
+    pub payments: Vec<Payment>,
+}
+
+// Loads everything matching `filter`. Customers are limited to the chosen one, contacts are
+// always exported in full and payments are matched on their own date and their invoice's customer.
+pub fn collect(store: &Store, filter: &ExportFilter) -> rusqlite::Result<ExportData> {
+    let all_invoices = store.invoices()?;
+    let customer_of: HashMap<&str, &str> = all_invoices
+        .iter()
+        .map(|invoice| (invoice.invoice_id.as_str(), invoice.customer.as_str()))
+        .collect();
+    let payments = store
+        .payments()?
+        .into_iter()
+        .filter(|payment| {
+            filter.includes_date(&payment.paid_on)
+                && customer_of
+                    .get(payment.invoice_id.as_str())
+                    .is_some_and(|company| filter.includes_customer(company))
+        })
+        .collect();
+    let mut invoices = Vec::new();
+    for invoice in all_invoices.iter().filter(|invoice| {
+        filter.includes_date(&invoice.issue_date) && filter.includes_customer(&invoice.customer)
+    }) {
+        let items = store.invoice_line_items(&invoice.cust_id, invoice.estimate_number)?;
+        invoices.push(ExportedInvoice {
+            invoice: invoice.clone(),
+            items,
+        });
+    }
+    let customers = store
+        .customers()?
+        .into_iter()
+        .filter(|customer| filter.includes_customer(&customer.company))
+        .collect();
+    Ok(ExportData {
+        invoices,
+        customers,
+        contacts: store.contacts()?,
+        payments,
+    })
+}
+
+// Writes the export to `path`, a directory for CSV and a file for everything else. Returns the
+// files written. The accounting formats use the account mapping and currency from `settings`.
+pub fn write_export(
+    store: &Store,
+    filter: &ExportFilter,
+    format: ExportFormat,
+    settings: &Settings,
+    path: &Path,
+) -> Result<Vec<PathBuf>, ExportError> {
+    let data = collect(store, filter)?;
+    match format {
+        ExportFormat::Json => {
+            let exported_at = chrono::Local::now().to_rfc3339();
+            fs::write(path, to_json(&data, filter, &exported_at))?;
+            Ok(vec![path.to_path_buf()])
+        }
+        ExportFormat::Csv => {
+            fs::create_dir_all(path)?;
+            let mut written = Vec::new();
+            for (file_name, contents) in csv_tables(&data) {
+                let file = path.join(file_name);
+                fs::write(&file, contents)?;
+                written.push(file);
+            }
+            Ok(written)
+        }
+        ExportFormat::Accounting(format) => {
+            let contents =
+                accounting::render(format, &data, &settings.accounts, &settings.currency);
+            fs::write(path, contents)?;
+            Ok(vec![path.to_path_buf()])
+        }
+    }
+}
+
+// JSON
+
+#[derive(Serialize)]
+struct Document<'a> {
+    format: &'static str,
+    version: u32,
+    exported_at: &'a str,
+    filter: FilterRecord<'a>,
+    invoices: Vec<InvoiceRecord<'a>>,
+    customers: Vec<CustomerRecord<'a>>,
+    contacts: Vec<ContactRecord<'a>>,
+    payments: Vec<PaymentRecord<'a>>,
+}
+
+#[derive(Serialize)]
+struct FilterRecord<'a> {
+    from: Option<String>,
+    to: Option<String>,
+    customer: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct InvoiceRecord<'a> {
+    invoice_id: &'a str,
+    customer_id: &'a str,
+    number: usize,
+    customer: &'a str,
+    contact: &'a str,
+    issue_date: &'a str,
+    grand_total: f64,
+    po_number: &'a str,
+    reference: &'a str,
+    notes: &'a str,
+    terms: &'a str,
+    file_path: Option<&'a str>,
+    file_hash: Option<&'a str>,
+    status: &'static str,
+    items: Vec<LineItemRecord<'a>>,
+}
+
+#[derive(Serialize)]
+struct LineItemRecord<'a> {
+    row: usize,
+    kind: &'static str,
+    sku: Option<&'a str>,
+    description: &'a str,
+    quantity: f64,
+    unit: &'a str,
+    price: f64,
+    total: f64,
+    tax_code: &'a str,
+}
+
+#[derive(Serialize)]
+struct CustomerRecord<'a> {
+    company: &'a str,
+    address: &'a str,
+    city: &'a str,
+    postal_code: &'a str,
+    country: &'a str,
+    vat_id: &'a str,
+    endpoint_id: &'a str,
+    notes: &'a str,
+    terms: &'a str,
+    email: &'a str,
+}
+
+#[derive(Serialize)]
+struct ContactRecord<'a> {
+    company: &'a str,
+    name: &'a str,
+    address: &'a str,
+    city: &'a str,
+    postal_code: &'a str,
+    country: &'a str,
+    telephone: &'a str,
+    email: &'a str,
+    website: &'a str,
+    vat_id: &'a str,
+    endpoint_id: &'a str,
+}
+
+#[derive(Serialize)]
+struct PaymentRecord<'a> {
+    payment_id: i64,
+    invoice_id: &'a str,
+    paid_on: &'a str,
+    amount: f64,
+    method: &'a str,
+    reference: &'a str,
+}
+
+fn invoice_record(exported: &ExportedInvoice) -> InvoiceRecord<'_> {
+    let invoice = &exported.invoice;
+    InvoiceRecord {
+        invoice_id: &invoice.invoice_id,
+        customer_id: &invoice.cust_id,
+        number: invoice.estimate_number,
+        customer: &invoice.customer,
+        contact: &invoice.contact,
+        issue_date: &invoice.issue_date,
+        grand_total: invoice.grand_total,
+        po_number: &invoice.text.po_number,
+        reference: &invoice.text.reference,
+        notes: &invoice.text.notes,
+        terms: &invoice.text.terms,
+        file_path: invoice.file_path.as_deref(),
+        file_hash: invoice.file_hash.as_deref(),
+        status: invoice.status.key(),
+        items: exported.items.iter().map(line_item_record).collect(),
+    }
+}
+
+fn line_item_record(item: &DatabaseData) -> LineItemRecord<'_> {
+    LineItemRecord {
+        row: item.row_number,
+        kind: item.kind.key(),
+        sku: item.sku.as_deref(),
+        description: &item.description,
+        quantity: item.quantity,
+        unit: &item.unit,
+        price: item.price,
+        total: item.total,
+        tax_code: &item.tax_code,
+    }
+}
+
+fn customer_record(customer: &Customer) -> CustomerRecord<'_> {
+    CustomerRecord {
+        company: &customer.company,
+        address: &customer.address,
+        city: &customer.city,
+        postal_code: &customer.postal_code,
+        country: &customer.country,
+        vat_id: &customer.vat_id,
+        endpoint_id: &customer.endpoint_id,
+        notes: &customer.notes,
+        terms: &customer.terms,
+        email: &customer.email,
+    }
+}
+
+fn contact_record(contact: &Contact) -> ContactRecord<'_> {
+    ContactRecord {
+        company: &contact.company,
+        name: &contact.name,
+        address: &contact.address,
+        city: &contact.city,
+        postal_code: &contact.postal_code,
+        country: &contact.country,
+        telephone: &contact.telephone,
+        email: &contact.email,
+        website: &contact.website,
+        vat_id: &contact.vat_id,
+        endpoint_id: &contact.endpoint_id,
+    }
+}
+
+fn payment_record(payment: &Payment) -> PaymentRecord<'_> {
+    PaymentRecord {
+        payment_id: payment.payment_id,
+        invoice_id: &payment.invoice_id,
+        paid_on: &payment.paid_on,
+        amount: payment.amount,
+        method: &payment.method,
+        reference: &payment.reference,
+    }
+}
+
+pub fn to_json(data: &ExportData, filter: &ExportFilter, exported_at: &str) -> String {
+    let date = |date: Option<NaiveDate>| date.map(|date| date.format(DATE_FORMAT).to_string());
+    let document = Document {
+        format: FORMAT_NAME,
+        version: SCHEMA_VERSION,
+        exported_at,
+        filter: FilterRecord {
+            from: date(filter.from),
+            to: date(filter.to),
+            customer: filter.customer.as_deref(),
+        },
+        invoices: data.invoices.iter().map(invoice_record).collect(),
+        customers: data.customers.iter().map(customer_record).collect(),
+        contacts: data.contacts.iter().map(contact_record).collect(),
+        payments: data.payments.iter().map(payment_record).collect(),
+    };
+    // the records only hold strings and numbers, which always serialize
+    let mut out = serde_json::to_string_pretty(&document).unwrap_or_default();
+    out.push('\n');
+    out
+}
+
+// CSV
+
+// RFC 4180: fields with separators, quotes or line breaks are quoted and quotes doubled
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub(crate) fn csv_table(header: &[&str], rows: Vec<Vec<String>>) -> String {
+    let mut out = header.join(",");
+    out.push_str("\r\n");
+    for row in rows {
+        let fields: Vec<String> = row.iter().map(|field| csv_field(field)).collect();
+        out.push_str(&fields.join(","));
+        out.push_str("\r\n");
+    }
+    out
+}
+
+// file name and contents of each table, columns carry the same names as the JSON fields
+pub fn csv_tables(data: &ExportData) -> Vec<(&'static str, String)> {
+    let invoices = csv_table(
+        &[
+            "invoice_id",
+            "customer_id",
+            "number",
+            "customer",
+            "contact",
+            "issue_date",
+            "grand_total",
+            "po_number",
+            "reference",
+            "notes",
+            "terms",
+            "file_path",
+            "file_hash",
+            "status",
+        ],
+        data.invoices
+            .iter()
+            .map(|exported| {
+                let invoice = &exported.invoice;
+                vec![
+                    invoice.invoice_id.clone(),
+                    invoice.cust_id.clone(),
+                    invoice.estimate_number.to_string(),
+                    invoice.customer.clone(),
+                    invoice.contact.clone(),
+                    invoice.issue_date.clone(),
+                    invoice.grand_total.to_string(),
+                    invoice.text.po_number.clone(),
+                    invoice.text.reference.clone(),
+                    invoice.text.notes.clone(),
+                    invoice.text.terms.clone(),
+                    invoice.file_path.clone().unwrap_or_default(),
+                    invoice.file_hash.clone().unwrap_or_default(),
+                    invoice.status.key().to_string(),
+                ]
+            })
+            .collect(),
+    );
+    let line_items = csv_table(
+        &[
+            "invoice_id",
+            "row",
+            "kind",
+            "sku",
+            "description",
+            "quantity",
+            "unit",
+            "price",
+            "total",
+            "tax_code",
+        ],
+        data.invoices
+            .iter()
+            .flat_map(|exported| {
+                exported.items.iter().map(move |item| {
+                    vec![
+                        exported.invoice.invoice_id.clone(),
+                        item.row_number.to_string(),
+                        item.kind.key().to_string(),
+                        item.sku.clone().unwrap_or_default(),
+                        item.description.clone(),
+                        item.quantity.to_string(),
+                        item.unit.clone(),
+                        item.price.to_string(),
+                        item.total.to_string(),
+                        item.tax_code.clone(),
+                    ]
+                })
+            })
+            .collect(),
+    );
+    let customers = csv_table(
+        &[
+            "company",
+            "address",
+            "city",
+            "postal_code",
+            "country",
+            "vat_id",
+            "endpoint_id",
+            "notes",
+            "terms",
+            "email",
+        ],
+        data.customers
+            .iter()
+            .map(|customer| {
+                vec![
+                    customer.company.clone(),
+                    customer.address.clone(),
+                    customer.city.clone(),
+                    customer.postal_code.clone(),
+                    customer.country.clone(),
+                    customer.vat_id.clone(),
+                    customer.endpoint_id.clone(),
+                    customer.notes.clone(),
+                    customer.terms.clone(),
+                    customer.email.clone(),
+                ]
+            })
+            .collect(),
+    );
+    let contacts = csv_table(
+        &[
+            "company",
+            "name",
+            "address",
+            "city",
+            "postal_code",
+            "country",
+            "telephone",
+            "email",
+            "website",
+            "vat_id",
+            "endpoint_id",
+        ],
+        data.contacts
+            .iter()
+            .map(|contact| {
+                vec![
+                    contact.company.clone(),
+                    contact.name.clone(),
+                    contact.address.clone(),
+                    contact.city.clone(),
+                    contact.postal_code.clone(),
+                    contact.country.clone(),
+                    contact.telephone.clone(),
+                    contact.email.clone(),
+                    contact.website.clone(),
+                    contact.vat_id.clone(),
+                    contact.endpoint_id.clone(),
+                ]
+            })
+            .collect(),
+    );
+    let payments = csv_table(
+        &[
+            "payment_id",
+            "invoice_id",
+            "paid_on",
+            "amount",
+            "method",
+            "reference",
+        ],
+        data.payments
+            .iter()
+            .map(|payment| {
+                vec![
+                    payment.payment_id.to_string(),
+                    payment.invoice_id.clone(),
+                    payment.paid_on.clone(),
+                    payment.amount.to_string(),
+                    payment.method.clone(),
+                    payment.reference.clone(),
+                ]
+            })
+            .collect(),
+    );
+    vec![
+        ("invoices.csv", invoices),
+        ("line_items.csv", line_items),
+        ("customers.csv", customers),
+        ("contacts.csv", contacts),
+        ("payments.csv", payments),
+    ]
+}
+
+// command line
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExportRequest {
+    pub format: ExportFormat,
+    pub filter: ExportFilter,
+    pub output: PathBuf,
+}
+
+// Reads `invoicy export ...`. Returns None when the first argument isn't `export`, so the app
+// starts normally. Flags accept both `--flag value` and `--flag=value`.
+pub fn parse_args(args: &[String]) -> Result<Option<ExportRequest>, String> {
+    if args.get(1).map(String::as_str) != Some("export") {
+        return Ok(None);
+    }
+    let mut format = None;
+    let mut filter = ExportFilter::default();
+    let mut output = None;
+    let mut iter = args[2..].iter();
+    while let Some(arg) = iter.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+        let mut value = || {
+            inline
+                .clone()
+                .or_else(|| iter.next().cloned())
+                .ok_or_else(|| format!("{} needs a value", flag))
+        };
+        let date = |value: String| {
+            NaiveDate::parse_from_str(&value, DATE_FORMAT)
+                .map_err(|_| format!("{} is not a date, use YYYY-MM-DD", value))
+        };
+        match flag {
+            "--format" => {
+                let key = value()?;
+                format = Some(
+                    ExportFormat::from_key(&key)
+                        .ok_or_else(|| format!("Unknown export format {}", key))?,
+                );
+            }
+            "--from" => filter.from = Some(date(value()?)?),
+            "--to" => filter.to = Some(date(value()?)?),
+            "--customer" => filter.customer = Some(value()?),
+            "--output" | "-o" => output = Some(PathBuf::from(value()?)),
+            // read by paths::database_path
+            "--database" | "--db" => {
+                value()?;
+            }
+            _ => return Err(format!("Unknown export option {}", arg)),
+        }
+    }
+    if let (Some(from), Some(to)) = (filter.from, filter.to) {
+        if from > to {
+            return Err(format!("--from {} is after --to {}", from, to));
+        }
+    }
+    let output = output.ok_or_else(|| "--output is required".to_string())?;
+    // a path without an extension is taken as a directory for CSV files
+    let format = format.unwrap_or(if output.extension().is_some() {
+        ExportFormat::Json
+    } else {
+        ExportFormat::Csv
+    });
+    Ok(Some(ExportRequest {
+        format,
+        filter,
+        output,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::NewInvoice;
+    use crate::structs::InvoiceText;
+    use crate::test_support;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn issue(store: &mut Store, company: &str, cust_id: &str, description: &str) -> String {
+        let customer = Customer {
+            company: company.to_string(),
+            ..Default::default()
+        };
+        store.save_customer(&customer).unwrap();
+        let invoice = NewInvoice {
+            cust_id: cust_id.to_string(),
+            customer,
+            contact_company: "Fake Co.".to_string(),
+            items: vec![DatabaseData {
+                row_number: 1,
+                description: description.to_string(),
+                quantity: 2.0,
+                price: 10.5,
+                total: 21.0,
+                ..Default::default()
+            }],
+            text: InvoiceText {
+                notes: "Thanks, \"valued\" customer\nsee you".to_string(),
+                ..Default::default()
+            },
+        };
+        let (issued, path) =
+            test_support::issue(store, &invoice, &format!("export-{}", cust_id), b"pdf");
+        fs::remove_file(&path).ok();
+        issued.invoice_id
+    }
+
+    #[test]
+    fn test_export_filters_and_formats() {
+        let mut store = Store::open_in_memory().unwrap();
+        store
+            .save_contact(&Contact {
+                company: "Fake Co.".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+        let acme = issue(&mut store, "Acme", "ACME", "Design, build");
+        let other = issue(&mut store, "Other", "OTHER", "Hosting");
+        for (invoice_id, paid_on) in [(&acme, "2024-03-01"), (&other, "2024-03-02")] {
+            store
+                .save_payment(&Payment {
+                    invoice_id: invoice_id.clone(),
+                    paid_on: paid_on.to_string(),
+                    amount: 21.0,
+                    method: "transfer".to_string(),
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+
+        let filter = ExportFilter {
+            customer: Some("Acme".to_string()),
+            ..Default::default()
+        };
+        let data = collect(&store, &filter).unwrap();
+        assert_eq!(data.invoices.len(), 1);
+        assert_eq!(data.invoices[0].invoice.invoice_id, acme);
+        assert_eq!(data.invoices[0].items[0].description, "Design, build");
+        assert_eq!(data.customers.len(), 1);
+        assert_eq!(data.contacts.len(), 1);
+        assert_eq!(data.payments.len(), 1);
+
+        // invoices are dated today, payments in 2024
+        let filter = ExportFilter {
+            from: NaiveDate::from_ymd_opt(2024, 3, 2),
+            to: NaiveDate::from_ymd_opt(2024, 12, 31),
+            customer: None,
+        };
+        let data = collect(&store, &filter).unwrap();
+        assert!(data.invoices.is_empty());
+        assert_eq!(data.payments.len(), 1);
+        assert_eq!(data.payments[0].invoice_id, other);
+
+        let data = collect(&store, &ExportFilter::default()).unwrap();
+        let json = to_json(&data, &filter, "2024-03-05T10:00:00+00:00");
+        assert!(json.starts_with("{\n  \"format\": \"invoicy-export\",\n  \"version\": 1,"));
+        assert!(json.contains("\"from\": \"2024-03-02\""));
+        assert!(json.contains("\"customer\": null"));
+        assert!(json.contains("\"notes\": \"Thanks, \\\"valued\\\" customer\\nsee you\""));
+        assert!(json.contains("\"price\": 10.5"));
+        assert!(json.contains("\"sku\": null"));
+
+        let tables = csv_tables(&data);
+        let names: Vec<&str> = tables.iter().map(|(name, _)| *name).collect();
+        assert_eq!(
+            names,
+            [
+                "invoices.csv",
+                "line_items.csv",
+                "customers.csv",
+                "contacts.csv",
+                "payments.csv"
+            ]
+        );
+        let line_items = &tables[1].1;
+        assert!(line_items.starts_with("invoice_id,row,kind,sku,description,"));
+        assert!(line_items.contains(&format!("{},1,item,,\"Design, build\",2,", acme)));
+        assert!(tables[0]
+            .1
+            .contains(",\"Thanks, \"\"valued\"\" customer\nsee you\","));
+        assert_eq!(tables[4].1.lines().count(), 3);
+        // fields added within version 1 come after the original columns
+        assert!(tables[0].1.starts_with(
+            "invoice_id,customer_id,number,customer,contact,issue_date,grand_total,po_number,\
+             reference,notes,terms,file_path,file_hash,status\r\n"
+        ));
+        assert!(tables[2].1.starts_with(
+            "company,address,city,postal_code,country,vat_id,endpoint_id,notes,terms,email\r\n"
+        ));
+    }
+
+    #[test]
+    fn test_json_values() {
+        let data = ExportData {
+            invoices: vec![],
+            customers: vec![Customer {
+                company: "tab\there \u{1}".to_string(),
+                ..Default::default()
+            }],
+            contacts: vec![],
+            payments: vec![Payment {
+                amount: f64::NAN,
+                ..Default::default()
+            }],
+        };
+        let json = to_json(&data, &ExportFilter::default(), "");
+        assert!(json.contains("\"invoices\": [],"));
+        assert!(json.contains("\"company\": \"tab\\there \\u0001\""));
+        // JSON has no NaN or infinity
+        assert!(json.contains("\"amount\": null"));
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(parse_args(&args(&["invoicy"])), Ok(None));
+        assert_eq!(parse_args(&args(&["invoicy", "--db", "x.db"])), Ok(None));
+        let request = parse_args(&args(&[
+            "invoicy",
+            "export",
+            "--db",
+            "x.db",
+            "--from=2024-01-01",
+            "--to",
+            "2024-12-31",
+            "--customer",
+            "Acme Ltd",
+            "--output",
+            "out.json",
+        ]))
+        .unwrap()
+        .unwrap();
+        assert_eq!(request.format, ExportFormat::Json);
+        assert_eq!(request.filter.from, NaiveDate::from_ymd_opt(2024, 1, 1));
+        assert_eq!(request.filter.customer.as_deref(), Some("Acme Ltd"));
+        assert_eq!(request.output, PathBuf::from("out.json"));
+
+        let request = parse_args(&args(&["invoicy", "export", "-o", "exports"]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(request.format, ExportFormat::Csv);
+        let request = parse_args(&args(&["invoicy", "export", "--format=ledger", "-o", "a"]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            request.format,
+            ExportFormat::Accounting(AccountingFormat::Ledger)
+        );
+
+        assert!(parse_args(&args(&["invoicy", "export"])).is_err());
+        assert!(parse_args(&args(&["invoicy", "export", "-o", "a", "--from", "May"])).is_err());
+        assert!(parse_args(&args(&["invoicy", "export", "-o", "a", "--format", "xls"])).is_err());
+        assert!(parse_args(&args(&[
+            "invoicy",
+            "export",
+            "-o",
+            "a",
+            "--from",
+            "2024-02-01",
+            "--to",
+            "2024-01-01"
+        ]))
+        .is_err());
+    }
+}
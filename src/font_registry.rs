@@ -0,0 +1,326 @@
+//! Registry of the font families available to the document generator.
+//!
+//! Calibri and JetBrains Mono are compiled into the binary. Any TrueType family found in the usual
+//! system font directories is listed as well. If a family fails to load the document falls back
+//! to the built-in PDF Helvetica instead of aborting.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use genpdf::error::Error;
+use genpdf::fonts::{FontData, FontFamily};
+use printpdf::BuiltinFont;
+
+pub const CALIBRI: &str = "Calibri";
+pub const JETBRAINS_MONO: &str = "JetBrains Mono";
+pub const HELVETICA: &str = "Helvetica (built-in)";
+// an embedded family, so a new invoice lays out with the font it is rendered in and meets PDF/A
+pub const DEFAULT_FONT: &str = CALIBRI;
+
+struct EmbeddedFamily {
+    name: &'static str,
+    regular: &'static [u8],
+    bold: &'static [u8],
+    italic: &'static [u8],
+    bold_italic: &'static [u8],
+}
+
+const EMBEDDED_FAMILIES: [EmbeddedFamily; 2] = [
+    EmbeddedFamily {
+        name: CALIBRI,
+        regular: include_bytes!("../fonts/Calibri/CALIBRI-REGULAR.TTF"),
+        bold: include_bytes!("../fonts/Calibri/CALIBRI-BOLD.TTF"),
+        italic: include_bytes!("../fonts/Calibri/CALIBRI-ITALIC.TTF"),
+        bold_italic: include_bytes!("../fonts/Calibri/CALIBRI-BOLDITALIC.TTF"),
+    },
+    EmbeddedFamily {
+        name: JETBRAINS_MONO,
+        regular: include_bytes!("../fonts/JetbrainsMono/JETBRAINSMONO-Regular.TTF"),
+        bold: include_bytes!("../fonts/JetbrainsMono/JETBRAINSMONO-Bold.TTF"),
+        italic: include_bytes!("../fonts/JetbrainsMono/JETBRAINSMONO-ITALIC.TTF"),
+        bold_italic: include_bytes!("../fonts/JetbrainsMono/JETBRAINSMONO-BOLDITALIC.TTF"),
+    },
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FontSource {
+    Embedded,
+    System(SystemFamily),
+    Builtin,
+}
+
+// styles that are missing on disk fall back to the regular file
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SystemFamily {
+    pub regular: PathBuf,
+    pub bold: Option<PathBuf>,
+    pub italic: Option<PathBuf>,
+    pub bold_italic: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FontEntry {
+    pub name: String,
+    pub source: FontSource,
+}
+
+#[derive(Clone, Debug)]
+pub struct FontRegistry {
+    pub entries: Vec<FontEntry>,
+}
+
+impl FontRegistry {
+    // embedded families and the built-in fallback only, no disk access
+    pub fn embedded() -> Self {
+        let mut entries: Vec<FontEntry> = EMBEDDED_FAMILIES
+            .iter()
+            .map(|family| FontEntry {
+                name: family.name.to_string(),
+                source: FontSource::Embedded,
+            })
+            .collect();
+        entries.push(FontEntry {
+            name: HELVETICA.to_string(),
+            source: FontSource::Builtin,
+        });
+        Self { entries }
+    }
+
+    pub fn discover() -> Self {
+        let mut registry = Self::embedded();
+        for (name, family) in scan_font_dirs(&system_font_dirs()) {
+            if registry.entries.iter().any(|entry| entry.name == name) {
+                continue;
+            }
+            registry.entries.push(FontEntry {
+                name,
+                source: FontSource::System(family),
+            });
+        }
+        registry
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.entries
+            .iter()
+            .map(|entry| entry.name.clone())
+            .collect()
+    }
+
+    pub fn find(&self, name: &str) -> Option<&FontEntry> {
+        self.entries.iter().find(|entry| entry.name == name)
+    }
+
+    // never fails: unknown or unreadable families are replaced by the built-in Helvetica
+    pub fn load(&self, name: &str) -> FontFamily<FontData> {
+        let result = match self.find(name) {
+            Some(entry) => load_entry(entry),
+            None => Err(Error::new(
+                format!("Unknown font family {}", name),
+                genpdf::error::ErrorKind::InvalidFont,
+            )),
+        };
+        match result {
+            Ok(family) => family,
+            Err(e) => {
+                println!("ERROR: Font {} unavailable, using Helvetica: {}", name, e);
+                builtin_helvetica()
+            }
+        }
+    }
+
+    // PDF/A needs every font embedded, so the built-in Helvetica and families that fail to load
+    // are replaced by the bundled Calibri rather than by Helvetica
+    pub fn load_embedded(&self, name: &str) -> FontFamily<FontData> {
+        let entry = self
+            .find(name)
+            .filter(|entry| entry.source != FontSource::Builtin);
+        if let Some(Ok(family)) = entry.map(load_entry) {
+            return family;
+        }
+        println!("LOG: Font {} cannot be embedded, using {}", name, CALIBRI);
+        load_entry(&FontEntry {
+            name: CALIBRI.to_string(),
+            source: FontSource::Embedded,
+        })
+        .expect("bundled font data is invalid")
+    }
+}
+
+fn load_entry(entry: &FontEntry) -> Result<FontFamily<FontData>, Error> {
+    match &entry.source {
+        FontSource::Embedded => {
+            let family = EMBEDDED_FAMILIES
+                .iter()
+                .find(|family| family.name == entry.name)
+                .expect("embedded font entry without data");
+            Ok(FontFamily {
+                regular: FontData::new(family.regular.to_vec(), None)?,
+                bold: FontData::new(family.bold.to_vec(), None)?,
+                italic: FontData::new(family.italic.to_vec(), None)?,
+                bold_italic: FontData::new(family.bold_italic.to_vec(), None)?,
+            })
+        }
+        FontSource::System(family) => {
+            let regular = &family.regular;
+            let load =
+                |path: &Option<PathBuf>| FontData::load(path.as_ref().unwrap_or(regular), None);
+            Ok(FontFamily {
+                regular: FontData::load(regular, None)?,
+                bold: load(&family.bold)?,
+                italic: load(&family.italic)?,
+                bold_italic: load(&family.bold_italic)?,
+            })
+        }
+        FontSource::Builtin => Ok(builtin_helvetica()),
+    }
+}
+
+// genpdf still needs glyph metrics for built-in fonts and no file with Helvetica's widths is
+// bundled. JetBrains Mono is at least as wide, so text never overflows its cell, but it is laid
+// out with monospace spacing. Only meant as a fallback, the default is an embedded family.
+pub fn builtin_helvetica() -> FontFamily<FontData> {
+    let family = &EMBEDDED_FAMILIES[1];
+    let data = |bytes: &[u8], builtin: BuiltinFont| {
+        FontData::new(bytes.to_vec(), Some(builtin)).expect("bundled font data is invalid")
+    };
+    FontFamily {
+        regular: data(family.regular, BuiltinFont::Helvetica),
+        bold: data(family.bold, BuiltinFont::HelveticaBold),
+        italic: data(family.italic, BuiltinFont::HelveticaOblique),
+        bold_italic: data(family.bold_italic, BuiltinFont::HelveticaBoldOblique),
+    }
+}
+
+fn system_font_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![
+        PathBuf::from("/usr/share/fonts"),
+        PathBuf::from("/usr/local/share/fonts"),
+        PathBuf::from("/Library/Fonts"),
+        PathBuf::from("/System/Library/Fonts"),
+        PathBuf::from(r"C:\Windows\Fonts"),
+    ];
+    if let Some(home) = dirs::home_dir() {
+        dirs.push(home.join(".fonts"));
+        dirs.push(home.join(".local/share/fonts"));
+        dirs.push(home.join("Library/Fonts"));
+    }
+    if let Some(local) = dirs::data_local_dir() {
+        dirs.push(local.join(r"Microsoft\Windows\Fonts"));
+    }
+    dirs
+}
+
+pub fn scan_font_dirs(dirs: &[PathBuf]) -> BTreeMap<String, SystemFamily> {
+    let mut files = Vec::new();
+    for dir in dirs {
+        collect_ttf_files(dir, &mut files, 0);
+    }
+    let mut styles: BTreeMap<String, BTreeMap<&'static str, PathBuf>> = BTreeMap::new();
+    for file in files {
+        let Some(stem) = file.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        let (family, style) = split_font_stem(stem);
+        styles
+            .entry(family)
+            .or_default()
+            .entry(style)
+            .or_insert(file);
+    }
+    styles
+        .into_iter()
+        .filter_map(|(name, mut styles)| {
+            Some((
+                name,
+                SystemFamily {
+                    regular: styles.remove("Regular")?,
+                    bold: styles.remove("Bold"),
+                    italic: styles.remove("Italic"),
+                    bold_italic: styles.remove("BoldItalic"),
+                },
+            ))
+        })
+        .collect()
+}
+
+fn collect_ttf_files(dir: &Path, files: &mut Vec<PathBuf>, depth: usize) {
+    // font directories are shallow, the limit guards against symlink loops
+    if depth > 4 {
+        return;
+    }
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_ttf_files(&path, files, depth + 1);
+        } else if path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.eq_ignore_ascii_case("ttf"))
+            .unwrap_or(false)
+        {
+            files.push(path);
+        }
+    }
+}
+
+// "DejaVuSans-BoldOblique" -> ("DejaVuSans", "BoldItalic"), "Arial" -> ("Arial", "Regular")
+pub fn split_font_stem(stem: &str) -> (String, &'static str) {
+    let (family, suffix) = match stem.rsplit_once(['-', '_', ' ']) {
+        Some((family, suffix)) if !family.is_empty() => (family, suffix),
+        _ => return (stem.to_string(), "Regular"),
+    };
+    let style = match suffix.to_lowercase().as_str() {
+        "regular" | "book" | "roman" | "normal" => "Regular",
+        "bold" => "Bold",
+        "italic" | "oblique" => "Italic",
+        "bolditalic" | "boldoblique" => "BoldItalic",
+        // anything else (Light, Condensed, ...) is its own family
+        _ => return (stem.to_string(), "Regular"),
+    };
+    (family.to_string(), style)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_font_stem() {
+        assert_eq!(
+            split_font_stem("DejaVuSans-Bold"),
+            ("DejaVuSans".to_string(), "Bold")
+        );
+        assert_eq!(
+            split_font_stem("DejaVuSans-BoldOblique"),
+            ("DejaVuSans".to_string(), "BoldItalic")
+        );
+        assert_eq!(split_font_stem("Arial"), ("Arial".to_string(), "Regular"));
+        assert_eq!(
+            split_font_stem("Roboto-Light"),
+            ("Roboto-Light".to_string(), "Regular")
+        );
+    }
+
+    #[test]
+    fn test_unknown_font_falls_back() {
+        let registry = FontRegistry::embedded();
+        // loading must not panic even though the family does not exist
+        registry.load("No Such Font");
+        assert!(registry.find(CALIBRI).is_some());
+        assert!(load_entry(registry.find(JETBRAINS_MONO).unwrap()).is_ok());
+    }
+
+    #[test]
+    fn test_default_font_is_embedded() {
+        let registry = FontRegistry::embedded();
+        assert_eq!(
+            registry.find(DEFAULT_FONT).unwrap().source,
+            FontSource::Embedded
+        );
+    }
+}
@@ -1,6 +1,5 @@
 use eframe::egui;
 
-use super::structs::Total;
 pub fn validate_text_input(input: &str) -> Option<String> {
     if input.is_empty() {
         Some("Input cannot be empty".to_string())
@@ -8,28 +7,17 @@ pub fn validate_text_input(input: &str) -> Option<String> {
         None
     }
 }
-pub fn contains_field(vec: &Vec<Total>, position: &(usize, i32)) -> bool {
-    vec.iter().any(|s| s.position == *position)
-}
 pub fn sanitize_string(input: &str) -> String {
     input
         .to_lowercase()
         .chars()
-        .map(|c| {
-            if c.is_alphanumeric() {
-                c
-            } else if c.is_whitespace() {
-                '_'
-            } else {
-                '_'
-            }
-        })
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
         .collect()
 }
-pub fn load_icon(path: &str) -> egui::IconData {
+pub fn load_icon(bytes: &[u8]) -> egui::IconData {
     let (icon_rgba, icon_width, icon_height) = {
-        let image = image::open(path)
-            .expect("Failed to open icon path")
+        let image = image::load_from_memory(bytes)
+            .expect("Failed to decode icon")
             .into_rgba8();
         let (width, height) = image.dimensions();
         (image.into_raw(), width, height)
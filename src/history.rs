@@ -0,0 +1,264 @@
+//! Undo/redo for the invoice editor. Every change to the item table or the customer/contact
+//! selection is recorded as a command that knows how to apply and revert itself.
+
+use crate::line_items::{LineItem, LineItems};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EditCommand {
+    // a cell edit, `before` and `after` are the whole row
+    EditRow {
+        index: usize,
+        before: Box<LineItem>,
+        after: Box<LineItem>,
+    },
+    InsertRow {
+        index: usize,
+        item: LineItem,
+    },
+    RemoveRow {
+        index: usize,
+        item: LineItem,
+    },
+    MoveRow {
+        from: usize,
+        to: usize,
+    },
+    SelectCustomer {
+        before: usize,
+        after: usize,
+    },
+    SelectContact {
+        before: usize,
+        after: usize,
+    },
+    // several commands undone and redone as one step, e.g. a pasted block
+    Batch(Vec<EditCommand>),
+}
+
+// the parts of the editor a command can touch
+pub struct EditorState<'a> {
+    pub line_items: &'a mut LineItems,
+    pub customer_selected: &'a mut usize,
+    pub contact_selected: &'a mut usize,
+}
+
+impl EditCommand {
+    pub fn apply(&self, state: &mut EditorState) {
+        match self {
+            EditCommand::EditRow { index, after, .. } => {
+                state.line_items.replace(*index, (**after).clone());
+            }
+            EditCommand::InsertRow { index, item } => {
+                state.line_items.insert_item(*index, item.clone());
+            }
+            EditCommand::RemoveRow { index, .. } => {
+                state.line_items.remove(*index);
+            }
+            EditCommand::MoveRow { from, to } => state.line_items.move_item(*from, *to),
+            EditCommand::SelectCustomer { after, .. } => *state.customer_selected = *after,
+            EditCommand::SelectContact { after, .. } => *state.contact_selected = *after,
+            EditCommand::Batch(commands) => {
+                for command in commands {
+                    command.apply(state);
+                }
+            }
+        }
+    }
+
+    pub fn inverse(&self) -> EditCommand {
+        match self.clone() {
+            EditCommand::EditRow {
+                index,
+                before,
+                after,
+            } => EditCommand::EditRow {
+                index,
+                before: after,
+                after: before,
+            },
+            EditCommand::InsertRow { index, item } => EditCommand::RemoveRow { index, item },
+            EditCommand::RemoveRow { index, item } => EditCommand::InsertRow { index, item },
+            EditCommand::MoveRow { from, to } => EditCommand::MoveRow { from: to, to: from },
+            EditCommand::SelectCustomer { before, after } => EditCommand::SelectCustomer {
+                before: after,
+                after: before,
+            },
+            EditCommand::SelectContact { before, after } => EditCommand::SelectContact {
+                before: after,
+                after: before,
+            },
+            EditCommand::Batch(commands) => {
+                EditCommand::Batch(commands.iter().rev().map(EditCommand::inverse).collect())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct History {
+    undo: Vec<EditCommand>,
+    redo: Vec<EditCommand>,
+    // while false, edits to the same row are folded into the last command so one
+    // undo reverts a whole burst of typing instead of a single keystroke
+    sealed: bool,
+}
+
+impl History {
+    // records a command that has already been applied
+    pub fn record(&mut self, command: EditCommand) {
+        self.redo.clear();
+        if let EditCommand::EditRow {
+            index, ref after, ..
+        } = command
+        {
+            if !self.sealed {
+                if let Some(EditCommand::EditRow {
+                    index: last_index,
+                    after: last_after,
+                    ..
+                }) = self.undo.last_mut()
+                {
+                    if *last_index == index && last_after.id == after.id {
+                        *last_after = after.clone();
+                        return;
+                    }
+                }
+            }
+        }
+        self.sealed = false;
+        self.undo.push(command);
+    }
+
+    // ends the current burst of typing, the next edit starts a new undo step
+    pub fn seal(&mut self) {
+        self.sealed = true;
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn undo(&mut self, state: &mut EditorState) -> bool {
+        match self.undo.pop() {
+            Some(command) => {
+                command.inverse().apply(state);
+                self.redo.push(command);
+                self.sealed = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn redo(&mut self, state: &mut EditorState) -> bool {
+        match self.redo.pop() {
+            Some(command) => {
+                command.apply(state);
+                self.undo.push(command);
+                self.sealed = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(items: &mut LineItems, index: usize, description: &str) -> EditCommand {
+        let before = Box::new(items.items()[index].clone());
+        items.items_mut()[index].description = description.to_string();
+        EditCommand::EditRow {
+            index,
+            before,
+            after: Box::new(items.items()[index].clone()),
+        }
+    }
+
+    fn descriptions(items: &LineItems) -> Vec<String> {
+        items
+            .items()
+            .iter()
+            .map(|item| item.description.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_undo_and_redo_row_changes() {
+        let mut items = LineItems::default();
+        let (mut customer, mut contact) = (0, 0);
+        let mut history = History::default();
+
+        let command = edit(&mut items, 0, "Design");
+        history.record(command);
+        let index = items.push();
+        history.record(EditCommand::InsertRow {
+            index,
+            item: items.items()[index].clone(),
+        });
+        let command = edit(&mut items, 1, "Build");
+        history.record(command);
+        items.move_item(1, 0);
+        history.record(EditCommand::MoveRow { from: 1, to: 0 });
+        let item = items.remove(1).unwrap();
+        history.record(EditCommand::RemoveRow { index: 1, item });
+        assert_eq!(descriptions(&items), vec!["Build"]);
+
+        let mut state = EditorState {
+            line_items: &mut items,
+            customer_selected: &mut customer,
+            contact_selected: &mut contact,
+        };
+        assert!(history.undo(&mut state));
+        assert!(history.undo(&mut state));
+        assert_eq!(descriptions(state.line_items), vec!["Design", "Build"]);
+        while history.undo(&mut state) {}
+        assert_eq!(descriptions(state.line_items), vec![""]);
+        while history.redo(&mut state) {}
+        assert_eq!(descriptions(state.line_items), vec!["Build"]);
+    }
+
+    #[test]
+    fn test_typing_is_one_undo_step() {
+        let mut items = LineItems::default();
+        let mut contact = 0;
+        let mut history = History::default();
+        for text in ["D", "De", "Des"] {
+            let command = edit(&mut items, 0, text);
+            history.record(command);
+        }
+        history.seal();
+        let command = edit(&mut items, 0, "Design");
+        history.record(command);
+        history.record(EditCommand::SelectCustomer {
+            before: 0,
+            after: 2,
+        });
+        let mut customer = 2;
+
+        let mut state = EditorState {
+            line_items: &mut items,
+            customer_selected: &mut customer,
+            contact_selected: &mut contact,
+        };
+        history.undo(&mut state);
+        assert_eq!(*state.customer_selected, 0);
+        history.undo(&mut state);
+        assert_eq!(state.line_items.items()[0].description, "Des");
+        history.undo(&mut state);
+        assert_eq!(state.line_items.items()[0].description, "");
+        assert!(!history.can_undo());
+        assert!(history.can_redo());
+    }
+}
@@ -0,0 +1,932 @@
+//! Bulk import of customers and contacts from spreadsheet CSV files and vCard 3/4 address
+//! books. Files are read into records, `plan` compares them with what is stored and nothing is
+//! written until the plan is applied, so the plan doubles as the dry-run report.
+
+use std::fmt;
+
+use crate::db::Store;
+use crate::structs::{Contact, Customer};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ImportTarget {
+    #[default]
+    Customers,
+    Contacts,
+}
+
+impl ImportTarget {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ImportTarget::Customers => "Customers",
+            ImportTarget::Contacts => "Contacts",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Field {
+    Company,
+    Name,
+    Address,
+    City,
+    PostalCode,
+    Country,
+    Telephone,
+    Email,
+    Website,
+    VatId,
+    EndpointId,
+    Notes,
+    Terms,
+}
+
+impl Field {
+    // the fields stored for `target`, customers have no person, phone or website
+    pub fn for_target(target: ImportTarget) -> Vec<Field> {
+        match target {
+            ImportTarget::Customers => vec![
+                Field::Company,
+                Field::Address,
+                Field::City,
+                Field::PostalCode,
+                Field::Country,
+                Field::Email,
+                Field::VatId,
+                Field::EndpointId,
+                Field::Notes,
+                Field::Terms,
+            ],
+            ImportTarget::Contacts => vec![
+                Field::Company,
+                Field::Name,
+                Field::Address,
+                Field::City,
+                Field::PostalCode,
+                Field::Country,
+                Field::Telephone,
+                Field::Email,
+                Field::Website,
+                Field::VatId,
+                Field::EndpointId,
+                Field::Notes,
+                Field::Terms,
+            ],
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Field::Company => "Company",
+            Field::Name => "Name",
+            Field::Address => "Address",
+            Field::City => "City",
+            Field::PostalCode => "Postal Code",
+            Field::Country => "Country",
+            Field::Telephone => "Telephone",
+            Field::Email => "Email",
+            Field::Website => "Website",
+            Field::VatId => "VAT ID",
+            Field::EndpointId => "Endpoint ID",
+            Field::Notes => "Notes",
+            Field::Terms => "Terms",
+        }
+    }
+
+    // column headings used by common address books and accounting tools, lower case without
+    // spaces or punctuation
+    fn aliases(&self) -> &'static [&'static str] {
+        match self {
+            Field::Company => &[
+                "company",
+                "companyname",
+                "organization",
+                "organisation",
+                "org",
+                "business",
+                "businessname",
+                "customer",
+                "customername",
+                "client",
+                "clientname",
+                "contactname",
+                "displayname",
+            ],
+            Field::Name => &["name", "fullname", "contact", "contactperson", "person"],
+            Field::Address => &[
+                "address",
+                "street",
+                "streetaddress",
+                "address1",
+                "addressline1",
+                "billingaddress",
+                "billingstreet",
+                "poaddressline1",
+            ],
+            Field::City => &["city", "town", "locality", "billingcity", "pocity"],
+            Field::PostalCode => &[
+                "postalcode",
+                "postcode",
+                "zip",
+                "zipcode",
+                "billingpostalcode",
+                "billingzip",
+                "popostalcode",
+            ],
+            Field::Country => &["country", "billingcountry", "pocountry"],
+            Field::Telephone => &["telephone", "phone", "tel", "phonenumber", "mobile"],
+            Field::Email => &["email", "emailaddress", "mail"],
+            Field::Website => &["website", "web", "url", "homepage"],
+            Field::VatId => &["vatid", "vat", "vatnumber", "taxid", "taxnumber"],
+            Field::EndpointId => &["endpointid", "endpoint", "peppolid"],
+            Field::Notes => &["notes", "note", "comments"],
+            Field::Terms => &["terms", "paymentterms"],
+        }
+    }
+}
+
+// a customer or contact as read from a file, empty fields were not given
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Record {
+    pub company: String,
+    pub name: String,
+    pub address: String,
+    pub city: String,
+    pub postal_code: String,
+    pub country: String,
+    pub telephone: String,
+    pub email: String,
+    pub website: String,
+    pub vat_id: String,
+    pub endpoint_id: String,
+    pub notes: String,
+    pub terms: String,
+}
+
+impl Record {
+    fn field_mut(&mut self, field: Field) -> &mut String {
+        match field {
+            Field::Company => &mut self.company,
+            Field::Name => &mut self.name,
+            Field::Address => &mut self.address,
+            Field::City => &mut self.city,
+            Field::PostalCode => &mut self.postal_code,
+            Field::Country => &mut self.country,
+            Field::Telephone => &mut self.telephone,
+            Field::Email => &mut self.email,
+            Field::Website => &mut self.website,
+            Field::VatId => &mut self.vat_id,
+            Field::EndpointId => &mut self.endpoint_id,
+            Field::Notes => &mut self.notes,
+            Field::Terms => &mut self.terms,
+        }
+    }
+
+    fn field(&self, field: Field) -> &str {
+        match field {
+            Field::Company => &self.company,
+            Field::Name => &self.name,
+            Field::Address => &self.address,
+            Field::City => &self.city,
+            Field::PostalCode => &self.postal_code,
+            Field::Country => &self.country,
+            Field::Telephone => &self.telephone,
+            Field::Email => &self.email,
+            Field::Website => &self.website,
+            Field::VatId => &self.vat_id,
+            Field::EndpointId => &self.endpoint_id,
+            Field::Notes => &self.notes,
+            Field::Terms => &self.terms,
+        }
+    }
+
+    // people without a company are filed under their own name
+    fn company_or_name(&self) -> &str {
+        if self.company.trim().is_empty() {
+            self.name.trim()
+        } else {
+            self.company.trim()
+        }
+    }
+
+    pub fn to_customer(&self) -> Customer {
+        Customer {
+            company: self.company.clone(),
+            address: self.address.clone(),
+            city: self.city.clone(),
+            postal_code: self.postal_code.clone(),
+            country: self.country.clone(),
+            notes: self.notes.clone(),
+            terms: self.terms.clone(),
+            vat_id: self.vat_id.clone(),
+            endpoint_id: self.endpoint_id.clone(),
+            email: self.email.clone(),
+        }
+    }
+
+    pub fn to_contact(&self) -> Contact {
+        Contact {
+            company: self.company.clone(),
+            address: self.address.clone(),
+            city: self.city.clone(),
+            postal_code: self.postal_code.clone(),
+            country: self.country.clone(),
+            name: self.name.clone(),
+            telephone: self.telephone.clone(),
+            email: self.email.clone(),
+            website: self.website.clone(),
+            default_notes: self.notes.clone(),
+            default_terms: self.terms.clone(),
+            vat_id: self.vat_id.clone(),
+            endpoint_id: self.endpoint_id.clone(),
+        }
+    }
+}
+
+impl From<&Customer> for Record {
+    fn from(customer: &Customer) -> Self {
+        Self {
+            company: customer.company.clone(),
+            address: customer.address.clone(),
+            city: customer.city.clone(),
+            postal_code: customer.postal_code.clone(),
+            country: customer.country.clone(),
+            email: customer.email.clone(),
+            vat_id: customer.vat_id.clone(),
+            endpoint_id: customer.endpoint_id.clone(),
+            notes: customer.notes.clone(),
+            terms: customer.terms.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+impl From<&Contact> for Record {
+    fn from(contact: &Contact) -> Self {
+        Self {
+            company: contact.company.clone(),
+            name: contact.name.clone(),
+            address: contact.address.clone(),
+            city: contact.city.clone(),
+            postal_code: contact.postal_code.clone(),
+            country: contact.country.clone(),
+            telephone: contact.telephone.clone(),
+            email: contact.email.clone(),
+            website: contact.website.clone(),
+            vat_id: contact.vat_id.clone(),
+            endpoint_id: contact.endpoint_id.clone(),
+            notes: contact.default_notes.clone(),
+            terms: contact.default_terms.clone(),
+        }
+    }
+}
+
+// CSV
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CsvTable {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+// Reads RFC 4180 CSV. Spreadsheets in many locales save with semicolons or tabs, so the
+// separator is whichever of the three appears most in the heading row.
+pub fn parse_csv(text: &str) -> CsvTable {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let first_line = text.lines().next().unwrap_or("");
+    let separator = [',', ';', '\t']
+        .iter()
+        .copied()
+        .max_by_key(|&separator| first_line.matches(separator).count())
+        .filter(|&separator| first_line.contains(separator))
+        .unwrap_or(',');
+
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    let mut row: Vec<String> = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.is_empty() => quoted = true,
+            c if quoted => field.push(c),
+            c if c == separator => row.push(std::mem::take(&mut field)),
+            '\r' => {}
+            '\n' => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows.retain(|row| row.iter().any(|field| !field.trim().is_empty()));
+
+    let mut rows = rows.into_iter();
+    let headers: Vec<String> = rows
+        .next()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|header| header.trim().to_string())
+        .collect();
+    let rows = rows
+        .map(|mut row| {
+            row.resize(headers.len(), String::new());
+            row
+        })
+        .collect();
+    CsvTable { headers, rows }
+}
+
+fn normalize_header(header: &str) -> String {
+    header
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+// a field per column from its heading, each field is taken by the first column that matches
+pub fn guess_mapping(headers: &[String], target: ImportTarget) -> Vec<Option<Field>> {
+    let fields = Field::for_target(target);
+    let mut taken = Vec::new();
+    let mut mapping: Vec<Option<Field>> = headers
+        .iter()
+        .map(|header| {
+            let header = normalize_header(header);
+            let field = fields
+                .iter()
+                .copied()
+                .find(|field| !taken.contains(field) && field.aliases().contains(&header.as_str()));
+            taken.extend(field);
+            field
+        })
+        .collect();
+    // customers are companies, a plain name column is the best there is
+    if target == ImportTarget::Customers && !taken.contains(&Field::Company) {
+        if let Some(idx) = headers.iter().position(|header| {
+            Field::Name
+                .aliases()
+                .contains(&normalize_header(header).as_str())
+        }) {
+            mapping[idx] = Some(Field::Company);
+        }
+    }
+    mapping
+}
+
+pub fn csv_records(table: &CsvTable, mapping: &[Option<Field>]) -> Vec<Record> {
+    table
+        .rows
+        .iter()
+        .map(|row| {
+            let mut record = Record::default();
+            for (value, field) in row.iter().zip(mapping) {
+                if let Some(field) = field {
+                    *record.field_mut(*field) = value.trim().to_string();
+                }
+            }
+            record
+        })
+        .collect()
+}
+
+// vCard
+
+// long lines are folded onto continuation lines starting with a space or tab
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        match line.strip_prefix([' ', '\t']) {
+            Some(rest) if !lines.is_empty() => lines.last_mut().unwrap().push_str(rest),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+// structured values such as N and ADR separate their components with unescaped semicolons
+fn components(value: &str) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') | Some('N') => parts.last_mut().unwrap().push('\n'),
+                Some(c) => parts.last_mut().unwrap().push(c),
+                None => {}
+            },
+            ';' => parts.push(String::new()),
+            c => parts.last_mut().unwrap().push(c),
+        }
+    }
+    parts
+}
+
+fn text_value(value: &str) -> String {
+    components(&value.replace(';', "\\;"))
+        .join("")
+        .trim()
+        .to_string()
+}
+
+// Reads vCard 3.0 and 4.0 cards (2.1 cards without encoded values work as well). The company
+// is the organisation, or the formatted name for people. Work addresses, phone numbers and
+// emails win over other kinds.
+pub fn parse_vcards(text: &str) -> Vec<Record> {
+    let mut records = Vec::new();
+    // the card being read and whether its address, phone and email are work ones
+    let mut card: Option<(Record, [bool; 3])> = None;
+    for line in unfold(text) {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let mut params = name.split(';');
+        let property = params.next().unwrap_or("");
+        // grouped properties look like item1.TEL
+        let property = property
+            .rsplit('.')
+            .next()
+            .unwrap_or(property)
+            .to_ascii_uppercase();
+        let params = params.collect::<Vec<&str>>().join(";").to_ascii_lowercase();
+        let work = params.contains("work");
+        match (property.as_str(), card.as_mut()) {
+            ("BEGIN", _) if value.trim().eq_ignore_ascii_case("vcard") => {
+                card = Some((Record::default(), [false; 3]))
+            }
+            ("END", Some(_)) if value.trim().eq_ignore_ascii_case("vcard") => {
+                let (mut record, _) = card.take().unwrap();
+                if record.company.is_empty() {
+                    record.company = record.name.clone();
+                }
+                records.push(record);
+            }
+            (_, None) => {}
+            ("FN", Some((record, _))) => record.name = text_value(value),
+            ("N", Some((record, _))) if record.name.is_empty() => {
+                let parts = components(value);
+                let given = parts.get(1).map_or("", |part| part.trim());
+                let family = parts.first().map_or("", |part| part.trim());
+                record.name = format!("{} {}", given, family).trim().to_string();
+            }
+            ("ORG", Some((record, _))) => {
+                record.company = components(value)
+                    .first()
+                    .map_or(String::new(), |org| org.trim().to_string())
+            }
+            ("ADR", Some((record, seen)))
+                if !seen[0] && (work || record.address.is_empty() && record.city.is_empty()) =>
+            {
+                let parts = components(value);
+                let part = |idx: usize| parts.get(idx).map_or("", |part| part.trim());
+                record.address = part(2)
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .collect::<Vec<&str>>()
+                    .join(", ");
+                record.city = match (part(3), part(4)) {
+                    (city, "") => city.to_string(),
+                    ("", region) => region.to_string(),
+                    (city, region) => format!("{}, {}", city, region),
+                };
+                record.postal_code = part(5).to_string();
+                record.country = part(6).to_string();
+                seen[0] = work;
+            }
+            ("TEL", Some((record, seen))) if !seen[1] && (work || record.telephone.is_empty()) => {
+                record.telephone = text_value(value).trim_start_matches("tel:").to_string();
+                seen[1] = work;
+            }
+            ("EMAIL", Some((record, seen))) if !seen[2] && (work || record.email.is_empty()) => {
+                record.email = text_value(value).trim_start_matches("mailto:").to_string();
+                seen[2] = work;
+            }
+            ("URL", Some((record, _))) if record.website.is_empty() => {
+                record.website = text_value(value)
+            }
+            ("NOTE", Some((record, _))) => record.notes = text_value(value),
+            _ => {}
+        }
+    }
+    records
+}
+
+// plan
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DuplicatePolicy {
+    // existing records are left alone
+    #[default]
+    Skip,
+    // fields given in the file replace the stored ones, empty cells keep what is there
+    Update,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    Add,
+    Update(Vec<Field>),
+    Unchanged,
+    // a company that is already stored, with the stored name
+    Existing(String),
+    // the same company appeared on an earlier row of the file
+    Repeated(usize),
+    MissingCompany,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlannedRow {
+    // 1-based position in the file, not counting the heading
+    pub row: usize,
+    pub record: Record,
+    pub action: Action,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImportPlan {
+    pub target: ImportTarget,
+    pub rows: Vec<PlannedRow>,
+}
+
+// company names are compared ignoring case and repeated spaces
+fn company_key(company: &str) -> String {
+    company
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+pub fn plan(
+    target: ImportTarget,
+    records: Vec<Record>,
+    existing: &[Record],
+    policy: DuplicatePolicy,
+) -> ImportPlan {
+    let fields = Field::for_target(target);
+    let mut seen: Vec<(String, usize)> = Vec::new();
+    let mut rows = Vec::new();
+    for (idx, mut record) in records.into_iter().enumerate() {
+        let row = idx + 1;
+        record.company = record.company_or_name().to_string();
+        let key = company_key(&record.company);
+        let action = if key.is_empty() {
+            Action::MissingCompany
+        } else if let Some((_, first)) = seen.iter().find(|(seen, _)| *seen == key) {
+            Action::Repeated(*first)
+        } else {
+            seen.push((key.clone(), row));
+            match existing
+                .iter()
+                .find(|stored| company_key(&stored.company) == key)
+            {
+                None => Action::Add,
+                Some(stored) if policy == DuplicatePolicy::Skip => {
+                    Action::Existing(stored.company.clone())
+                }
+                Some(stored) => {
+                    let mut merged = stored.clone();
+                    for field in &fields {
+                        if *field != Field::Company && !record.field(*field).is_empty() {
+                            *merged.field_mut(*field) = record.field(*field).to_string();
+                        }
+                    }
+                    let changes: Vec<Field> = fields
+                        .iter()
+                        .copied()
+                        .filter(|field| merged.field(*field) != stored.field(*field))
+                        .collect();
+                    record = merged;
+                    if changes.is_empty() {
+                        Action::Unchanged
+                    } else {
+                        Action::Update(changes)
+                    }
+                }
+            }
+        };
+        rows.push(PlannedRow {
+            row,
+            record,
+            action,
+        });
+    }
+    ImportPlan { target, rows }
+}
+
+impl ImportPlan {
+    fn count(&self, matches: impl Fn(&Action) -> bool) -> usize {
+        self.rows.iter().filter(|row| matches(&row.action)).count()
+    }
+
+    pub fn additions(&self) -> usize {
+        self.count(|action| *action == Action::Add)
+    }
+
+    pub fn updates(&self) -> usize {
+        self.count(|action| matches!(action, Action::Update(_)))
+    }
+
+    pub fn skipped(&self) -> usize {
+        self.rows.len() - self.additions() - self.updates()
+    }
+
+    // the records that will be written
+    fn changed(&self) -> impl Iterator<Item = &Record> {
+        self.rows
+            .iter()
+            .filter(|row| matches!(row.action, Action::Add | Action::Update(_)))
+            .map(|row| &row.record)
+    }
+
+    // Writes the added and updated rows in one transaction and returns how many were written
+    pub fn apply(&self, store: &mut Store) -> rusqlite::Result<usize> {
+        let (customers, contacts): (Vec<Customer>, Vec<Contact>) = match self.target {
+            ImportTarget::Customers => (self.changed().map(Record::to_customer).collect(), vec![]),
+            ImportTarget::Contacts => (vec![], self.changed().map(Record::to_contact).collect()),
+        };
+        store.import_parties(&customers, &contacts)?;
+        Ok(customers.len() + contacts.len())
+    }
+}
+
+// the dry-run report, a summary line followed by one line per row
+impl fmt::Display for ImportPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}: {} to add, {} to update, {} skipped",
+            self.rows.len(),
+            if self.rows.len() == 1 { "row" } else { "rows" },
+            self.additions(),
+            self.updates(),
+            self.skipped()
+        )?;
+        for row in &self.rows {
+            let company = &row.record.company;
+            match &row.action {
+                Action::Add => write!(f, "\nRow {}: add {}", row.row, company)?,
+                Action::Update(fields) => {
+                    let fields: Vec<&str> = fields.iter().map(Field::label).collect();
+                    write!(
+                        f,
+                        "\nRow {}: update {} ({})",
+                        row.row,
+                        company,
+                        fields.join(", ")
+                    )?
+                }
+                Action::Unchanged => {
+                    write!(f, "\nRow {}: {} is already up to date", row.row, company)?
+                }
+                Action::Existing(stored) => write!(
+                    f,
+                    "\nRow {}: skip {}, {} already exists",
+                    row.row, company, stored
+                )?,
+                Action::Repeated(first) => write!(
+                    f,
+                    "\nRow {}: skip {}, same company as row {}",
+                    row.row, company, first
+                )?,
+                Action::MissingCompany => write!(f, "\nRow {}: skip, no company or name", row.row)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+// what the user picked, CSV keeps its table so the column mapping can still change
+#[derive(Clone, Debug, PartialEq)]
+pub enum ImportSource {
+    Csv(CsvTable),
+    VCard(Vec<Record>),
+}
+
+impl ImportSource {
+    // vCards are recognised by their content, whatever the file is called
+    pub fn read(text: &str) -> Self {
+        let looks_like_vcard = text
+            .trim_start_matches('\u{feff}')
+            .trim_start()
+            .to_ascii_uppercase()
+            .starts_with("BEGIN:VCARD");
+        if looks_like_vcard {
+            ImportSource::VCard(parse_vcards(text))
+        } else {
+            ImportSource::Csv(parse_csv(text))
+        }
+    }
+
+    pub fn records(&self, mapping: &[Option<Field>]) -> Vec<Record> {
+        match self {
+            ImportSource::Csv(table) => csv_records(table, mapping),
+            ImportSource::VCard(records) => records.clone(),
+        }
+    }
+}
+
+// existing companies of `target`, for `plan`
+pub fn existing_records(
+    target: ImportTarget,
+    customers: &[Customer],
+    contacts: &[Contact],
+) -> Vec<Record> {
+    match target {
+        ImportTarget::Customers => customers.iter().map(Record::from).collect(),
+        ImportTarget::Contacts => contacts.iter().map(Record::from).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CSV: &str = "\u{feff}Company Name;Street;City;ZIP;Email;VAT number\r\n\
+Acme;\"1 Road; Unit 2\";Town;A1A 1A1;billing@acme.test;CA123\r\n\
+\"Globex \"\"Intl\"\"\";\"2 Way\nFloor 3\";Springfield;;;\r\n\
+acme ;;;;;\r\n\
+;;;;;\r\n\
+Fake Co.;9 Lane;Fakeston;;;FAKE9\r\n";
+
+    const VCARDS: &str = "BEGIN:VCARD\r\n\
+VERSION:3.0\r\n\
+FN:Jane Doe\r\n\
+N:Doe;Jane;;;\r\n\
+ORG:Initech\\, Inc.;Accounting\r\n\
+ADR;TYPE=HOME:;;5 Home St;Hometown;;11111;USA\r\n\
+item1.ADR;TYPE=WORK:;;42 Office Park\\nSuite 9;Austin;TX;78701;USA\r\n\
+TEL;TYPE=CELL:555-0100\r\n\
+TEL;TYPE=WORK,VOICE:555-0199\r\n\
+EMAIL;TYPE=INTERNET:jane@example.test\r\n\
+NOTE:Prefers email.\r\n Net 15.\r\n\
+END:VCARD\r\n\
+BEGIN:VCARD\r\n\
+VERSION:4.0\r\n\
+N:Smith;John;;;\r\n\
+TEL;VALUE=uri;TYPE=\"work,voice\":tel:+1-555-0123\r\n\
+EMAIL:john@smith.test\r\n\
+END:VCARD\r\n";
+
+    #[test]
+    fn test_csv_mapping_and_records() {
+        let table = parse_csv(CSV);
+        assert_eq!(table.headers[0], "Company Name");
+        assert_eq!(table.rows.len(), 4);
+        let mapping = guess_mapping(&table.headers, ImportTarget::Customers);
+        assert_eq!(
+            mapping,
+            vec![
+                Some(Field::Company),
+                Some(Field::Address),
+                Some(Field::City),
+                Some(Field::PostalCode),
+                Some(Field::Email),
+                Some(Field::VatId)
+            ]
+        );
+        let records = csv_records(&table, &mapping);
+        assert_eq!(records[0].address, "1 Road; Unit 2");
+        assert_eq!(records[1].company, "Globex \"Intl\"");
+        assert_eq!(records[1].address, "2 Way\nFloor 3");
+
+        let contact_mapping = guess_mapping(&table.headers, ImportTarget::Contacts);
+        assert_eq!(contact_mapping[4], Some(Field::Email));
+
+        // a bare name column stands in for the company of a customer
+        let headers = vec!["Name".to_string(), "Phone".to_string()];
+        assert_eq!(
+            guess_mapping(&headers, ImportTarget::Customers),
+            vec![Some(Field::Company), None]
+        );
+        assert_eq!(
+            guess_mapping(&headers, ImportTarget::Contacts),
+            vec![Some(Field::Name), Some(Field::Telephone)]
+        );
+    }
+
+    #[test]
+    fn test_vcards() {
+        let records = parse_vcards(VCARDS);
+        assert_eq!(records.len(), 2);
+        let jane = &records[0];
+        assert_eq!(jane.company, "Initech, Inc.");
+        assert_eq!(jane.name, "Jane Doe");
+        assert_eq!(jane.address, "42 Office Park, Suite 9");
+        assert_eq!(jane.city, "Austin, TX");
+        assert_eq!(jane.postal_code, "78701");
+        assert_eq!(jane.telephone, "555-0199");
+        assert_eq!(jane.email, "jane@example.test");
+        assert_eq!(jane.notes, "Prefers email.Net 15.");
+        let john = &records[1];
+        assert_eq!(john.company, "John Smith");
+        assert_eq!(john.telephone, "+1-555-0123");
+        assert!(matches!(ImportSource::read(VCARDS), ImportSource::VCard(_)));
+    }
+
+    #[test]
+    fn test_plan_and_apply() {
+        let mut store = Store::open_in_memory().unwrap();
+        let stored = Customer {
+            company: "ACME".to_string(),
+            address: "Old Road".to_string(),
+            city: "Town".to_string(),
+            ..Default::default()
+        };
+        store.save_customer(&stored).unwrap();
+        let table = parse_csv(CSV);
+        let mapping = guess_mapping(&table.headers, ImportTarget::Customers);
+        let existing = existing_records(ImportTarget::Customers, &[stored], &[]);
+
+        let skip = plan(
+            ImportTarget::Customers,
+            csv_records(&table, &mapping),
+            &existing,
+            DuplicatePolicy::Skip,
+        );
+        let actions: Vec<&Action> = skip.rows.iter().map(|row| &row.action).collect();
+        assert_eq!(
+            actions,
+            [
+                &Action::Existing("ACME".to_string()),
+                &Action::Add,
+                &Action::Repeated(1),
+                &Action::Add
+            ]
+        );
+        assert_eq!(
+            skip.to_string().lines().next(),
+            Some("4 rows: 2 to add, 0 to update, 2 skipped")
+        );
+
+        let update = plan(
+            ImportTarget::Customers,
+            csv_records(&table, &mapping),
+            &existing,
+            DuplicatePolicy::Update,
+        );
+        assert_eq!(
+            update.rows[0].action,
+            Action::Update(vec![
+                Field::Address,
+                Field::PostalCode,
+                Field::Email,
+                Field::VatId
+            ])
+        );
+        // the stored spelling is kept so the row is updated rather than added
+        assert_eq!(update.rows[0].record.company, "ACME");
+        assert!(update
+            .to_string()
+            .contains("Row 1: update ACME (Address, Postal Code, Email, VAT ID)"));
+
+        // nothing is written by planning
+        assert_eq!(store.customers().unwrap().len(), 1);
+        assert_eq!(update.apply(&mut store).unwrap(), 3);
+        let customers = store.customers().unwrap();
+        assert_eq!(customers.len(), 3);
+        let acme = store.customer("ACME").unwrap().unwrap();
+        assert_eq!(acme.address, "1 Road; Unit 2");
+        assert_eq!(acme.city, "Town");
+
+        // importing the same file again changes nothing
+        let existing = existing_records(ImportTarget::Customers, &customers, &[]);
+        let again = plan(
+            ImportTarget::Customers,
+            csv_records(&table, &mapping),
+            &existing,
+            DuplicatePolicy::Update,
+        );
+        assert_eq!(again.additions() + again.updates(), 0);
+    }
+
+    #[test]
+    fn test_short_and_multibyte_names() {
+        let mut store = Store::open_in_memory().unwrap();
+        let table = parse_csv("Company\r\nIBM\r\n3M\r\nCafé Noir\r\nÅr\r\n");
+        let mapping = guess_mapping(&table.headers, ImportTarget::Customers);
+        let plan = plan(
+            ImportTarget::Customers,
+            csv_records(&table, &mapping),
+            &[],
+            DuplicatePolicy::Skip,
+        );
+        assert_eq!(plan.apply(&mut store).unwrap(), 4);
+        let ids: Vec<String> = store
+            .customers()
+            .unwrap()
+            .iter()
+            .map(Customer::customer_id)
+            .collect();
+        assert_eq!(ids, ["IBMX", "3MXX", "CAFÉ", "ÅRXX"]);
+    }
+}
@@ -0,0 +1,26 @@
+//! Invoicy's non-GUI core: storage, document generation, fonts and settings. The desktop app in
+//! `main.rs` is built on top of this crate and other tooling can use it the same way.
+
+pub mod accounting;
+pub mod backup;
+pub mod cii;
+pub mod db;
+pub mod document;
+pub mod encryption;
+pub mod export;
+pub mod font_registry;
+pub mod history;
+pub mod import;
+pub mod line_items;
+pub mod mail;
+pub mod paths;
+pub mod pdfa;
+pub mod peppol;
+pub mod pricing;
+pub mod remote;
+pub mod settings;
+pub mod structs;
+#[cfg(test)]
+mod test_support;
+pub mod totals;
+pub mod ubl;
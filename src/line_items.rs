@@ -0,0 +1,811 @@
+//! Typed model behind the item table. Each row keeps the text the user typed next to the parsed
+//! value so the editor can show validation errors without losing the input.
+
+use std::ops::Range;
+
+use crate::pricing::{PriceSource, Quote};
+use crate::structs::{DatabaseData, DraftItem, LineKind, Product};
+use crate::totals;
+
+// offered in the unit picker, anything else can still be typed in as a custom unit
+pub const UNITS: [&str; 4] = ["hours", "days", "pcs", "km"];
+pub const HOURS: &str = "hours";
+
+// editable columns in the order the item table shows them, used for pasting blocks
+pub const DESCRIPTION: usize = 0;
+pub const QUANTITY: usize = 1;
+pub const UNIT: usize = 2;
+pub const PRICE: usize = 3;
+pub const COLUMNS: usize = 4;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LineItem {
+    // stable across inserts and reorders, used for widget ids
+    pub id: u64,
+    pub description: String,
+    pub quantity_input: String,
+    pub price_input: String,
+    pub quantity: f64,
+    pub price: f64,
+    pub quantity_error: Option<String>,
+    pub price_error: Option<String>,
+    // catalogue product the row was filled from, if any
+    pub sku: Option<String>,
+    pub unit: String,
+    pub tax_code: String,
+    pub kind: LineKind,
+    // set while the price is the one the catalogue or price list gave, typing a price clears it
+    pub price_source: Option<PriceSource>,
+}
+
+// description lines starting with one of these are printed as bullets under the line
+const BULLET_PREFIXES: [&str; 3] = ["- ", "* ", "• "];
+
+impl LineItem {
+    pub fn new(id: u64) -> Self {
+        let mut item = Self {
+            id,
+            description: String::new(),
+            quantity_input: "1".to_string(),
+            price_input: String::new(),
+            quantity: 0.0,
+            price: 0.0,
+            quantity_error: None,
+            price_error: None,
+            sku: None,
+            unit: String::new(),
+            tax_code: String::new(),
+            kind: LineKind::Item,
+            price_source: None,
+        };
+        item.parse();
+        item
+    }
+
+    pub fn is_item(&self) -> bool {
+        self.kind == LineKind::Item
+    }
+
+    // re-parses the text inputs, call after any edit
+    pub fn parse(&mut self) {
+        if !self.is_item() {
+            // headings and subtotals have no amount, leftover input is ignored
+            self.quantity = 0.0;
+            self.price = 0.0;
+            self.quantity_error = None;
+            self.price_error = None;
+            return;
+        }
+        let (quantity, quantity_error) = match parse_duration(&self.quantity_input) {
+            Some(hours) => {
+                // durations are always billed in hours, whatever unit the row had
+                self.unit = HOURS.to_string();
+                (hours, None)
+            }
+            None => parse_number(&self.quantity_input, "Quantity"),
+        };
+        let (price, price_error) = parse_number(&self.price_input, "Price");
+        self.quantity = quantity;
+        self.quantity_error = quantity_error;
+        self.price = price;
+        self.price_error = price_error;
+    }
+
+    pub fn total(&self) -> f64 {
+        self.quantity * self.price
+    }
+
+    // The description split into text lines and sub-item bullets. Bullets are the lines
+    // starting with "- ", "* " or "• ", everything else is kept as text.
+    pub fn description_lines(&self) -> (Vec<&str>, Vec<&str>) {
+        let mut text = Vec::new();
+        let mut bullets = Vec::new();
+        for line in self.description.lines().map(str::trim_end) {
+            let trimmed = line.trim_start();
+            match BULLET_PREFIXES
+                .iter()
+                .find_map(|prefix| trimmed.strip_prefix(prefix))
+            {
+                Some(bullet) => bullets.push(bullet.trim()),
+                None if !line.trim().is_empty() => text.push(line.trim()),
+                None => {}
+            }
+        }
+        (text, bullets)
+    }
+
+    // quantity as printed on the invoice, e.g. "2.5 hours"
+    pub fn quantity_label(&self) -> String {
+        if self.unit.trim().is_empty() {
+            self.quantity.to_string()
+        } else {
+            format!("{} {}", self.quantity, self.unit.trim())
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.quantity_error.is_none() && self.price_error.is_none()
+    }
+
+    // `quote` is the product's price for the selected customer
+    pub fn apply_product(&mut self, product: &Product, quote: Quote) {
+        if !self.is_item() {
+            return;
+        }
+        self.description = if product.description.trim().is_empty() {
+            product.name.clone()
+        } else {
+            product.description.clone()
+        };
+        self.unit = product.unit.clone();
+        self.tax_code = product.tax_code.clone();
+        self.sku = Some(product.sku.clone());
+        self.apply_quote(quote);
+    }
+
+    pub fn apply_quote(&mut self, quote: Quote) {
+        self.price_input = format!("{:.2}", quote.price);
+        self.price_source = Some(quote.source);
+        self.parse();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.description.trim().is_empty() && self.price_input.trim().is_empty()
+    }
+
+    // sets one of the editable columns from text, columns past PRICE are ignored
+    pub fn set_cell(&mut self, column: usize, value: &str) {
+        let value = value.trim().to_string();
+        match column {
+            DESCRIPTION => self.description = value,
+            QUANTITY => self.quantity_input = value,
+            UNIT => self.unit = value,
+            PRICE => {
+                self.price_input = value;
+                self.price_source = None;
+            }
+            _ => return,
+        }
+        self.parse();
+    }
+}
+
+// empty input counts as zero so fresh rows don't start out in an error state
+fn parse_number(input: &str, label: &str) -> (f64, Option<String>) {
+    let trimmed = input.trim().replace(',', "");
+    if trimmed.is_empty() {
+        return (0.0, None);
+    }
+    match trimmed.trim_start_matches('$').parse::<f64>() {
+        Ok(value) if value.is_finite() => (value, None),
+        _ => (
+            0.0,
+            Some(format!("{} '{}' is not a number", label, input.trim())),
+        ),
+    }
+}
+
+// reads durations such as "2h30m", "45m", "1.5h" or "2:30" as decimal hours,
+// plain numbers are left to parse_number
+pub fn parse_duration(input: &str) -> Option<f64> {
+    let input = input.trim().to_lowercase().replace(' ', "");
+    if input.is_empty() {
+        return None;
+    }
+    if let Some((hours, minutes)) = input.split_once(':') {
+        let hours = hours.parse::<u32>().ok()?;
+        let minutes = minutes.parse::<u32>().ok()?;
+        if minutes >= 60 {
+            return None;
+        }
+        return Some(hours as f64 + minutes as f64 / 60.0);
+    }
+    if !input.ends_with('h') && !input.ends_with('m') {
+        return None;
+    }
+    let (hours, minutes) = match input.split_once('h') {
+        Some((hours, rest)) => (hours, rest),
+        None => ("", input.as_str()),
+    };
+    let mut total = 0.0;
+    if !hours.is_empty() {
+        total += hours
+            .parse::<f64>()
+            .ok()
+            .filter(|value| value.is_finite())?;
+    }
+    if !minutes.is_empty() {
+        let minutes = minutes.strip_suffix('m')?;
+        total += minutes
+            .parse::<f64>()
+            .ok()
+            .filter(|value| value.is_finite())?
+            / 60.0;
+    }
+    Some(total)
+}
+
+// A group of rows started by a heading. Rows above the first heading form an untitled
+// section of their own.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Section {
+    pub title: String,
+    // includes the heading row itself
+    pub rows: Range<usize>,
+    pub total: f64,
+}
+
+// title used for rows above the first heading and for headings left blank
+pub const UNTITLED_SECTION: &str = "General";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LineItems {
+    items: Vec<LineItem>,
+    next_id: u64,
+}
+
+impl Default for LineItems {
+    fn default() -> Self {
+        let mut items = Self {
+            items: Vec::new(),
+            next_id: 0,
+        };
+        items.push();
+        items
+    }
+}
+
+impl LineItems {
+    pub fn items(&self) -> &[LineItem] {
+        &self.items
+    }
+
+    pub fn items_mut(&mut self) -> &mut [LineItem] {
+        &mut self.items
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    fn new_item(&mut self) -> LineItem {
+        let item = LineItem::new(self.next_id);
+        self.next_id += 1;
+        item
+    }
+
+    // inserts a heading or subtotal row, subtotals start out labelled "Subtotal"
+    pub fn insert_kind(&mut self, index: usize, kind: LineKind) -> usize {
+        let index = self.insert(index);
+        let item = &mut self.items[index];
+        item.kind = kind;
+        item.quantity_input.clear();
+        if kind == LineKind::Subtotal {
+            item.description = "Subtotal".to_string();
+        }
+        item.parse();
+        index
+    }
+
+    // Sum of the items above a subtotal row, back to the previous heading or subtotal.
+    // For any other row it is the running total of its section so far.
+    pub fn subtotal_at(&self, index: usize) -> f64 {
+        self.items[..index.min(self.items.len())]
+            .iter()
+            .rev()
+            .take_while(|item| item.is_item())
+            .map(LineItem::total)
+            .sum()
+    }
+
+    // sections in order, skipping an untitled leading group that holds no items
+    pub fn sections(&self) -> Vec<Section> {
+        let mut sections: Vec<Section> = Vec::new();
+        for (idx, item) in self.items.iter().enumerate() {
+            if item.kind == LineKind::Heading || sections.is_empty() {
+                let title = match item.kind {
+                    LineKind::Heading if !item.description.trim().is_empty() => {
+                        item.description.trim().to_string()
+                    }
+                    _ => UNTITLED_SECTION.to_string(),
+                };
+                sections.push(Section {
+                    title,
+                    rows: idx..idx,
+                    total: 0.0,
+                });
+            }
+            let section = sections.last_mut().unwrap();
+            section.rows.end = idx + 1;
+            section.total += item.total();
+        }
+        sections.retain(|section| {
+            self.items[section.rows.start].kind == LineKind::Heading
+                || self.items[section.rows.clone()]
+                    .iter()
+                    .any(LineItem::is_item)
+        });
+        sections
+    }
+
+    // total of the section the row at `index` belongs to
+    pub fn section_total(&self, index: usize) -> f64 {
+        self.sections()
+            .into_iter()
+            .find(|section| section.rows.contains(&index))
+            .map_or(0.0, |section| section.total)
+    }
+
+    pub fn push(&mut self) -> usize {
+        let item = self.new_item();
+        self.items.push(item);
+        self.items.len() - 1
+    }
+
+    pub fn insert(&mut self, index: usize) -> usize {
+        let index = index.min(self.items.len());
+        let item = self.new_item();
+        self.items.insert(index, item);
+        index
+    }
+
+    // puts back a row that was removed earlier, keeping its id
+    pub fn insert_item(&mut self, index: usize, item: LineItem) {
+        let index = index.min(self.items.len());
+        self.next_id = self.next_id.max(item.id + 1);
+        self.items.insert(index, item);
+    }
+
+    pub fn replace(&mut self, index: usize, item: LineItem) {
+        if let Some(slot) = self.items.get_mut(index) {
+            *slot = item;
+        }
+    }
+
+    // copies the row at `index` right below it, returns where the copy went
+    pub fn duplicate(&mut self, index: usize) -> Option<usize> {
+        let mut copy = self.items.get(index)?.clone();
+        copy.id = self.new_item().id;
+        self.items.insert(index + 1, copy);
+        Some(index + 1)
+    }
+
+    // Pastes a tab-separated block (as copied from a spreadsheet) starting at `row`/`column`,
+    // appending rows when the block runs past the end. Returns the indices of existing rows
+    // that were overwritten and the number of rows appended.
+    pub fn paste_block(&mut self, row: usize, column: usize, text: &str) -> (Vec<usize>, usize) {
+        let text = text.trim_end_matches(['\r', '\n']);
+        let mut edited = Vec::new();
+        let mut appended = 0;
+        for (offset, line) in text.lines().enumerate() {
+            let index = row + offset;
+            if index >= self.items.len() {
+                self.push();
+                appended += 1;
+            } else {
+                edited.push(index);
+            }
+            let item = &mut self.items[index];
+            for (cell, value) in line.trim_end_matches('\r').split('\t').enumerate() {
+                item.set_cell(column + cell, value);
+            }
+        }
+        (edited, appended)
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<LineItem> {
+        if index < self.items.len() {
+            Some(self.items.remove(index))
+        } else {
+            None
+        }
+    }
+
+    // moves the row at `from` so it ends up at position `to`
+    pub fn move_item(&mut self, from: usize, to: usize) {
+        if from >= self.items.len() || from == to {
+            return;
+        }
+        let item = self.items.remove(from);
+        let to = to.min(self.items.len());
+        self.items.insert(to, item);
+    }
+
+    // before tax, what the item rows add up to
+    pub fn net_total(&self) -> f64 {
+        self.items.iter().map(LineItem::total).sum()
+    }
+
+    // the invoice's grand total, VAT from the tax codes included, see `totals::gross_total`
+    pub fn gross_total(&self) -> f64 {
+        totals::gross_total(&self.to_database_data("", 0))
+    }
+
+    // Re-prices catalogue rows after the customer changed, `quote` looks up a sku. Rows whose
+    // price was typed by hand are kept, unless they happen to match the quote, which is how
+    // rows restored from a draft get their price source back. Returns the rows that changed.
+    pub fn reprice(&mut self, quote: impl Fn(&str) -> Option<Quote>) -> Vec<usize> {
+        let mut changed = Vec::new();
+        for (idx, item) in self.items.iter_mut().enumerate() {
+            let Some(quote) = item.sku.as_deref().and_then(&quote) else {
+                continue;
+            };
+            if !item.is_item()
+                || item.price_source == Some(quote.source) && item.price == quote.price
+            {
+                continue;
+            }
+            if item.price_source.is_some() || item.price == quote.price {
+                item.apply_quote(quote);
+                changed.push(idx);
+            }
+        }
+        changed
+    }
+
+    // first validation problem, prefixed with its 1-based row number
+    pub fn first_error(&self) -> Option<String> {
+        self.items.iter().enumerate().find_map(|(idx, item)| {
+            item.quantity_error
+                .as_ref()
+                .or(item.price_error.as_ref())
+                .map(|error| format!("Row {}: {}", idx + 1, error))
+        })
+    }
+
+    pub fn to_database_data(&self, cust_id: &str, estimate_number: usize) -> Vec<DatabaseData> {
+        self.items
+            .iter()
+            .enumerate()
+            .map(|(row_number, item)| DatabaseData {
+                entry_id: format!("{}-{}-{}", cust_id, estimate_number, row_number),
+                cust_id: cust_id.to_string(),
+                estimate_number,
+                row_number,
+                description: item.description.clone(),
+                quantity: item.quantity,
+                price: item.price,
+                total: item.total(),
+                sku: item.sku.clone(),
+                unit: item.unit.clone(),
+                tax_code: item.tax_code.clone(),
+                kind: item.kind,
+            })
+            .collect()
+    }
+
+    pub fn to_draft_items(&self) -> Vec<DraftItem> {
+        self.items
+            .iter()
+            .map(|item| DraftItem {
+                description: item.description.clone(),
+                quantity_input: item.quantity_input.clone(),
+                price_input: item.price_input.clone(),
+                sku: item.sku.clone(),
+                unit: item.unit.clone(),
+                tax_code: item.tax_code.clone(),
+                kind: item.kind,
+            })
+            .collect()
+    }
+
+    pub fn from_draft_items(data: &[DraftItem]) -> Self {
+        let mut items = Self {
+            items: Vec::new(),
+            next_id: 0,
+        };
+        for row in data {
+            let mut item = items.new_item();
+            item.description = row.description.clone();
+            item.quantity_input = row.quantity_input.clone();
+            item.price_input = row.price_input.clone();
+            item.sku = row.sku.clone();
+            item.unit = row.unit.clone();
+            item.tax_code = row.tax_code.clone();
+            item.kind = row.kind;
+            item.parse();
+            items.items.push(item);
+        }
+        if items.is_empty() {
+            items.push();
+        }
+        items
+    }
+
+    pub fn from_database_data(data: &[DatabaseData]) -> Self {
+        let mut rows = data.to_vec();
+        rows.sort_by_key(|row| row.row_number);
+        let mut items = Self {
+            items: Vec::new(),
+            next_id: 0,
+        };
+        for row in rows {
+            let mut item = items.new_item();
+            item.description = row.description;
+            item.quantity_input = row.quantity.to_string();
+            item.price_input = row.price.to_string();
+            item.sku = row.sku;
+            item.unit = row.unit;
+            item.tax_code = row.tax_code;
+            item.kind = row.kind;
+            item.parse();
+            items.items.push(item);
+        }
+        if items.is_empty() {
+            items.push();
+        }
+        items
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filled(items: &mut LineItems, index: usize, description: &str, quantity: &str, price: &str) {
+        let item = &mut items.items_mut()[index];
+        item.description = description.to_string();
+        item.quantity_input = quantity.to_string();
+        item.price_input = price.to_string();
+        item.parse();
+    }
+
+    #[test]
+    fn test_totals_are_per_row() {
+        let mut items = LineItems::default();
+        items.push();
+        filled(&mut items, 0, "Design", "2", "50");
+        filled(&mut items, 1, "Build", "3", "$1,000");
+
+        assert_eq!(items.items()[0].total(), 100.0);
+        assert_eq!(items.items()[1].total(), 3000.0);
+        assert_eq!(items.net_total(), 3100.0);
+    }
+
+    #[test]
+    fn test_apply_product_fills_row() {
+        let mut items = LineItems::default();
+        let product = Product {
+            sku: "CONS-1".to_string(),
+            name: "Consulting".to_string(),
+            unit: "h".to_string(),
+            price: 120.0,
+            tax_code: "S".to_string(),
+            active: true,
+            ..Default::default()
+        };
+        let quote = Quote {
+            price: product.price,
+            source: PriceSource::Catalogue,
+        };
+        items.items_mut()[0].apply_product(&product, quote);
+        let data = items.to_database_data("ACME", 1);
+
+        assert_eq!(data[0].description, "Consulting");
+        assert_eq!(data[0].total, 120.0);
+        assert_eq!(data[0].sku, Some("CONS-1".to_string()));
+        assert_eq!(data[0].unit, "h");
+    }
+
+    #[test]
+    fn test_reprice_keeps_typed_prices() {
+        let mut items = LineItems::default();
+        items.push();
+        items.push();
+        let catalogue = Quote {
+            price: 100.0,
+            source: PriceSource::Catalogue,
+        };
+        let product = Product {
+            sku: "CONS".to_string(),
+            ..Default::default()
+        };
+        for idx in 0..3 {
+            items.items_mut()[idx].apply_product(&product, catalogue);
+        }
+        items.items_mut()[1].set_cell(PRICE, "95");
+        // as restored from a draft, the source is not stored
+        items.items_mut()[2].price_source = None;
+
+        let changed = items.reprice(|sku| {
+            (sku == "CONS").then_some(Quote {
+                price: 80.0,
+                source: PriceSource::CustomerPrice,
+            })
+        });
+        assert_eq!(changed, vec![0]);
+        assert_eq!(items.items()[0].price, 80.0);
+        assert_eq!(items.items()[1].price, 95.0);
+        assert_eq!(items.items()[1].price_source, None);
+
+        // back to the catalogue, the restored row is recognised by its matching price
+        let changed = items.reprice(|_| Some(catalogue));
+        assert_eq!(changed, vec![0, 2]);
+        assert_eq!(items.items()[2].price_source, Some(PriceSource::Catalogue));
+    }
+
+    #[test]
+    fn test_durations_convert_to_hours() {
+        assert_eq!(parse_duration("2h30m"), Some(2.5));
+        assert_eq!(parse_duration("45m"), Some(0.75));
+        assert_eq!(parse_duration("1.5h"), Some(1.5));
+        assert_eq!(parse_duration("2:15"), Some(2.25));
+        assert_eq!(parse_duration("3"), None);
+        assert_eq!(parse_duration("2:75"), None);
+        assert_eq!(parse_duration("hm"), None);
+
+        let mut items = LineItems::default();
+        filled(&mut items, 0, "Consulting", "1h30m", "100");
+        assert_eq!(items.items()[0].unit, "hours");
+        assert_eq!(items.items()[0].quantity_label(), "1.5 hours");
+        assert_eq!(items.net_total(), 150.0);
+
+        // a row counted in days is not billed 2.5 days for "2h30m"
+        items.items_mut()[0].unit = "days".to_string();
+        items.items_mut()[0].set_cell(QUANTITY, "2h30m");
+        assert_eq!(items.items()[0].quantity_label(), "2.5 hours");
+        items.items_mut()[0].set_cell(QUANTITY, "3");
+        assert_eq!(items.items()[0].quantity_label(), "3 hours");
+    }
+
+    #[test]
+    fn test_invalid_input_reports_error() {
+        let mut items = LineItems::default();
+        filled(&mut items, 0, "Design", "two", "50");
+
+        assert!(!items.items()[0].is_valid());
+        assert_eq!(items.items()[0].total(), 0.0);
+        assert_eq!(
+            items.first_error(),
+            Some("Row 1: Quantity 'two' is not a number".to_string())
+        );
+    }
+
+    #[test]
+    fn test_insert_remove_and_reorder() {
+        let mut items = LineItems::default();
+        items.push();
+        items.push();
+        filled(&mut items, 0, "a", "1", "1");
+        filled(&mut items, 1, "b", "1", "1");
+        filled(&mut items, 2, "c", "1", "1");
+
+        items.move_item(0, 2);
+        let order: Vec<&str> = items
+            .items()
+            .iter()
+            .map(|i| i.description.as_str())
+            .collect();
+        assert_eq!(order, vec!["b", "c", "a"]);
+
+        items.insert(1);
+        assert_eq!(items.items()[1].description, "");
+        let removed = items.remove(0).unwrap();
+        assert_eq!(removed.description, "b");
+        assert_eq!(items.len(), 3);
+        // ids stay unique after inserting
+        let mut ids: Vec<u64> = items.items().iter().map(|i| i.id).collect();
+        ids.dedup();
+        assert_eq!(ids.len(), 3);
+    }
+
+    #[test]
+    fn test_paste_block_fills_and_appends_rows() {
+        let mut items = LineItems::default();
+        items.push();
+        let block = "Design\t2\thours\t50\r\nBuild\t1h30m\t\t100\r\nHosting\t12\tmonths\t5\n";
+        let (edited, appended) = items.paste_block(1, DESCRIPTION, block);
+
+        assert_eq!(edited, vec![1]);
+        assert_eq!(appended, 2);
+        assert_eq!(items.len(), 4);
+        assert_eq!(items.items()[1].description, "Design");
+        assert_eq!(items.items()[2].quantity, 1.5);
+        assert_eq!(items.items()[2].unit, "hours");
+        assert_eq!(items.items()[3].unit, "months");
+        assert_eq!(items.net_total(), 100.0 + 150.0 + 60.0);
+
+        // a single column pasted further right only touches that column
+        items.paste_block(0, PRICE, "10\n20");
+        assert_eq!(items.items()[0].price, 10.0);
+        assert_eq!(items.items()[1].description, "Design");
+        assert_eq!(items.items()[1].price, 20.0);
+    }
+
+    #[test]
+    fn test_duplicate_gets_new_id() {
+        let mut items = LineItems::default();
+        filled(&mut items, 0, "Design", "2", "50");
+        assert_eq!(items.duplicate(0), Some(1));
+        assert_eq!(items.items()[1].description, "Design");
+        assert_ne!(items.items()[0].id, items.items()[1].id);
+        assert_eq!(items.duplicate(5), None);
+    }
+
+    #[test]
+    fn test_sections_and_subtotals() {
+        let mut items = LineItems::default();
+        items.insert_kind(0, LineKind::Heading);
+        items.items_mut()[0].description = "Phase 1".to_string();
+        filled(&mut items, 1, "Design", "2", "50");
+        items.push();
+        filled(&mut items, 2, "Build", "1", "100");
+        let subtotal = items.insert_kind(3, LineKind::Subtotal);
+        let heading = items.insert_kind(4, LineKind::Heading);
+        items.push();
+        filled(&mut items, 5, "Hosting", "1", "10");
+
+        assert_eq!(items.subtotal_at(subtotal), 200.0);
+        assert_eq!(items.subtotal_at(heading + 2), 10.0);
+        // headings and subtotals don't add to the grand total
+        assert_eq!(items.net_total(), 210.0);
+        assert_eq!(items.items()[subtotal].total(), 0.0);
+
+        let sections = items.sections();
+        assert_eq!(sections.len(), 2);
+        assert_eq!(sections[0].title, "Phase 1");
+        assert_eq!(sections[0].rows, 0..4);
+        assert_eq!(sections[0].total, 200.0);
+        assert_eq!(sections[1].total, 10.0);
+        assert_eq!(items.section_total(heading), 10.0);
+
+        let restored = LineItems::from_draft_items(&items.to_draft_items());
+        assert_eq!(restored.items()[subtotal].kind, LineKind::Subtotal);
+        let data = items.to_database_data("ACME", 1);
+        assert_eq!(data[0].kind, LineKind::Heading);
+    }
+
+    #[test]
+    fn test_rows_above_first_heading_are_a_section() {
+        let mut items = LineItems::default();
+        filled(&mut items, 0, "Setup", "1", "25");
+        let heading = items.insert_kind(1, LineKind::Heading);
+        items.push();
+        filled(&mut items, 2, "Build", "1", "100");
+
+        let sections = items.sections();
+        assert_eq!(sections[0].title, UNTITLED_SECTION);
+        assert_eq!(sections[0].total, 25.0);
+        // a blank heading still starts its own section
+        assert_eq!(sections[1].title, UNTITLED_SECTION);
+        assert_eq!(sections[1].rows, heading..3);
+    }
+
+    #[test]
+    fn test_description_bullets() {
+        let mut items = LineItems::default();
+        items.items_mut()[0].description =
+            "Website build\nResponsive layout\n- Home page\n  * Contact form\n\n".to_string();
+        let (text, bullets) = items.items()[0].description_lines();
+
+        assert_eq!(text, vec!["Website build", "Responsive layout"]);
+        assert_eq!(bullets, vec!["Home page", "Contact form"]);
+    }
+
+    #[test]
+    fn test_database_round_trip() {
+        let mut items = LineItems::default();
+        filled(&mut items, 0, "Design", "2", "50");
+        let data = items.to_database_data("ACME", 3);
+
+        assert_eq!(data[0].entry_id, "ACME-3-0");
+        assert_eq!(data[0].total, 100.0);
+        let restored = LineItems::from_database_data(&data);
+        assert_eq!(restored.items()[0].description, "Design");
+        assert_eq!(restored.net_total(), 100.0);
+    }
+
+    #[test]
+    fn test_draft_keeps_raw_input() {
+        let mut items = LineItems::default();
+        filled(&mut items, 0, "Design", "two", "50");
+        let restored = LineItems::from_draft_items(&items.to_draft_items());
+
+        assert_eq!(restored.items()[0].quantity_input, "two");
+        assert_eq!(restored.first_error(), items.first_error());
+    }
+}
@@ -0,0 +1,600 @@
+//! Emailing issued invoices over SMTP. Every contact has its own sender address and message
+//! templates while the server is shared, see `MailSettings`. The PDF is attached as it was
+//! recorded when the invoice was issued, and a sent message is logged and marks the invoice sent.
+
+use std::fmt;
+use std::fs;
+use std::net::IpAddr;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use chrono::NaiveDateTime;
+use lettre::address::AddressError;
+use lettre::message::header::ContentType;
+use lettre::message::{Attachment, Mailbox, Mailboxes, MultiPart, SinglePart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::transport::smtp::client::{Tls, TlsParameters};
+use lettre::{Message, SmtpTransport, Transport};
+
+use crate::accounting;
+use crate::db;
+use crate::settings::{MailSecurity, MailSettings, Settings};
+use crate::structs::{Contact, Customer, Invoice, MailSender, SentMail};
+
+const SMTP_TIMEOUT: Duration = Duration::from_secs(30);
+const SENT_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+pub const DEFAULT_SUBJECT: &str = "Invoice {invoice_number} from {sender}";
+pub const DEFAULT_BODY: &str = "Hello,
+
+please find attached invoice {invoice_number} of {issue_date} over {amount} {currency}, due on {due_date}.
+
+Kind regards,
+{sender_name}
+{sender}
+";
+
+// what the templates can use, written as `{name}`
+pub const PLACEHOLDERS: [(&str, &str); 8] = [
+    ("customer", "company of the customer"),
+    ("invoice_number", "the invoice id, e.g. ACME-7"),
+    ("issue_date", "date the invoice was issued"),
+    ("due_date", "issue date plus the due days from the settings"),
+    ("amount", "grand total with two decimals"),
+    ("currency", "currency code from the settings"),
+    ("sender", "company of the contact sending"),
+    ("sender_name", "name of the contact sending"),
+];
+
+#[derive(Debug)]
+pub enum MailError {
+    Config(String),
+    // a `{name}` in a template that is not one of the placeholders
+    Template(String),
+    Address(String),
+    Attachment(String),
+    Message(lettre::error::Error),
+    Smtp(lettre::transport::smtp::Error),
+}
+
+impl fmt::Display for MailError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MailError::Config(reason) => write!(f, "{}", reason),
+            MailError::Template(name) => {
+                write!(f, "Unknown placeholder {{{}}} in the template", name)
+            }
+            MailError::Address(reason) => write!(f, "Invalid address {}", reason),
+            MailError::Attachment(reason) => write!(f, "{}", reason),
+            MailError::Message(e) => write!(f, "Unable to write the message: {}", e),
+            MailError::Smtp(e) => write!(f, "Sending failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for MailError {}
+
+impl From<lettre::error::Error> for MailError {
+    fn from(e: lettre::error::Error) -> Self {
+        MailError::Message(e)
+    }
+}
+
+impl From<lettre::transport::smtp::Error> for MailError {
+    fn from(e: lettre::transport::smtp::Error) -> Self {
+        MailError::Smtp(e)
+    }
+}
+
+// the message for one invoice, written from the sender's templates and edited before sending
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Email {
+    // several addresses are separated by commas
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+// a message ready to go out together with the log entry it leaves once it has
+#[derive(Clone, Debug)]
+pub struct Outgoing {
+    message: Message,
+    pub log: SentMail,
+}
+
+// the sender used for a contact that has not been set up yet
+pub fn default_sender(contact: &Contact) -> MailSender {
+    MailSender {
+        contact: contact.company.clone(),
+        from_name: contact.company.clone(),
+        from_address: contact.email.clone(),
+        subject: DEFAULT_SUBJECT.to_string(),
+        body: DEFAULT_BODY.to_string(),
+        ..Default::default()
+    }
+}
+
+pub fn template_values(
+    invoice: &Invoice,
+    contact: &Contact,
+    settings: &Settings,
+) -> Vec<(&'static str, String)> {
+    vec![
+        ("customer", invoice.customer.clone()),
+        ("invoice_number", invoice.invoice_id.clone()),
+        ("issue_date", invoice.issue_date.clone()),
+        (
+            "due_date",
+            accounting::due_date(&invoice.issue_date, &settings.accounts, "%Y-%m-%d"),
+        ),
+        ("amount", format!("{:.2}", invoice.grand_total)),
+        ("currency", settings.currency.clone()),
+        ("sender", contact.company.clone()),
+        ("sender_name", contact.name.clone()),
+    ]
+}
+
+// replaces every `{name}` with its value. Braces around anything but a lower case name are left
+// alone, an unknown name is an error so a typo is not mailed to the customer.
+pub fn render(template: &str, values: &[(&str, String)]) -> Result<String, MailError> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let name = after.find('}').map(|end| &after[..end]).filter(|name| {
+            !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        });
+        match name {
+            Some(name) => {
+                let (_, value) = values
+                    .iter()
+                    .find(|(key, _)| *key == name)
+                    .ok_or_else(|| MailError::Template(name.to_string()))?;
+                out.push_str(value);
+                rest = &after[name.len() + 1..];
+            }
+            None => {
+                out.push('{');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+// the message as the sender's templates write it for `invoice`
+pub fn compose(
+    invoice: &Invoice,
+    customer: &Customer,
+    contact: &Contact,
+    sender: &MailSender,
+    settings: &Settings,
+) -> Result<Email, MailError> {
+    let values = template_values(invoice, contact, settings);
+    Ok(Email {
+        to: customer.email.clone(),
+        subject: render(&sender.subject, &values)?,
+        body: render(&sender.body, &values)?,
+    })
+}
+
+// builds the message with the issued PDF attached. The file has to be the one recorded when the
+// invoice was issued, a changed or missing file is refused.
+pub fn prepare(
+    invoice: &Invoice,
+    sender: &MailSender,
+    email: &Email,
+    now: NaiveDateTime,
+) -> Result<Outgoing, MailError> {
+    let path = invoice.file_path.as_deref().ok_or_else(|| {
+        MailError::Attachment(format!(
+            "Invoice {} has no PDF on record",
+            invoice.invoice_id
+        ))
+    })?;
+    let pdf = read_issued_pdf(Path::new(path), invoice)?;
+
+    let from = Mailbox::new(
+        Some(sender.from_name.trim().to_string()).filter(|name| !name.is_empty()),
+        parse_address(&sender.from_address)?,
+    );
+    let to: Mailboxes = parse_address(&email.to)?;
+    if to.iter().next().is_none() {
+        return Err(MailError::Address("missing, enter a recipient".to_string()));
+    }
+
+    let message_id = message_id(&invoice.invoice_id, &from, now);
+    let mut builder = Message::builder()
+        .from(from.clone())
+        .subject(email.subject.trim())
+        .message_id(Some(message_id.clone()));
+    for mailbox in to.iter() {
+        builder = builder.to(mailbox.clone());
+    }
+    if !sender.reply_to.trim().is_empty() {
+        builder = builder.reply_to(parse_address(&sender.reply_to)?);
+    }
+    if !sender.bcc.trim().is_empty() {
+        let bcc: Mailboxes = parse_address(&sender.bcc)?;
+        for mailbox in bcc.iter() {
+            builder = builder.bcc(mailbox.clone());
+        }
+    }
+    let file_name = Path::new(path).file_name().map_or_else(
+        || "invoice.pdf".to_string(),
+        |name| name.to_string_lossy().to_string(),
+    );
+    let message = builder.multipart(
+        MultiPart::mixed()
+            .singlepart(SinglePart::plain(email.body.clone()))
+            .singlepart(
+                Attachment::new(file_name)
+                    .body(pdf, ContentType::parse("application/pdf").unwrap()),
+            ),
+    )?;
+    Ok(Outgoing {
+        message,
+        log: SentMail {
+            invoice_id: invoice.invoice_id.clone(),
+            sent_at: now.format(SENT_FORMAT).to_string(),
+            sender: from.to_string(),
+            recipients: to.to_string(),
+            subject: email.subject.trim().to_string(),
+            message_id,
+            ..Default::default()
+        },
+    })
+}
+
+// a single address or a comma separated list, with or without display names
+fn parse_address<T: FromStr<Err = AddressError>>(value: &str) -> Result<T, MailError> {
+    value
+        .trim()
+        .parse()
+        .map_err(|e| MailError::Address(format!("\"{}\": {}", value.trim(), e)))
+}
+
+fn read_issued_pdf(path: &Path, invoice: &Invoice) -> Result<Vec<u8>, MailError> {
+    let missing = |e: std::io::Error| {
+        MailError::Attachment(format!("Unable to read {}: {}", path.display(), e))
+    };
+    let hash = db::hash_file(path).map_err(missing)?;
+    if invoice
+        .file_hash
+        .as_ref()
+        .is_some_and(|issued| *issued != hash)
+    {
+        return Err(MailError::Attachment(format!(
+            "{} was changed after invoice {} was issued",
+            path.display(),
+            invoice.invoice_id
+        )));
+    }
+    fs::read(path).map_err(missing)
+}
+
+// unique per message and traceable to the invoice in the recipient's mail headers
+fn message_id(invoice_id: &str, from: &Mailbox, now: NaiveDateTime) -> String {
+    let invoice_id: String = invoice_id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    format!(
+        "<invoicy.{}.{}@{}>",
+        invoice_id,
+        now.format("%Y%m%d%H%M%S%3f"),
+        from.email.domain()
+    )
+}
+
+pub fn transport(settings: &MailSettings, password: &str) -> Result<SmtpTransport, MailError> {
+    let host = settings.host.trim();
+    if host.is_empty() {
+        return Err(MailError::Config(
+            "Set up the mail server first".to_string(),
+        ));
+    }
+    let login = !settings.user.trim().is_empty();
+    if login && settings.security == MailSecurity::None && !is_local(host) {
+        return Err(MailError::Config(
+            "A password is only sent over STARTTLS or TLS".to_string(),
+        ));
+    }
+    let tls = match settings.security {
+        MailSecurity::StartTls => Tls::Required(TlsParameters::new(host.to_string())?),
+        MailSecurity::Tls => Tls::Wrapper(TlsParameters::new(host.to_string())?),
+        MailSecurity::None => Tls::None,
+    };
+    let mut builder = SmtpTransport::builder_dangerous(host)
+        .port(settings.port)
+        .tls(tls)
+        .timeout(Some(SMTP_TIMEOUT));
+    if login {
+        builder = builder.credentials(Credentials::new(
+            settings.user.trim().to_string(),
+            password.to_string(),
+        ));
+    }
+    Ok(builder.build())
+}
+
+fn is_local(host: &str) -> bool {
+    host.eq_ignore_ascii_case("localhost")
+        || host
+            .trim_matches(|c| c == '[' || c == ']')
+            .parse::<IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback())
+}
+
+// sends the message and returns its log entry with the server's answer filled in
+pub fn send(
+    settings: &MailSettings,
+    password: &str,
+    outgoing: Outgoing,
+) -> Result<SentMail, MailError> {
+    let response = transport(settings, password)?.send(&outgoing.message)?;
+    let answer: Vec<&str> = response.message().collect();
+    Ok(SentMail {
+        response: format!("{} {}", response.code(), answer.join(" ")),
+        ..outgoing.log
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{NewInvoice, Store};
+    use crate::structs::{DatabaseData, InvoiceStatus};
+    use crate::test_support::{at, issue};
+    use base64::Engine;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::path::PathBuf;
+    use std::thread::JoinHandle;
+
+    // a local SMTP server that takes one connection and returns everything the client sent
+    fn sink(starttls: bool) -> (u16, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut transcript = String::new();
+            writer.write_all(b"220 sink ESMTP\r\n").unwrap();
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap_or(0) > 0 {
+                transcript.push_str(&line);
+                let command = line.to_ascii_uppercase();
+                let reply: &[u8] = if command.starts_with("EHLO") {
+                    if starttls {
+                        b"250-sink\r\n250-STARTTLS\r\n250 AUTH PLAIN LOGIN\r\n"
+                    } else {
+                        b"250-sink\r\n250-8BITMIME\r\n250 AUTH PLAIN LOGIN\r\n"
+                    }
+                } else if command.starts_with("AUTH") {
+                    b"235 2.7.0 Authentication successful\r\n"
+                } else if command.starts_with("DATA") {
+                    writer
+                        .write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n")
+                        .unwrap();
+                    loop {
+                        line.clear();
+                        reader.read_line(&mut line).unwrap();
+                        transcript.push_str(&line);
+                        if line == ".\r\n" {
+                            break;
+                        }
+                    }
+                    b"250 2.0.0 Ok: queued as 4F2A9\r\n"
+                } else if command.starts_with("QUIT") {
+                    writer.write_all(b"221 2.0.0 Bye\r\n").unwrap();
+                    break;
+                } else {
+                    b"250 2.0.0 Ok\r\n"
+                };
+                writer.write_all(reply).unwrap();
+                line.clear();
+            }
+            transcript
+        });
+        (port, handle)
+    }
+
+    fn issued(name: &str, pdf: &[u8]) -> (Store, Invoice, PathBuf) {
+        let mut store = Store::open_in_memory().unwrap();
+        let invoice = NewInvoice {
+            cust_id: "ACME".to_string(),
+            customer: Customer {
+                company: "Acme".to_string(),
+                email: "ap@acme.example".to_string(),
+                ..Default::default()
+            },
+            contact_company: "Fake Co.".to_string(),
+            items: vec![DatabaseData {
+                row_number: 1,
+                description: "Design".to_string(),
+                quantity: 2.0,
+                price: 60.25,
+                total: 120.5,
+                ..Default::default()
+            }],
+            text: Default::default(),
+        };
+        let (issued, path) = issue(&mut store, &invoice, &format!("mail-{}", name), pdf);
+        let invoice = store.invoice(&issued.invoice_id).unwrap().unwrap();
+        (store, invoice, path)
+    }
+
+    fn contact() -> Contact {
+        Contact {
+            company: "Fake Co.".to_string(),
+            name: "Fake Smith".to_string(),
+            email: "billing@fake.example".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn local(port: u16) -> MailSettings {
+        MailSettings {
+            host: "127.0.0.1".to_string(),
+            port,
+            security: MailSecurity::None,
+            user: "billing".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let values = vec![
+            ("customer", "Acme".to_string()),
+            ("amount", "120.50".to_string()),
+        ];
+        assert_eq!(
+            render("Dear {customer}, {amount} { x } {} {Upper}", &values).unwrap(),
+            "Dear Acme, 120.50 { x } {} {Upper}"
+        );
+        assert!(matches!(
+            render("Dear {custmer}", &values),
+            Err(MailError::Template(name)) if name == "custmer"
+        ));
+
+        let (_, invoice, path) = issued("render", b"%PDF-1.4");
+        let mut settings = Settings::default();
+        settings.accounts.due_days = 14;
+        let email = compose(
+            &invoice,
+            &Customer {
+                email: "ap@acme.example".to_string(),
+                ..Default::default()
+            },
+            &contact(),
+            &default_sender(&contact()),
+            &settings,
+        )
+        .unwrap();
+        assert_eq!(email.to, "ap@acme.example");
+        assert_eq!(email.subject, "Invoice ACME-1 from Fake Co.");
+        let due = accounting::due_date(&invoice.issue_date, &settings.accounts, "%Y-%m-%d");
+        assert!(email
+            .body
+            .contains(&format!("over 120.50 EUR, due on {}.", due)));
+        assert!(email.body.ends_with("Fake Smith\nFake Co.\n"));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_send_to_local_sink() {
+        // the binary marker line real PDFs start with makes the attachment base64 encoded
+        let pdf = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\ntest invoice";
+        let (mut store, invoice, path) = issued("send", pdf);
+        let sender = MailSender {
+            reply_to: "office@fake.example".to_string(),
+            bcc: "archive@fake.example".to_string(),
+            ..default_sender(&contact())
+        };
+        let email = Email {
+            to: "ap@acme.example, Jane Doe <jane@acme.example>".to_string(),
+            subject: "Invoice ACME-1".to_string(),
+            body: "Please find the invoice attached.".to_string(),
+        };
+        let outgoing = prepare(&invoice, &sender, &email, at(9)).unwrap();
+        let (port, sink) = sink(false);
+        let sent = send(&local(port), "secret", outgoing).unwrap();
+        let transcript = sink.join().unwrap();
+
+        let auth = base64::engine::general_purpose::STANDARD.encode("\0billing\0secret");
+        assert!(transcript.contains(&format!("AUTH PLAIN {}", auth)));
+        assert!(transcript.contains("MAIL FROM:<billing@fake.example>"));
+        for recipient in [
+            "ap@acme.example",
+            "jane@acme.example",
+            "archive@fake.example",
+        ] {
+            assert!(transcript.contains(&format!("RCPT TO:<{}>", recipient)));
+        }
+        assert!(transcript.contains("Subject: Invoice ACME-1\r\n"));
+        assert!(transcript.contains("Reply-To: office@fake.example\r\n"));
+        // the blind copy is only in the envelope
+        assert!(!transcript.contains("Bcc:"));
+        assert!(transcript.contains("attachment;") && transcript.contains("invoicy-mail-send"));
+        assert!(transcript.contains(&base64::engine::general_purpose::STANDARD.encode(pdf)));
+
+        assert_eq!(sent.response, "250 2.0.0 Ok: queued as 4F2A9");
+        assert_eq!(sent.sender, "\"Fake Co.\" <billing@fake.example>");
+        assert_eq!(
+            sent.recipients,
+            "ap@acme.example, Jane Doe <jane@acme.example>"
+        );
+        assert_eq!(
+            sent.message_id,
+            "<invoicy.ACME-1.20240301090000000@fake.example>"
+        );
+        assert!(transcript.contains(&format!("Message-ID: {}\r\n", sent.message_id)));
+        store.record_sent_mail(&sent).unwrap();
+        assert_eq!(
+            store.invoice("ACME-1").unwrap().unwrap().status,
+            InvoiceStatus::Sent
+        );
+        assert_eq!(
+            store.sent_mail("ACME-1").unwrap()[0].response,
+            sent.response
+        );
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_refuses_unsafe_sends() {
+        let (_, invoice, path) = issued("refuse", b"%PDF-1.4");
+        let sender = default_sender(&contact());
+        let email = Email {
+            to: "ap@acme.example".to_string(),
+            subject: "Invoice".to_string(),
+            body: String::new(),
+        };
+
+        // STARTTLS is required, a server without it is not talked to in plain text
+        let (port, sink) = sink(false);
+        let settings = MailSettings {
+            security: MailSecurity::StartTls,
+            ..local(port)
+        };
+        let outgoing = prepare(&invoice, &sender, &email, at(9)).unwrap();
+        assert!(matches!(
+            send(&settings, "secret", outgoing),
+            Err(MailError::Smtp(_))
+        ));
+        assert!(!sink.join().unwrap().contains("AUTH"));
+
+        let remote = MailSettings {
+            host: "smtp.example.com".to_string(),
+            ..local(25)
+        };
+        assert!(matches!(
+            transport(&remote, "secret"),
+            Err(MailError::Config(_))
+        ));
+
+        let no_recipient = Email {
+            to: String::new(),
+            ..email.clone()
+        };
+        assert!(matches!(
+            prepare(&invoice, &sender, &no_recipient, at(9)),
+            Err(MailError::Address(_))
+        ));
+
+        fs::write(&path, b"%PDF-1.4 edited").unwrap();
+        assert!(matches!(
+            prepare(&invoice, &sender, &email, at(9)),
+            Err(MailError::Attachment(_))
+        ));
+        fs::remove_file(&path).unwrap();
+    }
+}
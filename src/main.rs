@@ -1,50 +1,136 @@
 mod components;
-mod document;
 mod functions;
-mod structs;
 
-use eframe::egui::{self, Rect, Response};
+use eframe::egui;
 use egui::{Style, Vec2};
 use functions::*;
-use rusqlite::Connection;
+use invoicy::backup::ValidatedBackup;
+use invoicy::db::{self, Store};
+use invoicy::encryption::{self, EncryptionError};
+use invoicy::export::{self, ExportFormat, ExportRequest};
+use invoicy::font_registry::FontRegistry;
+use invoicy::history::History;
+use invoicy::import::{DuplicatePolicy, Field, ImportSource, ImportTarget};
+use invoicy::line_items::LineItems;
+use invoicy::mail::Email;
+use invoicy::paths;
+use invoicy::remote::Credentials;
+use invoicy::settings::Settings;
+use invoicy::structs::*;
 use std::path::PathBuf;
-use structs::*;
+use std::sync::mpsc::Receiver;
+use std::time::Instant;
 
 const LOGGER: bool = false;
 
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    match export::parse_args(&args) {
+        Ok(Some(request)) => std::process::exit(export_from_command_line(&request)),
+        Ok(None) => {}
+        Err(e) => {
+            println!("ERROR: {}\n{}", e, export::USAGE);
+            std::process::exit(2);
+        }
+    }
     egui_logger::builder().init().unwrap();
     let options = eframe::NativeOptions {
         // with_icon causes crashes on application when using 'cargo build' or 'cargo release'
         viewport: egui::ViewportBuilder::default()
-            .with_icon(load_icon("support/images/128x128.png"))
+            .with_icon(load_icon(paths::APP_ICON))
             .with_inner_size([450.0, 320.0]),
         ..Default::default()
     };
 
+    let app = match Invoicy::new() {
+        Ok(app) => app,
+        Err(e) => {
+            println!("ERROR: {}", e);
+            rfd::MessageDialog::new()
+                .set_level(rfd::MessageLevel::Error)
+                .set_title("Invoicy")
+                .set_description(&e)
+                .show();
+            std::process::exit(1);
+        }
+    };
     let _ = eframe::run_native(
         "Invoicy",
         options,
         Box::new(|_cc| {
             // This gives us image support:
-            Ok(Box::<Invoicy>::default())
+            Ok(Box::new(app))
         }),
     );
 }
 
+// `invoicy export ...` writes the export and exits without opening a window
+fn export_from_command_line(request: &ExportRequest) -> i32 {
+    let settings = Settings::load();
+    let database_path = paths::database_path(&settings);
+    // an export never creates an empty database
+    if !database_path.exists() {
+        println!("ERROR: No database at {:?}", database_path);
+        return 1;
+    }
+    let passphrase = std::env::var(encryption::PASSPHRASE_ENV_VAR).ok();
+    let store = match encryption::open(&database_path, passphrase.as_deref()) {
+        Ok(store) => store,
+        Err(EncryptionError::Locked) => {
+            println!(
+                "ERROR: {:?} is encrypted, set {} to its passphrase",
+                database_path,
+                encryption::PASSPHRASE_ENV_VAR
+            );
+            return 1;
+        }
+        Err(e) => {
+            println!("ERROR: Unable to open database {:?}: {}", database_path, e);
+            return 1;
+        }
+    };
+    match export::write_export(
+        &store,
+        &request.filter,
+        request.format,
+        &settings,
+        &request.output,
+    ) {
+        Ok(files) => {
+            for file in files {
+                println!("LOG: Exported {:?}", file);
+            }
+            0
+        }
+        Err(e) => {
+            println!("ERROR: {}", e);
+            1
+        }
+    }
+}
+
 impl eframe::App for Invoicy {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        if LOGGER == true {
+        if LOGGER {
             egui::Window::new("Log").show(ctx, |ui| {
                 // draws the logger ui.
                 egui_logger::logger_ui().show(ui);
             });
         }
+        if self.locked {
+            self.show_unlock(ctx);
+            return;
+        }
         self.initialize_application();
+        self.show_error(ctx);
+        self.show_recovery_prompt(ctx);
+        self.history_shortcuts(ctx);
 
         // constantly updating actions
         self.update_estimate_number();
         self.update_file_name();
+        self.update_text_defaults();
+        self.update_prices();
 
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
             ui.add_space(2.0);
@@ -59,9 +145,19 @@ impl eframe::App for Invoicy {
                     println!("{:?}", "template button not yet functional");
                 }
                 self.upload_logo(ui);
+                self.drafts_button(ui);
                 self.customer_and_contact_buttons(ui);
             });
             self.show_form(ui);
+            self.show_settings(ui);
+            self.show_products(ui);
+            self.show_drafts(ui);
+            self.show_payments(ui);
+            self.show_export(ui);
+            self.show_import(ui);
+            self.show_backup(ui);
+            self.show_encryption(ui);
+            self.show_mail(ui);
 
             ui.add_space(2.0);
         });
@@ -77,6 +173,7 @@ impl eframe::App for Invoicy {
             ui.spacing();
 
             self.table(ui);
+            self.invoice_text_form(ui);
 
             ui.add_space(2.0);
         });
@@ -93,28 +190,76 @@ impl eframe::App for Invoicy {
             });
             ui.add_space(2.0);
         });
+
+        self.schedule_autosave(ctx);
+        self.schedule_backup(ctx);
+        self.poll_mail_sending(ctx);
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        // nothing has been loaded from a database that was never unlocked
+        if self.locked {
+            return;
+        }
+        self.autosave_draft(true);
     }
 }
 
-impl Default for Invoicy {
-    fn default() -> Self {
-        Self {
+impl Invoicy {
+    // fails when the database, which can come from --database, INVOICY_DB or the settings,
+    // can't be opened
+    fn new() -> Result<Self, String> {
+        let settings = Settings::load();
+        let database_path = paths::database_path(&settings);
+        paths::ensure_parent_dir(&database_path);
+        println!("LOG: Using database at {:?}", database_path);
+        // an encrypted database is opened once its passphrase is entered
+        let locked = db::is_encrypted_file(&database_path);
+        let store = if locked {
+            Store::open_in_memory()
+        } else {
+            Store::open(&database_path)
+        }
+        .map_err(|e| format!("Unable to open the database {:?}: {}", database_path, e))?;
+        Ok(Self {
             max_file_size: 15360,
             image_file_path: Some(PathBuf::new()),
             company_error_contact: Some("".to_string()),
             company_error_customer: Some("".to_string()),
             initialized: false,
             progress: 0.0,
-            connection: Connection::open("invoicy.db").unwrap(),
+            store,
+            locked,
+            unlock_input: String::new(),
+            unlock_message: None,
+            database_path_input: database_path.display().to_string(),
+            database_path,
+            settings,
+            settings_form: false,
+            error_message: None,
+            status_message: None,
+            font_registry: FontRegistry::discover(),
             style: Style::default(),
             file_name: "invoice.pdf".to_string(),
             customer_selected: 0,
             contact_selected: 0,
-            table_data: [].to_vec(),
+            line_items: LineItems::default(),
+            history: History::default(),
+            invoice_text: InvoiceText::default(),
+            summary_only: false,
+            e_invoice: false,
+            factur_x: false,
+            text_defaults_for: None,
+            prices_for: None,
+            catalogue_version: 0,
+            drafts: [].to_vec(),
+            drafts_form: false,
+            draft_id: 0,
+            saved_draft: None,
+            draft_changed_at: None,
+            recovery_prompt: None,
+            edit_focus: None,
             database_data_vec: [].to_vec(),
-            new_database_data_vec: [].to_vec(),
-            row_count: 1,
-            last_updated_row: 0,
             contact: Contact {
                 company: "Fake Co.".to_string(),
                 address: "1111 Fake Ave.".to_string(),
@@ -125,6 +270,7 @@ impl Default for Invoicy {
                 telephone: "111-111-1111".to_string(),
                 email: "fake@fake.com".to_string(),
                 website: "fake.fake".to_string(),
+                ..Default::default()
             },
             contacts: [].to_vec(),
             contact_form: false,
@@ -134,44 +280,84 @@ impl Default for Invoicy {
                 city: "Fakeshire".to_string(),
                 postal_code: "F4K 3A3".to_string(),
                 country: "Fakeland".to_string(),
+                ..Default::default()
             },
             customers: [].to_vec(),
             customer_form: false,
-            current_row_value: DatabaseData {
-                entry_id: "FAKE-1-0".to_string(),
-                cust_id: "FAKE".to_string(),
-                row_number: 0,
-                description: "write something down".to_string(),
-                quantity: 1.0,
-                price: 10.0,
-                total: 10.0,
-                estimate_number: 1,
+            products: [].to_vec(),
+            product: Product {
+                active: true,
+                ..Default::default()
             },
-            grand_total: 0.0,
-            totals: [].to_vec(),
-        }
+            product_form: false,
+            product_price_input: String::new(),
+            product_error: None,
+            customer_prices: [].to_vec(),
+            price_rule_sku: None,
+            price_rule_percent: true,
+            price_rule_input: String::new(),
+            invoices: [].to_vec(),
+            payments: [].to_vec(),
+            payments_form: false,
+            payment: Payment {
+                paid_on: chrono::Local::now().format("%Y-%m-%d").to_string(),
+                ..Default::default()
+            },
+            payment_amount_input: String::new(),
+            payment_error: None,
+            export_form: false,
+            export_from: String::new(),
+            export_to: String::new(),
+            export_customer: None,
+            export_format: ExportFormat::Json,
+            export_message: None,
+            import_form: false,
+            import_target: ImportTarget::Customers,
+            import_source: None,
+            import_mapping: [].to_vec(),
+            import_policy: DuplicatePolicy::Skip,
+            import_message: None,
+            backup_form: false,
+            backup_dir_input: String::new(),
+            backup_message: None,
+            backup_restore: None,
+            backup_locked: None,
+            backup_passphrase_input: String::new(),
+            last_backup_check: None,
+            remote_credentials: Credentials::load(),
+            remote_backups: [].to_vec(),
+            remote_upload: None,
+            remote_message: None,
+            encryption_form: false,
+            passphrase_current: String::new(),
+            passphrase_new: String::new(),
+            passphrase_confirm: String::new(),
+            encryption_message: None,
+            mail_form: false,
+            mail_invoice: None,
+            mail_invoice_record: None,
+            mail_email: Email::default(),
+            mail_sender: MailSender::default(),
+            mail_sender_error: None,
+            mail_log: [].to_vec(),
+            mail_sending: None,
+            mail_message: None,
+            estimate_number: 1,
+        })
     }
 }
 impl Invoicy {
     fn update_estimate_number(&mut self) {
+        let cust_id = self.generate_customer_id(self.customer_selected);
         let curr_estimate_num = self
             .database_data_vec
             .iter()
-            .filter(|x| {
-                x.cust_id
-                    == self.customers[self.customer_selected]
-                        .company
-                        .clone()
-                        .to_uppercase()
-                        .get(0..4)
-                        .unwrap()
-            })
+            .filter(|x| x.cust_id == cust_id)
             .map(|item| item.estimate_number)
             .max();
-        if curr_estimate_num == None {
-            self.current_row_value.estimate_number = 1
-        } else {
-            self.current_row_value.estimate_number = curr_estimate_num.unwrap() + 1
+        match curr_estimate_num {
+            Some(number) => self.estimate_number = number + 1,
+            None => self.estimate_number = 1,
         }
     }
 
@@ -183,25 +369,28 @@ impl Invoicy {
             self.add_contact();
             self.add_customer();
 
-            self.totals.push(Total {
-                value: 0.0,
-                position: (0, 4),
-            });
-
             let contact_log = self.get_contacts();
             let customer_log = self.get_customers();
             let data_log = self.get_data();
+            let product_log = self.get_products();
+            let draft_log = self.get_drafts();
+            let price_log = self.get_customer_prices();
+            let payment_log = self.get_payments();
 
             println!("{:?}", contact_log.unwrap());
             println!("{:?}", customer_log.unwrap());
             println!("{:?}", data_log.unwrap());
+            println!("{:?}", product_log.unwrap());
+            println!("{:?}", draft_log.unwrap());
+            println!("{:?}", price_log.unwrap());
+            println!("{:?}", payment_log.unwrap());
 
             self.style.spacing.button_padding = Vec2::splat(5.0); // Set horizontal and vertical margins
 
             self.file_name = format!(
                 "{}-{:?}",
                 sanitize_string(&self.customer.company),
-                self.current_row_value.estimate_number
+                self.estimate_number
             );
 
             self.initialized = true;
@@ -215,24 +404,115 @@ struct Invoicy {
     company_error_contact: Option<String>,
     company_error_customer: Option<String>,
     initialized: bool,
-    connection: Connection,
+    store: Store,
+    // the database is encrypted and waits for its passphrase, `store` is empty until then
+    locked: bool,
+    unlock_input: String,
+    unlock_message: Option<String>,
+    database_path: PathBuf,
+    database_path_input: String,
+    settings: Settings,
+    settings_form: bool,
+    error_message: Option<String>,
+    status_message: Option<String>,
+    font_registry: FontRegistry,
     progress: f32,
     style: Style,
     file_name: String,
     customer_selected: usize,
     contact_selected: usize,
-    row_count: usize,
-    last_updated_row: usize,
-    table_data: Vec<(String, (usize, i32), (Rect, Response))>,
+    line_items: LineItems,
+    history: History,
+    invoice_text: InvoiceText,
+    summary_only: bool,
+    // also write a Peppol UBL e-invoice next to the pdf
+    e_invoice: bool,
+    // embed the invoice as Factur-X (CII) XML in a PDF/A-3
+    factur_x: bool,
+    // customer and contact whose default notes and terms were last filled in
+    text_defaults_for: Option<(usize, usize)>,
+    // customer and catalogue version the catalogue rows were last priced for, None after the
+    // editor was loaded so its rows are priced again without an undo step
+    prices_for: Option<(usize, u64)>,
+    // bumped whenever products or price rules change
+    catalogue_version: u64,
+    drafts: Vec<Draft>,
+    drafts_form: bool,
+    // 0 until the editor has been saved as a draft
+    draft_id: i64,
+    saved_draft: Option<Draft>,
+    draft_changed_at: Option<Instant>,
+    recovery_prompt: Option<Draft>,
+    edit_focus: Option<egui::Id>,
     database_data_vec: Vec<DatabaseData>,
-    new_database_data_vec: Vec<DatabaseData>,
     contact: Contact,
     contacts: Vec<Contact>,
     contact_form: bool,
     customer: Customer,
     customers: Vec<Customer>,
     customer_form: bool,
-    current_row_value: DatabaseData,
-    totals: Vec<Total>,
-    grand_total: f64,
+    products: Vec<Product>,
+    product: Product,
+    product_form: bool,
+    product_price_input: String,
+    product_error: Option<String>,
+    customer_prices: Vec<CustomerPrice>,
+    // the price list form, a sku of None applies the rule to every product
+    price_rule_sku: Option<String>,
+    price_rule_percent: bool,
+    price_rule_input: String,
+    // issued invoices, for recording payments against
+    invoices: Vec<Invoice>,
+    payments: Vec<Payment>,
+    payments_form: bool,
+    payment: Payment,
+    payment_amount_input: String,
+    payment_error: Option<String>,
+    export_form: bool,
+    // dates as typed, checked when exporting
+    export_from: String,
+    export_to: String,
+    export_customer: Option<String>,
+    export_format: ExportFormat,
+    export_message: Option<String>,
+    import_form: bool,
+    import_target: ImportTarget,
+    import_source: Option<ImportSource>,
+    // a field per CSV column, None leaves the column out
+    import_mapping: Vec<Option<Field>>,
+    import_policy: DuplicatePolicy,
+    import_message: Option<String>,
+    backup_form: bool,
+    backup_dir_input: String,
+    backup_message: Option<String>,
+    // a checked archive waiting for the restore to be confirmed
+    backup_restore: Option<(PathBuf, ValidatedBackup)>,
+    // an encrypted archive that needs a passphrase other than the database's
+    backup_locked: Option<PathBuf>,
+    backup_passphrase_input: String,
+    last_backup_check: Option<Instant>,
+    remote_credentials: Credentials,
+    // archives found on the remote target when last listed
+    remote_backups: Vec<(String, chrono::NaiveDateTime)>,
+    // a background upload, reporting back when done
+    remote_upload: Option<Receiver<String>>,
+    remote_message: Option<String>,
+    encryption_form: bool,
+    passphrase_current: String,
+    passphrase_new: String,
+    passphrase_confirm: String,
+    encryption_message: Option<String>,
+    mail_form: bool,
+    mail_invoice: Option<String>,
+    mail_invoice_record: Option<Invoice>,
+    // the message as it will be sent, written from the templates and edited in the window
+    mail_email: Email,
+    // the sender of the contact that issued the selected invoice
+    mail_sender: MailSender,
+    mail_sender_error: Option<String>,
+    mail_log: Vec<SentMail>,
+    // a message being sent in the background
+    mail_sending: Option<Receiver<Result<SentMail, String>>>,
+    mail_message: Option<String>,
+    estimate_number: usize,
 }
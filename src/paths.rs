@@ -0,0 +1,157 @@
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::settings::Settings;
+
+const APP_DIR_NAME: &str = "Invoicy";
+const DATABASE_FILE_NAME: &str = "invoicy.db";
+const LOGO_FILE_NAME: &str = "logo.jpg";
+const SETTINGS_FILE_NAME: &str = "settings.ini";
+const CREDENTIALS_FILE_NAME: &str = "credentials.ini";
+const BACKUP_DIR_NAME: &str = "backups";
+const TEMPLATES_DIR_NAME: &str = "templates";
+
+pub const DATABASE_ENV_VAR: &str = "INVOICY_DB";
+const DATABASE_FLAGS: [&str; 2] = ["--database", "--db"];
+
+const DEFAULT_LOGO: &[u8] = include_bytes!("../support/images/logo.jpg");
+pub const APP_ICON: &[u8] = include_bytes!("../support/images/128x128.png");
+
+// per-user data directory, e.g. ~/.local/share/Invoicy, ~/Library/Application Support/Invoicy
+// or %APPDATA%\Invoicy. Falls back to the working directory if the platform has none.
+pub fn data_dir() -> PathBuf {
+    let dir = dirs::data_dir()
+        .map(|dir| dir.join(APP_DIR_NAME))
+        .unwrap_or_else(|| PathBuf::from("."));
+    if let Err(e) = fs::create_dir_all(&dir) {
+        println!("ERROR: Unable to create data directory {:?}: {}", dir, e);
+    }
+    dir
+}
+
+pub fn default_database_path() -> PathBuf {
+    data_dir().join(DATABASE_FILE_NAME)
+}
+
+pub fn settings_path() -> PathBuf {
+    data_dir().join(SETTINGS_FILE_NAME)
+}
+
+// passwords and tokens for the remote backup targets, never part of the database or a backup
+pub fn credentials_path() -> PathBuf {
+    data_dir().join(CREDENTIALS_FILE_NAME)
+}
+
+// the logo lives in the data directory so it can be replaced without touching the install.
+// The bundled logo is written out the first time it is needed.
+pub fn logo_path() -> PathBuf {
+    let path = data_dir().join(LOGO_FILE_NAME);
+    if !path.exists() {
+        if let Err(e) = fs::write(&path, DEFAULT_LOGO) {
+            println!("ERROR: Unable to write default logo {:?}: {}", path, e);
+        }
+    }
+    path
+}
+
+// invoice templates live here once they can be edited, backups already include the folder
+pub fn templates_dir() -> PathBuf {
+    data_dir().join(TEMPLATES_DIR_NAME)
+}
+
+// the folder chosen under settings, often on a network share, else next to the database
+pub fn backup_dir(settings: &Settings) -> PathBuf {
+    settings
+        .backup
+        .dir
+        .clone()
+        .unwrap_or_else(|| data_dir().join(BACKUP_DIR_NAME))
+}
+
+// database location priority: command line flag, environment variable, settings file, default
+pub fn resolve_database_path(
+    args: &[String],
+    env_value: Option<OsString>,
+    settings: &Settings,
+) -> PathBuf {
+    if let Some(path) = database_flag(args) {
+        return path;
+    }
+    if let Some(value) = env_value.filter(|value| !value.is_empty()) {
+        return PathBuf::from(value);
+    }
+    if let Some(path) = &settings.database_path {
+        return path.clone();
+    }
+    default_database_path()
+}
+
+pub fn database_path(settings: &Settings) -> PathBuf {
+    let args: Vec<String> = std::env::args().collect();
+    resolve_database_path(&args, std::env::var_os(DATABASE_ENV_VAR), settings)
+}
+
+fn database_flag(args: &[String]) -> Option<PathBuf> {
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        for flag in DATABASE_FLAGS {
+            if arg == flag {
+                return iter.next().map(PathBuf::from);
+            }
+            if let Some(value) = arg.strip_prefix(&format!("{}=", flag)) {
+                return Some(PathBuf::from(value));
+            }
+        }
+    }
+    None
+}
+
+pub fn ensure_parent_dir(path: &Path) {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        if let Err(e) = fs::create_dir_all(parent) {
+            println!("ERROR: Unable to create directory {:?}: {}", parent, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_database_flag_wins() {
+        let settings = Settings {
+            database_path: Some(PathBuf::from("settings.db")),
+            ..Default::default()
+        };
+        let path = resolve_database_path(
+            &args(&["invoicy", "--database", "flag.db"]),
+            Some(OsString::from("env.db")),
+            &settings,
+        );
+        assert_eq!(path, PathBuf::from("flag.db"));
+        let path = resolve_database_path(&args(&["invoicy", "--db=eq.db"]), None, &settings);
+        assert_eq!(path, PathBuf::from("eq.db"));
+    }
+
+    #[test]
+    fn test_env_then_settings() {
+        let settings = Settings {
+            database_path: Some(PathBuf::from("settings.db")),
+            ..Default::default()
+        };
+        let path = resolve_database_path(
+            &args(&["invoicy"]),
+            Some(OsString::from("env.db")),
+            &settings,
+        );
+        assert_eq!(path, PathBuf::from("env.db"));
+        let path = resolve_database_path(&args(&["invoicy"]), None, &settings);
+        assert_eq!(path, PathBuf::from("settings.db"));
+    }
+}
@@ -0,0 +1,994 @@
+//! PDF/A output. genpdf (through printpdf) embeds the fonts but writes PDF/X style metadata, so
+//! the rendered file is reopened with lopdf and given what PDF/A asks for: XMP metadata that
+//! matches the document info, an sRGB output intent and, for Factur-X, the invoice XML as an
+//! associated file. `check` reads a finished file back against the PDF/A-2 and -3 rules our
+//! documents could break.
+
+use chrono::{DateTime, FixedOffset, SecondsFormat};
+use lopdf::{Dictionary, Document, Object, ObjectId, Stream, StringFormat};
+
+use crate::ubl::{escape, Violation};
+
+const CREATOR: &str = "Invoicy";
+const OUTPUT_CONDITION: &str = "sRGB IEC61966-2.1";
+const FACTUR_X_NAMESPACE: &str = "urn:factur-x:pdfa:CrossIndustryDocument:invoice:1p0#";
+
+// written to both the document info and the XMP metadata, readers compare the two
+pub struct Metadata {
+    pub title: String,
+    pub author: String,
+    pub subject: String,
+    // XMP only, the document info has no field for it
+    pub invoice_number: String,
+    pub created: DateTime<FixedOffset>,
+}
+
+// an embedded file, the first one becomes the document's associated file
+struct Attachment<'a> {
+    file_name: &'a str,
+    description: &'a str,
+    mime_type: &'a str,
+    data: &'a [u8],
+}
+
+// A PDF/A-3b with `xml` embedded as factur-x.xml, the hybrid invoice German and French
+// customers expect. `pdf` is a document rendered by genpdf.
+pub fn factur_x(pdf: &[u8], metadata: &Metadata, xml: &str) -> Result<Vec<u8>, lopdf::Error> {
+    convert(
+        pdf,
+        metadata,
+        Some(Attachment {
+            file_name: crate::cii::FILE_NAME,
+            description: "Factur-X invoice",
+            mime_type: "text/xml",
+            data: xml.as_bytes(),
+        }),
+    )
+}
+
+// A PDF/A-2b for long-term archiving, nothing is attached.
+pub fn archive(pdf: &[u8], metadata: &Metadata) -> Result<Vec<u8>, lopdf::Error> {
+    convert(pdf, metadata, None)
+}
+
+fn convert(
+    pdf: &[u8],
+    metadata: &Metadata,
+    attachment: Option<Attachment>,
+) -> Result<Vec<u8>, lopdf::Error> {
+    let mut doc = Document::load_mem(pdf)?;
+    // PDF/A-2 has no attachments other than PDF/A files, arbitrary ones need part 3
+    let part = if attachment.is_some() { 3 } else { 2 };
+
+    let xmp = xmp_packet(metadata, part, attachment.is_some());
+    let metadata_id = doc.add_object(
+        Stream::new(
+            dictionary(vec![
+                ("Type", Object::Name(b"Metadata".to_vec())),
+                ("Subtype", Object::Name(b"XML".to_vec())),
+            ]),
+            xmp.into_bytes(),
+        )
+        .with_compression(false),
+    );
+    let profile_id = doc.add_object(Stream::new(
+        dictionary(vec![("N", Object::Integer(3))]),
+        srgb_profile(),
+    ));
+    let output_intent = dictionary(vec![
+        ("Type", Object::Name(b"OutputIntent".to_vec())),
+        ("S", Object::Name(b"GTS_PDFA1".to_vec())),
+        ("OutputConditionIdentifier", text_string(OUTPUT_CONDITION)),
+        ("Info", text_string(OUTPUT_CONDITION)),
+        ("DestOutputProfile", Object::Reference(profile_id)),
+    ]);
+    let info_id = doc.add_object(dictionary(vec![
+        ("Title", text_string(&metadata.title)),
+        ("Author", text_string(&metadata.author)),
+        ("Subject", text_string(&metadata.subject)),
+        ("Creator", text_string(CREATOR)),
+        ("Producer", text_string(CREATOR)),
+        ("CreationDate", text_string(&pdf_date(&metadata.created))),
+        ("ModDate", text_string(&pdf_date(&metadata.created))),
+    ]));
+    doc.trailer.set("Info", Object::Reference(info_id));
+
+    let file_spec =
+        attachment.map(|attachment| embed_file(&mut doc, &attachment, &metadata.created));
+
+    let catalog_id = doc.trailer.get(b"Root")?.as_reference()?;
+    let catalog = doc.get_object_mut(catalog_id)?.as_dict_mut()?;
+    catalog.set("Metadata", Object::Reference(metadata_id));
+    catalog.set("OutputIntents", Object::Array(vec![output_intent.into()]));
+    if let Some((file_name, spec_id)) = file_spec {
+        catalog.set("AF", Object::Array(vec![Object::Reference(spec_id)]));
+        let embedded_files = dictionary(vec![(
+            "Names",
+            Object::Array(vec![text_string(&file_name), Object::Reference(spec_id)]),
+        )]);
+        catalog.set(
+            "Names",
+            dictionary(vec![("EmbeddedFiles", embedded_files.into())]),
+        );
+    }
+    // printpdf puts every page on a layer, the default layer configuration has to be named
+    if let Ok(config) = catalog
+        .get_mut(b"OCProperties")
+        .and_then(Object::as_dict_mut)
+        .and_then(|properties| properties.get_mut(b"D"))
+        .and_then(Object::as_dict_mut)
+    {
+        config.set("Name", text_string("Layers"));
+    }
+
+    // the glyph ids printpdf writes are the CIDs, PDF/A wants that stated. printpdf keeps the
+    // CID fonts inline in their Type0 font
+    for object in doc.objects.values_mut() {
+        let Object::Dictionary(font) = object else {
+            continue;
+        };
+        let Ok(descendants) = font
+            .get_mut(b"DescendantFonts")
+            .and_then(Object::as_array_mut)
+        else {
+            continue;
+        };
+        for descendant in descendants {
+            if let Object::Dictionary(descendant) = descendant {
+                if name_of(descendant, b"Subtype") == Some(b"CIDFontType2")
+                    && !descendant.has(b"CIDToGIDMap")
+                {
+                    descendant.set("CIDToGIDMap", Object::Name(b"Identity".to_vec()));
+                }
+            }
+        }
+    }
+
+    // drops printpdf's own document info and anything else no longer referenced
+    doc.prune_objects();
+    // the header has to be followed by a comment with binary characters, lopdf writes the
+    // version line only
+    doc.version = "1.7\n%\u{e2}\u{e3}\u{cf}\u{d3}".to_string();
+    let mut output = Vec::new();
+    doc.save_to(&mut output)?;
+    Ok(output)
+}
+
+// the embedded file and its file specification, returns the name and the specification's id
+fn embed_file(
+    doc: &mut Document,
+    attachment: &Attachment,
+    modified: &DateTime<FixedOffset>,
+) -> (String, ObjectId) {
+    let params = dictionary(vec![
+        ("Size", Object::Integer(attachment.data.len() as i64)),
+        ("ModDate", text_string(&pdf_date(modified))),
+    ]);
+    let file_id = doc.add_object(Stream::new(
+        dictionary(vec![
+            ("Type", Object::Name(b"EmbeddedFile".to_vec())),
+            (
+                "Subtype",
+                Object::Name(attachment.mime_type.as_bytes().to_vec()),
+            ),
+            ("Params", params.into()),
+        ]),
+        attachment.data.to_vec(),
+    ));
+    let spec_id = doc.add_object(dictionary(vec![
+        ("Type", Object::Name(b"Filespec".to_vec())),
+        ("F", text_string(attachment.file_name)),
+        ("UF", text_string(attachment.file_name)),
+        ("Desc", text_string(attachment.description)),
+        // the XML states the same invoice as the visible document
+        ("AFRelationship", Object::Name(b"Alternative".to_vec())),
+        (
+            "EF",
+            dictionary(vec![
+                ("F", Object::Reference(file_id)),
+                ("UF", Object::Reference(file_id)),
+            ])
+            .into(),
+        ),
+    ]));
+    (attachment.file_name.to_string(), spec_id)
+}
+
+fn dictionary(entries: Vec<(&str, Object)>) -> Dictionary {
+    let mut dictionary = Dictionary::new();
+    for (key, value) in entries {
+        dictionary.set(key, value);
+    }
+    dictionary
+}
+
+// PDF text strings are either PDFDocEncoding or UTF-16BE with a byte order mark
+fn text_string(text: &str) -> Object {
+    if text.is_ascii() {
+        return Object::String(text.as_bytes().to_vec(), StringFormat::Literal);
+    }
+    let mut bytes = vec![0xfe, 0xff];
+    for unit in text.encode_utf16() {
+        bytes.extend_from_slice(&unit.to_be_bytes());
+    }
+    Object::String(bytes, StringFormat::Hexadecimal)
+}
+
+// D:20240301120000+01'00'
+fn pdf_date(date: &DateTime<FixedOffset>) -> String {
+    let offset = date.offset().local_minus_utc();
+    let sign = if offset < 0 { '-' } else { '+' };
+    let offset = offset.abs() / 60;
+    format!(
+        "D:{}{}{:02}'{:02}'",
+        date.format("%Y%m%d%H%M%S"),
+        sign,
+        offset / 60,
+        offset % 60
+    )
+}
+
+fn xmp_packet(metadata: &Metadata, part: u8, factur_x: bool) -> String {
+    let date = metadata.created.to_rfc3339_opts(SecondsFormat::Secs, false);
+    let mut xmp = String::new();
+    xmp.push_str("<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n");
+    xmp.push_str("<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n");
+    xmp.push_str("<rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n");
+    xmp.push_str(&format!(
+        "<rdf:Description rdf:about=\"\" xmlns:pdfaid=\"http://www.aiim.org/pdfa/ns/id/\">\n\
+         <pdfaid:part>{}</pdfaid:part>\n\
+         <pdfaid:conformance>B</pdfaid:conformance>\n\
+         </rdf:Description>\n",
+        part
+    ));
+    xmp.push_str(&format!(
+        "<rdf:Description rdf:about=\"\" xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n\
+         <dc:format>application/pdf</dc:format>\n\
+         <dc:title><rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt></dc:title>\n\
+         <dc:creator><rdf:Seq><rdf:li>{}</rdf:li></rdf:Seq></dc:creator>\n\
+         <dc:description><rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt></dc:description>\n\
+         <dc:identifier>{}</dc:identifier>\n\
+         </rdf:Description>\n",
+        escape(&metadata.title),
+        escape(&metadata.author),
+        escape(&metadata.subject),
+        escape(&metadata.invoice_number)
+    ));
+    xmp.push_str(&format!(
+        "<rdf:Description rdf:about=\"\" xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\">\n\
+         <xmp:CreatorTool>{creator}</xmp:CreatorTool>\n\
+         <xmp:CreateDate>{date}</xmp:CreateDate>\n\
+         <xmp:ModifyDate>{date}</xmp:ModifyDate>\n\
+         <xmp:MetadataDate>{date}</xmp:MetadataDate>\n\
+         </rdf:Description>\n\
+         <rdf:Description rdf:about=\"\" xmlns:pdf=\"http://ns.adobe.com/pdf/1.3/\">\n\
+         <pdf:Producer>{creator}</pdf:Producer>\n\
+         </rdf:Description>\n",
+        creator = CREATOR,
+        date = date
+    ));
+    if factur_x {
+        xmp.push_str(&factur_x_schema());
+    }
+    xmp.push_str("</rdf:RDF>\n</x:xmpmeta>\n");
+    // room for editors to update the packet in place
+    xmp.push_str(&format!("{}\n", " ".repeat(100)).repeat(20));
+    xmp.push_str("<?xpacket end=\"w\"?>");
+    xmp
+}
+
+// the Factur-X properties and, because PDF/A only knows the standard schemas, their definition
+fn factur_x_schema() -> String {
+    let properties = [
+        (
+            "DocumentFileName",
+            "The name of the embedded XML invoice file",
+        ),
+        ("DocumentType", "INVOICE"),
+        ("Version", "The version of the Factur-X XML schema"),
+        (
+            "ConformanceLevel",
+            "The conformance level of the embedded XML",
+        ),
+    ];
+    let mut definitions = String::new();
+    for (name, description) in &properties {
+        definitions.push_str(&format!(
+            "<rdf:li rdf:parseType=\"Resource\">\n\
+             <pdfaProperty:name>{}</pdfaProperty:name>\n\
+             <pdfaProperty:valueType>Text</pdfaProperty:valueType>\n\
+             <pdfaProperty:category>external</pdfaProperty:category>\n\
+             <pdfaProperty:description>{}</pdfaProperty:description>\n\
+             </rdf:li>\n",
+            name, description
+        ));
+    }
+    format!(
+        "<rdf:Description rdf:about=\"\" xmlns:fx=\"{namespace}\">\n\
+         <fx:DocumentType>INVOICE</fx:DocumentType>\n\
+         <fx:DocumentFileName>{file_name}</fx:DocumentFileName>\n\
+         <fx:Version>1.0</fx:Version>\n\
+         <fx:ConformanceLevel>EN 16931</fx:ConformanceLevel>\n\
+         </rdf:Description>\n\
+         <rdf:Description rdf:about=\"\" \
+         xmlns:pdfaExtension=\"http://www.aiim.org/pdfa/ns/extension/\" \
+         xmlns:pdfaSchema=\"http://www.aiim.org/pdfa/ns/schema#\" \
+         xmlns:pdfaProperty=\"http://www.aiim.org/pdfa/ns/property#\">\n\
+         <pdfaExtension:schemas><rdf:Bag><rdf:li rdf:parseType=\"Resource\">\n\
+         <pdfaSchema:schema>Factur-X PDFA Extension Schema</pdfaSchema:schema>\n\
+         <pdfaSchema:namespaceURI>{namespace}</pdfaSchema:namespaceURI>\n\
+         <pdfaSchema:prefix>fx</pdfaSchema:prefix>\n\
+         <pdfaSchema:property><rdf:Seq>\n{definitions}</rdf:Seq></pdfaSchema:property>\n\
+         </rdf:li></rdf:Bag></pdfaExtension:schemas>\n\
+         </rdf:Description>\n",
+        namespace = FACTUR_X_NAMESPACE,
+        file_name = crate::cii::FILE_NAME,
+        definitions = definitions
+    )
+}
+
+const PDFAID: &str = "http://www.aiim.org/pdfa/ns/id/";
+const DC: &str = "http://purl.org/dc/elements/1.1/";
+const XMP: &str = "http://ns.adobe.com/xap/1.0/";
+const PDF: &str = "http://ns.adobe.com/pdf/1.3/";
+
+// Reads a finished file back against the PDF/A-2b and -3b rules our documents could break, each
+// named by its ISO 19005-2 clause. Not a full validator, veraPDF remains the reference.
+pub fn check(pdf: &[u8]) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    let binary_comment = pdf
+        .split(|byte| *byte == b'\n' || *byte == b'\r')
+        .filter(|line| !line.is_empty())
+        .nth(1)
+        .is_some_and(|line| {
+            line.starts_with(b"%") && line.iter().filter(|byte| **byte > 127).count() >= 4
+        });
+    if !pdf.starts_with(b"%PDF-1.") || !binary_comment {
+        violations.push(Violation::new(
+            "6.1.2",
+            "The header is not followed by a binary comment",
+        ));
+    }
+    let doc = match Document::load_mem(pdf) {
+        Ok(doc) => doc,
+        Err(e) => {
+            violations.push(Violation::new(
+                "6.1.2",
+                format!("Not a readable PDF: {}", e),
+            ));
+            return violations;
+        }
+    };
+    if !doc.trailer.has(b"ID") {
+        violations.push(Violation::new(
+            "6.1.3",
+            "The trailer has no file identifier",
+        ));
+    }
+    if doc.trailer.has(b"Encrypt") {
+        violations.push(Violation::new("6.1.3", "The file is encrypted"));
+    }
+    let catalog = match doc.catalog() {
+        Ok(catalog) => catalog,
+        Err(_) => {
+            violations.push(Violation::new("6.1.2", "The file has no catalog"));
+            return violations;
+        }
+    };
+
+    let info = doc
+        .trailer
+        .get(b"Info")
+        .ok()
+        .and_then(|info| resolve(&doc, info))
+        .and_then(|info| info.as_dict().ok());
+    let part = match catalog
+        .get(b"Metadata")
+        .ok()
+        .and_then(|metadata| resolve(&doc, metadata))
+        .and_then(|metadata| metadata.as_stream().ok())
+    {
+        Some(stream) => {
+            if stream.dict.has(b"Filter") {
+                violations.push(Violation::new(
+                    "6.6.2.1",
+                    "The metadata stream is compressed",
+                ));
+            }
+            check_xmp(&stream.content, info, &mut violations)
+        }
+        None => {
+            violations.push(Violation::new(
+                "6.6.2.1",
+                "The catalog has no XMP metadata stream",
+            ));
+            None
+        }
+    };
+
+    let intents: Vec<&Dictionary> = catalog
+        .get(b"OutputIntents")
+        .ok()
+        .and_then(|intents| resolve(&doc, intents))
+        .and_then(|intents| intents.as_array().ok())
+        .map(|intents| {
+            intents
+                .iter()
+                .filter_map(|intent| resolve(&doc, intent))
+                .filter_map(|intent| intent.as_dict().ok())
+                .filter(|intent| name_of(intent, b"S") == Some(b"GTS_PDFA1"))
+                .collect()
+        })
+        .unwrap_or_default();
+    if intents.is_empty() {
+        violations.push(Violation::new("6.2.3", "There is no PDF/A output intent"));
+    }
+    for intent in intents {
+        let profile = intent
+            .get(b"DestOutputProfile")
+            .ok()
+            .and_then(|profile| resolve(&doc, profile))
+            .and_then(|profile| profile.as_stream().ok());
+        match profile {
+            Some(profile) => check_profile(profile, &mut violations),
+            None => violations.push(Violation::new(
+                "6.2.3",
+                "The output intent has no ICC profile",
+            )),
+        }
+    }
+
+    let (mut lzw, mut javascript) = (false, false);
+    for object in doc.objects.values() {
+        let dict = match object {
+            Object::Dictionary(dict) => dict,
+            Object::Stream(stream) => {
+                let filters = stream.filters().unwrap_or_default();
+                lzw |= filters.iter().any(|filter| filter == "LZWDecode");
+                &stream.dict
+            }
+            _ => continue,
+        };
+        javascript |= dict.has(b"JS") || name_of(dict, b"S") == Some(b"JavaScript");
+        if name_of(dict, b"Type") != Some(b"Font") {
+            continue;
+        }
+        match name_of(dict, b"Subtype") {
+            Some(b"Type3") => {}
+            // descendants stored as objects of their own are checked as such
+            Some(b"Type0") => {
+                for descendant in dict
+                    .get(b"DescendantFonts")
+                    .ok()
+                    .and_then(|fonts| resolve(&doc, fonts))
+                    .and_then(|fonts| fonts.as_array().ok())
+                    .into_iter()
+                    .flatten()
+                {
+                    if let Object::Dictionary(font) = descendant {
+                        check_font(&doc, font, &mut violations);
+                    }
+                }
+            }
+            _ => check_font(&doc, dict, &mut violations),
+        }
+    }
+    if lzw {
+        violations.push(Violation::new(
+            "6.1.7.2",
+            "LZW compressed streams are not allowed",
+        ));
+    }
+    if javascript || catalog.has(b"AA") {
+        violations.push(Violation::new(
+            "6.6.1",
+            "JavaScript and additional actions are not allowed",
+        ));
+    }
+
+    let configurations = catalog
+        .get(b"OCProperties")
+        .ok()
+        .and_then(|properties| resolve(&doc, properties))
+        .and_then(|properties| properties.as_dict().ok())
+        .map(|properties| {
+            let mut configurations: Vec<&Object> = properties.get(b"D").ok().into_iter().collect();
+            if let Some(configs) = properties
+                .get(b"Configs")
+                .ok()
+                .and_then(|configs| resolve(&doc, configs))
+                .and_then(|configs| configs.as_array().ok())
+            {
+                configurations.extend(configs);
+            }
+            configurations
+        })
+        .unwrap_or_default();
+    for configuration in configurations {
+        let named = resolve(&doc, configuration)
+            .and_then(|configuration| configuration.as_dict().ok())
+            .is_some_and(|configuration| configuration.has(b"Name"));
+        if !named {
+            violations.push(Violation::new(
+                "6.9",
+                "An optional content configuration has no name",
+            ));
+        }
+    }
+
+    check_embedded_files(&doc, catalog, part.as_deref(), &mut violations);
+    violations
+}
+
+// the PDF/A identification and the document info entries mirrored in the XMP, returns the part
+fn check_xmp(
+    content: &[u8],
+    info: Option<&Dictionary>,
+    violations: &mut Vec<Violation>,
+) -> Option<String> {
+    let xmp = match std::str::from_utf8(content)
+        .ok()
+        .and_then(|text| roxmltree::Document::parse(text).ok())
+    {
+        Some(xmp) => xmp,
+        None => {
+            violations.push(Violation::new(
+                "6.6.2.1",
+                "The XMP metadata is not well-formed",
+            ));
+            return None;
+        }
+    };
+    // a property is either an element, possibly holding an rdf list, or a Description attribute
+    let property = |namespace: &str, name: &str| -> Option<String> {
+        xmp.descendants().find_map(|node| {
+            if let Some(value) = node.attribute((namespace, name)) {
+                return Some(value.to_string());
+            }
+            if node.tag_name().namespace() != Some(namespace) || node.tag_name().name() != name {
+                return None;
+            }
+            let item = node
+                .descendants()
+                .find(|child| child.tag_name().name() == "li")
+                .unwrap_or(node);
+            Some(item.text().unwrap_or("").trim().to_string())
+        })
+    };
+
+    let part = property(PDFAID, "part");
+    if !matches!(part.as_deref(), Some("2") | Some("3")) {
+        violations.push(Violation::new(
+            "6.6.4",
+            "The XMP metadata does not identify the file as PDF/A-2 or PDF/A-3",
+        ));
+    }
+    if property(PDFAID, "conformance").as_deref() != Some("B") {
+        violations.push(Violation::new(
+            "6.6.4",
+            "The XMP metadata does not claim conformance level B",
+        ));
+    }
+
+    let Some(info) = info else {
+        return part;
+    };
+    let texts = [
+        ("Title", DC, "title"),
+        ("Author", DC, "creator"),
+        ("Subject", DC, "description"),
+        ("Keywords", PDF, "Keywords"),
+        ("Creator", XMP, "CreatorTool"),
+        ("Producer", PDF, "Producer"),
+    ];
+    for (key, namespace, name) in texts.iter().copied() {
+        let Ok(value) = info.get(key.as_bytes()).and_then(Object::as_str) else {
+            continue;
+        };
+        // the XMP text is read trimmed, so the info entry is compared trimmed as well
+        if property(namespace, name).as_deref() != Some(decode_text(value).trim()) {
+            violations.push(Violation::new(
+                "6.6.3",
+                format!("The document info {} does not match the XMP metadata", key),
+            ));
+        }
+    }
+    for (key, name) in [("CreationDate", "CreateDate"), ("ModDate", "ModifyDate")] {
+        let Ok(value) = info.get(key.as_bytes()).and_then(Object::as_str) else {
+            continue;
+        };
+        let in_info = parse_pdf_date(&decode_text(value));
+        let in_xmp = property(XMP, name).and_then(|date| DateTime::parse_from_rfc3339(&date).ok());
+        if in_info.is_none() || in_info != in_xmp {
+            violations.push(Violation::new(
+                "6.6.3",
+                format!("The document info {} does not match the XMP metadata", key),
+            ));
+        }
+    }
+    part
+}
+
+fn check_profile(profile: &Stream, violations: &mut Vec<Violation>) {
+    let data = profile
+        .decompressed_content()
+        .unwrap_or_else(|_| profile.content.clone());
+    let components = profile.dict.get(b"N").and_then(Object::as_i64).ok();
+    let valid = data.len() >= 128
+        && &data[36..40] == b"acsp"
+        && data[8] <= 4
+        && matches!(&data[12..16], b"mntr" | b"prtr")
+        && match &data[16..20] {
+            b"GRAY" => components == Some(1),
+            b"RGB " => components == Some(3),
+            b"CMYK" => components == Some(4),
+            _ => false,
+        };
+    if !valid {
+        violations.push(Violation::new(
+            "6.2.3",
+            "The output intent's ICC profile is not a valid output profile",
+        ));
+    }
+}
+
+fn check_font(doc: &Document, font: &Dictionary, violations: &mut Vec<Violation>) {
+    let base_font = font
+        .get(b"BaseFont")
+        .and_then(Object::as_name_str)
+        .unwrap_or("unnamed");
+    let embedded = font
+        .get(b"FontDescriptor")
+        .ok()
+        .and_then(|descriptor| resolve(doc, descriptor))
+        .and_then(|descriptor| descriptor.as_dict().ok())
+        .is_some_and(|descriptor| {
+            [&b"FontFile"[..], b"FontFile2", b"FontFile3"]
+                .iter()
+                .any(|key| descriptor.has(key))
+        });
+    if !embedded {
+        violations.push(Violation::new(
+            "6.2.11.4.1",
+            format!("Font {} is not embedded", base_font),
+        ));
+    }
+    if name_of(font, b"Subtype") == Some(b"CIDFontType2") && !font.has(b"CIDToGIDMap") {
+        violations.push(Violation::new(
+            "6.2.11.3.2",
+            format!("Font {} has no CIDToGIDMap", base_font),
+        ));
+    }
+}
+
+// PDF/A-3 allows any file as long as it says what it is and how it relates to the document,
+// PDF/A-2 only other PDF/A files, which we never embed
+fn check_embedded_files(
+    doc: &Document,
+    catalog: &Dictionary,
+    part: Option<&str>,
+    violations: &mut Vec<Violation>,
+) {
+    let associated: Vec<ObjectId> = catalog
+        .get(b"AF")
+        .ok()
+        .and_then(|files| resolve(doc, files))
+        .and_then(|files| files.as_array().ok())
+        .map(|files| {
+            files
+                .iter()
+                .filter_map(|file| file.as_reference().ok())
+                .collect()
+        })
+        .unwrap_or_default();
+    for (id, object) in &doc.objects {
+        let Ok(spec) = object.as_dict() else {
+            continue;
+        };
+        if name_of(spec, b"Type") != Some(b"Filespec") || !spec.has(b"EF") {
+            continue;
+        }
+        let file_name = spec
+            .get(b"UF")
+            .or_else(|_| spec.get(b"F"))
+            .and_then(Object::as_str)
+            .map(decode_text)
+            .unwrap_or_default();
+        if part != Some("3") {
+            violations.push(Violation::new(
+                "6.8",
+                format!("Embedded file {} needs PDF/A-3", file_name),
+            ));
+            continue;
+        }
+        if !spec.has(b"F") || !spec.has(b"UF") {
+            violations.push(Violation::new(
+                "6.8",
+                format!("Embedded file {} needs both F and UF names", file_name),
+            ));
+        }
+        if !spec.has(b"AFRelationship") || !associated.contains(id) {
+            violations.push(Violation::new(
+                "6.8",
+                format!("Embedded file {} is not an associated file", file_name),
+            ));
+        }
+        let file = spec
+            .get(b"EF")
+            .ok()
+            .and_then(|files| resolve(doc, files))
+            .and_then(|files| files.as_dict().ok())
+            .and_then(|files| files.get(b"F").ok())
+            .and_then(|file| resolve(doc, file))
+            .and_then(|file| file.as_stream().ok());
+        let described = file.is_some_and(|file| {
+            file.dict.has(b"Subtype")
+                && file
+                    .dict
+                    .get(b"Params")
+                    .ok()
+                    .and_then(|params| resolve(doc, params))
+                    .and_then(|params| params.as_dict().ok())
+                    .is_some_and(|params| params.has(b"ModDate"))
+        });
+        if !described {
+            violations.push(Violation::new(
+                "6.8",
+                format!(
+                    "Embedded file {} has no MIME type or modification date",
+                    file_name
+                ),
+            ));
+        }
+    }
+}
+
+fn resolve<'a>(doc: &'a Document, object: &'a Object) -> Option<&'a Object> {
+    doc.dereference(object).ok().map(|(_, object)| object)
+}
+
+fn name_of<'a>(dict: &'a Dictionary, key: &[u8]) -> Option<&'a [u8]> {
+    dict.get(key).and_then(Object::as_name).ok()
+}
+
+// the reverse of `text_string`, PDFDocEncoding is read as Latin-1 which covers our own strings
+fn decode_text(bytes: &[u8]) -> String {
+    match bytes {
+        [0xfe, 0xff, rest @ ..] => {
+            let units: Vec<u16> = rest
+                .chunks(2)
+                .map(|pair| u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => bytes.iter().map(|byte| char::from(*byte)).collect(),
+    }
+}
+
+// D:20240301120000+01'00', only complete dates, which is what we write
+fn parse_pdf_date(date: &str) -> Option<DateTime<FixedOffset>> {
+    let date = date.strip_prefix("D:").unwrap_or(date);
+    let (local, offset) = date.split_at(date.len().min(14));
+    let offset = match offset {
+        "Z" | "Z00'00'" => "+0000".to_string(),
+        _ => offset.replace('\'', ""),
+    };
+    DateTime::parse_from_str(&format!("{}{}", local, offset), "%Y%m%d%H%M%S%z").ok()
+}
+
+// A minimal ICC v2 display profile for sRGB: D50 white point, the Bradford adapted primaries
+// and the sRGB transfer curve sampled at 1024 points.
+fn srgb_profile() -> Vec<u8> {
+    fn s15_fixed16(value: f64) -> [u8; 4] {
+        ((value * 65536.0).round() as i32).to_be_bytes()
+    }
+    fn xyz(x: f64, y: f64, z: f64) -> Vec<u8> {
+        let mut data = b"XYZ \0\0\0\0".to_vec();
+        for value in [x, y, z].iter().copied() {
+            data.extend_from_slice(&s15_fixed16(value));
+        }
+        data
+    }
+    let mut description = b"desc\0\0\0\0".to_vec();
+    description.extend_from_slice(&(OUTPUT_CONDITION.len() as u32 + 1).to_be_bytes());
+    description.extend_from_slice(OUTPUT_CONDITION.as_bytes());
+    // terminator, no unicode or scriptcode description
+    description.extend_from_slice(&[0; 1 + 4 + 4 + 2 + 1 + 67]);
+    let mut copyright = b"text\0\0\0\0".to_vec();
+    copyright.extend_from_slice(b"No copyright, use freely\0");
+    let mut curve = b"curv\0\0\0\0".to_vec();
+    curve.extend_from_slice(&1024u32.to_be_bytes());
+    for step in 0..1024 {
+        let value = f64::from(step) / 1023.0;
+        let linear = if value <= 0.04045 {
+            value / 12.92
+        } else {
+            ((value + 0.055) / 1.055).powf(2.4)
+        };
+        curve.extend_from_slice(&((linear * 65535.0).round() as u16).to_be_bytes());
+    }
+
+    let tags: Vec<(&[u8; 4], Vec<u8>)> = vec![
+        (b"desc", description),
+        (b"cprt", copyright),
+        (b"wtpt", xyz(0.9642, 1.0, 0.8249)),
+        (b"rXYZ", xyz(0.4361, 0.2225, 0.0139)),
+        (b"gXYZ", xyz(0.3851, 0.7169, 0.0971)),
+        (b"bXYZ", xyz(0.1431, 0.0606, 0.7141)),
+        (b"rTRC", curve.clone()),
+        (b"gTRC", curve.clone()),
+        (b"bTRC", curve),
+    ];
+    let mut table = (tags.len() as u32).to_be_bytes().to_vec();
+    let mut data = Vec::new();
+    let data_start = 128 + 4 + 12 * tags.len();
+    for (signature, tag) in &tags {
+        table.extend_from_slice(*signature);
+        table.extend_from_slice(&((data_start + data.len()) as u32).to_be_bytes());
+        table.extend_from_slice(&(tag.len() as u32).to_be_bytes());
+        data.extend_from_slice(tag);
+        // tags start on four byte boundaries
+        while data.len() % 4 != 0 {
+            data.push(0);
+        }
+    }
+
+    let mut header = Vec::with_capacity(128);
+    header.extend_from_slice(&((data_start + data.len()) as u32).to_be_bytes());
+    header.extend_from_slice(&[0; 4]);
+    header.extend_from_slice(&[2, 0x10, 0, 0]);
+    header.extend_from_slice(b"mntrRGB XYZ ");
+    for part in [2024u16, 1, 1, 0, 0, 0].iter() {
+        header.extend_from_slice(&part.to_be_bytes());
+    }
+    header.extend_from_slice(b"acsp");
+    header.extend_from_slice(&[0; 28]);
+    header.extend_from_slice(&s15_fixed16(0.9642));
+    header.extend_from_slice(&s15_fixed16(1.0));
+    header.extend_from_slice(&s15_fixed16(0.8249));
+    header.resize(128, 0);
+
+    [header, table, data].concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font_registry::{builtin_helvetica, FontRegistry, CALIBRI, JETBRAINS_MONO};
+    use genpdf::fonts::{FontData, FontFamily};
+    use genpdf::Element as _;
+
+    fn rendered(body: FontFamily<FontData>) -> Vec<u8> {
+        let mut doc = genpdf::Document::new(body);
+        let mono = doc.add_font_family(FontRegistry::embedded().load(JETBRAINS_MONO));
+        doc.set_minimal_conformance();
+        doc.push(genpdf::elements::Paragraph::new("Grand Total: 100.00"));
+        doc.push(
+            genpdf::elements::Paragraph::new("SKU-1")
+                .styled(genpdf::style::Style::new().with_font_family(mono)),
+        );
+        let mut pdf = Vec::new();
+        doc.render(&mut pdf).unwrap();
+        pdf
+    }
+
+    fn metadata() -> Metadata {
+        Metadata {
+            title: "Invoice 7".to_string(),
+            author: "Müller & Söhne".to_string(),
+            subject: "Invoice 7 to Client".to_string(),
+            invoice_number: "7".to_string(),
+            created: DateTime::parse_from_rfc3339("2024-03-01T12:30:00+01:00").unwrap(),
+        }
+    }
+
+    fn rules(pdf: &[u8]) -> Vec<&'static str> {
+        check(pdf).iter().map(|violation| violation.rule).collect()
+    }
+
+    #[test]
+    fn test_factur_x_structure() {
+        let pdf = factur_x(
+            &rendered(FontRegistry::embedded().load(CALIBRI)),
+            &metadata(),
+            "<rsm:CrossIndustryInvoice/>",
+        )
+        .unwrap();
+        assert_eq!(rules(&pdf), Vec::<&str>::new());
+        assert!(pdf.starts_with(b"%PDF-1.7\n%"));
+        assert!(pdf[10..14].iter().all(|byte| *byte > 127));
+
+        let doc = Document::load_mem(&pdf).unwrap();
+        let catalog = doc.catalog().unwrap();
+        let metadata_id = catalog.get(b"Metadata").unwrap().as_reference().unwrap();
+        let xmp = doc.get_object(metadata_id).unwrap().as_stream().unwrap();
+        let xmp = String::from_utf8(xmp.content.clone()).unwrap();
+        assert!(xmp.contains("<pdfaid:part>3</pdfaid:part>"));
+        assert!(xmp.contains("<fx:DocumentFileName>factur-x.xml</fx:DocumentFileName>"));
+        assert!(xmp.contains("<rdf:li>Müller &amp; Söhne</rdf:li>"));
+        assert!(xmp.contains("<xmp:CreateDate>2024-03-01T12:30:00+01:00</xmp:CreateDate>"));
+
+        let info_id = doc.trailer.get(b"Info").unwrap().as_reference().unwrap();
+        let info = doc.get_dictionary(info_id).unwrap();
+        assert_eq!(
+            info.get(b"CreationDate").unwrap().as_str().unwrap(),
+            b"D:20240301123000+01'00'"
+        );
+        assert!(!info.has(b"GTS_PDFXVersion"));
+
+        let intents = catalog.get(b"OutputIntents").unwrap().as_array().unwrap();
+        let intent = intents[0].as_dict().unwrap();
+        assert_eq!(intent.get(b"S").unwrap().as_name().unwrap(), b"GTS_PDFA1");
+
+        let spec_id = catalog.get(b"AF").unwrap().as_array().unwrap()[0]
+            .as_reference()
+            .unwrap();
+        let spec = doc.get_dictionary(spec_id).unwrap();
+        assert_eq!(
+            spec.get(b"AFRelationship").unwrap().as_name().unwrap(),
+            b"Alternative"
+        );
+        let file_id = spec
+            .get(b"EF")
+            .and_then(Object::as_dict)
+            .and_then(|files| files.get(b"F"))
+            .and_then(Object::as_reference)
+            .unwrap();
+        let file = doc.get_object(file_id).unwrap().as_stream().unwrap();
+        assert_eq!(file.content, b"<rsm:CrossIndustryInvoice/>");
+
+        for object in doc.objects.values() {
+            if let Ok(font) = object.as_dict() {
+                if font.type_name().ok() == Some("FontDescriptor") {
+                    assert!(font.has(b"FontFile2"));
+                }
+                if font.get(b"Subtype").and_then(Object::as_name).ok() == Some(b"CIDFontType2") {
+                    assert!(font.has(b"CIDToGIDMap"));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_archival_self_check() {
+        let plain = rendered(FontRegistry::embedded().load(CALIBRI));
+        let found = rules(&plain);
+        for rule in ["6.1.2", "6.6.2.1", "6.2.3", "6.2.11.3.2", "6.9"] {
+            assert!(found.contains(&rule), "{} missing from {:?}", rule, found);
+        }
+
+        let archived = archive(&plain, &metadata()).unwrap();
+        assert_eq!(rules(&archived), Vec::<&str>::new());
+        let doc = Document::load_mem(&archived).unwrap();
+        assert!(!doc.catalog().unwrap().has(b"AF"));
+        let text = String::from_utf8_lossy(&archived);
+        assert!(text.contains("<pdfaid:part>2</pdfaid:part>"));
+        assert!(text.contains("<dc:identifier>7</dc:identifier>"));
+
+        // a missing font falls back to the standard Helvetica, which is never embedded
+        let helvetica = archive(&rendered(builtin_helvetica()), &metadata()).unwrap();
+        let violations = check(&helvetica);
+        assert!(!violations.is_empty());
+        for violation in violations {
+            assert_eq!(violation.rule, "6.2.11.4.1");
+            assert!(violation.message.contains("Helvetica"));
+        }
+    }
+
+    #[test]
+    fn test_srgb_profile_layout() {
+        let profile = srgb_profile();
+        let size = u32::from_be_bytes([profile[0], profile[1], profile[2], profile[3]]);
+        assert_eq!(size as usize, profile.len());
+        assert_eq!(&profile[36..40], b"acsp");
+        assert_eq!(&profile[12..24], b"mntrRGB XYZ ");
+        let count = u32::from_be_bytes([profile[128], profile[129], profile[130], profile[131]]);
+        for idx in 0..count as usize {
+            let entry = &profile[132 + 12 * idx..144 + 12 * idx];
+            let offset = u32::from_be_bytes([entry[4], entry[5], entry[6], entry[7]]) as usize;
+            let length = u32::from_be_bytes([entry[8], entry[9], entry[10], entry[11]]) as usize;
+            assert_eq!(offset % 4, 0);
+            assert!(offset + length <= profile.len());
+        }
+    }
+}
@@ -0,0 +1,2011 @@
+//! Validation of a written UBL e-invoice before it is issued or saved: its structure against the
+//! UBL 2.1 schema and its content against the Peppol BIS Billing 3.0 rules.
+//!
+//! Neither the UBL XSD nor the Peppol Schematron files ship with Invoicy, both are transcribed
+//! here. The schema part holds the UBL 2.1 content models of the Invoice and CreditNote documents
+//! and of every aggregate Invoicy writes: element order, cardinality, namespaces, the attributes
+//! of the unqualified data types and the lexical form of numbers, dates and indicators. An
+//! aggregate without a transcribed model is reported, not let through. The rule part evaluates
+//! the EN 16931 rules (BR-*), the PEPPOL-EN16931 rules and the endpoint identifier checks of
+//! PEPPOL-COMMON on the XML itself, for the elements those models cover.
+
+use chrono::{NaiveDate, NaiveTime};
+use roxmltree::{Document, Node};
+
+use crate::totals::{round_cents, CATEGORIES};
+use crate::ubl::{
+    Violation, CAC, CBC, CREDIT_NOTE_NAMESPACE, CUSTOMIZATION_ID, EXT, INVOICE_NAMESPACE,
+};
+
+// UBL 2.1 content models as sequences of prefix:Name, the suffix gives the schema's cardinality:
+// none for exactly one, `?` for optional, `*` for any number and `+` for at least one
+const INVOICE: &[&str] = &[
+    "ext:UBLExtensions?",
+    "cbc:UBLVersionID?",
+    "cbc:CustomizationID?",
+    "cbc:ProfileID?",
+    "cbc:ProfileExecutionID?",
+    "cbc:ID",
+    "cbc:CopyIndicator?",
+    "cbc:UUID?",
+    "cbc:IssueDate",
+    "cbc:IssueTime?",
+    "cbc:DueDate?",
+    "cbc:InvoiceTypeCode?",
+    "cbc:Note*",
+    "cbc:TaxPointDate?",
+    "cbc:DocumentCurrencyCode?",
+    "cbc:TaxCurrencyCode?",
+    "cbc:PricingCurrencyCode?",
+    "cbc:PaymentCurrencyCode?",
+    "cbc:PaymentAlternativeCurrencyCode?",
+    "cbc:AccountingCostCode?",
+    "cbc:AccountingCost?",
+    "cbc:LineCountNumeric?",
+    "cbc:BuyerReference?",
+    "cac:InvoicePeriod*",
+    "cac:OrderReference?",
+    "cac:BillingReference*",
+    "cac:DespatchDocumentReference*",
+    "cac:ReceiptDocumentReference*",
+    "cac:StatementDocumentReference*",
+    "cac:OriginatorDocumentReference*",
+    "cac:ContractDocumentReference*",
+    "cac:AdditionalDocumentReference*",
+    "cac:ProjectReference*",
+    "cac:Signature*",
+    "cac:AccountingSupplierParty",
+    "cac:AccountingCustomerParty",
+    "cac:PayeeParty?",
+    "cac:BuyerCustomerParty?",
+    "cac:SellerSupplierParty?",
+    "cac:TaxRepresentativeParty?",
+    "cac:Delivery*",
+    "cac:DeliveryTerms?",
+    "cac:PaymentMeans*",
+    "cac:PaymentTerms*",
+    "cac:PrepaidPayment*",
+    "cac:AllowanceCharge*",
+    "cac:TaxExchangeRate?",
+    "cac:PricingExchangeRate?",
+    "cac:PaymentExchangeRate?",
+    "cac:PaymentAlternativeExchangeRate?",
+    "cac:TaxTotal*",
+    "cac:WithholdingTaxTotal*",
+    "cac:LegalMonetaryTotal",
+    "cac:InvoiceLine+",
+];
+
+const CREDIT_NOTE: &[&str] = &[
+    "ext:UBLExtensions?",
+    "cbc:UBLVersionID?",
+    "cbc:CustomizationID?",
+    "cbc:ProfileID?",
+    "cbc:ProfileExecutionID?",
+    "cbc:ID",
+    "cbc:CopyIndicator?",
+    "cbc:UUID?",
+    "cbc:IssueDate",
+    "cbc:IssueTime?",
+    "cbc:TaxPointDate?",
+    "cbc:CreditNoteTypeCode?",
+    "cbc:Note*",
+    "cbc:DocumentCurrencyCode?",
+    "cbc:TaxCurrencyCode?",
+    "cbc:PricingCurrencyCode?",
+    "cbc:PaymentCurrencyCode?",
+    "cbc:PaymentAlternativeCurrencyCode?",
+    "cbc:AccountingCostCode?",
+    "cbc:AccountingCost?",
+    "cbc:LineCountNumeric?",
+    "cbc:BuyerReference?",
+    "cac:InvoicePeriod*",
+    "cac:DiscrepancyResponse*",
+    "cac:OrderReference?",
+    "cac:BillingReference*",
+    "cac:DespatchDocumentReference*",
+    "cac:ReceiptDocumentReference*",
+    "cac:ContractDocumentReference*",
+    "cac:AdditionalDocumentReference*",
+    "cac:StatementDocumentReference*",
+    "cac:OriginatorDocumentReference*",
+    "cac:Signature*",
+    "cac:AccountingSupplierParty",
+    "cac:AccountingCustomerParty",
+    "cac:PayeeParty?",
+    "cac:BuyerCustomerParty?",
+    "cac:SellerSupplierParty?",
+    "cac:TaxRepresentativeParty?",
+    "cac:Delivery*",
+    "cac:DeliveryTerms*",
+    "cac:PaymentMeans*",
+    "cac:PaymentTerms*",
+    "cac:TaxExchangeRate?",
+    "cac:PricingExchangeRate?",
+    "cac:PaymentExchangeRate?",
+    "cac:PaymentAlternativeExchangeRate?",
+    "cac:AllowanceCharge*",
+    "cac:TaxTotal*",
+    "cac:LegalMonetaryTotal",
+    "cac:CreditNoteLine+",
+];
+
+const ORDER_REFERENCE: &[&str] = &[
+    "cbc:ID",
+    "cbc:SalesOrderID?",
+    "cbc:CopyIndicator?",
+    "cbc:UUID?",
+    "cbc:IssueDate?",
+    "cbc:IssueTime?",
+    "cbc:CustomerReference?",
+    "cbc:OrderTypeCode?",
+    "cac:DocumentReference?",
+];
+
+const SUPPLIER_PARTY: &[&str] = &[
+    "cbc:CustomerAssignedAccountID?",
+    "cbc:AdditionalAccountID*",
+    "cbc:DataSendingCapability?",
+    "cac:Party?",
+    "cac:DespatchContact?",
+    "cac:AccountingContact?",
+    "cac:SellerContact?",
+];
+
+const CUSTOMER_PARTY: &[&str] = &[
+    "cbc:CustomerAssignedAccountID?",
+    "cbc:SupplierAssignedAccountID?",
+    "cbc:AdditionalAccountID*",
+    "cac:Party?",
+    "cac:DeliveryContact?",
+    "cac:AccountingContact?",
+    "cac:BuyerContact?",
+];
+
+const PARTY: &[&str] = &[
+    "cbc:MarkCareIndicator?",
+    "cbc:MarkAttentionIndicator?",
+    "cbc:WebsiteURI?",
+    "cbc:LogoReferenceID?",
+    "cbc:EndpointID?",
+    "cbc:IndustryClassificationCode?",
+    "cac:PartyIdentification*",
+    "cac:PartyName*",
+    "cac:Language?",
+    "cac:PostalAddress?",
+    "cac:PhysicalLocation?",
+    "cac:PartyTaxScheme*",
+    "cac:PartyLegalEntity*",
+    "cac:Contact?",
+    "cac:Person*",
+    "cac:AgentParty?",
+    "cac:ServiceProviderParty*",
+    "cac:PowerOfAttorney*",
+    "cac:FinancialAccount?",
+];
+
+const PARTY_IDENTIFICATION: &[&str] = &["cbc:ID"];
+
+const PARTY_NAME: &[&str] = &["cbc:Name"];
+
+const ADDRESS: &[&str] = &[
+    "cbc:ID?",
+    "cbc:AddressTypeCode?",
+    "cbc:AddressFormatCode?",
+    "cbc:Postbox?",
+    "cbc:Floor?",
+    "cbc:Room?",
+    "cbc:StreetName?",
+    "cbc:AdditionalStreetName?",
+    "cbc:BlockName?",
+    "cbc:BuildingName?",
+    "cbc:BuildingNumber?",
+    "cbc:InhouseMail?",
+    "cbc:Department?",
+    "cbc:MarkAttention?",
+    "cbc:MarkCare?",
+    "cbc:PlotIdentification?",
+    "cbc:CitySubdivisionName?",
+    "cbc:CityName?",
+    "cbc:PostalZone?",
+    "cbc:CountrySubentity?",
+    "cbc:CountrySubentityCode?",
+    "cbc:Region?",
+    "cbc:District?",
+    "cbc:TimezoneOffset?",
+    "cac:AddressLine*",
+    "cac:Country?",
+    "cac:LocationCoordinate*",
+];
+
+const COUNTRY: &[&str] = &["cbc:IdentificationCode?", "cbc:Name?"];
+
+const PARTY_TAX_SCHEME: &[&str] = &[
+    "cbc:RegistrationName?",
+    "cbc:CompanyID?",
+    "cbc:TaxLevelCode?",
+    "cbc:ExemptionReasonCode?",
+    "cbc:ExemptionReason*",
+    "cac:RegistrationAddress?",
+    "cac:TaxScheme",
+];
+
+const TAX_SCHEME: &[&str] = &[
+    "cbc:ID?",
+    "cbc:Name?",
+    "cbc:TaxTypeCode?",
+    "cbc:CurrencyCode?",
+    "cac:JurisdictionRegionAddress*",
+];
+
+const PARTY_LEGAL_ENTITY: &[&str] = &[
+    "cbc:RegistrationName?",
+    "cbc:CompanyID?",
+    "cbc:RegistrationDate?",
+    "cbc:RegistrationExpirationDate?",
+    "cbc:CompanyLegalFormCode?",
+    "cbc:CompanyLegalForm?",
+    "cbc:SoleProprietorshipIndicator?",
+    "cbc:CompanyLiquidationStatusCode?",
+    "cbc:CorporateStockAmount?",
+    "cbc:FullyPaidSharesIndicator?",
+    "cac:RegistrationAddress?",
+    "cac:CorporateRegistrationScheme?",
+    "cac:HeadOfficeParty?",
+    "cac:ShareholderParty*",
+];
+
+const CONTACT: &[&str] = &[
+    "cbc:ID?",
+    "cbc:Name?",
+    "cbc:Telephone?",
+    "cbc:Telefax?",
+    "cbc:ElectronicMail?",
+    "cbc:Note*",
+    "cac:OtherCommunication*",
+];
+
+const PAYMENT_TERMS: &[&str] = &[
+    "cbc:ID?",
+    "cbc:PaymentMeansID*",
+    "cbc:PrepaidPaymentReferenceID?",
+    "cbc:Note*",
+    "cbc:ReferenceEventCode?",
+    "cbc:SettlementDiscountPercent?",
+    "cbc:PenaltySurchargePercent?",
+    "cbc:PaymentPercent?",
+    "cbc:Amount?",
+    "cbc:SettlementDiscountAmount?",
+    "cbc:PenaltyAmount?",
+    "cbc:PaymentTermsDetailsURI?",
+    "cbc:PaymentDueDate?",
+    "cbc:InstallmentDueDate?",
+    "cbc:InvoicingPartyReference?",
+    "cac:SettlementPeriod?",
+    "cac:PenaltyPeriod?",
+    "cac:ExchangeRate?",
+    "cac:ValidityPeriod?",
+];
+
+const TAX_TOTAL: &[&str] = &[
+    "cbc:TaxAmount",
+    "cbc:RoundingAmount?",
+    "cbc:TaxEvidenceIndicator?",
+    "cbc:TaxIncludedIndicator?",
+    "cac:TaxSubtotal*",
+];
+
+const TAX_SUBTOTAL: &[&str] = &[
+    "cbc:TaxableAmount?",
+    "cbc:TaxAmount",
+    "cbc:CalculationSequenceNumeric?",
+    "cbc:TransactionCurrencyTaxAmount?",
+    "cbc:Percent?",
+    "cbc:BaseUnitMeasure?",
+    "cbc:PerUnitAmount?",
+    "cbc:TierRange?",
+    "cbc:TierRatePercent?",
+    "cac:TaxCategory",
+];
+
+const TAX_CATEGORY: &[&str] = &[
+    "cbc:ID?",
+    "cbc:Name?",
+    "cbc:Percent?",
+    "cbc:BaseUnitMeasure?",
+    "cbc:PerUnitAmount?",
+    "cbc:TaxExemptionReasonCode?",
+    "cbc:TaxExemptionReason*",
+    "cbc:TierRange?",
+    "cbc:TierRatePercent?",
+    "cac:TaxScheme",
+];
+
+const MONETARY_TOTAL: &[&str] = &[
+    "cbc:LineExtensionAmount?",
+    "cbc:TaxExclusiveAmount?",
+    "cbc:TaxInclusiveAmount?",
+    "cbc:AllowanceTotalAmount?",
+    "cbc:ChargeTotalAmount?",
+    "cbc:PrepaidAmount?",
+    "cbc:PayableRoundingAmount?",
+    "cbc:PayableAmount",
+    "cbc:PayableAlternativeAmount?",
+];
+
+const INVOICE_LINE: &[&str] = &[
+    "cbc:ID",
+    "cbc:UUID?",
+    "cbc:Note*",
+    "cbc:InvoicedQuantity?",
+    "cbc:LineExtensionAmount",
+    "cbc:TaxPointDate?",
+    "cbc:AccountingCostCode?",
+    "cbc:AccountingCost?",
+    "cbc:PaymentPurposeCode?",
+    "cbc:FreeOfChargeIndicator?",
+    "cac:InvoicePeriod*",
+    "cac:OrderLineReference*",
+    "cac:DespatchLineReference*",
+    "cac:ReceiptLineReference*",
+    "cac:BillingReference*",
+    "cac:DocumentReference*",
+    "cac:PricingReference?",
+    "cac:OriginatorParty?",
+    "cac:Delivery*",
+    "cac:PaymentTerms*",
+    "cac:AllowanceCharge*",
+    "cac:TaxTotal*",
+    "cac:WithholdingTaxTotal*",
+    "cac:Item",
+    "cac:Price?",
+    "cac:DeliveryTerms?",
+    "cac:SubInvoiceLine*",
+    "cac:ItemPriceExtension?",
+];
+
+const CREDIT_NOTE_LINE: &[&str] = &[
+    "cbc:ID",
+    "cbc:UUID?",
+    "cbc:Note*",
+    "cbc:CreditedQuantity?",
+    "cbc:LineExtensionAmount?",
+    "cbc:TaxPointDate?",
+    "cbc:AccountingCostCode?",
+    "cbc:AccountingCost?",
+    "cbc:PaymentPurposeCode?",
+    "cbc:FreeOfChargeIndicator?",
+    "cac:InvoicePeriod*",
+    "cac:OrderLineReference*",
+    "cac:DiscrepancyResponse*",
+    "cac:DespatchLineReference*",
+    "cac:ReceiptLineReference*",
+    "cac:BillingReference*",
+    "cac:DocumentReference*",
+    "cac:PricingReference?",
+    "cac:OriginatorParty?",
+    "cac:Delivery*",
+    "cac:TaxTotal*",
+    "cac:AllowanceCharge*",
+    "cac:Item?",
+    "cac:Price?",
+    "cac:DeliveryTerms*",
+    "cac:SubCreditNoteLine*",
+    "cac:ItemPriceExtension?",
+];
+
+const ITEM: &[&str] = &[
+    "cbc:Description*",
+    "cbc:PackQuantity?",
+    "cbc:PackSizeNumeric?",
+    "cbc:CatalogueIndicator?",
+    "cbc:Name?",
+    "cbc:HazardousRiskIndicator?",
+    "cbc:AdditionalInformation*",
+    "cbc:Keyword*",
+    "cbc:BrandName*",
+    "cbc:ModelName*",
+    "cac:BuyersItemIdentification?",
+    "cac:SellersItemIdentification?",
+    "cac:ManufacturersItemIdentification*",
+    "cac:StandardItemIdentification?",
+    "cac:CatalogueItemIdentification?",
+    "cac:AdditionalItemIdentification*",
+    "cac:CatalogueDocumentReference?",
+    "cac:ItemSpecificationDocumentReference*",
+    "cac:OriginCountry?",
+    "cac:CommodityClassification*",
+    "cac:TransactionConditions*",
+    "cac:HazardousItem*",
+    "cac:ClassifiedTaxCategory*",
+    "cac:AdditionalItemProperty*",
+    "cac:ManufacturerParty*",
+    "cac:InformationContentProviderParty?",
+    "cac:OriginAddress*",
+    "cac:ItemInstance*",
+    "cac:Certificate*",
+    "cac:Dimension*",
+];
+
+const ITEM_IDENTIFICATION: &[&str] = &[
+    "cbc:ID",
+    "cbc:ExtendedID?",
+    "cbc:BarcodeSymbologyID?",
+    "cac:PhysicalAttribute*",
+    "cac:MeasurementDimension*",
+    "cac:IssuerParty?",
+];
+
+const PRICE: &[&str] = &[
+    "cbc:PriceAmount",
+    "cbc:BaseQuantity?",
+    "cbc:PriceChangeReason*",
+    "cbc:PriceTypeCode?",
+    "cbc:PriceType?",
+    "cbc:OrderableUnitFactorRate?",
+    "cac:ValidityPeriod*",
+    "cac:PriceList?",
+    "cac:AllowanceCharge*",
+    "cac:PricingExchangeRate?",
+];
+
+// UNCL1001 document types Peppol allows for invoices (PEPPOL-EN16931-P0100) and credit notes
+// (PEPPOL-EN16931-P0101)
+const INVOICE_TYPES: &str = "71 80 82 84 102 218 219 326 331 380 382 383 384 386 388 393 395 553 \
+                             575 623 780 817 870 875 876 877";
+const CREDIT_NOTE_TYPES: &str = "81 83 381 396 532";
+
+const CURRENCIES: &str = "AED AFN ALL AMD ANG AOA ARS AUD AWG AZN BAM BBD BDT BGN BHD BIF BMD \
+                          BND BOB BOV BRL BSD BTN BWP BYN BZD CAD CDF CHE CHF CHW CLF CLP CNY COP \
+                          COU CRC CUC CUP CVE CZK DJF DKK DOP DZD EGP ERN ETB EUR FJD FKP GBP GEL \
+                          GHS GIP GMD GNF GTQ GYD HKD HNL HRK HTG HUF IDR ILS INR IQD IRR ISK JMD \
+                          JOD JPY KES KGS KHR KMF KPW KRW KWD KYD KZT LAK LBP LKR LRD LSL LYD MAD \
+                          MDL MGA MKD MMK MNT MOP MRU MUR MVR MWK MXN MXV MYR MZN NAD NGN NIO NOK \
+                          NPR NZD OMR PAB PEN PGK PHP PKR PLN PYG QAR RON RSD RUB RWF SAR SBD SCR \
+                          SDG SEK SGD SHP SLE SLL SOS SRD SSP STN SVC SYP SZL THB TJS TMT TND TOP \
+                          TRY TTD TWD TZS UAH UGX USD USN UYI UYU UYW UZS VED VES VND VUV WST XAF \
+                          XAG XAU XBA XBB XBC XBD XCD XDR XOF XPD XPF XPT XSU XTS XUA XXX YER ZAR \
+                          ZMW ZWL";
+
+// ISO 3166-1 alpha-2, with 1A for Kosovo and XI for Northern Ireland as EN 16931 allows
+const COUNTRIES: &str = "1A AD AE AF AG AI AL AM AO AQ AR AS AT AU AW AX AZ BA BB BD BE BF BG BH \
+                         BI BJ BL BM BN BO BQ BR BS BT BV BW BY BZ CA CC CD CF CG CH CI CK CL CM \
+                         CN CO CR CU CV CW CX CY CZ DE DJ DK DM DO DZ EC EE EG EH ER ES ET FI FJ \
+                         FK FM FO FR GA GB GD GE GF GG GH GI GL GM GN GP GQ GR GS GT GU GW GY HK \
+                         HM HN HR HT HU ID IE IL IM IN IO IQ IR IS IT JE JM JO JP KE KG KH KI KM \
+                         KN KP KR KW KY KZ LA LB LC LI LK LR LS LT LU LV LY MA MC MD ME MF MG MH \
+                         MK ML MM MN MO MP MQ MR MS MT MU MV MW MX MY MZ NA NC NE NF NG NI NL NO \
+                         NP NR NU NZ OM PA PE PF PG PH PK PL PM PN PR PS PT PW PY QA RE RO RS RU \
+                         RW SA SB SC SD SE SG SH SI SJ SK SL SM SN SO SR SS ST SV SX SY SZ TC TD \
+                         TF TG TH TJ TK TL TM TN TO TR TT TV TW TZ UA UG UM US UY UZ VA VC VE VG \
+                         VI VN VU WF WS XI YE YT ZA ZM ZW";
+
+// the electronic address schemes (EAS) Peppol accepts for endpoint identifiers
+const ENDPOINT_SCHEMES: &str = "0002 0007 0009 0037 0060 0088 0096 0097 0106 0130 0135 0142 0147 \
+                                0151 0154 0158 0170 0177 0183 0184 0188 0190 0191 0192 0193 0194 \
+                                0195 0196 0198 0199 0200 0201 0202 0203 0204 0205 0208 0209 0210 \
+                                0211 0212 0213 0215 0216 0217 0218 0221 0225 0230 0235 0240 9901 \
+                                9910 9913 9914 9915 9918 9919 9920 9922 9923 9924 9925 9926 9927 \
+                                9928 9929 9930 9931 9932 9933 9934 9935 9936 9937 9938 9939 9940 \
+                                9941 9942 9943 9944 9945 9946 9947 9948 9949 9950 9951 9952 9953 \
+                                9957 9959 AN AQ AS AU EM";
+
+// per VAT category the rules for its breakdown (-01), the VAT IDs (-02), the line rate (-05),
+// the taxable amount (-08), the VAT amount (-09) and the exemption reason (-10)
+const CATEGORY_RULES: [(&str, [&str; 6]); 9] = [
+    (
+        "S",
+        [
+            "BR-S-01", "BR-S-02", "BR-S-05", "BR-S-08", "BR-S-09", "BR-S-10",
+        ],
+    ),
+    (
+        "Z",
+        [
+            "BR-Z-01", "BR-Z-02", "BR-Z-05", "BR-Z-08", "BR-Z-09", "BR-Z-10",
+        ],
+    ),
+    (
+        "E",
+        [
+            "BR-E-01", "BR-E-02", "BR-E-05", "BR-E-08", "BR-E-09", "BR-E-10",
+        ],
+    ),
+    (
+        "AE",
+        [
+            "BR-AE-01", "BR-AE-02", "BR-AE-05", "BR-AE-08", "BR-AE-09", "BR-AE-10",
+        ],
+    ),
+    (
+        "K",
+        [
+            "BR-IC-01", "BR-IC-02", "BR-IC-05", "BR-IC-08", "BR-IC-09", "BR-IC-10",
+        ],
+    ),
+    (
+        "G",
+        [
+            "BR-G-01", "BR-G-02", "BR-G-05", "BR-G-08", "BR-G-09", "BR-G-10",
+        ],
+    ),
+    (
+        "O",
+        [
+            "BR-O-01", "BR-O-02", "BR-O-05", "BR-O-08", "BR-O-09", "BR-O-10",
+        ],
+    ),
+    (
+        "L",
+        [
+            "BR-AF-01", "BR-AF-02", "BR-AF-05", "BR-AF-08", "BR-AF-09", "BR-AF-10",
+        ],
+    ),
+    (
+        "M",
+        [
+            "BR-AG-01", "BR-AG-02", "BR-AG-05", "BR-AG-08", "BR-AG-09", "BR-AG-10",
+        ],
+    ),
+];
+
+fn listed(list: &str, code: &str) -> bool {
+    list.split_whitespace().any(|known| known == code)
+}
+
+pub(crate) fn is_country_code(code: &str) -> bool {
+    listed(COUNTRIES, code)
+}
+
+pub(crate) fn is_currency_code(code: &str) -> bool {
+    listed(CURRENCIES, code)
+}
+
+// the aggregate's content model, UBL declares every element globally so the name decides it
+fn content_model(name: &str) -> Option<&'static [&'static str]> {
+    Some(match name {
+        "OrderReference" => ORDER_REFERENCE,
+        "AccountingSupplierParty" => SUPPLIER_PARTY,
+        "AccountingCustomerParty" => CUSTOMER_PARTY,
+        "Party" => PARTY,
+        "PartyIdentification" => PARTY_IDENTIFICATION,
+        "PartyName" => PARTY_NAME,
+        "PostalAddress" => ADDRESS,
+        "Country" => COUNTRY,
+        "PartyTaxScheme" => PARTY_TAX_SCHEME,
+        "TaxScheme" => TAX_SCHEME,
+        "PartyLegalEntity" => PARTY_LEGAL_ENTITY,
+        "Contact" => CONTACT,
+        "PaymentTerms" => PAYMENT_TERMS,
+        "TaxTotal" => TAX_TOTAL,
+        "TaxSubtotal" => TAX_SUBTOTAL,
+        "TaxCategory" | "ClassifiedTaxCategory" => TAX_CATEGORY,
+        "LegalMonetaryTotal" => MONETARY_TOTAL,
+        "InvoiceLine" => INVOICE_LINE,
+        "CreditNoteLine" => CREDIT_NOTE_LINE,
+        "Item" => ITEM,
+        "BuyersItemIdentification" | "SellersItemIdentification" | "StandardItemIdentification" => {
+            ITEM_IDENTIFICATION
+        }
+        "Price" => PRICE,
+        _ => return None,
+    })
+}
+
+struct Particle {
+    label: &'static str,
+    namespace: &'static str,
+    name: &'static str,
+    min: usize,
+    max: usize,
+}
+
+impl Particle {
+    fn new(entry: &'static str) -> Self {
+        let (label, min, max) = match entry.chars().last() {
+            Some('?') => (&entry[..entry.len() - 1], 0, 1),
+            Some('*') => (&entry[..entry.len() - 1], 0, usize::MAX),
+            Some('+') => (&entry[..entry.len() - 1], 1, usize::MAX),
+            _ => (entry, 1, 1),
+        };
+        let (prefix, name) = label.split_once(':').unwrap_or(("cbc", label));
+        let namespace = match prefix {
+            "cac" => CAC,
+            "ext" => EXT,
+            _ => CBC,
+        };
+        Self {
+            label,
+            namespace,
+            name,
+            min,
+            max,
+        }
+    }
+
+    fn matches(&self, node: Node) -> bool {
+        node.tag_name().namespace() == Some(self.namespace) && node.tag_name().name() == self.name
+    }
+}
+
+// the unqualified data types of UBL 2.1, UBL names each basic component after its type
+#[derive(Clone, Copy, PartialEq)]
+enum DataType {
+    Amount,
+    Quantity,
+    Measure,
+    Numeric,
+    Date,
+    Time,
+    Indicator,
+    Identifier,
+    Code,
+    Binary,
+    Text,
+}
+
+impl DataType {
+    fn of(name: &str) -> Self {
+        if name.ends_with("Amount") {
+            DataType::Amount
+        } else if name.ends_with("Quantity") {
+            DataType::Quantity
+        } else if name.ends_with("Measure") {
+            DataType::Measure
+        } else if name.ends_with("Percent") || name.ends_with("Numeric") || name.ends_with("Rate") {
+            DataType::Numeric
+        } else if name.ends_with("Date") {
+            DataType::Date
+        } else if name.ends_with("Time") {
+            DataType::Time
+        } else if name.ends_with("Indicator") {
+            DataType::Indicator
+        } else if name.ends_with("ID") || name.ends_with("URI") {
+            DataType::Identifier
+        } else if name.ends_with("Code") {
+            DataType::Code
+        } else if name.ends_with("BinaryObject") {
+            DataType::Binary
+        } else {
+            DataType::Text
+        }
+    }
+
+    // the attributes the type allows and the one it requires
+    fn attributes(self) -> (&'static [&'static str], Option<&'static str>) {
+        match self {
+            DataType::Amount => (
+                &["currencyID", "currencyCodeListVersionID"],
+                Some("currencyID"),
+            ),
+            DataType::Quantity => (
+                &[
+                    "unitCode",
+                    "unitCodeListID",
+                    "unitCodeListAgencyID",
+                    "unitCodeListAgencyName",
+                ],
+                None,
+            ),
+            DataType::Measure => (&["unitCode", "unitCodeListVersionID"], Some("unitCode")),
+            DataType::Numeric => (&["format"], None),
+            DataType::Date | DataType::Time | DataType::Indicator => (&[], None),
+            DataType::Identifier => (
+                &[
+                    "schemeID",
+                    "schemeName",
+                    "schemeAgencyID",
+                    "schemeAgencyName",
+                    "schemeVersionID",
+                    "schemeDataURI",
+                    "schemeURI",
+                ],
+                None,
+            ),
+            DataType::Code => (
+                &[
+                    "listID",
+                    "listAgencyID",
+                    "listAgencyName",
+                    "listName",
+                    "listVersionID",
+                    "name",
+                    "languageID",
+                    "listURI",
+                    "listSchemeURI",
+                ],
+                None,
+            ),
+            DataType::Binary => (
+                &[
+                    "mimeCode",
+                    "format",
+                    "encodingCode",
+                    "characterSetCode",
+                    "uri",
+                    "filename",
+                ],
+                Some("mimeCode"),
+            ),
+            DataType::Text => (&["languageID", "languageLocaleID"], None),
+        }
+    }
+
+    // the lexical check of the value and what it should have been
+    fn accepts(self, value: &str) -> Result<(), &'static str> {
+        let valid = match self {
+            DataType::Amount | DataType::Quantity | DataType::Measure | DataType::Numeric => {
+                is_decimal(value)
+            }
+            DataType::Date => is_date(value),
+            DataType::Time => is_time(value),
+            DataType::Indicator => matches!(value, "true" | "false" | "1" | "0"),
+            _ => true,
+        };
+        if valid {
+            return Ok(());
+        }
+        Err(match self {
+            DataType::Date => "a date",
+            DataType::Time => "a time",
+            DataType::Indicator => "true or false",
+            _ => "a decimal number",
+        })
+    }
+}
+
+// xsd:decimal, a sign, digits and at most one point, no exponent
+fn is_decimal(value: &str) -> bool {
+    let digits = value.strip_prefix(['+', '-']).unwrap_or(value);
+    let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+    !(whole.is_empty() && fraction.is_empty())
+        && whole.chars().all(|c| c.is_ascii_digit())
+        && fraction.chars().all(|c| c.is_ascii_digit())
+}
+
+// an xsd time zone: Z or +hh:mm / -hh:mm
+fn is_time_zone(zone: &str) -> bool {
+    zone.is_empty()
+        || zone == "Z"
+        || (zone.len() == 6
+            && (zone.starts_with('+') || zone.starts_with('-'))
+            && NaiveTime::parse_from_str(&zone[1..], "%H:%M").is_ok())
+}
+
+fn is_date(value: &str) -> bool {
+    value.len() >= 10
+        && value.is_char_boundary(10)
+        && NaiveDate::parse_from_str(&value[..10], "%Y-%m-%d").is_ok()
+        && is_time_zone(&value[10..])
+}
+
+fn is_time(value: &str) -> bool {
+    let end = value.find(['Z', '+', '-']).unwrap_or(value.len());
+    value.len() >= 8
+        && NaiveTime::parse_from_str(&value[..end], "%H:%M:%S%.f").is_ok()
+        && is_time_zone(&value[end..])
+}
+
+// the element name with the prefix Invoicy writes for its namespace
+fn qualified(node: Node) -> String {
+    let prefix = match node.tag_name().namespace() {
+        Some(CAC) => "cac:",
+        Some(CBC) => "cbc:",
+        Some(EXT) => "ext:",
+        _ => "",
+    };
+    format!("{}{}", prefix, node.tag_name().name())
+}
+
+fn schema_violation(violations: &mut Vec<Violation>, message: String) {
+    violations.push(Violation::new("UBL-SCHEMA", message));
+}
+
+// required elements of `particles` that were not seen, `first_count` is how often the first one was
+fn check_missing(
+    particles: &[Particle],
+    first_count: usize,
+    misplaced: &[String],
+    path: &str,
+    violations: &mut Vec<Violation>,
+) {
+    for (idx, particle) in particles.iter().enumerate() {
+        let count = if idx == 0 { first_count } else { 0 };
+        if count < particle.min && !misplaced.iter().any(|name| name == particle.label) {
+            schema_violation(
+                violations,
+                format!("Required element {} is missing in {}", particle.label, path),
+            );
+        }
+    }
+}
+
+fn check_aggregate(
+    node: Node,
+    model: &[&'static str],
+    path: &str,
+    violations: &mut Vec<Violation>,
+) {
+    for attribute in node.attributes() {
+        schema_violation(
+            violations,
+            format!("Attribute {} is not allowed on {}", attribute.name(), path),
+        );
+    }
+    let particles: Vec<Particle> = model.iter().map(|entry| Particle::new(entry)).collect();
+    let children: Vec<Node> = node.children().filter(Node::is_element).collect();
+
+    // walks the sequence once, an element that belongs earlier is out of order
+    let (mut position, mut count) = (0, 0);
+    let mut misplaced = Vec::new();
+    for child in &children {
+        let name = qualified(*child);
+        if position < particles.len() && particles[position].matches(*child) {
+            count += 1;
+            if particles[position].max == 1 && count == 2 {
+                schema_violation(
+                    violations,
+                    format!("{} may appear only once in {}", name, path),
+                );
+            }
+            continue;
+        }
+        let later = particles
+            .iter()
+            .skip(position + 1)
+            .position(|particle| particle.matches(*child));
+        match later {
+            Some(offset) => {
+                let next = position + 1 + offset;
+                check_missing(
+                    &particles[position.min(next)..next],
+                    count,
+                    &misplaced,
+                    path,
+                    violations,
+                );
+                position = next;
+                count = 1;
+            }
+            None if particles.iter().any(|particle| particle.matches(*child)) => {
+                schema_violation(
+                    violations,
+                    format!("{} is out of the UBL element order in {}", name, path),
+                );
+                misplaced.push(name);
+            }
+            None => schema_violation(violations, format!("{} is not allowed in {}", name, path)),
+        }
+    }
+    if position < particles.len() {
+        check_missing(&particles[position..], count, &misplaced, path, violations);
+    }
+
+    for child in children {
+        let child_path = format!("{}/{}", path, qualified(child));
+        match child.tag_name().namespace() {
+            Some(CBC) => check_basic(child, &child_path, violations),
+            Some(CAC) | Some(EXT) => match content_model(child.tag_name().name()) {
+                Some(model) => check_aggregate(child, model, &child_path, violations),
+                None => schema_violation(
+                    violations,
+                    format!(
+                        "{} is not covered by the UBL 2.1 models Invoicy checks against",
+                        child_path
+                    ),
+                ),
+            },
+            _ => {}
+        }
+    }
+}
+
+fn check_basic(node: Node, path: &str, violations: &mut Vec<Violation>) {
+    if node.children().any(|child| child.is_element()) {
+        schema_violation(violations, format!("{} cannot contain elements", path));
+    }
+    let data_type = DataType::of(node.tag_name().name());
+    let (allowed, required) = data_type.attributes();
+    for attribute in node.attributes() {
+        if attribute.namespace().is_some() || !allowed.contains(&attribute.name()) {
+            schema_violation(
+                violations,
+                format!("Attribute {} is not allowed on {}", attribute.name(), path),
+            );
+        }
+    }
+    if let Some(required) = required {
+        if node.attribute(required).is_none() {
+            schema_violation(
+                violations,
+                format!("{} needs a {} attribute", path, required),
+            );
+        }
+    }
+    let value = node.text().unwrap_or("").trim();
+    if let Err(expected) = data_type.accepts(value) {
+        schema_violation(
+            violations,
+            format!("'{}' in {} is not {}", value, path, expected),
+        );
+    }
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children()
+        .find(|child| child.is_element() && child.tag_name().name() == name)
+}
+
+fn elements<'a, 'input: 'a>(
+    node: Node<'a, 'input>,
+    name: &'a str,
+) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+    node.children()
+        .filter(move |child| child.is_element() && child.tag_name().name() == name)
+}
+
+// the first element along `path`, by local name as the schema part checks the namespaces
+fn find<'a, 'input>(node: Node<'a, 'input>, path: &[&str]) -> Option<Node<'a, 'input>> {
+    path.iter().try_fold(node, |node, name| child(node, name))
+}
+
+fn text<'a>(node: Node<'a, '_>, path: &[&str]) -> Option<&'a str> {
+    find(node, path)
+        .and_then(|node| node.text())
+        .map(str::trim)
+        .filter(|text| !text.is_empty())
+}
+
+fn number(node: Node, path: &[&str]) -> Option<f64> {
+    text(node, path).and_then(|text| text.parse::<f64>().ok())
+}
+
+fn has_cents_at_most(value: &str) -> bool {
+    value
+        .split_once('.')
+        .is_none_or(|(_, cents)| cents.len() <= 2)
+}
+
+fn differs(a: f64, b: f64) -> bool {
+    (round_cents(a) - round_cents(b)).abs() > 0.001
+}
+
+// the party's VAT identifier, the CompanyID of its VAT tax scheme
+fn vat_id<'a>(party: Option<Node<'a, '_>>) -> Option<&'a str> {
+    party
+        .and_then(|party| {
+            elements(party, "PartyTaxScheme")
+                .find(|scheme| text(*scheme, &["TaxScheme", "ID"]) == Some("VAT"))
+        })
+        .and_then(|scheme| text(scheme, &["CompanyID"]))
+}
+
+// what the VAT rules need to know about a line
+struct LineVat<'a> {
+    code: &'a str,
+    percent: Option<f64>,
+    amount: f64,
+}
+
+struct BreakdownVat<'a> {
+    code: &'a str,
+    percent: Option<f64>,
+    taxable: Option<f64>,
+    tax: Option<f64>,
+    exempt: bool,
+}
+
+fn check_header(root: Node, violations: &mut Vec<Violation>) {
+    let credit_note = root.tag_name().name() == "CreditNote";
+    match text(root, &["CustomizationID"]) {
+        None => violations.push(Violation::new(
+            "BR-01",
+            "The specification identifier is missing",
+        )),
+        Some(id) if !id.starts_with(CUSTOMIZATION_ID) => violations.push(Violation::new(
+            "PEPPOL-EN16931-R004",
+            format!("'{}' is not a Peppol BIS Billing 3.0 specification", id),
+        )),
+        Some(_) => {}
+    }
+    match text(root, &["ProfileID"]) {
+        None => violations.push(Violation::new(
+            "PEPPOL-EN16931-R001",
+            "The business process is missing",
+        )),
+        Some(profile) => {
+            let process = profile
+                .strip_prefix("urn:fdc:peppol.eu:2017:poacc:billing:")
+                .and_then(|rest| rest.strip_suffix(":1.0"));
+            if !process.is_some_and(|nn| nn.len() == 2 && nn.chars().all(|c| c.is_ascii_digit())) {
+                violations.push(Violation::new(
+                    "PEPPOL-EN16931-R007",
+                    format!(
+                        "Business process '{}' is not of the form \
+                         urn:fdc:peppol.eu:2017:poacc:billing:NN:1.0",
+                        profile
+                    ),
+                ));
+            }
+        }
+    }
+    if text(root, &["ID"]).is_none() {
+        violations.push(Violation::new("BR-02", "The invoice number is missing"));
+    }
+    if text(root, &["IssueDate"]).is_none() {
+        violations.push(Violation::new("BR-03", "The issue date is missing"));
+    }
+    let (type_name, types, types_rule) = if credit_note {
+        (
+            "CreditNoteTypeCode",
+            CREDIT_NOTE_TYPES,
+            "PEPPOL-EN16931-P0101",
+        )
+    } else {
+        ("InvoiceTypeCode", INVOICE_TYPES, "PEPPOL-EN16931-P0100")
+    };
+    match text(root, &[type_name]) {
+        None => violations.push(Violation::new("BR-04", "The invoice type code is missing")),
+        Some(code) if !listed(types, code) => violations.push(Violation::new(
+            types_rule,
+            format!("Type code '{}' is not allowed for this document", code),
+        )),
+        Some(_) => {}
+    }
+    let currency = text(root, &["DocumentCurrencyCode"]);
+    match currency {
+        None => violations.push(Violation::new("BR-05", "The invoice currency is missing")),
+        Some(code) if !is_currency_code(code) => violations.push(Violation::new(
+            "BR-CL-04",
+            format!("Currency '{}' is not an ISO 4217 code", code),
+        )),
+        Some(_) => {}
+    }
+    if elements(root, "Note").count() > 1 {
+        violations.push(Violation::new(
+            "PEPPOL-EN16931-R002",
+            "Only one note is allowed on the invoice",
+        ));
+    }
+    if text(root, &["BuyerReference"]).is_none() && text(root, &["OrderReference", "ID"]).is_none()
+    {
+        violations.push(Violation::new(
+            "PEPPOL-EN16931-R003",
+            "A buyer reference or order reference is required",
+        ));
+    }
+
+    let tax_currency = text(root, &["TaxCurrencyCode"]);
+    for node in root.descendants().filter(Node::is_element) {
+        if let Some(amount_currency) = node.attribute("currencyID") {
+            let in_tax_currency = Some(amount_currency) == tax_currency
+                && node.parent().map(|parent| parent.tag_name().name()) == Some("TaxTotal");
+            if !is_currency_code(amount_currency) {
+                violations.push(Violation::new(
+                    "BR-CL-03",
+                    format!(
+                        "Currency '{}' of {} is not an ISO 4217 code",
+                        amount_currency,
+                        qualified(node)
+                    ),
+                ));
+            } else if currency.is_some() && Some(amount_currency) != currency && !in_tax_currency {
+                violations.push(Violation::new(
+                    "PEPPOL-EN16931-R051",
+                    format!(
+                        "{} is in {}, the invoice is in {}",
+                        qualified(node),
+                        amount_currency,
+                        currency.unwrap_or_default()
+                    ),
+                ));
+            }
+        }
+        let empty = !node.children().any(|child| child.is_element())
+            && node.text().is_none_or(|text| text.trim().is_empty());
+        if empty {
+            violations.push(Violation::new(
+                "PEPPOL-EN16931-R008",
+                format!("{} is empty", qualified(node)),
+            ));
+        }
+    }
+
+    let payable = number(root, &["LegalMonetaryTotal", "PayableAmount"]).unwrap_or(0.0);
+    if payable > 0.0
+        && text(root, &["DueDate"]).is_none()
+        && text(root, &["PaymentTerms", "Note"]).is_none()
+    {
+        violations.push(Violation::new(
+            "BR-CO-25",
+            "Payment terms or a due date are required when an amount is due",
+        ));
+    }
+}
+
+// the check digits of the identifier schemes PEPPOL-COMMON verifies
+fn check_identifier(label: &str, scheme: &str, id: &str, violations: &mut Vec<Violation>) {
+    let digits: Vec<u32> = id.chars().filter_map(|c| c.to_digit(10)).collect();
+    let all_digits = |len: usize| id.len() == len && digits.len() == len;
+    let (rule, valid) = match scheme {
+        // GLN, a GS1 check digit with weights 3 and 1 from the right
+        "0088" => (
+            "PEPPOL-COMMON-R040",
+            all_digits(13) && {
+                let sum: u32 = digits[..12]
+                    .iter()
+                    .rev()
+                    .enumerate()
+                    .map(|(idx, digit)| digit * if idx % 2 == 0 { 3 } else { 1 })
+                    .sum();
+                (10 - sum % 10) % 10 == digits[12]
+            },
+        ),
+        // Norwegian organisation number, modulus 11
+        "0192" => (
+            "PEPPOL-COMMON-R041",
+            all_digits(9) && {
+                let sum: u32 = digits[..8]
+                    .iter()
+                    .zip([3, 2, 7, 6, 5, 4, 3, 2])
+                    .map(|(digit, weight)| digit * weight)
+                    .sum();
+                match 11 - sum % 11 {
+                    11 => digits[8] == 0,
+                    10 => false,
+                    check => digits[8] == check,
+                }
+            },
+        ),
+        // Belgian enterprise number, modulus 97
+        "0208" => (
+            "PEPPOL-COMMON-R043",
+            all_digits(10) && {
+                let base = digits[..8].iter().fold(0, |acc, digit| acc * 10 + digit);
+                97 - base % 97 == digits[8] * 10 + digits[9]
+            },
+        ),
+        // Italian IPA code
+        "0201" => (
+            "PEPPOL-COMMON-R044",
+            id.len() == 6 && id.chars().all(|c| c.is_ascii_alphanumeric()),
+        ),
+        _ => return,
+    };
+    if !valid {
+        violations.push(Violation::new(
+            rule,
+            format!(
+                "{} electronic address '{}' is not a valid {} identifier",
+                label, id, scheme
+            ),
+        ));
+    }
+}
+
+fn check_parties(root: Node, violations: &mut Vec<Violation>) {
+    let parties = [
+        (
+            "AccountingSupplierParty",
+            "Seller",
+            ["BR-06", "BR-08", "BR-09", "PEPPOL-EN16931-R020", "BR-62"],
+        ),
+        (
+            "AccountingCustomerParty",
+            "Buyer",
+            ["BR-07", "BR-10", "BR-11", "PEPPOL-EN16931-R010", "BR-63"],
+        ),
+    ];
+    for (tag, label, [name_rule, address_rule, country_rule, endpoint_rule, scheme_rule]) in parties
+    {
+        let party = find(root, &[tag, "Party"]);
+        let field = |path: &[&str]| party.and_then(|party| text(party, path));
+        if field(&["PartyLegalEntity", "RegistrationName"]).is_none() {
+            violations.push(Violation::new(
+                name_rule,
+                format!("{} name is missing", label),
+            ));
+        }
+        if party
+            .and_then(|party| child(party, "PostalAddress"))
+            .is_none()
+        {
+            violations.push(Violation::new(
+                address_rule,
+                format!("{} address is missing", label),
+            ));
+        }
+        match field(&["PostalAddress", "Country", "IdentificationCode"]) {
+            None => violations.push(Violation::new(
+                country_rule,
+                format!("{} country code is missing", label),
+            )),
+            Some(country) if !is_country_code(country) => violations.push(Violation::new(
+                "BR-CL-14",
+                format!("{} country '{}' is not an ISO 3166 code", label, country),
+            )),
+            Some(_) => {}
+        }
+        match party.and_then(|party| child(party, "EndpointID")) {
+            None => violations.push(Violation::new(
+                endpoint_rule,
+                format!("{} electronic address is missing", label),
+            )),
+            Some(endpoint) => match endpoint.attribute("schemeID") {
+                None => violations.push(Violation::new(
+                    scheme_rule,
+                    format!("{} electronic address has no scheme", label),
+                )),
+                Some(scheme) if !listed(ENDPOINT_SCHEMES, scheme) => {
+                    violations.push(Violation::new(
+                        "BR-CL-25",
+                        format!(
+                            "{} electronic address scheme '{}' is not in the EAS code list",
+                            label, scheme
+                        ),
+                    ))
+                }
+                Some(scheme) => check_identifier(
+                    label,
+                    scheme,
+                    endpoint.text().unwrap_or("").trim(),
+                    violations,
+                ),
+            },
+        }
+        if let Some(vat) = vat_id(party) {
+            let prefix = vat.get(..2).unwrap_or("");
+            if !(is_country_code(prefix) || prefix == "EL") {
+                violations.push(Violation::new(
+                    "BR-CO-09",
+                    format!(
+                        "{} VAT ID '{}' does not start with a country code",
+                        label, vat
+                    ),
+                ));
+            }
+        }
+        if label == "Seller"
+            && vat_id(party).is_none()
+            && field(&["PartyIdentification", "ID"]).is_none()
+            && field(&["PartyLegalEntity", "CompanyID"]).is_none()
+        {
+            violations.push(Violation::new(
+                "BR-CO-26",
+                "The seller needs a VAT ID, an identifier or a legal registration number",
+            ));
+        }
+    }
+}
+
+fn check_lines<'a>(root: Node<'a, '_>, violations: &mut Vec<Violation>) -> Vec<LineVat<'a>> {
+    let (line_name, quantity_name) = if root.tag_name().name() == "CreditNote" {
+        ("CreditNoteLine", "CreditedQuantity")
+    } else {
+        ("InvoiceLine", "InvoicedQuantity")
+    };
+    let mut lines = Vec::new();
+    for (idx, line) in elements(root, line_name).enumerate() {
+        let row = idx + 1;
+        if text(line, &["ID"]).is_none() {
+            violations.push(Violation::new(
+                "BR-21",
+                format!("Line {}: the line number is missing", row),
+            ));
+        }
+        let quantity = child(line, quantity_name);
+        match quantity.map(|quantity| quantity.attribute("unitCode")) {
+            None => violations.push(Violation::new(
+                "BR-22",
+                format!("Line {}: the quantity is missing", row),
+            )),
+            Some(None) => violations.push(Violation::new(
+                "BR-23",
+                format!("Line {}: the quantity has no unit", row),
+            )),
+            // the full recommendation 20 list has some two thousand codes, only their form is
+            // checked
+            Some(Some(unit))
+                if !((2..=3).contains(&unit.len())
+                    && unit
+                        .chars()
+                        .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())) =>
+            {
+                violations.push(Violation::new(
+                    "BR-CL-23",
+                    format!(
+                        "Line {}: '{}' is not a UN/ECE recommendation 20 unit code",
+                        row, unit
+                    ),
+                ))
+            }
+            Some(Some(_)) => {}
+        }
+        let amount = text(line, &["LineExtensionAmount"]);
+        match amount {
+            None => violations.push(Violation::new(
+                "BR-24",
+                format!("Line {}: the net amount is missing", row),
+            )),
+            Some(amount) if !has_cents_at_most(amount) => violations.push(Violation::new(
+                "BR-DEC-23",
+                format!("Line {}: the net amount has more than two decimals", row),
+            )),
+            Some(_) => {}
+        }
+        let amount = number(line, &["LineExtensionAmount"]);
+        if text(line, &["Item", "Name"]).is_none() {
+            violations.push(Violation::new(
+                "BR-25",
+                format!("Line {}: the item name is missing", row),
+            ));
+        }
+        let price = number(line, &["Price", "PriceAmount"]);
+        match price {
+            None => violations.push(Violation::new(
+                "BR-26",
+                format!("Line {}: the item price is missing", row),
+            )),
+            Some(price) if price < 0.0 => violations.push(Violation::new(
+                "BR-27",
+                format!("Line {}: the item price cannot be negative", row),
+            )),
+            Some(_) => {}
+        }
+        let base = number(line, &["Price", "BaseQuantity"]);
+        if base.is_some_and(|base| base <= 0.0) {
+            violations.push(Violation::new(
+                "PEPPOL-EN16931-R121",
+                format!("Line {}: the base quantity must be above 0", row),
+            ));
+        }
+        let quantity = quantity
+            .and_then(|quantity| quantity.text())
+            .and_then(|text| text.trim().parse::<f64>().ok());
+        if let (Some(quantity), Some(price), Some(amount)) = (quantity, price, amount) {
+            let base = base.filter(|base| *base > 0.0).unwrap_or(1.0);
+            if differs(quantity * price / base, amount) {
+                violations.push(Violation::new(
+                    "PEPPOL-EN16931-R120",
+                    format!(
+                        "Line {}: the net amount is not the quantity times the price",
+                        row
+                    ),
+                ));
+            }
+        }
+
+        let category = find(line, &["Item", "ClassifiedTaxCategory"]);
+        let code = match category.and_then(|category| text(category, &["ID"])) {
+            Some(code) => code,
+            None => {
+                violations.push(Violation::new(
+                    "BR-CO-04",
+                    format!("Line {}: the VAT category is missing", row),
+                ));
+                continue;
+            }
+        };
+        let percent = category.and_then(|category| number(category, &["Percent"]));
+        let rules = CATEGORY_RULES.iter().find(|(known, _)| *known == code);
+        let line_rate = match rules {
+            Some((_, rules)) => rules[2],
+            None => {
+                violations.push(Violation::new(
+                    "BR-CL-18",
+                    format!("Line {}: '{}' is not a VAT category code", row, code),
+                ));
+                continue;
+            }
+        };
+        let message = match code {
+            "S" if percent.is_none_or(|percent| percent <= 0.0) => {
+                Some("standard rated lines need a VAT rate above 0".to_string())
+            }
+            "Z" | "E" | "AE" | "K" | "G" if percent != Some(0.0) => {
+                Some(format!("category {} lines have a VAT rate of 0", code))
+            }
+            "O" if percent.is_some() => {
+                Some("lines not subject to VAT have no VAT rate".to_string())
+            }
+            "L" | "M" if percent.is_none_or(|percent| percent < 0.0) => Some(format!(
+                "category {} lines need a VAT rate of 0 or more",
+                code
+            )),
+            _ => None,
+        };
+        if let Some(message) = message {
+            violations.push(Violation::new(
+                line_rate,
+                format!("Line {}: {}", row, message),
+            ));
+        }
+        lines.push(LineVat {
+            code,
+            percent,
+            amount: amount.unwrap_or(0.0),
+        });
+    }
+    if lines.is_empty() && child(root, line_name).is_none() {
+        violations.push(Violation::new("BR-16", "The invoice has no item lines"));
+    }
+    lines
+}
+
+fn check_totals(root: Node, lines: &[LineVat], violations: &mut Vec<Violation>) {
+    let totals = child(root, "LegalMonetaryTotal");
+    for (name, label, rule, decimals_rule) in [
+        (
+            "LineExtensionAmount",
+            "sum of line net amounts",
+            "BR-12",
+            "BR-DEC-09",
+        ),
+        (
+            "TaxExclusiveAmount",
+            "total without VAT",
+            "BR-13",
+            "BR-DEC-12",
+        ),
+        ("TaxInclusiveAmount", "total with VAT", "BR-14", "BR-DEC-14"),
+        ("PayableAmount", "amount due", "BR-15", "BR-DEC-18"),
+    ] {
+        match totals.and_then(|totals| text(totals, &[name])) {
+            None => violations.push(Violation::new(rule, format!("The {} is missing", label))),
+            Some(value) if !has_cents_at_most(value) => violations.push(Violation::new(
+                decimals_rule,
+                format!("The {} has more than two decimals", label),
+            )),
+            Some(_) => {}
+        }
+    }
+    let total = |name: &str| totals.and_then(|totals| number(totals, &[name]));
+
+    if let Some(line_total) = total("LineExtensionAmount") {
+        let sum: f64 = lines.iter().map(|line| line.amount).sum();
+        if differs(sum, line_total) {
+            violations.push(Violation::new(
+                "BR-CO-10",
+                "The line total does not match the sum of the lines",
+            ));
+        }
+    }
+    if let (Some(line_total), Some(exclusive)) =
+        (total("LineExtensionAmount"), total("TaxExclusiveAmount"))
+    {
+        let expected = line_total - total("AllowanceTotalAmount").unwrap_or(0.0)
+            + total("ChargeTotalAmount").unwrap_or(0.0);
+        if differs(expected, exclusive) {
+            violations.push(Violation::new(
+                "BR-CO-13",
+                "The total without VAT does not match the line total",
+            ));
+        }
+    }
+    let currency = text(root, &["DocumentCurrencyCode"]);
+    let tax = elements(root, "TaxTotal")
+        .filter_map(|tax_total| child(tax_total, "TaxAmount"))
+        .find(|amount| amount.attribute("currencyID") == currency)
+        .and_then(|amount| amount.text())
+        .and_then(|text| text.trim().parse::<f64>().ok())
+        .unwrap_or(0.0);
+    if let (Some(exclusive), Some(inclusive)) =
+        (total("TaxExclusiveAmount"), total("TaxInclusiveAmount"))
+    {
+        if differs(exclusive + tax, inclusive) {
+            violations.push(Violation::new(
+                "BR-CO-15",
+                "The total with VAT does not equal the total without VAT plus VAT",
+            ));
+        }
+    }
+    if let (Some(inclusive), Some(payable)) = (total("TaxInclusiveAmount"), total("PayableAmount"))
+    {
+        let expected = inclusive - total("PrepaidAmount").unwrap_or(0.0)
+            + total("PayableRoundingAmount").unwrap_or(0.0);
+        if differs(expected, payable) {
+            violations.push(Violation::new(
+                "BR-CO-16",
+                "The amount due does not equal the total with VAT less prepaid amounts",
+            ));
+        }
+    }
+}
+
+fn check_breakdown<'a>(
+    root: Node<'a, '_>,
+    violations: &mut Vec<Violation>,
+) -> Vec<BreakdownVat<'a>> {
+    let with_subtotals: Vec<Node> = elements(root, "TaxTotal")
+        .filter(|tax_total| child(*tax_total, "TaxSubtotal").is_some())
+        .collect();
+    let tax_total = match with_subtotals.as_slice() {
+        [] => {
+            violations.push(Violation::new(
+                "BR-CO-18",
+                "The invoice has no VAT breakdown",
+            ));
+            return Vec::new();
+        }
+        [tax_total] => *tax_total,
+        [tax_total, ..] => {
+            violations.push(Violation::new(
+                "PEPPOL-EN16931-R053",
+                "Only one tax total may have a VAT breakdown",
+            ));
+            *tax_total
+        }
+    };
+    if text(tax_total, &["TaxAmount"]).is_some_and(|amount| !has_cents_at_most(amount)) {
+        violations.push(Violation::new(
+            "BR-DEC-13",
+            "The VAT total has more than two decimals",
+        ));
+    }
+
+    let mut breakdown = Vec::new();
+    for subtotal in elements(tax_total, "TaxSubtotal") {
+        let category = child(subtotal, "TaxCategory");
+        let code = category.and_then(|category| text(category, &["ID"]));
+        let label = code.unwrap_or("?");
+        for (name, rule, decimals_rule, what) in [
+            ("TaxableAmount", "BR-45", "BR-DEC-19", "taxable amount"),
+            ("TaxAmount", "BR-46", "BR-DEC-20", "VAT amount"),
+        ] {
+            match text(subtotal, &[name]) {
+                None => violations.push(Violation::new(
+                    rule,
+                    format!("The VAT breakdown of category {} has no {}", label, what),
+                )),
+                Some(value) if !has_cents_at_most(value) => violations.push(Violation::new(
+                    decimals_rule,
+                    format!(
+                        "The {} of category {} has more than two decimals",
+                        what, label
+                    ),
+                )),
+                Some(_) => {}
+            }
+        }
+        let code = match code {
+            Some(code) if CATEGORIES.contains(&code) => code,
+            Some(code) => {
+                violations.push(Violation::new(
+                    "BR-CL-17",
+                    format!("'{}' is not a VAT category code", code),
+                ));
+                continue;
+            }
+            None => {
+                violations.push(Violation::new(
+                    "BR-47",
+                    "A VAT breakdown has no VAT category",
+                ));
+                continue;
+            }
+        };
+        let percent = category.and_then(|category| number(category, &["Percent"]));
+        if code != "O" && percent.is_none() {
+            violations.push(Violation::new(
+                "BR-48",
+                format!("The VAT breakdown of category {} has no VAT rate", code),
+            ));
+        }
+        let (taxable, tax) = (
+            number(subtotal, &["TaxableAmount"]),
+            number(subtotal, &["TaxAmount"]),
+        );
+        if let (Some(taxable), Some(tax), Some(percent)) = (taxable, tax, percent) {
+            if differs(round_cents(taxable * percent / 100.0), tax) {
+                violations.push(Violation::new(
+                    "BR-CO-17",
+                    format!(
+                        "The VAT of category {} is not the taxable amount times the rate",
+                        code
+                    ),
+                ));
+            }
+        }
+        let exempt = category.is_some_and(|category| {
+            text(category, &["TaxExemptionReason"]).is_some()
+                || text(category, &["TaxExemptionReasonCode"]).is_some()
+        });
+        breakdown.push(BreakdownVat {
+            code,
+            percent,
+            taxable,
+            tax,
+            exempt,
+        });
+    }
+    if let Some(tax) = number(tax_total, &["TaxAmount"]) {
+        let sum: f64 = breakdown.iter().filter_map(|entry| entry.tax).sum();
+        if differs(sum, tax) {
+            violations.push(Violation::new(
+                "BR-CO-14",
+                "The VAT total does not match the sum of the VAT breakdown",
+            ));
+        }
+    }
+    breakdown
+}
+
+fn check_categories(
+    root: Node,
+    lines: &[LineVat],
+    breakdown: &[BreakdownVat],
+    violations: &mut Vec<Violation>,
+) {
+    let seller_vat = vat_id(find(root, &["AccountingSupplierParty", "Party"]));
+    let buyer_vat = vat_id(find(root, &["AccountingCustomerParty", "Party"]));
+    for (code, rules) in &CATEGORY_RULES {
+        let [breakdown_rule, vat_ids_rule, _, taxable_rule, tax_rule, reason_rule] = *rules;
+        let code_lines: Vec<&LineVat> = lines.iter().filter(|line| line.code == *code).collect();
+        let entries: Vec<&BreakdownVat> = breakdown
+            .iter()
+            .filter(|entry| entry.code == *code)
+            .collect();
+        // standard rated and the Canary and Ceuta categories have a breakdown per rate
+        let rated = matches!(*code, "S" | "L" | "M");
+        if !code_lines.is_empty() && entries.is_empty() {
+            violations.push(Violation::new(
+                breakdown_rule,
+                format!(
+                    "Category {} lines need a VAT breakdown of category {}",
+                    code, code
+                ),
+            ));
+        }
+        if !rated && entries.len() > 1 {
+            violations.push(Violation::new(
+                breakdown_rule,
+                format!("Category {} has more than one VAT breakdown", code),
+            ));
+        }
+
+        if !code_lines.is_empty() {
+            if *code == "O" {
+                if seller_vat.is_some() || buyer_vat.is_some() {
+                    violations.push(Violation::new(
+                        vat_ids_rule,
+                        "Lines not subject to VAT cannot have the seller's or buyer's VAT ID",
+                    ));
+                }
+            } else {
+                if seller_vat.is_none() {
+                    violations.push(Violation::new(
+                        vat_ids_rule,
+                        format!("Category {} lines need the seller's VAT ID", code),
+                    ));
+                }
+                if matches!(*code, "AE" | "K") && buyer_vat.is_none() {
+                    violations.push(Violation::new(
+                        vat_ids_rule,
+                        format!("Category {} lines need the buyer's VAT ID", code),
+                    ));
+                }
+            }
+        }
+
+        for entry in entries {
+            let expected: f64 = code_lines
+                .iter()
+                .filter(|line| !rated || line.percent == entry.percent)
+                .map(|line| line.amount)
+                .sum();
+            if entry
+                .taxable
+                .is_some_and(|taxable| differs(expected, taxable))
+            {
+                violations.push(Violation::new(
+                    taxable_rule,
+                    format!(
+                        "The taxable amount of category {} does not match its lines",
+                        code
+                    ),
+                ));
+            }
+            let wrong_tax = match (entry.tax, entry.taxable, entry.percent) {
+                (Some(tax), Some(taxable), Some(percent)) if rated => {
+                    differs(round_cents(taxable * percent / 100.0), tax)
+                }
+                (Some(tax), _, _) if !rated => tax != 0.0,
+                _ => false,
+            };
+            if wrong_tax {
+                violations.push(Violation::new(
+                    tax_rule,
+                    if rated {
+                        format!(
+                            "The VAT of category {} is not the taxable amount times the rate",
+                            code
+                        )
+                    } else {
+                        format!("Category {} carries no VAT", code)
+                    },
+                ));
+            }
+            let needs_reason = !matches!(*code, "S" | "Z" | "L" | "M");
+            if needs_reason && !entry.exempt {
+                violations.push(Violation::new(
+                    reason_rule,
+                    format!(
+                        "The VAT breakdown of category {} needs an exemption reason",
+                        code
+                    ),
+                ));
+            } else if !needs_reason && entry.exempt {
+                violations.push(Violation::new(
+                    reason_rule,
+                    format!(
+                        "The VAT breakdown of category {} cannot have an exemption reason",
+                        code
+                    ),
+                ));
+            }
+        }
+    }
+
+    let has = |code: &str| breakdown.iter().any(|entry| entry.code == code);
+    if has("O") && breakdown.iter().any(|entry| entry.code != "O") {
+        violations.push(Violation::new(
+            "BR-O-11",
+            "A VAT breakdown of category O cannot be combined with other categories",
+        ));
+    }
+    if has("K") {
+        if text(root, &["Delivery", "ActualDeliveryDate"]).is_none()
+            && child(root, "InvoicePeriod").is_none()
+        {
+            violations.push(Violation::new(
+                "BR-IC-11",
+                "Intra-community supplies need a delivery date or an invoicing period",
+            ));
+        }
+        let country = [
+            "Delivery",
+            "DeliveryLocation",
+            "Address",
+            "Country",
+            "IdentificationCode",
+        ];
+        if text(root, &country).is_none() {
+            violations.push(Violation::new(
+                "BR-IC-12",
+                "Intra-community supplies need the country they are delivered to",
+            ));
+        }
+    }
+}
+
+// Checks a UBL Invoice or CreditNote against the UBL 2.1 schema and the Peppol BIS Billing 3.0
+// rules, empty when the document can be sent. Schema violations carry the rule UBL-SCHEMA.
+pub fn validate_document(xml: &str) -> Vec<Violation> {
+    let document = match Document::parse(xml) {
+        Ok(document) => document,
+        Err(e) => return vec![Violation::new("UBL-XML", format!("Not well-formed: {}", e))],
+    };
+    let root = document.root_element();
+    let model = match (root.tag_name().namespace(), root.tag_name().name()) {
+        (Some(INVOICE_NAMESPACE), "Invoice") => INVOICE,
+        (Some(CREDIT_NOTE_NAMESPACE), "CreditNote") => CREDIT_NOTE,
+        _ => {
+            return vec![Violation::new(
+                "UBL-SCHEMA",
+                format!(
+                    "{} is not a UBL 2.1 Invoice or CreditNote",
+                    root.tag_name().name()
+                ),
+            )]
+        }
+    };
+    let mut violations = Vec::new();
+    check_aggregate(root, model, root.tag_name().name(), &mut violations);
+    check_header(root, &mut violations);
+    check_parties(root, &mut violations);
+    let lines = check_lines(root, &mut violations);
+    check_totals(root, &lines, &mut violations);
+    let breakdown = check_breakdown(root, &mut violations);
+    check_categories(root, &lines, &breakdown, &mut violations);
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structs::{Contact, Customer, DatabaseData};
+    use crate::test_support::{buyer, invoice, item, seller};
+    use crate::ubl::{to_xml, EInvoice};
+
+    fn document(items: &[DatabaseData], seller: &Contact, buyer: &Customer) -> String {
+        to_xml(&EInvoice {
+            invoice: &invoice(),
+            seller,
+            buyer,
+            items,
+            currency: "EUR",
+        })
+    }
+
+    fn rules(xml: &str) -> Vec<&'static str> {
+        validate_document(xml)
+            .iter()
+            .map(|violation| violation.rule)
+            .collect()
+    }
+
+    #[test]
+    fn test_written_documents_are_valid() {
+        let items = vec![
+            item("Design", 2.0, 50.0, "S:19"),
+            item("Books", 3.0, 12.5, "S:7"),
+            item("Training", 1.0, 40.0, "E"),
+        ];
+        assert_eq!(
+            validate_document(&document(&items, &seller(), &buyer())),
+            vec![]
+        );
+        let refund = vec![item("Refund", -1.0, 100.0, "S:19")];
+        assert_eq!(
+            validate_document(&document(&refund, &seller(), &buyer())),
+            vec![]
+        );
+        let reverse_charge = vec![item("Consulting", 4.0, 80.0, "AE")];
+        assert_eq!(
+            validate_document(&document(&reverse_charge, &seller(), &buyer())),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_schema_violations_are_reported() {
+        let xml = document(&[item("Design", 1.0, 100.0, "S:19")], &seller(), &buyer());
+
+        let reordered = xml
+            .replace(
+                "<cbc:DocumentCurrencyCode>EUR</cbc:DocumentCurrencyCode>",
+                "",
+            )
+            .replace(
+                "<cac:TaxTotal>",
+                "<cbc:DocumentCurrencyCode>EUR</cbc:DocumentCurrencyCode>\n<cac:TaxTotal>",
+            );
+        assert_eq!(
+            validate_document(&reordered)[0].message,
+            "cbc:DocumentCurrencyCode is out of the UBL element order in Invoice"
+        );
+
+        // a nested aggregate without its required child
+        let without_scheme = xml.replacen(
+            "<cac:TaxScheme>\n          <cbc:ID>VAT</cbc:ID>\n        </cac:TaxScheme>",
+            "",
+            1,
+        );
+        assert_ne!(without_scheme, xml);
+        assert_eq!(
+            validate_document(&without_scheme)[0].message,
+            "Required element cac:TaxScheme is missing in \
+             Invoice/cac:AccountingSupplierParty/cac:Party/cac:PartyTaxScheme"
+        );
+
+        let without_currency = xml.replace(
+            "<cbc:PayableAmount currencyID=\"EUR\">",
+            "<cbc:PayableAmount>",
+        );
+        assert_eq!(
+            validate_document(&without_currency)[0].message,
+            "Invoice/cac:LegalMonetaryTotal/cbc:PayableAmount needs a currencyID attribute"
+        );
+
+        let bad_date = xml.replace("2024-05-01", "2024-02-30");
+        assert_eq!(
+            validate_document(&bad_date)[0].message,
+            "'2024-02-30' in Invoice/cbc:IssueDate is not a date"
+        );
+
+        let twice = xml.replace(
+            "</cac:OrderReference>",
+            "</cac:OrderReference>\n<cac:OrderReference><cbc:ID>PO-2</cbc:ID></cac:OrderReference>",
+        );
+        assert_eq!(
+            validate_document(&twice)[0].message,
+            "cac:OrderReference may appear only once in Invoice"
+        );
+
+        let unknown = xml.replace(
+            "<cbc:PayableAmount",
+            "<cbc:DiscountAmount currencyID=\"EUR\">1.00</cbc:DiscountAmount>\n<cbc:PayableAmount",
+        );
+        assert_eq!(
+            validate_document(&unknown)[0].message,
+            "cbc:DiscountAmount is not allowed in Invoice/cac:LegalMonetaryTotal"
+        );
+
+        let uncovered = xml.replace(
+            "<cac:PaymentTerms>",
+            "<cac:PaymentMeans><cbc:PaymentMeansCode>30</cbc:PaymentMeansCode></cac:PaymentMeans>\n\
+             <cac:PaymentTerms>",
+        );
+        assert_eq!(
+            validate_document(&uncovered)[0].message,
+            "Invoice/cac:PaymentMeans is not covered by the UBL 2.1 models Invoicy checks against"
+        );
+        assert!(validate_document("<Invoice>")[0]
+            .message
+            .starts_with("Not well-formed"));
+    }
+
+    #[test]
+    fn test_business_rules_are_checked_on_the_xml() {
+        let xml = document(&[item("Design", 1.0, 100.0, "S:19")], &seller(), &buyer());
+
+        let tampered = xml.replace(
+            "<cbc:TaxInclusiveAmount currencyID=\"EUR\">119.00",
+            "<cbc:TaxInclusiveAmount currencyID=\"EUR\">120.00",
+        );
+        assert_eq!(rules(&tampered), vec!["BR-CO-15", "BR-CO-16"]);
+
+        let wrong_tax = xml.replace(
+            "<cbc:TaxAmount currencyID=\"EUR\">19.00</cbc:TaxAmount>\n      <cac:TaxCategory>",
+            "<cbc:TaxAmount currencyID=\"EUR\">18.00</cbc:TaxAmount>\n      <cac:TaxCategory>",
+        );
+        assert_ne!(wrong_tax, xml);
+        assert_eq!(rules(&wrong_tax), vec!["BR-CO-17", "BR-CO-14", "BR-S-09"]);
+
+        let other_currency = xml.replace(
+            "<cbc:PayableAmount currencyID=\"EUR\">",
+            "<cbc:PayableAmount currencyID=\"USD\">",
+        );
+        assert_eq!(rules(&other_currency), vec!["PEPPOL-EN16931-R051"]);
+
+        let bad_endpoint = xml.replace(
+            "<cbc:EndpointID schemeID=\"0208\">0123456749</cbc:EndpointID>",
+            "<cbc:EndpointID schemeID=\"0208\">0123456789</cbc:EndpointID>",
+        );
+        assert_eq!(rules(&bad_endpoint), vec!["PEPPOL-COMMON-R043"]);
+
+        let empty = xml.replace(
+            "<cbc:DocumentCurrencyCode>",
+            "<cbc:Note></cbc:Note>\n<cbc:DocumentCurrencyCode>",
+        );
+        assert_eq!(rules(&empty), vec!["PEPPOL-EN16931-R008"]);
+    }
+
+    #[test]
+    fn test_vat_categories_are_checked() {
+        let without_vat = Contact {
+            vat_id: String::new(),
+            ..seller()
+        };
+        let xml = document(&[item("Books", 1.0, 10.0, "Z")], &without_vat, &buyer());
+        assert_eq!(rules(&xml), vec!["BR-CO-26", "BR-Z-02"]);
+
+        let xml = document(&[item("Outside", 1.0, 10.0, "O")], &seller(), &buyer());
+        assert_eq!(rules(&xml), vec!["BR-O-02"]);
+
+        let xml = document(&[item("Parts", 1.0, 10.0, "K")], &seller(), &buyer());
+        assert_eq!(rules(&xml), vec!["BR-IC-11", "BR-IC-12"]);
+    }
+
+    #[test]
+    fn test_identifier_check_digits() {
+        let mut violations = Vec::new();
+        check_identifier("Buyer", "0088", "5790000435975", &mut violations);
+        check_identifier("Buyer", "0192", "974760673", &mut violations);
+        check_identifier("Buyer", "0208", "0403170701", &mut violations);
+        check_identifier("Buyer", "0201", "UFY9MH", &mut violations);
+        assert_eq!(violations, vec![]);
+        check_identifier("Buyer", "0088", "5790000435976", &mut violations);
+        check_identifier("Buyer", "0192", "974760674", &mut violations);
+        check_identifier("Buyer", "0208", "0403170702", &mut violations);
+        let rules: Vec<&str> = violations.iter().map(|violation| violation.rule).collect();
+        assert_eq!(
+            rules,
+            vec![
+                "PEPPOL-COMMON-R040",
+                "PEPPOL-COMMON-R041",
+                "PEPPOL-COMMON-R043"
+            ]
+        );
+    }
+}
@@ -0,0 +1,138 @@
+//! Customer price lists. A product's catalogue price can be replaced per customer by a fixed
+//! price or reduced by a percentage, either for one product or for everything they buy.
+
+use crate::structs::{CustomerPrice, PriceRule, Product};
+use crate::totals::round_cents;
+
+// where the price of a line filled from the catalogue came from, shown next to the price cell
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PriceSource {
+    Catalogue,
+    // fixed price negotiated for this product
+    CustomerPrice,
+    // percentage off this product or, with `all_products`, the whole catalogue
+    CustomerDiscount { percent: f64, all_products: bool },
+}
+
+impl PriceSource {
+    pub fn label(&self) -> String {
+        match self {
+            PriceSource::Catalogue => "Catalogue price".to_string(),
+            PriceSource::CustomerPrice => "Customer price".to_string(),
+            PriceSource::CustomerDiscount {
+                percent,
+                all_products: false,
+            } => format!("Customer discount: {}% off", percent),
+            PriceSource::CustomerDiscount {
+                percent,
+                all_products: true,
+            } => format!("Customer discount: {}% off all products", percent),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quote {
+    pub price: f64,
+    pub source: PriceSource,
+}
+
+// The price `customer` pays for `product`. A rule for the product itself wins over a
+// customer-wide discount, and a fixed price wins over a percentage for the same product.
+pub fn quote(product: &Product, customer: &str, prices: &[CustomerPrice]) -> Quote {
+    let own_rules = rules_for(prices, customer, Some(&product.sku));
+    if let Some(price) = own_rules.iter().find_map(|rule| match rule {
+        PriceRule::Fixed(price) => Some(*price),
+        PriceRule::PercentOff(_) => None,
+    }) {
+        return Quote {
+            price,
+            source: PriceSource::CustomerPrice,
+        };
+    }
+    let discount = |rules: &[PriceRule], all_products| {
+        rules.iter().find_map(|rule| match rule {
+            PriceRule::PercentOff(percent) => Some(Quote {
+                price: round_cents(product.price * (1.0 - percent / 100.0)),
+                source: PriceSource::CustomerDiscount {
+                    percent: *percent,
+                    all_products,
+                },
+            }),
+            // a fixed price only makes sense for a single product
+            PriceRule::Fixed(_) => None,
+        })
+    };
+    let all_rules = rules_for(prices, customer, None);
+    discount(&own_rules, false)
+        .or_else(|| discount(&all_rules, true))
+        .unwrap_or(Quote {
+            price: product.price,
+            source: PriceSource::Catalogue,
+        })
+}
+
+fn rules_for(prices: &[CustomerPrice], customer: &str, sku: Option<&str>) -> Vec<PriceRule> {
+    prices
+        .iter()
+        .filter(|price| price.customer == customer && price.sku.as_deref() == sku)
+        .map(|price| price.rule)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn product(sku: &str, price: f64) -> Product {
+        Product {
+            sku: sku.to_string(),
+            price,
+            active: true,
+            ..Default::default()
+        }
+    }
+
+    fn rule(customer: &str, sku: Option<&str>, rule: PriceRule) -> CustomerPrice {
+        CustomerPrice {
+            customer: customer.to_string(),
+            sku: sku.map(str::to_string),
+            rule,
+        }
+    }
+
+    #[test]
+    fn test_customer_rules_take_precedence() {
+        let prices = vec![
+            rule("ACME", None, PriceRule::PercentOff(10.0)),
+            rule("ACME", Some("CONS"), PriceRule::Fixed(80.0)),
+            rule("ACME", Some("CONS"), PriceRule::PercentOff(50.0)),
+            rule("ACME", Some("HOST"), PriceRule::PercentOff(25.0)),
+            rule("Other", Some("SUPP"), PriceRule::Fixed(1.0)),
+        ];
+        let consulting = quote(&product("CONS", 100.0), "ACME", &prices);
+        assert_eq!(consulting.price, 80.0);
+        assert_eq!(consulting.source, PriceSource::CustomerPrice);
+
+        let hosting = quote(&product("HOST", 19.99), "ACME", &prices);
+        assert_eq!(hosting.price, 14.99);
+        assert_eq!(
+            hosting.source,
+            PriceSource::CustomerDiscount {
+                percent: 25.0,
+                all_products: false
+            }
+        );
+
+        let support = quote(&product("SUPP", 50.0), "ACME", &prices);
+        assert_eq!(support.price, 45.0);
+        assert_eq!(
+            support.source.label(),
+            "Customer discount: 10% off all products"
+        );
+
+        let catalogue = quote(&product("SUPP", 50.0), "Nobody", &prices);
+        assert_eq!(catalogue.price, 50.0);
+        assert_eq!(catalogue.source, PriceSource::Catalogue);
+    }
+}
@@ -0,0 +1,508 @@
+use std::fs;
+use std::path::PathBuf;
+
+use crate::font_registry::DEFAULT_FONT;
+use crate::paths::settings_path;
+
+// settings are stored as plain `key=value` lines so they can be edited by hand
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+    pub database_path: Option<PathBuf>,
+    pub fonts: DocumentFonts,
+    pub text_layout: TextLayout,
+    // ISO 4217 code, amounts are stored without one but e-invoices have to state it
+    pub currency: String,
+    // invoices are written as PDF/A-2b so they can be archived for the retention period
+    pub archival_pdf: bool,
+    pub accounts: AccountMap,
+    pub backup: BackupSettings,
+    pub remote: RemoteSettings,
+    pub mail: MailSettings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            database_path: None,
+            fonts: DocumentFonts::default(),
+            text_layout: TextLayout::default(),
+            currency: "EUR".to_string(),
+            archival_pdf: false,
+            accounts: AccountMap::default(),
+            backup: BackupSettings::default(),
+            remote: RemoteSettings::default(),
+            mail: MailSettings::default(),
+        }
+    }
+}
+
+// automatic backups, written to `dir` or the `backups` folder of the data directory
+#[derive(Clone, Debug, PartialEq)]
+pub struct BackupSettings {
+    pub dir: Option<PathBuf>,
+    // 0 turns automatic backups off
+    pub interval_hours: u32,
+    // number of archives kept, older ones are removed after each backup
+    pub keep: usize,
+    // archives older than this are removed as well, 0 keeps them regardless of age
+    pub keep_days: u32,
+}
+
+impl Default for BackupSettings {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            interval_hours: 24,
+            keep: 14,
+            keep_days: 0,
+        }
+    }
+}
+
+// where a copy of each backup is pushed. Passwords and tokens are not stored here but in the
+// credentials file, see `remote::Credentials`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RemoteSettings {
+    pub target: Option<RemoteKind>,
+    // the WebDAV folder or the S3 endpoint, Google Drive needs none
+    pub url: String,
+    pub bucket: String,
+    pub region: String,
+    // WebDAV user name, S3 access key id or Google OAuth client id
+    pub user: String,
+    // S3 key prefix or Google Drive folder id, empty for the top level
+    pub folder: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RemoteKind {
+    WebDav,
+    S3,
+    GoogleDrive,
+}
+
+impl RemoteKind {
+    pub const ALL: [RemoteKind; 3] = [RemoteKind::WebDav, RemoteKind::S3, RemoteKind::GoogleDrive];
+
+    pub fn key(&self) -> &'static str {
+        match self {
+            RemoteKind::WebDav => "webdav",
+            RemoteKind::S3 => "s3",
+            RemoteKind::GoogleDrive => "google_drive",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            RemoteKind::WebDav => "WebDAV",
+            RemoteKind::S3 => "S3 Compatible",
+            RemoteKind::GoogleDrive => "Google Drive",
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|kind| kind.key() == key)
+    }
+}
+
+// the SMTP server invoices are emailed through, the sender is set per contact. The password is
+// kept in the credentials file with the other secrets.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MailSettings {
+    pub host: String,
+    pub port: u16,
+    pub security: MailSecurity,
+    // empty for servers that need no login
+    pub user: String,
+}
+
+impl Default for MailSettings {
+    fn default() -> Self {
+        Self {
+            host: String::new(),
+            port: MailSecurity::StartTls.default_port(),
+            security: MailSecurity::StartTls,
+            user: String::new(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MailSecurity {
+    // a plain connection upgraded with STARTTLS, refused if the server does not offer it
+    StartTls,
+    // TLS from the start, also called SMTPS
+    Tls,
+    // for a relay or test sink on this machine
+    None,
+}
+
+impl MailSecurity {
+    pub const ALL: [MailSecurity; 3] = [
+        MailSecurity::StartTls,
+        MailSecurity::Tls,
+        MailSecurity::None,
+    ];
+
+    pub fn key(&self) -> &'static str {
+        match self {
+            MailSecurity::StartTls => "starttls",
+            MailSecurity::Tls => "tls",
+            MailSecurity::None => "none",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            MailSecurity::StartTls => "STARTTLS",
+            MailSecurity::Tls => "TLS",
+            MailSecurity::None => "None (local only)",
+        }
+    }
+
+    pub fn default_port(&self) -> u16 {
+        match self {
+            MailSecurity::StartTls => 587,
+            MailSecurity::Tls => 465,
+            MailSecurity::None => 25,
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|security| security.key() == key)
+    }
+}
+
+// accounts the bookkeeping exports post to, named as in the bookkeeper's chart of accounts
+#[derive(Clone, Debug, PartialEq)]
+pub struct AccountMap {
+    pub receivable: String,
+    pub revenue: String,
+    pub tax_payable: String,
+    // where payments are deposited
+    pub bank: String,
+    // due date offset for formats that need one, the invoice terms are free text
+    pub due_days: u32,
+    // line tax codes renamed to the tax rate names of the accounting tool, e.g. "S:21" to
+    // "21% (VAT on Income)"
+    pub tax_types: Vec<(String, String)>,
+}
+
+impl Default for AccountMap {
+    fn default() -> Self {
+        Self {
+            receivable: "Accounts Receivable".to_string(),
+            revenue: "Sales".to_string(),
+            tax_payable: "Sales Tax Payable".to_string(),
+            bank: "Undeposited Funds".to_string(),
+            due_days: 30,
+            tax_types: Vec::new(),
+        }
+    }
+}
+
+impl AccountMap {
+    // the mapped tax rate name, unmapped codes are passed through as typed
+    pub fn tax_type(&self, tax_code: &str) -> String {
+        let tax_code = tax_code.trim();
+        self.tax_types
+            .iter()
+            .find(|(code, _)| code == tax_code)
+            .map_or_else(|| tax_code.to_string(), |(_, name)| name.clone())
+    }
+}
+
+// font family names per section of the invoice template, resolved through the font registry
+#[derive(Clone, Debug, PartialEq)]
+pub struct DocumentFonts {
+    pub body: String,
+    pub heading: String,
+    pub table: String,
+}
+
+impl Default for DocumentFonts {
+    fn default() -> Self {
+        Self {
+            body: DEFAULT_FONT.to_string(),
+            heading: DEFAULT_FONT.to_string(),
+            table: DEFAULT_FONT.to_string(),
+        }
+    }
+}
+
+// where free-form invoice text is printed
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TextPosition {
+    AboveItems,
+    BelowTotal,
+    Footer,
+}
+
+impl TextPosition {
+    pub const ALL: [TextPosition; 3] = [
+        TextPosition::AboveItems,
+        TextPosition::BelowTotal,
+        TextPosition::Footer,
+    ];
+
+    pub fn key(&self) -> &'static str {
+        match self {
+            TextPosition::AboveItems => "above_items",
+            TextPosition::BelowTotal => "below_total",
+            TextPosition::Footer => "footer",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TextPosition::AboveItems => "Above Items",
+            TextPosition::BelowTotal => "Below Total",
+            TextPosition::Footer => "Footer",
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|position| position.key() == key)
+    }
+}
+
+// the PO number and reference always sit next to the estimate number, only notes and terms move
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextLayout {
+    pub notes: TextPosition,
+    pub terms: TextPosition,
+}
+
+impl Default for TextLayout {
+    fn default() -> Self {
+        Self {
+            notes: TextPosition::BelowTotal,
+            terms: TextPosition::Footer,
+        }
+    }
+}
+
+impl Settings {
+    pub fn load() -> Self {
+        match fs::read_to_string(settings_path()) {
+            Ok(contents) => Self::parse(&contents),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        fs::write(settings_path(), self.serialize())
+    }
+
+    pub fn parse(contents: &str) -> Self {
+        let mut settings = Self::default();
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim() {
+                "database_path" if !value.is_empty() => {
+                    settings.database_path = Some(PathBuf::from(value))
+                }
+                "font_body" if !value.is_empty() => settings.fonts.body = value.to_string(),
+                "font_heading" if !value.is_empty() => settings.fonts.heading = value.to_string(),
+                "font_table" if !value.is_empty() => settings.fonts.table = value.to_string(),
+                "currency" if !value.is_empty() => settings.currency = value.to_uppercase(),
+                "archival_pdf" => settings.archival_pdf = value == "true",
+                "account_receivable" if !value.is_empty() => {
+                    settings.accounts.receivable = value.to_string()
+                }
+                "account_revenue" if !value.is_empty() => {
+                    settings.accounts.revenue = value.to_string()
+                }
+                "account_tax_payable" if !value.is_empty() => {
+                    settings.accounts.tax_payable = value.to_string()
+                }
+                "account_bank" if !value.is_empty() => settings.accounts.bank = value.to_string(),
+                "backup_dir" if !value.is_empty() => {
+                    settings.backup.dir = Some(PathBuf::from(value))
+                }
+                "backup_interval_hours" => {
+                    if let Ok(hours) = value.parse() {
+                        settings.backup.interval_hours = hours
+                    }
+                }
+                "backup_keep" => {
+                    if let Ok(keep) = value.parse() {
+                        settings.backup.keep = keep
+                    }
+                }
+                "backup_keep_days" => {
+                    if let Ok(days) = value.parse() {
+                        settings.backup.keep_days = days
+                    }
+                }
+                "remote_target" => settings.remote.target = RemoteKind::from_key(value),
+                "remote_url" => settings.remote.url = value.to_string(),
+                "remote_bucket" => settings.remote.bucket = value.to_string(),
+                "remote_region" => settings.remote.region = value.to_string(),
+                "remote_user" => settings.remote.user = value.to_string(),
+                "remote_folder" => settings.remote.folder = value.to_string(),
+                "smtp_host" => settings.mail.host = value.to_string(),
+                "smtp_port" => {
+                    if let Ok(port) = value.parse() {
+                        settings.mail.port = port
+                    }
+                }
+                "smtp_security" => {
+                    if let Some(security) = MailSecurity::from_key(value) {
+                        settings.mail.security = security
+                    }
+                }
+                "smtp_user" => settings.mail.user = value.to_string(),
+                "due_days" => {
+                    if let Ok(days) = value.parse() {
+                        settings.accounts.due_days = days
+                    }
+                }
+                key if key.starts_with("tax_type.") && !value.is_empty() => {
+                    settings.accounts.tax_types.push((
+                        key["tax_type.".len()..].trim().to_string(),
+                        value.to_string(),
+                    ))
+                }
+                "notes_position" => {
+                    if let Some(position) = TextPosition::from_key(value) {
+                        settings.text_layout.notes = position
+                    }
+                }
+                "terms_position" => {
+                    if let Some(position) = TextPosition::from_key(value) {
+                        settings.text_layout.terms = position
+                    }
+                }
+                _ => {}
+            }
+        }
+        settings
+    }
+
+    pub fn serialize(&self) -> String {
+        let mut output = String::new();
+        if let Some(path) = &self.database_path {
+            output.push_str(&format!("database_path={}\n", path.display()));
+        }
+        output.push_str(&format!("font_body={}\n", self.fonts.body));
+        output.push_str(&format!("font_heading={}\n", self.fonts.heading));
+        output.push_str(&format!("font_table={}\n", self.fonts.table));
+        output.push_str(&format!(
+            "notes_position={}\n",
+            self.text_layout.notes.key()
+        ));
+        output.push_str(&format!(
+            "terms_position={}\n",
+            self.text_layout.terms.key()
+        ));
+        output.push_str(&format!("currency={}\n", self.currency));
+        output.push_str(&format!("archival_pdf={}\n", self.archival_pdf));
+        output.push_str(&format!(
+            "account_receivable={}\n",
+            self.accounts.receivable
+        ));
+        output.push_str(&format!("account_revenue={}\n", self.accounts.revenue));
+        output.push_str(&format!(
+            "account_tax_payable={}\n",
+            self.accounts.tax_payable
+        ));
+        output.push_str(&format!("account_bank={}\n", self.accounts.bank));
+        output.push_str(&format!("due_days={}\n", self.accounts.due_days));
+        if let Some(dir) = &self.backup.dir {
+            output.push_str(&format!("backup_dir={}\n", dir.display()));
+        }
+        output.push_str(&format!(
+            "backup_interval_hours={}\n",
+            self.backup.interval_hours
+        ));
+        output.push_str(&format!("backup_keep={}\n", self.backup.keep));
+        output.push_str(&format!("backup_keep_days={}\n", self.backup.keep_days));
+        if let Some(target) = self.remote.target {
+            output.push_str(&format!("remote_target={}\n", target.key()));
+        }
+        output.push_str(&format!("remote_url={}\n", self.remote.url));
+        output.push_str(&format!("remote_bucket={}\n", self.remote.bucket));
+        output.push_str(&format!("remote_region={}\n", self.remote.region));
+        output.push_str(&format!("remote_user={}\n", self.remote.user));
+        output.push_str(&format!("remote_folder={}\n", self.remote.folder));
+        output.push_str(&format!("smtp_host={}\n", self.mail.host));
+        output.push_str(&format!("smtp_port={}\n", self.mail.port));
+        output.push_str(&format!("smtp_security={}\n", self.mail.security.key()));
+        output.push_str(&format!("smtp_user={}\n", self.mail.user));
+        for (code, name) in self
+            .accounts
+            .tax_types
+            .iter()
+            .filter(|(code, name)| !code.trim().is_empty() && !name.trim().is_empty())
+        {
+            output.push_str(&format!("tax_type.{}={}\n", code, name));
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_settings_round_trip() {
+        let settings = Settings {
+            database_path: Some(PathBuf::from("/shared/invoicy.db")),
+            fonts: DocumentFonts {
+                body: "JetBrains Mono".to_string(),
+                ..Default::default()
+            },
+            text_layout: TextLayout {
+                notes: TextPosition::AboveItems,
+                terms: TextPosition::BelowTotal,
+            },
+            currency: "CHF".to_string(),
+            archival_pdf: true,
+            accounts: AccountMap {
+                revenue: "4000 Sales".to_string(),
+                due_days: 14,
+                tax_types: vec![("S:21".to_string(), "21% (VAT on Income)".to_string())],
+                ..Default::default()
+            },
+            backup: BackupSettings {
+                dir: Some(PathBuf::from("/mnt/nas/invoicy")),
+                interval_hours: 0,
+                keep: 3,
+                keep_days: 90,
+            },
+            remote: RemoteSettings {
+                target: Some(RemoteKind::S3),
+                url: "http://127.0.0.1:9000".to_string(),
+                bucket: "invoicy".to_string(),
+                region: "us-east-1".to_string(),
+                user: "minio".to_string(),
+                folder: "office/".to_string(),
+            },
+            mail: MailSettings {
+                host: "smtp.example.com".to_string(),
+                port: 465,
+                security: MailSecurity::Tls,
+                user: "billing@example.com".to_string(),
+            },
+        };
+        assert_eq!(Settings::parse(&settings.serialize()), settings);
+        assert_eq!(
+            Settings::parse("# comment\nunknown=1\n"),
+            Settings::default()
+        );
+    }
+}
@@ -1,17 +1,58 @@
-#[derive(Clone, Debug, PartialEq)]
-pub struct Total {
-    pub value: f64,
-    pub position: (usize, i32),
-}
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct Customer {
     pub company: String,
     pub address: String,
     pub city: String,
     pub postal_code: String,
     pub country: String,
+    // replace the contact's default notes and terms when not empty
+    pub notes: String,
+    pub terms: String,
+    // needed for e-invoices, the endpoint is an electronic address written as "scheme:id"
+    pub vat_id: String,
+    pub endpoint_id: String,
+    // where invoices are emailed to, several addresses are separated by commas
+    pub email: String,
 }
-#[derive(Clone, Debug, PartialEq)]
+impl Customer {
+    // the prefix of the customer's invoice ids: the first four characters of the company name,
+    // upper cased and padded with X for names such as "3M"
+    pub fn customer_id(&self) -> String {
+        self.company
+            .to_uppercase()
+            .chars()
+            .chain(std::iter::repeat('X'))
+            .take(4)
+            .collect()
+    }
+}
+// what a row of the item table is, headings and subtotals carry no amount of their own
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LineKind {
+    #[default]
+    Item,
+    Heading,
+    Subtotal,
+}
+impl LineKind {
+    pub fn key(&self) -> &'static str {
+        match self {
+            LineKind::Item => "item",
+            LineKind::Heading => "heading",
+            LineKind::Subtotal => "subtotal",
+        }
+    }
+
+    // unknown values read back as plain items
+    pub fn from_key(key: &str) -> Self {
+        match key {
+            "heading" => LineKind::Heading,
+            "subtotal" => LineKind::Subtotal,
+            _ => LineKind::Item,
+        }
+    }
+}
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct DatabaseData {
     pub entry_id: String,
     pub estimate_number: usize,
@@ -21,8 +62,165 @@ pub struct DatabaseData {
     pub quantity: f64,
     pub price: f64,
     pub total: f64,
+    pub sku: Option<String>,
+    pub unit: String,
+    pub tax_code: String,
+    pub kind: LineKind,
+}
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Product {
+    pub sku: String,
+    pub name: String,
+    pub description: String,
+    pub unit: String,
+    pub price: f64,
+    pub tax_code: String,
+    pub active: bool,
+}
+// a negotiated rate for one customer, `sku` of None applies to every product
+#[derive(Clone, Debug, PartialEq)]
+pub struct CustomerPrice {
+    pub customer: String,
+    pub sku: Option<String>,
+    pub rule: PriceRule,
+}
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PriceRule {
+    // replaces the catalogue price
+    Fixed(f64),
+    // percentage taken off the catalogue price
+    PercentOff(f64),
 }
 #[derive(Clone, Debug, PartialEq)]
+pub struct Invoice {
+    pub invoice_id: String,
+    pub cust_id: String,
+    pub estimate_number: usize,
+    pub customer: String,
+    pub contact: String,
+    pub issue_date: String,
+    pub grand_total: f64,
+    pub file_path: Option<String>,
+    pub file_hash: Option<String>,
+    pub text: InvoiceText,
+    pub status: InvoiceStatus,
+}
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum InvoiceStatus {
+    #[default]
+    Issued,
+    // emailed to the customer at least once
+    Sent,
+}
+impl InvoiceStatus {
+    pub fn key(&self) -> &'static str {
+        match self {
+            InvoiceStatus::Issued => "issued",
+            InvoiceStatus::Sent => "sent",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            InvoiceStatus::Issued => "Issued",
+            InvoiceStatus::Sent => "Sent",
+        }
+    }
+
+    // unknown values read back as issued
+    pub fn from_key(key: &str) -> Self {
+        match key {
+            "sent" => InvoiceStatus::Sent,
+            _ => InvoiceStatus::Issued,
+        }
+    }
+}
+// how invoices from one contact are emailed, the SMTP server itself is in the settings
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MailSender {
+    // company of the contact sending
+    pub contact: String,
+    pub from_name: String,
+    pub from_address: String,
+    pub reply_to: String,
+    // a copy of every message goes here, e.g. the bookkeeper or the sender's own archive
+    pub bcc: String,
+    // templates with placeholders, see `mail::PLACEHOLDERS`
+    pub subject: String,
+    pub body: String,
+}
+// one message in the sent log
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SentMail {
+    pub mail_id: i64,
+    pub invoice_id: String,
+    // YYYY-MM-DD HH:MM:SS, local time
+    pub sent_at: String,
+    pub sender: String,
+    pub recipients: String,
+    pub subject: String,
+    pub message_id: String,
+    // the server's answer to the message, usually with its queue id
+    pub response: String,
+}
+// money received against an issued invoice, partial payments are separate rows
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Payment {
+    pub payment_id: i64,
+    pub invoice_id: String,
+    // YYYY-MM-DD
+    pub paid_on: String,
+    pub amount: f64,
+    pub method: String,
+    pub reference: String,
+}
+// free-form text printed on an invoice
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InvoiceText {
+    pub po_number: String,
+    pub reference: String,
+    pub notes: String,
+    pub terms: String,
+}
+impl InvoiceText {
+    // notes and terms for a new invoice: the customer's override, else the contact's default
+    pub fn defaults_for(contact: &Contact, customer: &Customer) -> Self {
+        let pick = |custom: &String, default: &String| {
+            if custom.trim().is_empty() {
+                default.clone()
+            } else {
+                custom.clone()
+            }
+        };
+        Self {
+            notes: pick(&customer.notes, &contact.default_notes),
+            terms: pick(&customer.terms, &contact.default_terms),
+            ..Default::default()
+        }
+    }
+}
+// an invoice still being edited, rows keep the raw text so invalid input survives a restart
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Draft {
+    pub draft_id: i64,
+    pub customer: String,
+    pub contact: String,
+    pub updated_at: String,
+    pub text: InvoiceText,
+    pub items: Vec<DraftItem>,
+}
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DraftItem {
+    pub description: String,
+    pub quantity_input: String,
+    pub price_input: String,
+    pub sku: Option<String>,
+    pub unit: String,
+    pub tax_code: String,
+    pub kind: LineKind,
+}
+// the business issuing the invoice, its defaults apply unless the customer overrides them
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct Contact {
     pub company: String,
     pub address: String,
@@ -33,4 +231,8 @@ pub struct Contact {
     pub telephone: String,
     pub email: String,
     pub website: String,
+    pub default_notes: String,
+    pub default_terms: String,
+    pub vat_id: String,
+    pub endpoint_id: String,
 }
@@ -0,0 +1,100 @@
+//! Fixtures shared by the unit tests of several modules.
+
+use std::fs;
+use std::path::PathBuf;
+
+use chrono::{NaiveDate, NaiveDateTime};
+
+use crate::db::{IssuedInvoice, NewInvoice, Store};
+use crate::structs::{Contact, Customer, DatabaseData, Invoice, InvoiceStatus, InvoiceText};
+
+// 1 March 2024 at `hour` o'clock
+pub(crate) fn at(hour: u32) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2024, 3, 1)
+        .unwrap()
+        .and_hms_opt(hour, 0, 0)
+        .unwrap()
+}
+
+// a pdf path in the temp dir, `name` keeps tests running in parallel apart
+pub(crate) fn temp_pdf(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("invoicy-{}-{}.pdf", name, std::process::id()))
+}
+
+// issues `invoice` with `pdf` written as its file, removing the file is up to the test
+pub(crate) fn issue(
+    store: &mut Store,
+    invoice: &NewInvoice,
+    name: &str,
+    pdf: &[u8],
+) -> (IssuedInvoice, PathBuf) {
+    let path = temp_pdf(name);
+    let issued = store
+        .issue_invoice(invoice, &path, |_| {
+            fs::write(&path, pdf).unwrap();
+            Ok(())
+        })
+        .unwrap();
+    (issued, path)
+}
+
+// a German seller and a Belgian buyer with everything Peppol asks of them
+pub(crate) fn seller() -> Contact {
+    Contact {
+        company: "Fake Co.".to_string(),
+        address: "1111 Fake Ave.".to_string(),
+        city: "Berlin".to_string(),
+        postal_code: "10115".to_string(),
+        country: "DE".to_string(),
+        name: "Fake Smith".to_string(),
+        email: "billing@fake.example".to_string(),
+        vat_id: "DE123456789".to_string(),
+        endpoint_id: "9930:DE123456789".to_string(),
+        ..Default::default()
+    }
+}
+
+pub(crate) fn buyer() -> Customer {
+    Customer {
+        company: "Acme & Sons".to_string(),
+        address: "1 Road".to_string(),
+        city: "Brussels".to_string(),
+        postal_code: "1000".to_string(),
+        country: "BE".to_string(),
+        vat_id: "BE0123456749".to_string(),
+        endpoint_id: "0208:0123456749".to_string(),
+        ..Default::default()
+    }
+}
+
+pub(crate) fn invoice() -> Invoice {
+    Invoice {
+        invoice_id: "ACME-7".to_string(),
+        cust_id: "ACME".to_string(),
+        estimate_number: 7,
+        customer: "Acme & Sons".to_string(),
+        contact: "Fake Co.".to_string(),
+        issue_date: "2024-05-01".to_string(),
+        grand_total: 0.0,
+        file_path: None,
+        file_hash: None,
+        text: InvoiceText {
+            po_number: "PO-1".to_string(),
+            terms: "Net 30".to_string(),
+            ..Default::default()
+        },
+        status: InvoiceStatus::Issued,
+    }
+}
+
+pub(crate) fn item(description: &str, quantity: f64, price: f64, tax_code: &str) -> DatabaseData {
+    DatabaseData {
+        description: description.to_string(),
+        quantity,
+        price,
+        total: quantity * price,
+        unit: "hours".to_string(),
+        tax_code: tax_code.to_string(),
+        ..Default::default()
+    }
+}
@@ -0,0 +1,175 @@
+//! The figures of an invoice: line amounts, the VAT breakdown per category and the amount
+//! payable. The stored grand total, the PDF, both e-invoice formats and the accounting exports
+//! all work them out here, so they round the same way.
+
+use crate::structs::{DatabaseData, LineKind};
+
+// VAT category codes of the UNCL5305 subset allowed by Peppol
+pub(crate) const CATEGORIES: [&str; 9] = ["S", "Z", "E", "AE", "K", "G", "O", "L", "M"];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TaxCategory {
+    pub code: String,
+    pub percent: f64,
+}
+
+// Reads a line's tax code as a VAT category: "S:21", "S 21" or a plain rate such as "21" for
+// standard rated lines, a bare code such as "Z", "E" or "AE" for the zero rated categories.
+pub fn tax_category(tax_code: &str) -> Option<TaxCategory> {
+    let code = tax_code.trim();
+    if let Ok(percent) = code.trim_end_matches('%').parse::<f64>() {
+        return Some(TaxCategory {
+            code: "S".to_string(),
+            percent,
+        });
+    }
+    let (category, percent) = match code.split_once(|c: char| c == ':' || c.is_whitespace()) {
+        Some((category, percent)) => (
+            category.trim(),
+            percent.trim().trim_end_matches('%').parse::<f64>().ok()?,
+        ),
+        None => (code, 0.0),
+    };
+    let category = category.to_uppercase();
+    CATEGORIES
+        .contains(&category.as_str())
+        .then_some(TaxCategory {
+            code: category,
+            percent,
+        })
+}
+
+pub(crate) fn round_cents(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+pub(crate) struct Line<'a> {
+    pub(crate) item: &'a DatabaseData,
+    pub(crate) quantity: f64,
+    pub(crate) amount: f64,
+    pub(crate) category: Option<TaxCategory>,
+}
+
+pub(crate) struct Breakdown {
+    pub(crate) category: TaxCategory,
+    pub(crate) taxable: f64,
+    pub(crate) tax: f64,
+}
+
+// the figures of the document, credit notes state the lines with positive quantities
+pub(crate) struct Totals<'a> {
+    pub(crate) credit_note: bool,
+    pub(crate) lines: Vec<Line<'a>>,
+    pub(crate) breakdown: Vec<Breakdown>,
+    pub(crate) line_total: f64,
+    pub(crate) tax_total: f64,
+}
+
+impl<'a> Totals<'a> {
+    pub(crate) fn new(items: &'a [DatabaseData]) -> Self {
+        let items: Vec<&DatabaseData> = items
+            .iter()
+            .filter(|item| item.kind == LineKind::Item)
+            .collect();
+        let net: f64 = items.iter().map(|item| item.quantity * item.price).sum();
+        let credit_note = net < 0.0;
+        let sign = if credit_note { -1.0 } else { 1.0 };
+        let lines: Vec<Line> = items
+            .into_iter()
+            .map(|item| {
+                let quantity = item.quantity * sign;
+                Line {
+                    item,
+                    quantity,
+                    amount: round_cents(quantity * item.price),
+                    category: tax_category(&item.tax_code),
+                }
+            })
+            .collect();
+        let mut breakdown: Vec<Breakdown> = Vec::new();
+        for line in &lines {
+            let Some(category) = &line.category else {
+                continue;
+            };
+            match breakdown
+                .iter_mut()
+                .find(|entry| entry.category == *category)
+            {
+                Some(entry) => entry.taxable += line.amount,
+                None => breakdown.push(Breakdown {
+                    category: category.clone(),
+                    taxable: line.amount,
+                    tax: 0.0,
+                }),
+            }
+        }
+        for entry in &mut breakdown {
+            entry.taxable = round_cents(entry.taxable);
+            entry.tax = round_cents(entry.taxable * entry.category.percent / 100.0);
+        }
+        Self {
+            credit_note,
+            line_total: round_cents(lines.iter().map(|line| line.amount).sum()),
+            tax_total: round_cents(breakdown.iter().map(|entry| entry.tax).sum()),
+            lines,
+            breakdown,
+        }
+    }
+
+    pub(crate) fn payable(&self) -> f64 {
+        round_cents(self.line_total + self.tax_total)
+    }
+}
+
+// What the customer owes for the lines, VAT from the tax codes included and negative for a credit
+// note. Stored as the invoice's grand total, so the PDF, the e-invoices, payments, emails and the
+// accounting exports all state this one amount.
+pub fn gross_total(items: &[DatabaseData]) -> f64 {
+    let totals = Totals::new(items);
+    if totals.credit_note {
+        -totals.payable()
+    } else {
+        totals.payable()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tax_codes() {
+        let category = |code: &str, percent: f64| {
+            Some(TaxCategory {
+                code: code.to_string(),
+                percent,
+            })
+        };
+        assert_eq!(tax_category("S:19"), category("S", 19.0));
+        assert_eq!(tax_category("s 7%"), category("S", 7.0));
+        assert_eq!(tax_category("21"), category("S", 21.0));
+        assert_eq!(tax_category("AE"), category("AE", 0.0));
+        assert_eq!(tax_category(""), None);
+        assert_eq!(tax_category("VAT"), None);
+    }
+
+    #[test]
+    fn test_gross_total() {
+        let item = |quantity: f64, price: f64, tax_code: &str| DatabaseData {
+            quantity,
+            price,
+            total: quantity * price,
+            tax_code: tax_code.to_string(),
+            ..Default::default()
+        };
+        let items = [item(2.0, 50.0, "S:21"), item(1.0, 10.0, "Z")];
+        let totals = Totals::new(&items);
+        assert_eq!(totals.line_total, 110.0);
+        assert_eq!(totals.tax_total, 21.0);
+        assert_eq!(gross_total(&items), 131.0);
+        // a credit note states positive amounts, its grand total is negative
+        let refund = [item(-1.0, 40.0, "S:21")];
+        assert!(Totals::new(&refund).credit_note);
+        assert_eq!(gross_total(&refund), -48.4);
+    }
+}
@@ -0,0 +1,760 @@
+//! Peppol BIS Billing 3.0 e-invoices in UBL 2.1. A stored invoice becomes an Invoice, or a
+//! CreditNote when its total is negative. The Peppol rules that can be decided from our own data
+//! are checked first, then the written XML is validated by `peppol` against the UBL 2.1 schema
+//! and the Peppol rules, before the invoice is issued and again before anything is written.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use chrono::NaiveDate;
+
+use crate::db::Store;
+use crate::peppol;
+use crate::structs::{Contact, Customer, DatabaseData, Invoice};
+use crate::totals::{round_cents, TaxCategory, Totals};
+
+pub(crate) const CUSTOMIZATION_ID: &str =
+    "urn:cen.eu:en16931:2017#compliant#urn:fdc:peppol.eu:2017:poacc:billing:3.0";
+const PROFILE_ID: &str = "urn:fdc:peppol.eu:2017:poacc:billing:01:1.0";
+pub(crate) const INVOICE_NAMESPACE: &str = "urn:oasis:names:specification:ubl:schema:xsd:Invoice-2";
+pub(crate) const CREDIT_NOTE_NAMESPACE: &str =
+    "urn:oasis:names:specification:ubl:schema:xsd:CreditNote-2";
+pub(crate) const CAC: &str =
+    "urn:oasis:names:specification:ubl:schema:xsd:CommonAggregateComponents-2";
+pub(crate) const CBC: &str = "urn:oasis:names:specification:ubl:schema:xsd:CommonBasicComponents-2";
+pub(crate) const EXT: &str =
+    "urn:oasis:names:specification:ubl:schema:xsd:CommonExtensionComponents-2";
+
+pub struct EInvoice<'a> {
+    pub invoice: &'a Invoice,
+    pub seller: &'a Contact,
+    pub buyer: &'a Customer,
+    pub items: &'a [DatabaseData],
+    pub currency: &'a str,
+}
+
+// a failed check, `rule` is the identifier used by the EN 16931 / Peppol validation artefacts
+#[derive(Clone, Debug, PartialEq)]
+pub struct Violation {
+    pub rule: &'static str,
+    pub message: String,
+}
+
+impl Violation {
+    pub(crate) fn new(rule: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            rule,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.rule, self.message)
+    }
+}
+
+#[derive(Debug)]
+pub enum UblError {
+    Invalid(Vec<Violation>),
+    NotFound(String),
+    Database(rusqlite::Error),
+    File(std::io::Error),
+}
+
+impl fmt::Display for UblError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UblError::Invalid(violations) => {
+                write!(f, "The e-invoice is not valid:")?;
+                for violation in violations {
+                    write!(f, "\n{}", violation)?;
+                }
+                Ok(())
+            }
+            UblError::NotFound(what) => write!(f, "{} not found", what),
+            UblError::Database(e) => write!(f, "Unable to load the invoice: {}", e),
+            UblError::File(e) => write!(f, "Unable to write the e-invoice: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for UblError {}
+
+impl From<rusqlite::Error> for UblError {
+    fn from(e: rusqlite::Error) -> Self {
+        UblError::Database(e)
+    }
+}
+
+impl From<std::io::Error> for UblError {
+    fn from(e: std::io::Error) -> Self {
+        UblError::File(e)
+    }
+}
+
+// why a zero rated category carries no VAT, required on its breakdown
+pub(crate) fn exemption_reason(category: &str) -> Option<&'static str> {
+    match category {
+        "E" => Some("Exempt from VAT"),
+        "AE" => Some("Reverse charge"),
+        "K" => Some("Intra-community supply"),
+        "G" => Some("Export outside the EU"),
+        "O" => Some("Not subject to VAT"),
+        _ => None,
+    }
+}
+
+// UN/ECE recommendation 20 code for our units
+pub(crate) fn unit_code(unit: &str) -> &'static str {
+    match unit.trim().to_lowercase().as_str() {
+        "hours" | "hour" | "h" | "hrs" => "HUR",
+        "days" | "day" | "d" => "DAY",
+        "km" => "KMT",
+        "pcs" | "pc" => "H87",
+        _ => "C62",
+    }
+}
+
+pub(crate) fn amount(value: f64) -> String {
+    format!("{:.2}", round_cents(value) + 0.0)
+}
+
+// quantities and unit prices keep up to four decimals, without trailing zeros
+pub(crate) fn decimal(value: f64) -> String {
+    let text = format!("{:.4}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    if text == "-0" {
+        "0".to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+// the address fields shared by the seller (our contact) and the buyer (the customer)
+pub(crate) struct Party<'a> {
+    pub(crate) name: &'a str,
+    pub(crate) street: &'a str,
+    pub(crate) city: &'a str,
+    pub(crate) postal_code: &'a str,
+    pub(crate) country: &'a str,
+    pub(crate) vat_id: &'a str,
+    pub(crate) endpoint_id: &'a str,
+}
+
+impl<'a> From<&'a Contact> for Party<'a> {
+    fn from(contact: &'a Contact) -> Self {
+        Self {
+            name: &contact.company,
+            street: &contact.address,
+            city: &contact.city,
+            postal_code: &contact.postal_code,
+            country: &contact.country,
+            vat_id: &contact.vat_id,
+            endpoint_id: &contact.endpoint_id,
+        }
+    }
+}
+
+impl<'a> From<&'a Customer> for Party<'a> {
+    fn from(customer: &'a Customer) -> Self {
+        Self {
+            name: &customer.company,
+            street: &customer.address,
+            city: &customer.city,
+            postal_code: &customer.postal_code,
+            country: &customer.country,
+            vat_id: &customer.vat_id,
+            endpoint_id: &customer.endpoint_id,
+        }
+    }
+}
+
+// Peppol rules that can be decided before the XML exists, empty when the invoice can be sent
+pub fn validate(e_invoice: &EInvoice) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    let EInvoice {
+        invoice,
+        seller,
+        buyer,
+        ..
+    } = e_invoice;
+    let totals = Totals::new(e_invoice.items);
+
+    if invoice.invoice_id.trim().is_empty() {
+        violations.push(Violation::new("BR-02", "The invoice has no number"));
+    }
+    if NaiveDate::parse_from_str(&invoice.issue_date, "%Y-%m-%d").is_err() {
+        violations.push(Violation::new(
+            "BR-03",
+            format!("Issue date '{}' is not a valid date", invoice.issue_date),
+        ));
+    }
+    if !peppol::is_currency_code(e_invoice.currency) {
+        violations.push(Violation::new(
+            "BR-CL-04",
+            format!(
+                "Currency '{}' is not a three-letter ISO 4217 code",
+                e_invoice.currency
+            ),
+        ));
+    }
+
+    let parties = [
+        ("Seller", Party::from(*seller)),
+        ("Buyer", Party::from(*buyer)),
+    ];
+    for (label, party) in &parties {
+        let (label, seller) = (*label, *label == "Seller");
+        let Party {
+            name,
+            city,
+            country,
+            endpoint_id,
+            ..
+        } = party;
+        if name.trim().is_empty() {
+            violations.push(Violation::new(
+                if seller { "BR-06" } else { "BR-07" },
+                format!("{} name is missing", label),
+            ));
+        }
+        if city.trim().is_empty() {
+            violations.push(Violation::new(
+                if seller { "BR-08" } else { "BR-10" },
+                format!("{} address has no city", label),
+            ));
+        }
+        if !peppol::is_country_code(country.trim()) {
+            violations.push(Violation::new(
+                if seller { "BR-09" } else { "BR-11" },
+                format!(
+                    "{} country '{}' is not a two-letter ISO 3166 code",
+                    label, country
+                ),
+            ));
+        }
+        let endpoint_rule = if seller {
+            "PEPPOL-EN16931-R020"
+        } else {
+            "PEPPOL-EN16931-R010"
+        };
+        match endpoint_id.split_once(':') {
+            Some((scheme, id)) if !scheme.trim().is_empty() && !id.trim().is_empty() => {}
+            _ if endpoint_id.trim().is_empty() => violations.push(Violation::new(
+                endpoint_rule,
+                format!("{} electronic address is missing", label),
+            )),
+            _ => violations.push(Violation::new(
+                endpoint_rule,
+                format!(
+                    "{} electronic address '{}' should be written as scheme:id",
+                    label, endpoint_id
+                ),
+            )),
+        }
+    }
+
+    if invoice.text.reference.trim().is_empty() && invoice.text.po_number.trim().is_empty() {
+        violations.push(Violation::new(
+            "PEPPOL-EN16931-R003",
+            "A project reference or PO number is required",
+        ));
+    }
+    if totals.lines.is_empty() {
+        violations.push(Violation::new("BR-16", "The invoice has no item lines"));
+    }
+    for (idx, line) in totals.lines.iter().enumerate() {
+        let row = idx + 1;
+        if line.item.price < 0.0 {
+            violations.push(Violation::new(
+                "BR-27",
+                format!("Line {}: the price cannot be negative", row),
+            ));
+        }
+        match &line.category {
+            None => violations.push(Violation::new(
+                "BR-CO-04",
+                format!(
+                    "Line {}: tax code '{}' is not a VAT category such as S:21, Z or E",
+                    row, line.item.tax_code
+                ),
+            )),
+            Some(category) if category.code == "S" && category.percent <= 0.0 => {
+                violations.push(Violation::new(
+                    "BR-S-05",
+                    format!("Line {}: standard rated lines need a VAT rate above 0", row),
+                ))
+            }
+            Some(category) if category.code != "S" && category.percent != 0.0 => {
+                violations.push(Violation::new(
+                    "BR-CO-04",
+                    format!(
+                        "Line {}: category {} lines have a VAT rate of 0",
+                        row, category.code
+                    ),
+                ))
+            }
+            Some(_) => {}
+        }
+    }
+    for entry in &totals.breakdown {
+        let code = entry.category.code.as_str();
+        let (rule, needs_buyer_vat) = match code {
+            "S" => ("BR-S-02", false),
+            "Z" => ("BR-Z-02", false),
+            "E" => ("BR-E-02", false),
+            "AE" => ("BR-AE-02", true),
+            "K" => ("BR-IC-02", true),
+            "G" => ("BR-G-02", false),
+            _ => continue,
+        };
+        if seller.vat_id.trim().is_empty() {
+            violations.push(Violation::new(
+                rule,
+                format!("Category {} lines need the seller's VAT ID", code),
+            ));
+        }
+        if needs_buyer_vat && buyer.vat_id.trim().is_empty() {
+            violations.push(Violation::new(
+                rule,
+                format!("Category {} lines need the buyer's VAT ID", code),
+            ));
+        }
+    }
+    if totals.payable() > 0.0 && invoice.text.terms.trim().is_empty() {
+        violations.push(Violation::new(
+            "BR-CO-25",
+            "Payment terms are required when an amount is due",
+        ));
+    }
+    violations
+}
+
+// a minimal writer, elements are opened and closed in document order
+pub(crate) struct Xml {
+    pub(crate) output: String,
+    depth: usize,
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl Xml {
+    pub(crate) fn new() -> Self {
+        Self {
+            output: "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n".to_string(),
+            depth: 0,
+        }
+    }
+
+    fn indent(&mut self) {
+        self.output.push_str(&"  ".repeat(self.depth));
+    }
+
+    pub(crate) fn open(&mut self, tag: &str) {
+        self.indent();
+        self.output.push_str(&format!("<{}>\n", tag));
+        self.depth += 1;
+    }
+
+    pub(crate) fn close(&mut self, tag: &str) {
+        self.depth -= 1;
+        self.indent();
+        self.output.push_str(&format!("</{}>\n", tag));
+    }
+
+    pub(crate) fn leaf(&mut self, tag: &str, value: &str) {
+        self.leaf_with(tag, &[], value);
+    }
+
+    pub(crate) fn leaf_with(&mut self, tag: &str, attributes: &[(&str, &str)], value: &str) {
+        self.indent();
+        self.output.push('<');
+        self.output.push_str(tag);
+        for (name, value) in attributes {
+            self.output
+                .push_str(&format!(" {}=\"{}\"", name, escape(value)));
+        }
+        self.output
+            .push_str(&format!(">{}</{}>\n", escape(value), tag));
+    }
+
+    fn money(&mut self, tag: &str, currency: &str, value: f64) {
+        self.leaf_with(tag, &[("currencyID", currency)], &amount(value));
+    }
+
+    fn tax_category(&mut self, tag: &str, category: &TaxCategory, with_reason: bool) {
+        self.open(tag);
+        self.leaf("cbc:ID", &category.code);
+        // category O is outside the scope of VAT and has no rate at all
+        if category.code != "O" {
+            self.leaf("cbc:Percent", &decimal(category.percent));
+        }
+        if with_reason {
+            if let Some(reason) = exemption_reason(&category.code) {
+                self.leaf("cbc:TaxExemptionReason", reason);
+            }
+        }
+        self.open("cac:TaxScheme");
+        self.leaf("cbc:ID", "VAT");
+        self.close("cac:TaxScheme");
+        self.close(tag);
+    }
+
+    // the seller also lists the contact person
+    fn party(&mut self, tag: &str, party: Party, contact: Option<&Contact>) {
+        let Party {
+            name,
+            street,
+            city,
+            postal_code,
+            country,
+            vat_id,
+            endpoint_id,
+        } = party;
+        self.open(tag);
+        self.open("cac:Party");
+        let (scheme, id) = endpoint_id.split_once(':').unwrap_or(("", endpoint_id));
+        self.leaf_with("cbc:EndpointID", &[("schemeID", scheme.trim())], id.trim());
+        self.open("cac:PartyName");
+        self.leaf("cbc:Name", name);
+        self.close("cac:PartyName");
+        self.open("cac:PostalAddress");
+        if !street.trim().is_empty() {
+            self.leaf("cbc:StreetName", street.trim());
+        }
+        self.leaf("cbc:CityName", city.trim());
+        if !postal_code.trim().is_empty() {
+            self.leaf("cbc:PostalZone", postal_code.trim());
+        }
+        self.open("cac:Country");
+        self.leaf("cbc:IdentificationCode", country.trim());
+        self.close("cac:Country");
+        self.close("cac:PostalAddress");
+        if !vat_id.trim().is_empty() {
+            self.open("cac:PartyTaxScheme");
+            self.leaf("cbc:CompanyID", vat_id.trim());
+            self.open("cac:TaxScheme");
+            self.leaf("cbc:ID", "VAT");
+            self.close("cac:TaxScheme");
+            self.close("cac:PartyTaxScheme");
+        }
+        self.open("cac:PartyLegalEntity");
+        self.leaf("cbc:RegistrationName", name);
+        self.close("cac:PartyLegalEntity");
+        if let Some(contact) = contact {
+            let fields: Vec<(&str, &str)> = [
+                ("cbc:Name", &contact.name),
+                ("cbc:Telephone", &contact.telephone),
+                ("cbc:ElectronicMail", &contact.email),
+            ]
+            .iter()
+            .map(|(tag, value)| (*tag, value.trim()))
+            .filter(|(_, value)| !value.is_empty())
+            .collect();
+            // Peppol doesn't allow empty elements, no contact details means no Contact
+            if !fields.is_empty() {
+                self.open("cac:Contact");
+                for (tag, value) in fields {
+                    self.leaf(tag, value);
+                }
+                self.close("cac:Contact");
+            }
+        }
+        self.close("cac:Party");
+        self.close(tag);
+    }
+}
+
+// the UBL document, call `validate` first, the XML is only meaningful for a valid invoice
+pub fn to_xml(e_invoice: &EInvoice) -> String {
+    let EInvoice {
+        invoice,
+        seller,
+        buyer,
+        currency,
+        ..
+    } = e_invoice;
+    let totals = Totals::new(e_invoice.items);
+    let (root, namespace, type_code, line_tag, quantity_tag) = if totals.credit_note {
+        (
+            "CreditNote",
+            CREDIT_NOTE_NAMESPACE,
+            "381",
+            "cac:CreditNoteLine",
+            "cbc:CreditedQuantity",
+        )
+    } else {
+        (
+            "Invoice",
+            INVOICE_NAMESPACE,
+            "380",
+            "cac:InvoiceLine",
+            "cbc:InvoicedQuantity",
+        )
+    };
+
+    let mut xml = Xml::new();
+    xml.open(&format!(
+        "{} xmlns=\"{}\" xmlns:cac=\"{}\" xmlns:cbc=\"{}\"",
+        root, namespace, CAC, CBC
+    ));
+    xml.leaf("cbc:CustomizationID", CUSTOMIZATION_ID);
+    xml.leaf("cbc:ProfileID", PROFILE_ID);
+    xml.leaf("cbc:ID", &invoice.invoice_id);
+    xml.leaf("cbc:IssueDate", &invoice.issue_date);
+    xml.leaf(&format!("cbc:{}TypeCode", root), type_code);
+    if !invoice.text.notes.trim().is_empty() {
+        xml.leaf("cbc:Note", invoice.text.notes.trim());
+    }
+    xml.leaf("cbc:DocumentCurrencyCode", currency);
+    if !invoice.text.reference.trim().is_empty() {
+        xml.leaf("cbc:BuyerReference", invoice.text.reference.trim());
+    }
+    if !invoice.text.po_number.trim().is_empty() {
+        xml.open("cac:OrderReference");
+        xml.leaf("cbc:ID", invoice.text.po_number.trim());
+        xml.close("cac:OrderReference");
+    }
+    xml.party(
+        "cac:AccountingSupplierParty",
+        Party::from(*seller),
+        Some(seller),
+    );
+    xml.party("cac:AccountingCustomerParty", Party::from(*buyer), None);
+    if !invoice.text.terms.trim().is_empty() {
+        xml.open("cac:PaymentTerms");
+        xml.leaf("cbc:Note", invoice.text.terms.trim());
+        xml.close("cac:PaymentTerms");
+    }
+
+    xml.open("cac:TaxTotal");
+    xml.money("cbc:TaxAmount", currency, totals.tax_total);
+    for entry in &totals.breakdown {
+        xml.open("cac:TaxSubtotal");
+        xml.money("cbc:TaxableAmount", currency, entry.taxable);
+        xml.money("cbc:TaxAmount", currency, entry.tax);
+        xml.tax_category("cac:TaxCategory", &entry.category, true);
+        xml.close("cac:TaxSubtotal");
+    }
+    xml.close("cac:TaxTotal");
+
+    xml.open("cac:LegalMonetaryTotal");
+    xml.money("cbc:LineExtensionAmount", currency, totals.line_total);
+    xml.money("cbc:TaxExclusiveAmount", currency, totals.line_total);
+    xml.money("cbc:TaxInclusiveAmount", currency, totals.payable());
+    xml.money("cbc:PayableAmount", currency, totals.payable());
+    xml.close("cac:LegalMonetaryTotal");
+
+    for (idx, line) in totals.lines.iter().enumerate() {
+        xml.open(line_tag);
+        xml.leaf("cbc:ID", &(idx + 1).to_string());
+        xml.leaf_with(
+            quantity_tag,
+            &[("unitCode", unit_code(&line.item.unit))],
+            &decimal(line.quantity),
+        );
+        xml.money("cbc:LineExtensionAmount", currency, line.amount);
+        xml.open("cac:Item");
+        // the first line names the item, the full text goes in the description
+        let description = line.item.description.trim();
+        let name = description.lines().next().unwrap_or("").trim();
+        if description != name {
+            xml.leaf("cbc:Description", description);
+        }
+        xml.leaf("cbc:Name", name);
+        if let Some(sku) = &line.item.sku {
+            xml.open("cac:SellersItemIdentification");
+            xml.leaf("cbc:ID", sku);
+            xml.close("cac:SellersItemIdentification");
+        }
+        if let Some(category) = &line.category {
+            xml.tax_category("cac:ClassifiedTaxCategory", category, false);
+        }
+        xml.close("cac:Item");
+        xml.open("cac:Price");
+        xml.leaf_with(
+            "cbc:PriceAmount",
+            &[("currencyID", currency)],
+            &decimal(line.item.price),
+        );
+        xml.close("cac:Price");
+        xml.close(line_tag);
+    }
+    xml.close(root);
+    xml.output
+}
+
+// The data rules of `validate`, then the document `to_xml` writes against the UBL 2.1 schema and
+// the Peppol rules, empty when the e-invoice can be sent
+pub fn check(e_invoice: &EInvoice) -> Vec<Violation> {
+    let violations = validate(e_invoice);
+    if !violations.is_empty() {
+        return violations;
+    }
+    peppol::validate_document(&to_xml(e_invoice))
+}
+
+// validates, builds and checks the document, then writes it to `path`
+pub fn write_e_invoice(e_invoice: &EInvoice, path: &Path) -> Result<(), UblError> {
+    let violations = validate(e_invoice);
+    if !violations.is_empty() {
+        return Err(UblError::Invalid(violations));
+    }
+    let xml = to_xml(e_invoice);
+    let violations = peppol::validate_document(&xml);
+    if !violations.is_empty() {
+        return Err(UblError::Invalid(violations));
+    }
+    fs::write(path, xml)?;
+    Ok(())
+}
+
+// exports an issued invoice as stored, with the customer and contact as they are now
+pub fn export_invoice(
+    store: &Store,
+    invoice_id: &str,
+    currency: &str,
+    path: &Path,
+) -> Result<(), UblError> {
+    let invoice = store
+        .invoice(invoice_id)?
+        .ok_or_else(|| UblError::NotFound(format!("Invoice {}", invoice_id)))?;
+    let seller = store
+        .contact(&invoice.contact)?
+        .ok_or_else(|| UblError::NotFound(format!("Contact {}", invoice.contact)))?;
+    let buyer = store
+        .customer(&invoice.customer)?
+        .ok_or_else(|| UblError::NotFound(format!("Customer {}", invoice.customer)))?;
+    let items = store.invoice_line_items(&invoice.cust_id, invoice.estimate_number)?;
+    write_e_invoice(
+        &EInvoice {
+            invoice: &invoice,
+            seller: &seller,
+            buyer: &buyer,
+            items: &items,
+            currency,
+        },
+        path,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structs::{InvoiceText, LineKind};
+    use crate::test_support::{buyer, invoice, item, seller};
+
+    #[test]
+    fn test_valid_invoice_round_trips() {
+        let items = vec![
+            item("Design\n- wireframes", 2.0, 50.0, "S:19"),
+            DatabaseData {
+                kind: LineKind::Heading,
+                description: "Phase 2".to_string(),
+                ..Default::default()
+            },
+            item("Hosting", 1.0, 10.0, "S:19"),
+            item("Training", 1.0, 40.0, "E"),
+        ];
+        let (invoice, seller, buyer) = (invoice(), seller(), buyer());
+        let e_invoice = EInvoice {
+            invoice: &invoice,
+            seller: &seller,
+            buyer: &buyer,
+            items: &items,
+            currency: "EUR",
+        };
+        assert_eq!(validate(&e_invoice), vec![]);
+        let xml = to_xml(&e_invoice);
+        assert_eq!(peppol::validate_document(&xml), vec![]);
+
+        assert!(xml.contains("<cbc:InvoiceTypeCode>380</cbc:InvoiceTypeCode>"));
+        assert!(xml.contains("<cbc:Name>Acme &amp; Sons</cbc:Name>"));
+        assert!(xml.contains("<cbc:EndpointID schemeID=\"0208\">0123456749</cbc:EndpointID>"));
+        assert!(xml.contains("<cbc:InvoicedQuantity unitCode=\"HUR\">2</cbc:InvoicedQuantity>"));
+        // 110.00 at 19% plus 40.00 exempt
+        assert!(xml.contains("<cbc:TaxAmount currencyID=\"EUR\">20.90</cbc:TaxAmount>"));
+        assert!(xml.contains("<cbc:TaxExemptionReason>Exempt from VAT</cbc:TaxExemptionReason>"));
+        assert!(xml.contains("<cbc:PayableAmount currencyID=\"EUR\">170.90</cbc:PayableAmount>"));
+        assert_eq!(xml.matches("<cac:InvoiceLine>").count(), 3);
+    }
+
+    #[test]
+    fn test_negative_total_is_a_credit_note() {
+        let items = vec![item("Refund", -1.0, 100.0, "S:19")];
+        let (invoice, seller, buyer) = (invoice(), seller(), buyer());
+        let e_invoice = EInvoice {
+            invoice: &invoice,
+            seller: &seller,
+            buyer: &buyer,
+            items: &items,
+            currency: "EUR",
+        };
+        assert_eq!(validate(&e_invoice), vec![]);
+        let xml = to_xml(&e_invoice);
+        assert_eq!(peppol::validate_document(&xml), vec![]);
+        assert!(xml.contains(
+            "<CreditNote xmlns=\"urn:oasis:names:specification:ubl:schema:xsd:CreditNote-2\""
+        ));
+        assert!(xml.contains("<cbc:CreditedQuantity unitCode=\"HUR\">1</cbc:CreditedQuantity>"));
+        assert!(xml.contains("<cbc:PayableAmount currencyID=\"EUR\">119.00</cbc:PayableAmount>"));
+    }
+
+    #[test]
+    fn test_business_rules_are_reported() {
+        let items = vec![
+            item("Design", 1.0, 100.0, ""),
+            item("Goods", 1.0, 5.0, "AE"),
+        ];
+        let mut invoice = invoice();
+        invoice.text = InvoiceText::default();
+        let seller = Contact {
+            country: "Germany".to_string(),
+            vat_id: String::new(),
+            ..seller()
+        };
+        let buyer = Customer {
+            endpoint_id: "0123456789".to_string(),
+            vat_id: String::new(),
+            ..buyer()
+        };
+        let e_invoice = EInvoice {
+            invoice: &invoice,
+            seller: &seller,
+            buyer: &buyer,
+            items: &items,
+            currency: "euro",
+        };
+        let rules: Vec<&str> = validate(&e_invoice)
+            .iter()
+            .map(|violation| violation.rule)
+            .collect();
+        assert_eq!(
+            rules,
+            vec![
+                "BR-CL-04",
+                "BR-09",
+                "PEPPOL-EN16931-R010",
+                "PEPPOL-EN16931-R003",
+                "BR-CO-04",
+                "BR-AE-02",
+                "BR-AE-02",
+                "BR-CO-25",
+            ]
+        );
+        let path = std::env::temp_dir().join("invoicy-invalid-ubl.xml");
+        assert!(matches!(
+            write_e_invoice(&e_invoice, &path),
+            Err(UblError::Invalid(_))
+        ));
+        assert!(!path.exists());
+    }
+}